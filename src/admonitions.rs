@@ -0,0 +1,141 @@
+//! MkDocs admonitions, a `!!! note "Title"` line over an indented body, as
+//! callout fences (` ```note "Title" `), which the renderer draws as boxes.
+//! The collapsible `??? note` and `???+ note` are drawn open. Every line
+//! stays where it was, so rendered lines still map to source lines: the
+//! fence that closes an admonition takes the place of the blank line after
+//! it, and is only added when there is none.
+
+use std::borrow::Cow;
+
+use crate::render::{is_callout, title_case};
+
+/// `input` with its admonitions turned into callout fences. Types the
+/// renderer has no callout for become notes titled by the type, as MkDocs
+/// styles them.
+pub(crate) fn to_fences(input: &str) -> Cow<'_, str> {
+    if !input.lines().any(|line| header(line).is_some()) {
+        return Cow::Borrowed(input);
+    }
+    let lines: Vec<&str> = input.split_inclusive('\n').collect();
+    let mut out = String::with_capacity(input.len() + 64);
+    // A code fence open in the input, whose lines are left alone.
+    let mut code: Option<(char, usize)> = None;
+    let mut i = 0;
+    while i < lines.len() {
+        let line = lines[i];
+        i += 1;
+        if let Some((c, n)) = code {
+            if closes(line, c, n) {
+                code = None;
+            }
+            out.push_str(line);
+            continue;
+        }
+        if let Some(fence) = opens(line) {
+            code = Some(fence);
+            out.push_str(line);
+            continue;
+        }
+        let Some((kind, title)) = header(line) else {
+            out.push_str(line);
+            continue;
+        };
+        // The body runs over indented lines and the blank lines between them.
+        let mut end = i;
+        for (j, line) in lines.iter().enumerate().skip(i) {
+            if !line.trim().is_empty() {
+                if !line.starts_with("    ") && !line.starts_with('\t') {
+                    break;
+                }
+                end = j + 1;
+            }
+        }
+        let body: Vec<&str> = lines[i..end]
+            .iter()
+            .map(|line| line.strip_prefix("    ").or_else(|| line.strip_prefix('\t')).unwrap_or(line))
+            .collect();
+        let longest = body.iter().flat_map(|line| line.split(|c| c != '`')).map(str::len).max().unwrap_or(0);
+        let fence = "`".repeat(longest.max(2) + 1);
+        match (is_callout(kind), title) {
+            (true, Some(title)) => out.push_str(&format!("{fence}{kind} \"{title}\"\n")),
+            (true, None) => out.push_str(&format!("{fence}{kind}\n")),
+            (false, Some(title)) => out.push_str(&format!("{fence}note \"{title}\"\n")),
+            (false, None) => out.push_str(&format!("{fence}note \"{}\"\n", title_case(kind))),
+        }
+        for line in body {
+            out.push_str(line);
+        }
+        if !out.ends_with('\n') {
+            out.push('\n');
+        }
+        out.push_str(&fence);
+        out.push('\n');
+        i = end;
+        if lines.get(end).is_some_and(|line| line.trim().is_empty()) {
+            i += 1;
+        }
+    }
+    Cow::Owned(out)
+}
+
+/// The type and quoted title of an admonition's first line: `!!! type`,
+/// then other classes (`inline end`) and a title, all optional.
+fn header(line: &str) -> Option<(&str, Option<&str>)> {
+    let rest = ["!!!", "???+", "???"].iter().find_map(|marker| line.strip_prefix(marker))?;
+    if !rest.starts_with([' ', '\t']) {
+        return None;
+    }
+    let rest = rest.trim();
+    let (kind, rest) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+    if kind.is_empty() || !kind.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        return None;
+    }
+    let title = rest.find('"').and_then(|start| {
+        let quoted = &rest[start + 1..];
+        quoted.rfind('"').map(|end| &quoted[..end])
+    });
+    Some((kind, title))
+}
+
+/// The character and length of the code fence `line` opens, if it does.
+fn opens(line: &str) -> Option<(char, usize)> {
+    let trimmed = line.trim_start_matches(' ');
+    if line.len() - trimmed.len() > 3 {
+        return None;
+    }
+    let c = trimmed.chars().next().filter(|&c| c == '`' || c == '~')?;
+    let n = trimmed.chars().take_while(|&x| x == c).count();
+    (n >= 3).then_some((c, n))
+}
+
+/// Whether `line` closes a code fence of at least `n` of `c`.
+fn closes(line: &str, c: char, n: usize) -> bool {
+    opens(line).is_some_and(|(x, m)| x == c && m >= n && line.trim().chars().all(|x| x == c))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_admonitions_become_fences() {
+        let md = "Intro.\n\n!!! warning \"Careful now\"\n    Body *text*.\n\n    More.\n\nAfter.\n";
+        let fenced = "Intro.\n\n```warning \"Careful now\"\nBody *text*.\n\nMore.\n```\nAfter.\n";
+        assert_eq!(to_fences(md), fenced);
+        assert_eq!(to_fences(md).lines().count(), md.lines().count());
+        // No blank line to replace at the end of the input.
+        assert_eq!(to_fences("??? tip\n    Hidden.").as_ref(), "```tip\nHidden.\n```\n");
+        // Unknown types are notes, as in MkDocs; titles may be empty.
+        assert_eq!(to_fences("!!! custom\n    x\n").as_ref(), "```note \"Custom\"\nx\n```\n");
+        assert_eq!(to_fences("???+ note inline \"\"\n    x\n").as_ref(), "```note \"\"\nx\n```\n");
+        // Fences in the body get a longer one around them.
+        assert_eq!(to_fences("!!! note\n    ```sh\n    ls\n    ```\n").as_ref(), "````note\n```sh\nls\n```\n````\n");
+    }
+
+    #[test]
+    fn test_lookalikes_are_left_alone() {
+        for md in ["```\n!!! note\n    x\n```\n", "!!!note\n", "!!! \n", "Wow!!! note\n", "!!! a.b\n"] {
+            assert_eq!(to_fences(md), md);
+        }
+    }
+}
@@ -0,0 +1,760 @@
+use std::collections::HashMap;
+use std::iter::Peekable;
+
+use pulldown_cmark::{
+    Alignment, BlockQuoteKind, CodeBlockKind, CowStr, Event, HeadingLevel, LinkType, Parser, Tag, TagEnd,
+    TextMergeStream,
+};
+use serde::Serialize;
+
+use crate::admonitions;
+use crate::frontmatter;
+use crate::render::{RenderOptions, unique_slug};
+
+/// A block-level element of a parsed document.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Block {
+    Heading {
+        level: u8,
+        /// The explicit `{#id}`, or the GitHub-style slug of the title,
+        /// numbered when repeated.
+        id: String,
+        content: Vec<Inline>,
+    },
+    Paragraph {
+        content: Vec<Inline>,
+    },
+    CodeBlock {
+        /// First word of a fenced block's info string.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        language: Option<String>,
+        /// The rest of the info string, such as the title of a callout
+        /// fence.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        info: Option<String>,
+        code: String,
+    },
+    BlockQuote {
+        /// `note`, `tip`, `important`, `warning` or `caution` for GitHub alerts.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        alert: Option<&'static str>,
+        blocks: Vec<Block>,
+    },
+    List {
+        /// First number of an ordered list; `None` for bullets.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        start: Option<u64>,
+        /// Whether items are written without blank lines between them.
+        tight: bool,
+        items: Vec<ListItem>,
+    },
+    Table {
+        /// `none`, `left`, `center` or `right` per column.
+        alignments: Vec<&'static str>,
+        header: Vec<Vec<Inline>>,
+        rows: Vec<Vec<Vec<Inline>>>,
+    },
+    Rule,
+    Html {
+        html: String,
+    },
+    FootnoteDefinition {
+        label: String,
+        blocks: Vec<Block>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ListItem {
+    /// Whether a task list item is checked; `None` for plain items.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub checked: Option<bool>,
+    /// The item's content. Text in a tight list item is wrapped in a
+    /// paragraph too.
+    pub blocks: Vec<Block>,
+}
+
+/// A run of inline content. Nested emphasis and links are flattened into
+/// runs that each carry their whole style.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Inline {
+    Text {
+        text: String,
+        #[serde(flatten)]
+        style: TextStyle,
+        /// Destination of the link the run is part of.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        link: Option<String>,
+    },
+    Code {
+        code: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        link: Option<String>,
+    },
+    Image {
+        url: String,
+        #[serde(skip_serializing_if = "String::is_empty")]
+        title: String,
+        alt: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        link: Option<String>,
+    },
+    SoftBreak,
+    HardBreak,
+    Html {
+        html: String,
+    },
+    FootnoteReference {
+        label: String,
+    },
+    Math {
+        tex: String,
+        display: bool,
+    },
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct TextStyle {
+    #[serde(skip_serializing_if = "is_false")]
+    pub bold: bool,
+    #[serde(skip_serializing_if = "is_false")]
+    pub italic: bool,
+    #[serde(skip_serializing_if = "is_false")]
+    pub strikethrough: bool,
+}
+
+fn is_false(b: &bool) -> bool {
+    !b
+}
+
+/// Parses `input`, without its frontmatter, into blocks, with the markdown
+/// extensions enabled in `options`.
+///
+/// ```
+/// use mdview::ast::{Block, Inline, parse};
+///
+/// let blocks = parse("# Hi\n", &mdview::RenderOptions::new(80));
+/// let Block::Heading { level, id, content } = &blocks[0] else { panic!() };
+/// assert_eq!((*level, id.as_str()), (1, "hi"));
+/// assert!(matches!(&content[0], Inline::Text { text, .. } if text == "Hi"));
+/// ```
+pub fn parse(input: &str, options: &RenderOptions) -> Vec<Block> {
+    let input = frontmatter::blank(input);
+    from_events(Parser::new_ext(&admonitions::to_fences(&input), options.extensions))
+}
+
+/// Builds blocks from an already-parsed event stream.
+pub(crate) fn from_events<'a>(events: impl Iterator<Item = Event<'a>>) -> Vec<Block> {
+    let mut builder = Builder {
+        events: TextMergeStream::new(events).peekable(),
+        slugs: HashMap::new(),
+        task: None,
+        loose: false,
+    };
+    builder.blocks()
+}
+
+struct Builder<'a, I: Iterator<Item = Event<'a>>> {
+    events: Peekable<I>,
+    slugs: HashMap<String, usize>,
+    /// The task marker seen in the list item being built.
+    task: Option<bool>,
+    /// Whether the last container finished had paragraphs of its own.
+    loose: bool,
+}
+
+impl<'a, I: Iterator<Item = Event<'a>>> Builder<'a, I> {
+    /// Blocks up to the end of the enclosing container (consumed) or of the
+    /// input.
+    fn blocks(&mut self) -> Vec<Block> {
+        let mut blocks = Vec::new();
+        let mut loose = false;
+        while let Some(event) = self.events.peek() {
+            if is_inline(event) {
+                // Tight list items hold their text without a paragraph.
+                let content = self.inlines_until_block();
+                blocks.push(Block::Paragraph { content });
+                continue;
+            }
+            let Some(event) = self.events.next() else { break };
+            match event {
+                Event::End(_) => break,
+                Event::Start(tag) => {
+                    loose |= tag == Tag::Paragraph;
+                    blocks.extend(self.block(tag));
+                }
+                Event::Rule => blocks.push(Block::Rule),
+                Event::Html(html) => blocks.push(Block::Html { html: html.to_string() }),
+                _ => {}
+            }
+        }
+        self.loose = loose;
+        blocks
+    }
+
+    fn block(&mut self, tag: Tag<'a>) -> Vec<Block> {
+        let block = match tag {
+            Tag::Paragraph => Block::Paragraph { content: self.inlines() },
+            Tag::Heading { level, id, .. } => {
+                let content = self.inlines();
+                let id = match id {
+                    Some(id) => id.to_string(),
+                    None => unique_slug(&mut self.slugs, &plain_text(&content)),
+                };
+                Block::Heading { level: level as u8, id, content }
+            }
+            Tag::BlockQuote(kind) => Block::BlockQuote { alert: kind.map(alert_name), blocks: self.blocks() },
+            Tag::CodeBlock(kind) => {
+                let (language, info) = match kind {
+                    CodeBlockKind::Fenced(info) => {
+                        let info = info.trim();
+                        let (language, rest) = info.split_once(char::is_whitespace).unwrap_or((info, ""));
+                        let rest = rest.trim_start();
+                        (
+                            Some(language.to_string()).filter(|l| !l.is_empty()),
+                            Some(rest.to_string()).filter(|r| !r.is_empty()),
+                        )
+                    }
+                    CodeBlockKind::Indented => (None, None),
+                };
+                let mut code = String::new();
+                for event in self.events.by_ref() {
+                    match event {
+                        Event::Text(text) => code.push_str(&text),
+                        Event::End(_) => break,
+                        _ => {}
+                    }
+                }
+                Block::CodeBlock { language, info, code }
+            }
+            Tag::List(start) => {
+                let mut items = Vec::new();
+                let mut tight = true;
+                while let Some(event) = self.events.next() {
+                    match event {
+                        Event::Start(Tag::Item) => {
+                            self.task = None;
+                            let blocks = self.blocks();
+                            tight &= !self.loose;
+                            items.push(ListItem { checked: self.task.take(), blocks });
+                        }
+                        Event::End(_) => break,
+                        _ => {}
+                    }
+                }
+                Block::List { start, tight, items }
+            }
+            Tag::Table(alignments) => {
+                let alignments = alignments.into_iter().map(alignment_name).collect();
+                let mut header = Vec::new();
+                let mut rows = Vec::new();
+                while let Some(event) = self.events.next() {
+                    match event {
+                        Event::Start(Tag::TableHead) => header = self.cells(),
+                        Event::Start(Tag::TableRow) => rows.push(self.cells()),
+                        Event::End(_) => break,
+                        _ => {}
+                    }
+                }
+                Block::Table { alignments, header, rows }
+            }
+            Tag::HtmlBlock => {
+                let mut html = String::new();
+                for event in self.events.by_ref() {
+                    match event {
+                        Event::Html(text) | Event::Text(text) => html.push_str(&text),
+                        Event::End(_) => break,
+                        _ => {}
+                    }
+                }
+                Block::Html { html }
+            }
+            Tag::FootnoteDefinition(label) => {
+                Block::FootnoteDefinition { label: label.to_string(), blocks: self.blocks() }
+            }
+            // Containers the model has no block for keep their content.
+            _ => return self.blocks(),
+        };
+        vec![block]
+    }
+
+    /// The cells of a table row or header, up to its end.
+    fn cells(&mut self) -> Vec<Vec<Inline>> {
+        let mut cells = Vec::new();
+        while let Some(event) = self.events.next() {
+            match event {
+                Event::Start(Tag::TableCell) => cells.push(self.inlines()),
+                Event::End(_) => break,
+                _ => {}
+            }
+        }
+        cells
+    }
+
+    /// Inline content up to the end of the enclosing block (consumed).
+    fn inlines(&mut self) -> Vec<Inline> {
+        let mut runs = Runs::default();
+        while let Some(event) = self.events.next() {
+            if !runs.push(event, self) {
+                break;
+            }
+        }
+        runs.content
+    }
+
+    /// Inline content up to the next block-level event (not consumed).
+    fn inlines_until_block(&mut self) -> Vec<Inline> {
+        let mut runs = Runs::default();
+        while self.events.peek().is_some_and(|e| is_inline(e) || runs.depth > 0) {
+            let Some(event) = self.events.next() else { break };
+            runs.push(event, self);
+        }
+        runs.content
+    }
+}
+
+/// Inline content being collected, with the styles and link in effect.
+#[derive(Default)]
+struct Runs {
+    content: Vec<Inline>,
+    style: TextStyle,
+    /// Open emphasis, strong and strikethrough tags, innermost last.
+    styles: Vec<TagEnd>,
+    links: Vec<String>,
+    /// Open inline tags; an end tag at zero closes the enclosing block.
+    depth: usize,
+}
+
+impl Runs {
+    /// Adds `event`, returning false once it ends the enclosing block.
+    fn push<'a, I>(&mut self, event: Event<'a>, builder: &mut Builder<'a, I>) -> bool
+    where
+        I: Iterator<Item = Event<'a>>,
+    {
+        let link = self.links.last().cloned();
+        match event {
+            Event::Start(Tag::Image { dest_url, title, .. }) => {
+                let mut alt = String::new();
+                let mut depth = 0;
+                for event in builder.events.by_ref() {
+                    match event {
+                        Event::Start(_) => depth += 1,
+                        Event::End(_) if depth == 0 => break,
+                        Event::End(_) => depth -= 1,
+                        Event::Text(text) | Event::Code(text) => alt.push_str(&text),
+                        _ => {}
+                    }
+                }
+                let (url, title) = (dest_url.to_string(), title.to_string());
+                self.content.push(Inline::Image { url, title, alt, link });
+            }
+            Event::Start(tag) => {
+                self.depth += 1;
+                match tag {
+                    Tag::Emphasis => self.styles.push(TagEnd::Emphasis),
+                    Tag::Strong => self.styles.push(TagEnd::Strong),
+                    Tag::Strikethrough => self.styles.push(TagEnd::Strikethrough),
+                    Tag::Link { dest_url, .. } => self.links.push(dest_url.to_string()),
+                    _ => {}
+                }
+                self.restyle();
+            }
+            Event::End(_) if self.depth == 0 => return false,
+            Event::End(tag) => {
+                self.depth -= 1;
+                match tag {
+                    TagEnd::Link => {
+                        self.links.pop();
+                    }
+                    tag => {
+                        if let Some(i) = self.styles.iter().rposition(|&s| s == tag) {
+                            self.styles.remove(i);
+                        }
+                    }
+                }
+                self.restyle();
+            }
+            Event::Text(text) => {
+                self.content.push(Inline::Text { text: text.to_string(), style: self.style, link })
+            }
+            Event::Code(code) => self.content.push(Inline::Code { code: code.to_string(), link }),
+            Event::SoftBreak => self.content.push(Inline::SoftBreak),
+            Event::HardBreak => self.content.push(Inline::HardBreak),
+            Event::InlineHtml(html) | Event::Html(html) => {
+                self.content.push(Inline::Html { html: html.to_string() })
+            }
+            Event::FootnoteReference(label) => {
+                self.content.push(Inline::FootnoteReference { label: label.to_string() })
+            }
+            Event::InlineMath(tex) => self.math(tex.to_string(), false),
+            Event::DisplayMath(tex) => self.math(tex.to_string(), true),
+            Event::TaskListMarker(checked) => builder.task = Some(checked),
+            Event::Rule => {}
+        }
+        true
+    }
+
+    fn math(&mut self, tex: String, display: bool) {
+        self.content.push(Inline::Math { tex, display });
+    }
+
+    fn restyle(&mut self) {
+        self.style = TextStyle {
+            bold: self.styles.contains(&TagEnd::Strong),
+            italic: self.styles.contains(&TagEnd::Emphasis),
+            strikethrough: self.styles.contains(&TagEnd::Strikethrough),
+        };
+    }
+}
+
+/// Whether `event` belongs inside a paragraph rather than starting a block.
+fn is_inline(event: &Event) -> bool {
+    match event {
+        Event::Start(tag) => matches!(
+            tag,
+            Tag::Emphasis | Tag::Strong | Tag::Strikethrough | Tag::Link { .. } | Tag::Image { .. }
+        ),
+        Event::End(_) | Event::Rule | Event::Html(_) => false,
+        _ => true,
+    }
+}
+
+/// The text of inline content without markup, for heading slugs.
+pub fn plain_text(content: &[Inline]) -> String {
+    content
+        .iter()
+        .map(|inline| match inline {
+            Inline::Text { text, .. } => text.as_str(),
+            Inline::Code { code, .. } => code.as_str(),
+            Inline::Image { alt, .. } => alt.as_str(),
+            Inline::Math { tex, .. } => tex.as_str(),
+            Inline::SoftBreak | Inline::HardBreak => " ",
+            Inline::Html { .. } | Inline::FootnoteReference { .. } => "",
+        })
+        .collect()
+}
+
+const ALERTS: [(BlockQuoteKind, &str); 5] = [
+    (BlockQuoteKind::Note, "note"),
+    (BlockQuoteKind::Tip, "tip"),
+    (BlockQuoteKind::Important, "important"),
+    (BlockQuoteKind::Warning, "warning"),
+    (BlockQuoteKind::Caution, "caution"),
+];
+
+const ALIGNMENTS: [(Alignment, &str); 4] = [
+    (Alignment::None, "none"),
+    (Alignment::Left, "left"),
+    (Alignment::Center, "center"),
+    (Alignment::Right, "right"),
+];
+
+fn alert_name(kind: BlockQuoteKind) -> &'static str {
+    ALERTS.iter().find(|(k, _)| *k == kind).map_or("note", |(_, name)| name)
+}
+
+fn alignment_name(alignment: Alignment) -> &'static str {
+    ALIGNMENTS.iter().find(|(a, _)| *a == alignment).map_or("none", |(_, name)| name)
+}
+
+/// The event stream `blocks` stand for: what the parser would produce for
+/// them, short of the source details the model drops (link titles and
+/// reference kinds, fence info past the language; adjacent links to the same
+/// URL come back as one). The renderer lays blocks out by walking these.
+pub(crate) fn events(blocks: &[Block]) -> Vec<Event<'_>> {
+    let mut events = Vec::new();
+    for block in blocks {
+        push_block(&mut events, block, false);
+    }
+    events
+}
+
+fn push_block<'b>(events: &mut Vec<Event<'b>>, block: &'b Block, tight: bool) {
+    let wrap = |events: &mut Vec<Event<'b>>, tag: Tag<'b>, inner: &mut dyn FnMut(&mut Vec<Event<'b>>)| {
+        let end = tag.to_end();
+        events.push(Event::Start(tag));
+        inner(events);
+        events.push(Event::End(end));
+    };
+    match block {
+        Block::Heading { level, content, .. } => {
+            let level = HeadingLevel::try_from(*level as usize).unwrap_or(HeadingLevel::H6);
+            let tag = Tag::Heading { level, id: None, classes: Vec::new(), attrs: Vec::new() };
+            wrap(events, tag, &mut |events| push_inlines(events, content));
+        }
+        // Tight list items hold their text without a paragraph.
+        Block::Paragraph { content } if tight => push_inlines(events, content),
+        Block::Paragraph { content } => {
+            wrap(events, Tag::Paragraph, &mut |events| push_inlines(events, content))
+        }
+        Block::CodeBlock { language, info, code } => {
+            let info = match (language, info) {
+                (Some(language), Some(info)) => CowStr::from(format!("{language} {info}")),
+                (language, _) => CowStr::from(language.as_deref().unwrap_or("")),
+            };
+            wrap(events, Tag::CodeBlock(CodeBlockKind::Fenced(info)), &mut |events| {
+                events.push(Event::Text(code.as_str().into()))
+            });
+        }
+        Block::BlockQuote { alert, blocks } => {
+            let kind = ALERTS.iter().find(|(_, name)| Some(*name) == *alert).map(|(kind, _)| *kind);
+            wrap(events, Tag::BlockQuote(kind), &mut |events| push_blocks(events, blocks, false));
+        }
+        Block::List { start, tight, items } => wrap(events, Tag::List(*start), &mut |events| {
+            for item in items {
+                wrap(events, Tag::Item, &mut |events| push_item(events, item, *tight));
+            }
+        }),
+        Block::Table { alignments, header, rows } => {
+            let alignments = alignments
+                .iter()
+                .map(|name| ALIGNMENTS.iter().find(|(_, n)| n == name).map_or(Alignment::None, |(a, _)| *a))
+                .collect();
+            wrap(events, Tag::Table(alignments), &mut |events| {
+                wrap(events, Tag::TableHead, &mut |events| push_cells(events, header));
+                for row in rows {
+                    wrap(events, Tag::TableRow, &mut |events| push_cells(events, row));
+                }
+            });
+        }
+        Block::Rule => events.push(Event::Rule),
+        Block::Html { html } => {
+            wrap(events, Tag::HtmlBlock, &mut |events| events.push(Event::Html(html.as_str().into())))
+        }
+        Block::FootnoteDefinition { label, blocks } => {
+            let tag = Tag::FootnoteDefinition(label.as_str().into());
+            wrap(events, tag, &mut |events| push_blocks(events, blocks, false));
+        }
+    }
+}
+
+fn push_blocks<'b>(events: &mut Vec<Event<'b>>, blocks: &'b [Block], tight: bool) {
+    for block in blocks {
+        push_block(events, block, tight);
+    }
+}
+
+/// A list item's content, with its task marker where the parser puts it:
+/// first thing in the item's first paragraph.
+fn push_item<'b>(events: &mut Vec<Event<'b>>, item: &'b ListItem, tight: bool) {
+    let Some(checked) = item.checked else {
+        return push_blocks(events, &item.blocks, tight);
+    };
+    match item.blocks.split_first() {
+        Some((Block::Paragraph { content }, rest)) => {
+            if !tight {
+                events.push(Event::Start(Tag::Paragraph));
+            }
+            events.push(Event::TaskListMarker(checked));
+            push_inlines(events, content);
+            if !tight {
+                events.push(Event::End(TagEnd::Paragraph));
+            }
+            push_blocks(events, rest, tight);
+        }
+        _ => {
+            events.push(Event::TaskListMarker(checked));
+            push_blocks(events, &item.blocks, tight);
+        }
+    }
+}
+
+fn push_cells<'b>(events: &mut Vec<Event<'b>>, cells: &'b [Vec<Inline>]) {
+    for cell in cells {
+        events.push(Event::Start(Tag::TableCell));
+        push_inlines(events, cell);
+        events.push(Event::End(TagEnd::TableCell));
+    }
+}
+
+/// Inline runs as nested emphasis and link tags again. A link stays open
+/// over content that carries no link of its own (HTML, breaks, footnote
+/// references) when the link continues after it.
+fn push_inlines<'b>(events: &mut Vec<Event<'b>>, content: &'b [Inline]) {
+    #[derive(PartialEq)]
+    enum Open<'b> {
+        Link(&'b str),
+        Strong,
+        Emphasis,
+        Strikethrough,
+    }
+    let mut open: Vec<Open> = Vec::new();
+    for (i, inline) in content.iter().enumerate() {
+        let (style, link) = match inline {
+            Inline::Text { style, link, .. } => (*style, link.as_deref()),
+            Inline::Code { link, .. } | Inline::Image { link, .. } => (TextStyle::default(), link.as_deref()),
+            _ => {
+                let next = content[i..].iter().find_map(|inline| match inline {
+                    Inline::Text { link, .. } | Inline::Code { link, .. } | Inline::Image { link, .. } => {
+                        Some(link.as_deref())
+                    }
+                    _ => None,
+                });
+                let link = open.iter().find_map(|o| match o {
+                    Open::Link(url) if next == Some(Some(url)) => Some(*url),
+                    _ => None,
+                });
+                (TextStyle::default(), link)
+            }
+        };
+        let wanted = |o: &Open| match o {
+            Open::Link(url) => link == Some(*url),
+            Open::Strong => style.bold,
+            Open::Emphasis => style.italic,
+            Open::Strikethrough => style.strikethrough,
+        };
+        if let Some(first) = open.iter().position(|o| !wanted(o)) {
+            for o in open.drain(first..).rev() {
+                events.push(Event::End(match o {
+                    Open::Link(_) => TagEnd::Link,
+                    Open::Strong => TagEnd::Strong,
+                    Open::Emphasis => TagEnd::Emphasis,
+                    Open::Strikethrough => TagEnd::Strikethrough,
+                }));
+            }
+        }
+        let wanted = [
+            link.map(Open::Link),
+            style.bold.then_some(Open::Strong),
+            style.italic.then_some(Open::Emphasis),
+            style.strikethrough.then_some(Open::Strikethrough),
+        ];
+        for o in wanted.into_iter().flatten() {
+            if open.contains(&o) {
+                continue;
+            }
+            events.push(Event::Start(match o {
+                Open::Link(url) => Tag::Link {
+                    link_type: LinkType::Inline,
+                    dest_url: url.into(),
+                    title: CowStr::Borrowed(""),
+                    id: CowStr::Borrowed(""),
+                },
+                Open::Strong => Tag::Strong,
+                Open::Emphasis => Tag::Emphasis,
+                Open::Strikethrough => Tag::Strikethrough,
+            }));
+            open.push(o);
+        }
+        match inline {
+            Inline::Text { text, .. } => events.push(Event::Text(text.as_str().into())),
+            Inline::Code { code, .. } => events.push(Event::Code(code.as_str().into())),
+            Inline::Image { url, title, alt, .. } => {
+                events.push(Event::Start(Tag::Image {
+                    link_type: LinkType::Inline,
+                    dest_url: url.as_str().into(),
+                    title: title.as_str().into(),
+                    id: CowStr::Borrowed(""),
+                }));
+                events.push(Event::Text(alt.as_str().into()));
+                events.push(Event::End(TagEnd::Image));
+            }
+            Inline::SoftBreak => events.push(Event::SoftBreak),
+            Inline::HardBreak => events.push(Event::HardBreak),
+            Inline::Html { html } => events.push(Event::InlineHtml(html.as_str().into())),
+            Inline::FootnoteReference { label } => {
+                events.push(Event::FootnoteReference(label.as_str().into()))
+            }
+            Inline::Math { tex, display: false } => events.push(Event::InlineMath(tex.as_str().into())),
+            Inline::Math { tex, display: true } => events.push(Event::DisplayMath(tex.as_str().into())),
+        }
+    }
+    for o in open.into_iter().rev() {
+        events.push(Event::End(match o {
+            Open::Link(_) => TagEnd::Link,
+            Open::Strong => TagEnd::Strong,
+            Open::Emphasis => TagEnd::Emphasis,
+            Open::Strikethrough => TagEnd::Strikethrough,
+        }));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render::render_events;
+
+    fn text(text: &str, style: TextStyle, link: Option<&str>) -> Inline {
+        Inline::Text { text: text.to_string(), style, link: link.map(str::to_string) }
+    }
+
+    #[test]
+    fn test_inline_runs_carry_style_and_link() {
+        let blocks = parse("Plain **bold *both*** [link `code`](u)\n", &RenderOptions::new(80));
+        let bold = TextStyle { bold: true, ..TextStyle::default() };
+        let both = TextStyle { italic: true, ..bold };
+        assert_eq!(
+            blocks,
+            [Block::Paragraph {
+                content: vec![
+                    text("Plain ", TextStyle::default(), None),
+                    text("bold ", bold, None),
+                    text("both", both, None),
+                    text(" ", TextStyle::default(), None),
+                    text("link ", TextStyle::default(), Some("u")),
+                    Inline::Code { code: "code".to_string(), link: Some("u".to_string()) },
+                ],
+            }]
+        );
+    }
+
+    #[test]
+    fn test_lists_and_quotes() {
+        let md = "> [!NOTE]\n> Hi\n\n- [x] done\n- two\n\n   para\n\n3. three\n";
+        let blocks = parse(md, &RenderOptions::new(80));
+        let paragraph = |s: &str| Block::Paragraph { content: vec![text(s, TextStyle::default(), None)] };
+        assert_eq!(blocks[0], Block::BlockQuote { alert: Some("note"), blocks: vec![paragraph("Hi")] });
+        let Block::List { start: None, tight: false, items } = &blocks[1] else { panic!("{:?}", blocks[1]) };
+        assert_eq!(items[0], ListItem { checked: Some(true), blocks: vec![paragraph("done")] });
+        assert_eq!(items[1], ListItem { checked: None, blocks: vec![paragraph("two"), paragraph("para")] });
+        assert!(matches!(&blocks[2], Block::List { start: Some(3), tight: true, .. }));
+    }
+
+    #[test]
+    fn test_headings_get_unique_ids() {
+        let blocks = parse("# Intro\n\n## Intro\n\n## Use `x`\n", &RenderOptions::new(80));
+        let ids: Vec<&str> = blocks
+            .iter()
+            .filter_map(|b| match b {
+                Block::Heading { id, .. } => Some(id.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(ids, ["intro", "intro-1", "use-x"]);
+    }
+
+    #[test]
+    fn test_events_render_like_the_source() {
+        let fixtures = ["alerts", "blockquotes", "code-blocks", "edge-cases", "footnotes", "inline", "lists", "tables"];
+        for fixture in fixtures {
+            let path = format!("{}/tests/fixtures/{fixture}.md", env!("CARGO_MANIFEST_DIR"));
+            let md = std::fs::read_to_string(path).unwrap();
+            let options = RenderOptions::new(60);
+            let direct = render_events(Parser::new_ext(&md, options.extensions), &options);
+            let laid_out = render_events(events(&parse(&md, &options)).into_iter(), &options);
+            assert_eq!(laid_out.text, direct.text, "{fixture}");
+            assert_eq!(laid_out.links, direct.links, "{fixture}");
+        }
+    }
+
+    #[test]
+    fn test_tables_and_code() {
+        let md = "| a | b |\n|:--|--:|\n| 1 | 2 |\n\n```rust title\nfn x() {}\n```\n";
+        let blocks = parse(md, &RenderOptions::new(80));
+        let Block::Table { alignments, header, rows } = &blocks[0] else { panic!() };
+        assert_eq!(alignments, &["left", "right"]);
+        assert_eq!(header.len(), 2);
+        assert_eq!(rows[0][1], [text("2", TextStyle::default(), None)]);
+        assert_eq!(
+            blocks[1],
+            Block::CodeBlock {
+                language: Some("rust".to_string()),
+                info: Some("title".to_string()),
+                code: "fn x() {}\n".to_string()
+            }
+        );
+    }
+}
+
@@ -0,0 +1,233 @@
+//! Timing of the rendering pipeline, for reporting where a document's
+//! render time goes.
+//!
+//! ```
+//! let input = "# Title\n\n```rust\nfn main() {}\n```\n";
+//! let profile = mdview::bench::profile(input, &mdview::RenderOptions::new(80));
+//! assert_eq!(profile.blocks.len(), 2);
+//! assert_eq!(profile.highlight.count, 1);
+//! ```
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use pulldown_cmark::Parser;
+
+use crate::ast::{self, Block};
+use crate::highlight;
+use crate::incremental::split_blocks;
+use crate::render::{RenderOptions, layout};
+
+/// Times a document is rendered; each phase reports its fastest run, so
+/// that a busy machine does not show as a regression.
+const RUNS: usize = 5;
+
+thread_local! {
+    /// Time spent in each phase and the number of times it was entered, on
+    /// this thread while a profile runs.
+    static PHASES: RefCell<Option<HashMap<&'static str, Phase>>> = const { RefCell::new(None) };
+}
+
+/// Records the time until it is dropped under its phase.
+pub(crate) struct Span {
+    phase: &'static str,
+    start: Instant,
+}
+
+/// Starts timing `phase`, or does nothing when no profile is running.
+pub(crate) fn span(phase: &'static str) -> Option<Span> {
+    profiling().then(|| Span { phase, start: Instant::now() })
+}
+
+/// Whether a profile is running on this thread.
+pub(crate) fn profiling() -> bool {
+    PHASES.with_borrow(Option::is_some)
+}
+
+impl Drop for Span {
+    fn drop(&mut self) {
+        let elapsed = self.start.elapsed();
+        PHASES.with_borrow_mut(|phases| {
+            if let Some(phase) = phases.as_mut().map(|p| p.entry(self.phase).or_default()) {
+                phase.time += elapsed;
+                phase.count += 1;
+            }
+        });
+    }
+}
+
+/// Time spent in a phase and the number of times it was entered.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Phase {
+    pub time: Duration,
+    pub count: usize,
+}
+
+/// A top-level block and the time it takes to parse and lay out alone.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockTime {
+    /// 1-based source line the block starts on.
+    pub line: usize,
+    /// What the block is, like `paragraph` or `code (rust)`.
+    pub kind: String,
+    pub time: Duration,
+}
+
+/// Where the time goes in rendering a document.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Profile {
+    pub bytes: usize,
+    pub lines: usize,
+    /// Loading the syntax definitions and themes, which happens once per
+    /// process before the first code block is highlighted.
+    pub load: Duration,
+    pub parse: Duration,
+    /// Highlighting code blocks, uncached.
+    pub highlight: Phase,
+    /// Drawing mermaid diagrams.
+    pub diagrams: Phase,
+    /// Laying the parsed document out, highlighting and diagrams excluded.
+    pub layout: Duration,
+    /// Every top-level block, slowest first.
+    pub blocks: Vec<BlockTime>,
+    /// Peak resident memory of the process in bytes, where the platform
+    /// reports it.
+    pub peak_memory: Option<u64>,
+}
+
+impl Profile {
+    /// Parse and layout time of the whole document.
+    pub fn total(&self) -> Duration {
+        self.parse + self.highlight.time + self.diagrams.time + self.layout
+    }
+
+    /// Label and value pairs in display order.
+    pub fn rows(&self) -> Vec<(&'static str, String)> {
+        let mut rows = vec![
+            ("Size", format!("{} bytes, {} lines, {} blocks", self.bytes, self.lines, self.blocks.len())),
+            ("Load", millis(self.load)),
+            ("Parse", millis(self.parse)),
+            ("Highlight", format!("{} ({} code blocks)", millis(self.highlight.time), self.highlight.count)),
+            ("Layout", millis(self.layout)),
+            ("Total", millis(self.total())),
+        ];
+        if self.diagrams.count > 0 {
+            rows.insert(4, ("Diagrams", format!("{} ({})", millis(self.diagrams.time), self.diagrams.count)));
+        }
+        let memory = self.peak_memory.map_or("n/a".to_string(), |b| format!("{:.1} MiB", b as f64 / (1024.0 * 1024.0)));
+        rows.push(("Peak memory", memory));
+        rows
+    }
+}
+
+/// Formats `time` in milliseconds.
+pub fn millis(time: Duration) -> String {
+    format!("{:.2} ms", time.as_secs_f64() * 1000.0)
+}
+
+/// Renders `input` a few times, timing each phase, then each top-level block
+/// alone. Code blocks are highlighted afresh on every run.
+pub fn profile(input: &str, options: &RenderOptions) -> Profile {
+    let start = Instant::now();
+    highlight::load();
+    let load = start.elapsed();
+
+    let mut best: Option<(Duration, HashMap<&'static str, Phase>, Duration)> = None;
+    for _ in 0..RUNS {
+        highlight::clear_cache();
+        PHASES.set(Some(HashMap::new()));
+        let start = Instant::now();
+        let blocks = ast::parse(input, options);
+        let parse = start.elapsed();
+        let start = Instant::now();
+        layout(&blocks, options);
+        let laid_out = start.elapsed();
+        let phases = PHASES.take().unwrap_or_default();
+        if best.as_ref().is_none_or(|(p, _, l)| parse + laid_out < *p + *l) {
+            best = Some((parse, phases, laid_out));
+        }
+    }
+    let (parse, phases, laid_out) = best.unwrap_or_default();
+    let highlight = phases.get("highlight").copied().unwrap_or_default();
+    let diagrams = phases.get("diagrams").copied().unwrap_or_default();
+
+    let parser = Parser::new_ext(input, options.extensions).into_offset_iter();
+    let mut blocks: Vec<BlockTime> = split_blocks(parser)
+        .map(|(range, events)| {
+            let time = (0..RUNS)
+                .map(|_| {
+                    highlight::clear_cache();
+                    let start = Instant::now();
+                    layout(&ast::from_events(events.iter().cloned()), options);
+                    start.elapsed()
+                })
+                .min()
+                .unwrap_or_default();
+            let line = input[..range.start].matches('\n').count() + 1;
+            BlockTime { line, kind: kind(&ast::from_events(events.into_iter())), time }
+        })
+        .collect();
+    blocks.sort_by_key(|b| std::cmp::Reverse(b.time));
+
+    Profile {
+        bytes: input.len(),
+        lines: input.lines().count(),
+        load,
+        parse,
+        highlight,
+        diagrams,
+        layout: laid_out.saturating_sub(highlight.time + diagrams.time),
+        blocks,
+        peak_memory: peak_memory(),
+    }
+}
+
+/// Names the first of `blocks`, with the language of a code block.
+fn kind(blocks: &[Block]) -> String {
+    let name = match blocks.first() {
+        Some(Block::Heading { level, .. }) => return format!("heading (h{level})"),
+        Some(Block::CodeBlock { language: Some(lang), .. }) => return format!("code ({lang})"),
+        Some(Block::CodeBlock { language: None, .. }) => "code",
+        Some(Block::Paragraph { .. }) => "paragraph",
+        Some(Block::BlockQuote { alert: Some(alert), .. }) => return format!("alert ({alert})"),
+        Some(Block::BlockQuote { .. }) => "blockquote",
+        Some(Block::List { .. }) => "list",
+        Some(Block::Table { .. }) => "table",
+        Some(Block::Rule) => "rule",
+        Some(Block::Html { .. }) => "html",
+        Some(Block::FootnoteDefinition { .. }) => "footnote",
+        None => "other",
+    };
+    name.to_string()
+}
+
+/// Peak resident memory of the process in bytes, from `/proc` on Linux.
+fn peak_memory() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find_map(|l| l.strip_prefix("VmHWM:"))?;
+    let kib: u64 = line.trim().strip_suffix("kB")?.trim().parse().ok()?;
+    Some(kib * 1024)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_profile_times_each_block() {
+        let input = "# Title\n\nSome text.\n\n```rust\nfn main() {}\n```\n\n```mermaid\ngraph LR\nA --> B\n```\n";
+        let profile = profile(input, &RenderOptions::new(80));
+        let mut kinds: Vec<(usize, &str)> = profile.blocks.iter().map(|b| (b.line, b.kind.as_str())).collect();
+        kinds.sort();
+        assert_eq!(
+            kinds,
+            [(1, "heading (h1)"), (3, "paragraph"), (5, "code (rust)"), (9, "code (mermaid)")]
+        );
+        assert_eq!(profile.highlight.count, 1);
+        assert_eq!(profile.diagrams.count, 1);
+        assert!(profile.blocks.windows(2).all(|w| w[0].time >= w[1].time));
+        // Outside a profile nothing is recorded.
+        assert!(span("highlight").is_none());
+    }
+}
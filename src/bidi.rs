@@ -0,0 +1,127 @@
+//! Bidirectional text: lines of Hebrew, Arabic and other right-to-left
+//! scripts put in the order a terminal that knows no better draws them.
+//!
+//! Levels come from the Unicode Bidirectional Algorithm as the unicode-bidi
+//! crate implements it, explicit embeddings and isolates included. Rows are
+//! reordered here rather than with `reorder_line` since they carry a style
+//! and an offset per char: by grapheme cluster, so marks stay on their base
+//! (L3), with brackets at odd levels mirrored (L4).
+
+use ratatui::style::Style;
+use unicode_bidi::{BidiInfo, Level, ParagraphBidiInfo};
+use unicode_width::UnicodeWidthChar as _;
+
+/// A char of a wrapped row, with its style and its byte offset in the
+/// paragraph.
+type Item = (char, Style, usize);
+
+/// The direction of a paragraph, from its first strong character.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Direction {
+    Ltr,
+    Rtl,
+}
+
+/// The direction of `text` when it has right-to-left characters, `None`
+/// when it needs no reordering.
+pub(crate) fn direction(text: &str) -> Option<Direction> {
+    let info = ParagraphBidiInfo::new(text, None);
+    info.has_rtl().then(|| match info.paragraph_level.is_rtl() {
+        true => Direction::Rtl,
+        false => Direction::Ltr,
+    })
+}
+
+/// The embedding level of each char of `text` in a paragraph going
+/// `base`, by the byte offset the char starts at, with trailing whitespace
+/// reset to the paragraph's level (L1).
+pub(crate) fn levels(text: &str, base: Direction) -> Vec<u8> {
+    let level = match base {
+        Direction::Ltr => Level::ltr(),
+        Direction::Rtl => Level::rtl(),
+    };
+    let info = ParagraphBidiInfo::new(text, Some(level));
+    info.reordered_levels(0..text.len()).into_iter().map(|level| level.number()).collect()
+}
+
+/// Puts a row of chars, each with its style and the byte offset `level_of`
+/// takes, from logical into visual order (L2), mirroring the brackets that
+/// end up reading right to left (L4).
+pub(crate) fn reorder(row: &mut Vec<Item>, level_of: impl Fn(usize) -> u8) {
+    // A char and the zero-width ones after it (marks, joiners) move as one.
+    let mut clusters: Vec<(Level, Vec<Item>)> = Vec::new();
+    for item in row.drain(..) {
+        match clusters.last_mut() {
+            Some((_, cluster)) if item.0.width() == Some(0) => cluster.push(item),
+            _ => clusters.push((Level::new(level_of(item.2)).unwrap_or(Level::ltr()), vec![item])),
+        }
+    }
+    let levels: Vec<Level> = clusters.iter().map(|c| c.0).collect();
+    let mut clusters: Vec<Option<(Level, Vec<Item>)>> = clusters.into_iter().map(Some).collect();
+    for i in BidiInfo::reorder_visual(&levels) {
+        let Some((level, cluster)) = clusters[i].take() else {
+            continue;
+        };
+        for (c, style, offset) in cluster {
+            let c = if level.is_rtl() { mirror(c) } else { c };
+            row.push((c, style, offset));
+        }
+    }
+}
+
+fn mirror(c: char) -> char {
+    match c {
+        '(' => ')',
+        ')' => '(',
+        '[' => ']',
+        ']' => '[',
+        '{' => '}',
+        '}' => '{',
+        '<' => '>',
+        '>' => '<',
+        '«' => '»',
+        '»' => '«',
+        c => c,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn visual(text: &str) -> String {
+        let base = direction(text).unwrap();
+        let levels = levels(text, base);
+        let mut row: Vec<_> = text.char_indices().map(|(i, c)| (c, Style::default(), i)).collect();
+        reorder(&mut row, |at| levels[at]);
+        row.into_iter().map(|c| c.0).collect()
+    }
+
+    #[test]
+    fn test_reorders_right_to_left_runs() {
+        assert_eq!(direction("plain text"), None);
+        assert_eq!(direction("see שלום"), Some(Direction::Ltr));
+        assert_eq!(direction("12 שלום world"), Some(Direction::Rtl));
+        // Right-to-left words inside English read from the right, numbers
+        // inside Hebrew from the left.
+        assert_eq!(visual("say שלום עולם now"), "say םלוע םולש now");
+        assert_eq!(visual("שלום 123 עולם"), "םלוע 123 םולש");
+        assert_eq!(visual("שלום (world) עולם."), ".םלוע (world) םולש");
+        assert_eq!(visual("שלום (עולם)"), "(םלוע) םולש");
+        assert_eq!(visual("שלום עולם (a.md) 42"), "42 (a.md) םלוע םולש");
+        assert_eq!(visual("see (שלום) here"), "see (םולש) here");
+        // Marks stay after the letter they sit on.
+        let word = "\u{5e9}\u{5b8}\u{5c1}\u{5dc}\u{5d5}\u{5b9}\u{5dd}";
+        assert_eq!(visual(word), "\u{5dd}\u{5d5}\u{5b9}\u{5dc}\u{5e9}\u{5b8}\u{5c1}");
+    }
+
+    #[test]
+    fn test_explicit_embeddings_and_isolates() {
+        let strip = |s: String| s.chars().filter(|c| !('\u{202a}'..='\u{2069}').contains(c)).collect::<String>();
+        // An override reads right to left with no right-to-left letters.
+        assert_eq!(direction("a \u{202e}bc d\u{202c} e"), Some(Direction::Ltr));
+        assert_eq!(strip(visual("a \u{202e}bc d\u{202c} e")), "a d cb e");
+        // An isolate keeps its numbers from the text around it.
+        assert_eq!(strip(visual("שלום \u{2066}v2 (x)\u{2069} עולם")), "םלוע v2 (x) םולש");
+    }
+}
@@ -0,0 +1,468 @@
+use std::ffi::OsString;
+use std::io::IsTerminal as _;
+use std::path::PathBuf;
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+
+use mdview::LinkStyle;
+use mdview::hyphenate::Language;
+use mdview::images::ImageMode;
+use mdview::palette::ColorDepth;
+
+use crate::config::Width;
+use crate::git::Revision;
+
+/// A terminal markdown viewer.
+///
+/// Without a subcommand, the files open in the interactive viewer.
+#[derive(Debug, Parser)]
+#[command(version, args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+    #[command(flatten)]
+    pub view: ViewArgs,
+    /// Log file watch events, reloads, render times and input events to
+    /// this file.
+    #[arg(long, global = true, value_name = "PATH")]
+    pub debug: Option<PathBuf>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Open files in the interactive viewer (the default).
+    View(ViewArgs),
+    /// Render to stdout, styled with ANSI escapes.
+    Dump(DumpArgs),
+    /// Convert to another format on stdout.
+    Export(ExportArgs),
+    /// Report common markdown problems; exits 1 when there are any.
+    Lint(LintArgs),
+}
+
+/// Options shared by every command. Each overrides the config file.
+#[derive(Debug, Args)]
+pub struct RenderArgs {
+    /// Config file to load instead of ~/.config/mdview/config.toml.
+    #[arg(long, value_name = "PATH")]
+    pub config: Option<PathBuf>,
+    /// Content width in columns, or a percentage of the terminal like 80%.
+    #[arg(short, long, value_name = "N", value_parser = parse_width)]
+    pub width: Option<Width>,
+    /// Cap on the content width; the viewer centers the capped content.
+    #[arg(long, value_name = "N")]
+    pub max_width: Option<u16>,
+    /// Blank columns on each side of the content.
+    #[arg(long, value_name = "N")]
+    pub margin: Option<u16>,
+    /// Color theme: auto (light or dark by the terminal background), dark,
+    /// light, solarized or dracula.
+    #[arg(long, value_name = "NAME")]
+    pub theme: Option<String>,
+    /// Syntect theme for code blocks.
+    #[arg(long, value_name = "NAME")]
+    pub syntax_theme: Option<String>,
+    /// Columns per tab stop in code blocks, table cells and text.
+    #[arg(long, visible_alias = "tab-width", value_name = "N")]
+    pub tab_size: Option<usize>,
+    /// Number the lines of code blocks.
+    #[arg(long)]
+    pub line_numbers: bool,
+    /// Show a language label above fenced code blocks.
+    #[arg(long)]
+    pub language_labels: bool,
+    /// Reorder right-to-left text (Hebrew, Arabic) for terminals that do not.
+    #[arg(long)]
+    pub bidi: bool,
+    /// Hyphenate long words at line ends by the patterns of a language: en,
+    /// de, fr and so on.
+    #[arg(long, value_name = "LANG", value_parser = parse_language)]
+    pub hyphenate: Option<Language>,
+    /// Show control characters (escapes, carriage returns, form feeds) as dim
+    /// symbols, and trailing spaces in code as dots.
+    #[arg(long)]
+    pub show_invisibles: bool,
+    /// Draw borders, bullets, rules and marks with ASCII characters only.
+    #[arg(long)]
+    pub ascii: bool,
+    /// Colors the terminal can show: auto, truecolor, 256 or 16. RGB colors
+    /// are mapped to the nearest one below truecolor.
+    #[arg(long, value_name = "DEPTH", value_parser = parse_color_depth)]
+    pub color_depth: Option<ColorDepth>,
+    /// Number headings by their place in the document: 1., 1.1, 1.2.3.
+    #[arg(long)]
+    pub number_headings: bool,
+    /// How link destinations are shown: inline, osc8, footnote, section or hide.
+    #[arg(long, value_name = "STYLE", value_parser = parse_link_style)]
+    pub link_style: Option<LinkStyle>,
+    /// Open files larger than `max_file_size` in the config (10 MiB unless
+    /// set).
+    #[arg(long)]
+    pub force: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct ViewArgs {
+    /// Markdown files, or http(s) URLs to fetch, one buffer each.
+    #[arg(required_unless_present_any = ["url", "diff_files", "last", "vault"], value_name = "FILE")]
+    pub files: Vec<PathBuf>,
+    /// Fetch a document over HTTP(S); `https://` is assumed without a
+    /// scheme. May be repeated.
+    #[arg(long, value_name = "URL")]
+    pub url: Vec<String>,
+    /// Re-fetch URL documents every SECS seconds, reloading them when they
+    /// change. Files are watched instead.
+    #[arg(long, value_name = "SECS", value_parser = clap::value_parser!(u64).range(1..))]
+    pub poll: Option<u64>,
+    #[command(flatten)]
+    pub render: RenderArgs,
+    /// Jump to the bottom whenever the file changes.
+    #[arg(long)]
+    pub follow: bool,
+    /// Show the status bar.
+    #[arg(long)]
+    pub status_bar: bool,
+    /// Present the files as slides, split at `---` rules and H1/H2 headings.
+    #[arg(long)]
+    pub slides: bool,
+    /// Print word, heading, code block and link counts and the reading time
+    /// of each file instead of opening the viewer.
+    #[arg(long)]
+    pub stats: bool,
+    /// Print the heading tree of each file, with the number of lines of
+    /// each section, instead of opening the viewer.
+    #[arg(long, conflicts_with = "stats")]
+    pub outline: bool,
+    /// Time how long each file takes to render, phase by phase and block by
+    /// block, and print the report with peak memory use instead of opening
+    /// the viewer.
+    #[arg(long, conflicts_with_all = ["stats", "outline"])]
+    pub bench: bool,
+    /// Start at the top instead of where each file was last left.
+    #[arg(long)]
+    pub no_restore: bool,
+    /// Reopen the files that were open when the viewer last quit, showing
+    /// the one that was shown.
+    #[arg(long, conflicts_with_all = ["files", "url"])]
+    pub last: bool,
+    /// Show the files as they were at a git revision, like `HEAD~3` or a
+    /// tag.
+    #[arg(long, value_name = "REV", conflicts_with = "diff")]
+    pub rev: Option<String>,
+    /// Show what changed since a git revision: added blocks get a green
+    /// gutter, and removed blocks are put back with a red one.
+    #[arg(long, value_name = "REV", conflicts_with = "slides")]
+    pub diff: Option<String>,
+    /// Compare two files side by side, their blocks lined up and the
+    /// differences marked, instead of opening the files.
+    #[arg(
+        long,
+        num_args = 2,
+        value_names = ["OLD", "NEW"],
+        conflicts_with_all = ["files", "url", "last", "slides", "rev", "diff", "stats", "outline", "bench", "vault"]
+    )]
+    pub diff_files: Option<Vec<PathBuf>>,
+    /// Show only the section under this heading, with its subsections. The
+    /// heading is found by its anchor (`quick-start`), else by part of its
+    /// title, ignoring case.
+    #[arg(long, value_name = "HEADING", conflicts_with = "diff_files")]
+    pub section: Option<String>,
+    /// Open the first file scrolled to the content of this source line; a
+    /// `+N` argument does the same, as in vim.
+    #[arg(long, value_name = "LINE")]
+    pub goto_line: Option<usize>,
+    /// Treat DIR as an Obsidian vault: `[[Note]]` links and `![[Note]]`
+    /// embeds find notes anywhere in it, `o` opens a quick switcher over
+    /// its notes and `b` lists the notes linking to the current one.
+    /// Without files, opens the note changed last.
+    #[arg(long, value_name = "DIR")]
+    pub vault: Option<PathBuf>,
+    /// Terminal graphics protocol: auto, kitty, iterm2, sixel, blocks (half-block
+    /// art where no protocol is detected) or off.
+    #[arg(long, value_name = "MODE", value_parser = parse_image_mode)]
+    pub images: Option<ImageMode>,
+}
+
+#[derive(Debug, Args)]
+pub struct DumpArgs {
+    /// Markdown files or URLs, rendered one after another.
+    #[arg(required = true, value_name = "FILE")]
+    pub files: Vec<PathBuf>,
+    #[command(flatten)]
+    pub render: RenderArgs,
+    /// When to write ANSI styling.
+    #[arg(long, value_enum, value_name = "WHEN", default_value_t = ColorMode::Auto)]
+    pub color: ColorMode,
+    /// Render only the section under this heading, as in the viewer.
+    #[arg(long, value_name = "HEADING")]
+    pub section: Option<String>,
+    /// Print only the blocks matching PATTERN, each under the headings of
+    /// its sections, with the matches highlighted; exits 1 when nothing
+    /// matches. Smart-case, and `/regex/flags` as in the viewer's search.
+    #[arg(long, value_name = "PATTERN")]
+    pub grep: Option<String>,
+    /// Page output taller than the terminal through $PAGER (less by
+    /// default), when stdout is a terminal.
+    #[arg(long, conflicts_with = "watch")]
+    pub pager: bool,
+    /// Write the output again whenever a file changes, until interrupted.
+    #[arg(long)]
+    pub watch: bool,
+    /// With --watch, clear the screen before each rewrite.
+    #[arg(long, requires = "watch")]
+    pub clear: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct ExportArgs {
+    /// Output format.
+    #[arg(short, long, value_enum)]
+    pub format: Format,
+    /// Markdown files or URLs; HTML, JSON and PDF take exactly one.
+    #[arg(required = true, value_name = "FILE")]
+    pub files: Vec<PathBuf>,
+    #[command(flatten)]
+    pub render: RenderArgs,
+    /// When to write ANSI styling (man format).
+    #[arg(long, value_enum, value_name = "WHEN", default_value_t = ColorMode::Auto)]
+    pub color: ColorMode,
+    /// Columns body text is indented under section headings (man format).
+    #[arg(long, value_name = "N")]
+    pub man_indent: Option<u16>,
+}
+
+#[derive(Debug, Args)]
+pub struct LintArgs {
+    /// Markdown files to check.
+    #[arg(required = true, value_name = "FILE")]
+    pub files: Vec<PathBuf>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    /// Standalone HTML page styled with the theme.
+    Html,
+    /// Wrapped plain text with ASCII markup.
+    Text,
+    /// Man-page layout.
+    Man,
+    /// The parsed document as a JSON tree of blocks and inline runs.
+    Json,
+    /// Paginated PDF styled like the terminal view.
+    Pdf,
+}
+
+/// When output written to stdout carries ANSI styling.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ColorMode {
+    /// Style only when stdout is a terminal and NO_COLOR is unset.
+    Auto,
+    Never,
+    Always,
+}
+
+impl ColorMode {
+    pub fn enabled(self) -> bool {
+        match self {
+            Self::Auto => {
+                std::env::var_os("NO_COLOR").is_none_or(|v| v.is_empty())
+                    && std::io::stdout().is_terminal()
+            }
+            Self::Never => false,
+            Self::Always => true,
+        }
+    }
+}
+
+impl Cli {
+    /// Parses `args`, the program name first, like [`Parser::parse_from`]
+    /// after [`legacy_flags`] turned the output flags of older versions into
+    /// subcommands.
+    pub fn parse_args(args: impl IntoIterator<Item = OsString>) -> Self {
+        Self::parse_from(legacy_flags(args.into_iter().collect()))
+    }
+}
+
+/// `args` with the flags that chose the output before there were
+/// subcommands turned into them, so scripts written for those versions keep
+/// working: `--dump` becomes `dump`, `--plain` `export -f text` and
+/// `--export FMT` (or `--export=FMT`) `export -f FMT`, with the other
+/// arguments after it. Arguments that already start with a subcommand, and
+/// those after `--`, are left alone.
+fn legacy_flags(mut args: Vec<OsString>) -> Vec<OsString> {
+    let subcommand = args.get(1).and_then(|arg| arg.to_str()).is_some_and(|arg| {
+        <Command as Subcommand>::has_subcommand(arg) || arg == "help"
+    });
+    let end = args.iter().position(|arg| arg == "--").unwrap_or(args.len());
+    let flag = args[..end].iter().enumerate().skip(1).find_map(|(i, arg)| {
+        let arg = arg.to_str()?;
+        let format = match arg {
+            "--dump" => return Some((i, 1, None)),
+            "--plain" => "text",
+            "--export" => args.get(i + 1)?.to_str()?,
+            _ => arg.strip_prefix("--export=")?,
+        };
+        Some((i, if arg == "--export" { 2 } else { 1 }, Some(format.to_string())))
+    });
+    let Some((i, len, format)) = flag.filter(|_| !subcommand) else {
+        return args;
+    };
+    args.drain(i..i + len);
+    let inserted = match format {
+        None => vec!["dump".into()],
+        Some(format) => vec!["export".into(), "--format".into(), format.into()],
+    };
+    args.splice(1..1, inserted);
+    args
+}
+
+impl ViewArgs {
+    /// The source line to open the first file at, from `--goto-line` or a
+    /// `+N` argument. The argument is taken out of `files`, unless a file by
+    /// that name exists.
+    pub fn take_goto_line(&mut self) -> Option<usize> {
+        let plus = self.files.iter().position(|f| !f.exists() && plus_line(f).is_some());
+        let line = plus.and_then(|i| plus_line(&self.files.remove(i)));
+        self.goto_line.or(line)
+    }
+
+    /// The revision given by `--rev` or `--diff`, if any.
+    pub fn revision(&self) -> Option<Revision> {
+        match (&self.rev, &self.diff) {
+            (Some(rev), _) => Some(Revision::At(rev.clone())),
+            (_, Some(rev)) => Some(Revision::Diff(rev.clone())),
+            _ => None,
+        }
+    }
+}
+
+fn plus_line(arg: &std::path::Path) -> Option<usize> {
+    arg.to_str()?.strip_prefix('+')?.parse().ok()
+}
+
+fn parse_width(s: &str) -> Result<Width, String> {
+    Width::parse(s).ok_or_else(|| "expected a number of columns or a percentage like 80%".to_string())
+}
+
+fn parse_image_mode(s: &str) -> Result<ImageMode, String> {
+    ImageMode::parse(s).ok_or_else(|| "expected auto, kitty, iterm2, sixel, blocks or off".to_string())
+}
+
+fn parse_color_depth(s: &str) -> Result<ColorDepth, String> {
+    ColorDepth::parse(s).ok_or_else(|| "expected auto, truecolor, 256 or 16".to_string())
+}
+
+fn parse_language(s: &str) -> Result<Language, String> {
+    Language::parse(s).ok_or_else(|| "expected a language with hyphenation patterns, like en or de".to_string())
+}
+
+fn parse_link_style(s: &str) -> Result<LinkStyle, String> {
+    LinkStyle::parse(s).ok_or_else(|| "expected inline, osc8, footnote, section or hide".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+
+    #[test]
+    fn test_cli_is_well_formed() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn test_files_without_subcommand_open_the_viewer() {
+        let cli = Cli::try_parse_from(["mdview", "-w", "80", "a.md", "b.md"]).unwrap();
+        assert!(cli.command.is_none());
+        assert_eq!(cli.view.files, [PathBuf::from("a.md"), PathBuf::from("b.md")]);
+        assert_eq!(cli.view.render.width, Some(Width::Columns(80)));
+        let cli = Cli::try_parse_from(["mdview", "dump", "-w", "80%", "a.md"]).unwrap();
+        let Some(Command::Dump(args)) = cli.command else { panic!("expected dump") };
+        assert_eq!(args.render.width, Some(Width::Percent(80)));
+    }
+
+    #[test]
+    fn test_last_session() {
+        let cli = Cli::try_parse_from(["mdview", "--last"]).unwrap();
+        assert!(cli.view.last && cli.view.files.is_empty());
+        assert!(Cli::try_parse_from(["mdview", "--last", "a.md"]).is_err());
+    }
+
+    #[test]
+    fn test_subcommands() {
+        let cli = Cli::try_parse_from(["mdview", "dump", "--color", "never", "a.md"]).unwrap();
+        assert!(matches!(cli.command, Some(Command::Dump(DumpArgs { color: ColorMode::Never, .. }))));
+        let cli = Cli::try_parse_from(["mdview", "dump", "--grep", "TODO", "a.md"]).unwrap();
+        assert!(matches!(cli.command, Some(Command::Dump(DumpArgs { grep: Some(p), .. })) if p == "TODO"));
+        let cli = Cli::try_parse_from(["mdview", "--section", "Install", "a.md"]).unwrap();
+        assert_eq!(cli.view.section.as_deref(), Some("Install"));
+        let cli = Cli::try_parse_from(["mdview", "export", "-f", "man", "a.md"]).unwrap();
+        assert!(matches!(cli.command, Some(Command::Export(ExportArgs { format: Format::Man, .. }))));
+        let cli = Cli::try_parse_from(["mdview", "lint", "a.md", "b.md"]).unwrap();
+        assert!(matches!(cli.command, Some(Command::Lint(LintArgs { files })) if files.len() == 2));
+        let cli = Cli::try_parse_from(["mdview", "lint", "a.md", "--debug", "log.txt"]).unwrap();
+        assert_eq!(cli.debug, Some(PathBuf::from("log.txt")));
+    }
+
+    #[test]
+    fn test_legacy_output_flags() {
+        let parse = |args: &[&str]| Cli::try_parse_from(legacy_flags(args.iter().map(OsString::from).collect()));
+        let cli = parse(&["mdview", "--dump", "-w", "80", "a.md"]).unwrap();
+        let Some(Command::Dump(args)) = cli.command else { panic!("expected dump") };
+        assert_eq!((args.render.width, args.files), (Some(Width::Columns(80)), vec![PathBuf::from("a.md")]));
+        let cli = parse(&["mdview", "-w", "72", "--plain", "a.md"]).unwrap();
+        assert!(matches!(cli.command, Some(Command::Export(ExportArgs { format: Format::Text, .. }))));
+        let cli = parse(&["mdview", "--export", "man", "--man-indent", "4", "a.md"]).unwrap();
+        let Some(Command::Export(args)) = cli.command else { panic!("expected export") };
+        assert_eq!((args.format, args.man_indent), (Format::Man, Some(4)));
+        let cli = parse(&["mdview", "--export=html", "a.md"]).unwrap();
+        assert!(matches!(cli.command, Some(Command::Export(ExportArgs { format: Format::Html, .. }))));
+        // A file named like a flag, after `--`, is a file.
+        let cli = parse(&["mdview", "--", "--dump"]).unwrap();
+        assert!(cli.command.is_none() && cli.view.files == [PathBuf::from("--dump")]);
+        assert!(parse(&["mdview", "--export", "docx", "a.md"]).is_err());
+    }
+
+    #[test]
+    fn test_goto_line() {
+        let mut cli = Cli::try_parse_from(["mdview", "+120", "a.md"]).unwrap();
+        assert_eq!(cli.view.take_goto_line(), Some(120));
+        assert_eq!(cli.view.files, [PathBuf::from("a.md")]);
+        let mut cli = Cli::try_parse_from(["mdview", "--goto-line", "7", "a.md", "+x"]).unwrap();
+        assert_eq!(cli.view.take_goto_line(), Some(7));
+        assert_eq!(cli.view.files.len(), 2);
+    }
+
+    #[test]
+    fn test_urls() {
+        let cli = Cli::try_parse_from(["mdview", "--url", "host/README.md", "--poll", "30"]).unwrap();
+        assert!(cli.view.files.is_empty());
+        assert_eq!(cli.view.url, ["host/README.md"]);
+        assert_eq!(cli.view.poll, Some(30));
+        assert!(Cli::try_parse_from(["mdview", "--poll", "0", "https://host/a.md"]).is_err());
+    }
+
+    #[test]
+    fn test_revisions() {
+        let cli = Cli::try_parse_from(["mdview", "--rev", "HEAD~3", "a.md"]).unwrap();
+        assert_eq!(cli.view.revision(), Some(Revision::At("HEAD~3".into())));
+        let cli = Cli::try_parse_from(["mdview", "--diff", "main", "a.md"]).unwrap();
+        assert_eq!(cli.view.revision(), Some(Revision::Diff("main".into())));
+        assert!(Cli::try_parse_from(["mdview", "--rev", "a", "--diff", "b", "a.md"]).is_err());
+        assert!(Cli::try_parse_from(["mdview", "--diff", "b", "--slides", "a.md"]).is_err());
+        let cli = Cli::try_parse_from(["mdview", "--diff-files", "old.md", "new.md"]).unwrap();
+        assert_eq!(cli.view.diff_files, Some(vec![PathBuf::from("old.md"), PathBuf::from("new.md")]));
+        assert!(Cli::try_parse_from(["mdview", "--diff-files", "old.md"]).is_err());
+    }
+
+    #[test]
+    fn test_invalid_values_are_rejected() {
+        assert!(Cli::try_parse_from(["mdview"]).is_err());
+        assert!(Cli::try_parse_from(["mdview", "--bogus", "a.md"]).is_err());
+        assert!(Cli::try_parse_from(["mdview", "--images", "png", "a.md"]).is_err());
+        assert!(Cli::try_parse_from(["mdview", "--color-depth", "8", "a.md"]).is_err());
+        assert!(Cli::try_parse_from(["mdview", "export", "-f", "docx", "a.md"]).is_err());
+        assert!(Cli::try_parse_from(["mdview", "--outline", "--stats", "a.md"]).is_err());
+        assert!(Cli::try_parse_from(["mdview", "--bench", "--stats", "a.md"]).is_err());
+    }
+}
@@ -0,0 +1,56 @@
+use std::io::{self, Write};
+
+use base64::Engine as _;
+use base64::engine::general_purpose::STANDARD as BASE64;
+
+/// Where copied text went.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Copied {
+    /// Onto the system clipboard, which took it.
+    System,
+    /// To the terminal as OSC 52, which may or may not pass it on.
+    Terminal,
+}
+
+/// The system clipboard, connected to on the first copy and kept open
+/// after it: on X11 and Wayland the text is served by this process, so it
+/// would be gone with the connection.
+#[derive(Default)]
+pub struct Clipboard {
+    system: Option<arboard::Clipboard>,
+}
+
+impl Clipboard {
+    /// Puts `text` on the system clipboard, or, without one to connect to
+    /// (over SSH, on a console), sends it to the terminal with [`osc52`].
+    pub fn copy(&mut self, out: &mut impl Write, text: &str) -> io::Result<Copied> {
+        if self.system.is_none() {
+            self.system = arboard::Clipboard::new().ok();
+        }
+        match self.system.as_mut().map(|c| c.set_text(text)) {
+            Some(Ok(())) => Ok(Copied::System),
+            _ => osc52(out, text).map(|()| Copied::Terminal),
+        }
+    }
+}
+
+/// Puts `text` on the clipboard with the OSC 52 escape sequence, which the
+/// terminal (or an SSH client's terminal) handles, so copying works without
+/// a display server connection. Terminals give no answer, so whether it
+/// worked cannot be known.
+pub fn osc52(out: &mut impl Write, text: &str) -> io::Result<()> {
+    write!(out, "\x1b]52;c;{}\x07", BASE64.encode(text))?;
+    out.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_osc52() {
+        let mut out = Vec::new();
+        osc52(&mut out, "hi").unwrap();
+        assert_eq!(out, b"\x1b]52;c;aGk=\x07");
+    }
+}
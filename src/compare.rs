@@ -0,0 +1,215 @@
+use std::io;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::mpsc;
+use std::time::Duration;
+
+use anyhow::Result;
+use crossterm::event::{self, Event, MouseEventKind};
+use ratatui::Terminal;
+use ratatui::backend::CrosstermBackend;
+use ratatui::layout::Rect;
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span, Text};
+use ratatui::widgets::{Block, Borders, Paragraph};
+use unicode_width::UnicodeWidthStr as _;
+
+use mdview::RenderOptions;
+use mdview::diff::{SideBySide, side_by_side};
+use mdview::palette::{self, ColorDepth};
+
+use crate::keymap::{Action, Keymap};
+use crate::watch;
+
+/// Shows the files at `paths`, old then new, side by side until quit, with
+/// one scroll position for both. `read` reads a file as markdown and
+/// `options` gives the render options for a pane that many columns wide,
+/// and `depth` the colors the terminal has. Either file changing on disk
+/// updates the comparison.
+pub fn run(
+    terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
+    keymap: &Keymap,
+    paths: [PathBuf; 2],
+    read: impl Fn(&Path) -> Result<String>,
+    options: impl Fn(u16) -> RenderOptions,
+    depth: ColorDepth,
+) -> Result<ExitCode> {
+    let (tx, rx) = mpsc::channel();
+    let _watchers = [watch::setup(&paths[0], tx.clone())?, watch::setup(&paths[1], tx)?];
+    let mut sources = [read(&paths[0])?, read(&paths[1])?];
+    let mut diff = SideBySide::default();
+    // Pane width `diff` was laid out for; 0 lays it out again.
+    let mut laid_out = 0;
+    let mut scroll = 0;
+    let mut message: Option<String> = None;
+
+    loop {
+        let size = terminal.size()?;
+        let pane = size.width.saturating_sub(1) / 2;
+        let rows = size.height.saturating_sub(1);
+        if pane != laid_out {
+            diff = side_by_side(&sources[0], &sources[1], &options(pane));
+            laid_out = pane;
+        }
+        let max = diff.old.lines.len().saturating_sub(rows as usize);
+        scroll = scroll.min(max);
+
+        terminal.draw(|f| {
+            let shown = |text: &Text<'static>| {
+                Text::from(text.lines.iter().skip(scroll).take(rows as usize).cloned().collect::<Vec<_>>())
+            };
+            let border = Block::default().borders(Borders::RIGHT).border_style(Style::default().fg(Color::DarkGray));
+            f.render_widget(Paragraph::new(shown(&diff.old)).block(border), Rect::new(0, 0, pane + 1, rows));
+            f.render_widget(Paragraph::new(shown(&diff.new)), Rect::new(size.width - pane, 0, pane, rows));
+            let status = status_line(&paths, &diff, scroll, rows, size.width, message.as_deref());
+            f.render_widget(Paragraph::new(status), Rect::new(0, rows, size.width, 1));
+            palette::quantize_buffer(f.buffer_mut(), depth);
+        })?;
+
+        if rx.try_iter().count() > 0 {
+            match reread(&paths, &read) {
+                Ok(read) => {
+                    sources = read;
+                    laid_out = 0;
+                }
+                Err(e) => message = Some(format!("{e:#}")),
+            }
+        }
+        if !event::poll(Duration::from_millis(50))? {
+            continue;
+        }
+        let page = rows as usize;
+        match event::read()? {
+            Event::Key(key) => {
+                message = None;
+                match keymap.action(key) {
+                    Some(Action::Quit | Action::Cancel) => return Ok(ExitCode::SUCCESS),
+                    Some(Action::QuitWithError) => return Ok(ExitCode::FAILURE),
+                    Some(Action::ScrollDown) => scroll += 1,
+                    Some(Action::ScrollUp) => scroll = scroll.saturating_sub(1),
+                    Some(Action::HalfPageDown) => scroll += page / 2,
+                    Some(Action::HalfPageUp) => scroll = scroll.saturating_sub(page / 2),
+                    Some(Action::PageDown) => scroll += page,
+                    Some(Action::PageUp) => scroll = scroll.saturating_sub(page),
+                    Some(Action::Top) => scroll = 0,
+                    Some(Action::Bottom) => scroll = max,
+                    Some(Action::NextMatch) => match change_scroll(&diff.changes, scroll, max, true) {
+                        Some(to) => scroll = to,
+                        None => message = Some("No more changes".to_string()),
+                    },
+                    Some(Action::PrevMatch) => match change_scroll(&diff.changes, scroll, max, false) {
+                        Some(to) => scroll = to,
+                        None => message = Some("No earlier changes".to_string()),
+                    },
+                    Some(Action::Reload) => match reread(&paths, &read) {
+                        Ok(read) => {
+                            sources = read;
+                            laid_out = 0;
+                        }
+                        Err(e) => message = Some(format!("{e:#}")),
+                    },
+                    _ => {}
+                }
+            }
+            Event::Mouse(mouse) => match mouse.kind {
+                MouseEventKind::ScrollDown => scroll += 3,
+                MouseEventKind::ScrollUp => scroll = scroll.saturating_sub(3),
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+}
+
+/// Where the next change (`forward`) or the previous one takes a view
+/// scrolled to `scroll`, at most `max`, if there is one. A change that
+/// starts on the last screen is reached at `max`, so once there it is not
+/// found again.
+fn change_scroll(changes: &[Range<usize>], scroll: usize, max: usize, forward: bool) -> Option<usize> {
+    let mut starts = changes.iter().map(|change| change.start.min(max));
+    match forward {
+        true => starts.find(|&start| start > scroll),
+        false => starts.rev().find(|&start| start < scroll),
+    }
+}
+
+fn reread(paths: &[PathBuf; 2], read: impl Fn(&Path) -> Result<String>) -> Result<[String; 2]> {
+    Ok([read(&paths[0])?, read(&paths[1])?])
+}
+
+/// ` old.md → new.md  2 changes ...... L12  40%`, with `message` in place
+/// of the file names when there is one.
+fn status_line(
+    paths: &[PathBuf; 2],
+    diff: &SideBySide,
+    scroll: usize,
+    rows: u16,
+    width: u16,
+    message: Option<&str>,
+) -> Line<'static> {
+    let name = |path: &PathBuf| path.file_name().map_or_else(String::new, |n| n.to_string_lossy().into_owned());
+    let mut left = match message {
+        Some(message) => format!(" {message}"),
+        None => {
+            let changes = match diff.changes.len() {
+                0 => "no changes".to_string(),
+                1 => "1 change".to_string(),
+                n => format!("{n} changes"),
+            };
+            format!(" {} → {}  {changes}", name(&paths[0]), name(&paths[1]))
+        }
+    };
+    let max = diff.old.lines.len().saturating_sub(rows as usize);
+    let right = format!("L{}  {}", scroll + 1, match scroll {
+        _ if max == 0 => "All ".to_string(),
+        0 => "Top ".to_string(),
+        s if s >= max => "Bot ".to_string(),
+        s => format!("{}% ", s * 100 / max),
+    });
+
+    let room = (width as usize).saturating_sub(right.width());
+    if left.width() > room {
+        while left.width() + 1 > room && left.pop().is_some() {}
+        left.push('…');
+    }
+    let pad = " ".repeat(room.saturating_sub(left.width()));
+    Line::from(Span::styled(format!("{left}{pad}{right}"), Style::default().add_modifier(Modifier::REVERSED)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn diff(lines: usize, changes: Vec<Range<usize>>) -> SideBySide {
+        let text = Text::from(vec![Line::default(); lines]);
+        SideBySide { old: text.clone(), new: text, changes }
+    }
+
+    #[test]
+    fn test_status_line() {
+        let paths = [PathBuf::from("docs/old.md"), PathBuf::from("new.md")];
+        let status = |diff: &SideBySide, scroll, width, message| {
+            status_line(&paths, diff, scroll, 10, width, message).to_string()
+        };
+        let two = diff(30, vec![2..4, 20..21]);
+        assert_eq!(status(&two, 0, 40, None), " old.md → new.md  2 changes     L1  Top ");
+        assert_eq!(status(&two, 10, 40, None), " old.md → new.md  2 changes    L11  50% ");
+        assert_eq!(status(&two, 20, 40, None), " old.md → new.md  2 changes    L21  Bot ");
+        let one = diff(5, vec![Range { start: 1, end: 2 }]);
+        assert_eq!(status(&one, 0, 36, None), " old.md → new.md  1 change  L1  All ");
+        assert_eq!(status(&diff(5, vec![]), 0, 26, Some("No more changes")), " No more changes  L1  All ");
+        assert_eq!(status(&two, 0, 20, None), " old.md → n…L1  Top ");
+    }
+
+    #[test]
+    fn test_change_navigation() {
+        let changes = [2..4, 10..12, 28..29];
+        // The last change starts past the last screen, at 20.
+        let (next, prev) = (|at| change_scroll(&changes, at, 20, true), |at| change_scroll(&changes, at, 20, false));
+        assert_eq!((next(0), next(2), next(10)), (Some(2), Some(10), Some(20)));
+        assert_eq!(next(20), None);
+        assert_eq!((prev(20), prev(10), prev(2)), (Some(10), Some(2), None));
+        assert_eq!(change_scroll(&[], 0, 0, true), None);
+    }
+}
@@ -0,0 +1,337 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+
+use mdview::{LinkStyle, TableOverflow};
+use mdview::hyphenate::Language;
+use mdview::images::ImageMode;
+use mdview::palette::ColorDepth;
+
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Content width, in columns or as a percentage of the terminal such as
+    /// `"80%"`. The terminal width is used when unset; a narrower terminal
+    /// always wins.
+    pub width: Option<Width>,
+    /// Upper bound on the content width. The viewer centers content capped by
+    /// it, which keeps lines readable on very wide terminals.
+    pub max_width: Option<u16>,
+    /// Blank columns on each side of the content.
+    pub margin: u16,
+    /// Color theme: `auto`, `dark`, `light`, `solarized` or `dracula`. `auto`
+    /// picks `light` or `dark` by the terminal background.
+    pub theme: String,
+    /// Name of a syntect theme used for code blocks. Defaults to the one
+    /// paired with `theme`.
+    pub syntax_theme: Option<String>,
+    /// Columns per tab stop when expanding tabs in code blocks, table cells
+    /// and text. `tab_width` is accepted too.
+    #[serde(alias = "tab_width")]
+    pub tab_size: usize,
+    /// Number the lines of code blocks.
+    pub line_numbers: bool,
+    /// Show a `─ lang ───` header above fenced code blocks.
+    pub language_labels: bool,
+    /// Narrowest a table column is squeezed to on a narrow terminal.
+    pub table_min_column_width: usize,
+    /// Lines a wrapped table cell may take before it is cut off with `…`.
+    pub table_max_cell_lines: usize,
+    /// What the viewer does with tables wider than the content: `wrap`
+    /// squeezes them, `scroll` keeps their columns whole and scrolls them
+    /// sideways with Left/Right.
+    pub table_overflow: TableOverflow,
+    /// How link destinations are shown: `inline` after the text, `osc8`
+    /// as clickable text in terminals that support OSC 8 hyperlinks,
+    /// `footnote` as numbers listed at the end, `section` as superscript
+    /// numbers listed at the end of each section, or `hide`.
+    pub link_style: LinkStyle,
+    /// Draw table borders, bullets, quote bars, rules, task marks and
+    /// ellipses with ASCII characters, for fonts that lack the Unicode ones.
+    pub ascii: bool,
+    /// Number headings by their place in the document: `1.`, `1.1`,
+    /// `1.2.3`.
+    pub number_headings: bool,
+    /// Turn `:rocket:`-style shortcodes into emoji.
+    pub emoji: bool,
+    /// Draw `[[Page]]` wiki links as links to the page's Markdown file.
+    pub wiki_links: bool,
+    /// Put right-to-left text in visual order and right-to-left paragraphs
+    /// on the right, for terminals that do not reorder text themselves.
+    pub bidi: bool,
+    /// Hyphenate long words at line ends by the patterns of a language,
+    /// given as a code like `"en"` or `"de"`. Off when unset.
+    pub hyphenate: Option<Language>,
+    /// Show control characters as dim symbols and trailing spaces in code
+    /// as dots instead of dropping the control characters.
+    pub show_invisibles: bool,
+    /// Lines scrolled per `j`/`k` press.
+    pub scroll_step: u16,
+    /// Show the status bar (file, heading, position) at the bottom.
+    pub status_bar: bool,
+    /// Terminal graphics protocol for images: `auto`, `kitty`, `iterm2`,
+    /// `sixel`, `blocks` (half-block art without a protocol) or `off`.
+    pub images: ImageMode,
+    /// Most rows an image drawn with half blocks takes.
+    pub block_image_rows: u16,
+    /// Colors the terminal can show: `auto` (from `COLORTERM` and `TERM`),
+    /// `truecolor`, `256` or `16`. Below `truecolor`, RGB colors are drawn
+    /// as the nearest palette entry.
+    pub color_depth: ColorDepth,
+    /// Largest file opened, in bytes or with a unit like `"50M"`; larger
+    /// ones need `--force`.
+    pub max_file_size: FileSize,
+    /// Key bindings by action name, e.g. `scroll_down = ["j", "Down"]`.
+    /// Listed actions replace their default keys.
+    pub keys: BTreeMap<String, Vec<String>>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            width: None,
+            max_width: None,
+            margin: 0,
+            theme: "auto".to_string(),
+            syntax_theme: None,
+            tab_size: 4,
+            line_numbers: false,
+            language_labels: false,
+            table_min_column_width: 5,
+            table_max_cell_lines: 5,
+            table_overflow: TableOverflow::Wrap,
+            link_style: LinkStyle::Inline,
+            ascii: false,
+            number_headings: false,
+            emoji: true,
+            wiki_links: true,
+            bidi: false,
+            hyphenate: None,
+            show_invisibles: false,
+            scroll_step: 1,
+            status_bar: false,
+            images: ImageMode::Auto,
+            block_image_rows: 12,
+            color_depth: ColorDepth::Auto,
+            max_file_size: FileSize::DEFAULT,
+            keys: BTreeMap::new(),
+        }
+    }
+}
+
+impl Config {
+    /// Loads the config from `path`, or from the default location when `None`.
+    /// A missing default config is not an error; a missing explicit one is.
+    pub fn load(path: Option<&Path>) -> Result<Self> {
+        let (path, explicit) = match path {
+            Some(p) => (p.to_path_buf(), true),
+            None => match default_path() {
+                Some(p) => (p, false),
+                None => return Ok(Self::default()),
+            },
+        };
+
+        let raw = match std::fs::read_to_string(&path) {
+            Ok(raw) => raw,
+            Err(e) if !explicit && e.kind() == std::io::ErrorKind::NotFound => {
+                return Ok(Self::default());
+            }
+            Err(e) => {
+                return Err(e).with_context(|| format!("Cannot read config {}", path.display()));
+            }
+        };
+        Self::parse(&raw).with_context(|| format!("Invalid config {}", path.display()))
+    }
+
+    pub fn parse(raw: &str) -> Result<Self> {
+        Ok(toml::from_str(raw)?)
+    }
+}
+
+/// A content width: a number of columns, or a percentage of the space
+/// available.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "RawWidth")]
+pub enum Width {
+    Columns(u16),
+    Percent(u16),
+}
+
+impl Width {
+    /// Parses `80` or `80%`.
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim().strip_suffix('%') {
+            Some(p) => p.trim().parse().ok().filter(|p| (1..=100).contains(p)).map(Self::Percent),
+            None => s.trim().parse().ok().map(Self::Columns),
+        }
+    }
+
+    /// The width in columns out of `available`.
+    pub fn columns(self, available: u16) -> u16 {
+        match self {
+            Self::Columns(n) => n,
+            Self::Percent(p) => (available as u32 * p as u32 / 100) as u16,
+        }
+    }
+}
+
+/// `width` as written in the config: `100` or `"80%"`.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawWidth {
+    Columns(u16),
+    Text(String),
+}
+
+impl TryFrom<RawWidth> for Width {
+    type Error = String;
+
+    fn try_from(raw: RawWidth) -> Result<Self, String> {
+        match raw {
+            RawWidth::Columns(n) => Ok(Self::Columns(n)),
+            RawWidth::Text(s) => Self::parse(&s)
+                .ok_or_else(|| format!("invalid width '{s}', expected columns or a percentage")),
+        }
+    }
+}
+
+/// A size in bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "RawFileSize")]
+pub struct FileSize(pub u64);
+
+impl FileSize {
+    pub const DEFAULT: Self = Self(10 * 1024 * 1024);
+
+    /// Parses `1048576`, or a number with a unit: `500K`, `10M`, `1G`, also
+    /// written `KB`/`KiB` and so on. Units are powers of 1024.
+    pub fn parse(s: &str) -> Option<Self> {
+        let s = s.trim();
+        let digits = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+        let (number, unit) = s.split_at(digits);
+        let shift = match unit.trim().to_ascii_uppercase().as_str() {
+            "" | "B" => 0,
+            "K" | "KB" | "KIB" => 10,
+            "M" | "MB" | "MIB" => 20,
+            "G" | "GB" | "GIB" => 30,
+            _ => return None,
+        };
+        number.parse::<u64>().ok()?.checked_mul(1 << shift).map(Self)
+    }
+}
+
+impl fmt::Display for FileSize {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let units = [(1 << 30, "GiB"), (1 << 20, "MiB"), (1 << 10, "KiB")];
+        match units.iter().find(|(size, _)| self.0 >= *size) {
+            Some((size, unit)) => write!(f, "{:.1} {unit}", self.0 as f64 / *size as f64),
+            None => write!(f, "{} bytes", self.0),
+        }
+    }
+}
+
+/// `max_file_size` as written in the config: `1048576` or `"10M"`.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawFileSize {
+    Bytes(u64),
+    Text(String),
+}
+
+impl TryFrom<RawFileSize> for FileSize {
+    type Error = String;
+
+    fn try_from(raw: RawFileSize) -> Result<Self, String> {
+        match raw {
+            RawFileSize::Bytes(n) => Ok(Self(n)),
+            RawFileSize::Text(s) => {
+                Self::parse(&s).ok_or_else(|| format!("invalid size '{s}', expected bytes or a size like 10M"))
+            }
+        }
+    }
+}
+
+/// `$XDG_CONFIG_HOME/mdview/config.toml`, falling back to `~/.config`.
+pub fn default_path() -> Option<PathBuf> {
+    Some(xdg_dir("XDG_CONFIG_HOME", ".config")?.join("config.toml"))
+}
+
+/// `$XDG_CONFIG_HOME/mdview/syntaxes`, where `.sublime-syntax` files add
+/// languages to highlight.
+pub fn syntaxes_dir() -> Option<PathBuf> {
+    Some(xdg_dir("XDG_CONFIG_HOME", ".config")?.join("syntaxes"))
+}
+
+/// `$XDG_CACHE_HOME/mdview`, falling back to `~/.cache`.
+pub fn cache_dir() -> Option<PathBuf> {
+    xdg_dir("XDG_CACHE_HOME", ".cache")
+}
+
+/// The `mdview` directory in the base directory `var` names, or in
+/// `fallback` under the home directory when it is unset.
+fn xdg_dir(var: &str, fallback: &str) -> Option<PathBuf> {
+    let base = std::env::var_os(var)
+        .filter(|v| !v.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|h| PathBuf::from(h).join(fallback)))?;
+    Some(base.join("mdview"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_partial_config() {
+        let config = Config::parse("width = 100\ntab_size = 2\n").unwrap();
+        assert_eq!(config.width, Some(Width::Columns(100)));
+        assert_eq!(config.tab_size, 2);
+        assert_eq!(Config::parse("tab_width = 8\n").unwrap().tab_size, 8);
+        assert_eq!(config.scroll_step, 1, "Unset keys keep their defaults");
+        assert_eq!(config.table_max_cell_lines, 5);
+        let config = Config::parse("table_min_column_width = 8\ntable_max_cell_lines = 2\n").unwrap();
+        assert_eq!((config.table_min_column_width, config.table_max_cell_lines), (8, 2));
+        assert_eq!(Config::parse("table_overflow = \"scroll\"\n").unwrap().table_overflow, TableOverflow::Scroll);
+        assert_eq!(Config::parse("link_style = \"osc8\"\n").unwrap().link_style, LinkStyle::Osc8);
+        assert_eq!(Config::parse("color_depth = \"256\"\n").unwrap().color_depth, ColorDepth::Ansi256);
+        assert_eq!(Config::parse("hyphenate = \"de\"\n").unwrap().hyphenate, Language::parse("de"));
+        assert!(Config::parse("hyphenate = \"klingon\"\n").is_err());
+    }
+
+    #[test]
+    fn test_parse_widths() {
+        let config = Config::parse("width = \"80%\"\nmax_width = 120\n").unwrap();
+        assert_eq!(config.width, Some(Width::Percent(80)));
+        assert_eq!(config.max_width, Some(120));
+        assert_eq!(Width::Percent(50).columns(81), 40);
+        assert_eq!(Width::parse("72"), Some(Width::Columns(72)));
+        assert!(Config::parse("width = \"wide\"\n").is_err());
+        assert!(Config::parse("width = \"150%\"\n").is_err());
+    }
+
+    #[test]
+    fn test_parse_file_sizes() {
+        assert_eq!(Config::default().max_file_size, FileSize(10 * 1024 * 1024));
+        assert_eq!(Config::parse("max_file_size = 4096\n").unwrap().max_file_size, FileSize(4096));
+        assert_eq!(Config::parse("max_file_size = \"50M\"\n").unwrap().max_file_size, FileSize(50 << 20));
+        assert_eq!(FileSize::parse("512 KiB"), Some(FileSize(512 << 10)));
+        assert_eq!(FileSize::parse("1gb"), Some(FileSize(1 << 30)));
+        assert!(Config::parse("max_file_size = \"big\"\n").is_err());
+        assert_eq!(FileSize(10 << 20).to_string(), "10.0 MiB");
+        assert_eq!(FileSize(500).to_string(), "500 bytes");
+    }
+
+    #[test]
+    fn test_parse_key_bindings() {
+        let config = Config::parse("[keys]\nscroll_down = [\"j\", \"Down\"]\n").unwrap();
+        assert_eq!(config.keys["scroll_down"], ["j", "Down"]);
+    }
+
+    #[test]
+    fn test_parse_rejects_unknown_keys() {
+        assert!(Config::parse("widht = 100\n").is_err());
+    }
+}
@@ -0,0 +1,103 @@
+/// `body` as a fenced code block in language `lang`, fenced with more
+/// backticks than any run of them in `body`.
+pub(crate) fn fenced(body: &str, lang: &str) -> String {
+    let longest = body.split(|c| c != '`').map(str::len).max().unwrap_or(0);
+    let fence = "`".repeat(longest.max(2) + 1);
+    let body = body.strip_suffix('\n').unwrap_or(body);
+    format!("{fence}{lang}\n{body}\n{fence}\n")
+}
+
+/// Splits `input` into rows of fields. Fields may be quoted with `"`, which
+/// lets them hold the delimiter, newlines and `""` for a quote. Blank lines
+/// are skipped.
+pub(crate) fn parse(input: &str, delimiter: char) -> Vec<Vec<String>> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = input.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' if quoted => quoted = false,
+            '"' if field.is_empty() => quoted = true,
+            _ if quoted => field.push(c),
+            '\r' if chars.peek() == Some(&'\n') => {}
+            '\n' => {
+                row.push(std::mem::take(&mut field));
+                if row.len() > 1 || !row[0].is_empty() {
+                    rows.push(row);
+                }
+                row = Vec::new();
+            }
+            _ if c == delimiter => row.push(std::mem::take(&mut field)),
+            _ => field.push(c),
+        }
+    }
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+    rows
+}
+
+/// Whether `field` reads as a number: digits with an optional sign,
+/// decimal point, exponent, thousands separators, leading currency sign or
+/// trailing `%`.
+pub(crate) fn is_numeric(field: &str) -> bool {
+    let field = field.trim();
+    let field = field.strip_suffix('%').unwrap_or(field);
+    let field = field.strip_prefix(['$', '€', '£', '¥']).unwrap_or(field);
+    let field = field.replace(',', "");
+    field.bytes().any(|b| b.is_ascii_digit()) && field.parse::<f64>().is_ok_and(f64::is_finite)
+}
+
+/// Whether the first of `rows` names the columns rather than holding data:
+/// it does unless one of its fields is a number.
+pub(crate) fn has_header(rows: &[Vec<String>]) -> bool {
+    rows.first().is_some_and(|row| !row.iter().any(|field| is_numeric(field)))
+}
+
+/// Spreadsheet-style name of column `index`: `A` to `Z`, then `AA`.
+pub(crate) fn column_name(index: usize) -> String {
+    let mut name = String::new();
+    let mut n = index + 1;
+    while n > 0 {
+        n -= 1;
+        name.insert(0, (b'A' + (n % 26) as u8) as char);
+        n /= 26;
+    }
+    name
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_quoted_fields() {
+        let rows = parse("name,note\r\n\"Smith, J\",\"said \"\"hi\"\"\nthen left\"\n\nlast,\n", ',');
+        assert_eq!(rows, [
+            vec!["name", "note"],
+            vec!["Smith, J", "said \"hi\"\nthen left"],
+            vec!["last", ""],
+        ]);
+        assert_eq!(parse("a\tb\n1\t2", '\t'), [["a", "b"], ["1", "2"]]);
+    }
+
+    #[test]
+    fn test_numbers_and_headers() {
+        for field in ["42", "-3.5", "1,234", "$9.99", "12%", "1e3"] {
+            assert!(is_numeric(field), "{field}");
+        }
+        for field in ["", "abc", "inf", "NaN", "1.2.3", "-"] {
+            assert!(!is_numeric(field), "{field}");
+        }
+        assert!(has_header(&parse("city,population\nOslo,709000\n", ',')));
+        assert!(!has_header(&parse("Oslo,709000\nBergen,291000\n", ',')));
+        assert_eq!([column_name(0), column_name(25), column_name(26), column_name(27)], ["A", "Z", "AA", "AB"]);
+    }
+}
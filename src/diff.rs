@@ -0,0 +1,296 @@
+use std::ops::Range;
+
+use pulldown_cmark::Parser;
+use ratatui::style::{Color, Style};
+use ratatui::text::{Line, Span, Text};
+
+use crate::incremental::{render_blocks, split_blocks};
+use crate::render::RenderOptions;
+
+/// Largest number of block pairs compared; past it, the differing middle of
+/// two documents is shown as all removed and all added.
+const MAX_COMPARISONS: usize = 4_000_000;
+
+/// Put on both sides of a removed block, so it neither joins its neighbours
+/// (as two lists would) nor renders as anything itself.
+const SEPARATOR: &str = "<!-- -->\n\n";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Change {
+    Added,
+    Removed,
+}
+
+/// A block of [`Diff::text`] that was added or removed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChangedBlock {
+    pub change: Change,
+    /// Source lines of the block in the merged text, from 0.
+    pub lines: Range<usize>,
+}
+
+/// Two versions of a document merged into one for display.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diff {
+    /// The new version with each removed block put back where it was.
+    pub text: String,
+    pub blocks: Vec<ChangedBlock>,
+}
+
+/// Compares `old` and `new` a top-level block (paragraph, list, code block,
+/// table, ...) at a time. A block edited in any way counts as the old one
+/// removed and the new one added.
+///
+/// ```
+/// use mdview::diff::{Change, diff};
+///
+/// let diff = diff("# Notes\n\nOld line.\n", "# Notes\n\nNew line.\n");
+/// assert_eq!(diff.text, "# Notes\n\n<!-- -->\n\nOld line.\n\n<!-- -->\n\nNew line.\n");
+/// assert_eq!(diff.blocks[0].change, Change::Removed);
+/// assert_eq!(diff.blocks[1].lines, 8..9);
+/// ```
+pub fn diff(old: &str, new: &str) -> Diff {
+    let old_blocks = blocks(old);
+    let new_blocks = blocks(new);
+    let old_text: Vec<&str> = old_blocks.iter().map(|r| old[r.clone()].trim_end()).collect();
+    let new_text: Vec<&str> = new_blocks.iter().map(|r| new[r.clone()].trim_end()).collect();
+
+    let mut text = String::new();
+    // Lines in `text`, kept as it grows.
+    let mut lines = 0;
+    let mut changed = Vec::new();
+    let mut pos = 0;
+    for step in steps(&old_text, &new_text) {
+        // What lies between blocks, blank lines and link definitions, comes
+        // from the new version.
+        let next = match step {
+            Step::Keep(_, i) | Step::Add(i) => new_blocks[i].start,
+            Step::Remove(_) => new_blocks.iter().find(|r| r.start >= pos).map_or(new.len(), |r| r.start),
+        };
+        push(&mut text, &mut lines, &new[pos..next]);
+        pos = next;
+        let (change, block) = match step {
+            Step::Keep(_, i) => {
+                push(&mut text, &mut lines, &new[new_blocks[i].clone()]);
+                pos = new_blocks[i].end;
+                continue;
+            }
+            Step::Add(i) => {
+                pos = new_blocks[i].end;
+                (Change::Added, &new[new_blocks[i].clone()])
+            }
+            Step::Remove(i) => {
+                while !text.is_empty() && !text.ends_with("\n\n") {
+                    push(&mut text, &mut lines, "\n");
+                }
+                if !text.is_empty() {
+                    push(&mut text, &mut lines, SEPARATOR);
+                }
+                (Change::Removed, old_text[i])
+            }
+        };
+        let start = lines;
+        let end = start + block.trim_end_matches('\n').matches('\n').count() + 1;
+        push(&mut text, &mut lines, block);
+        if change == Change::Removed {
+            push(&mut text, &mut lines, "\n\n");
+            push(&mut text, &mut lines, SEPARATOR);
+        }
+        changed.push(ChangedBlock { change, lines: start..end });
+    }
+    text.push_str(&new[pos..]);
+    Diff { text, blocks: changed }
+}
+
+/// Appends `s` to `text`, counting its lines into `lines`.
+fn push(text: &mut String, lines: &mut usize, s: &str) {
+    *lines += s.matches('\n').count();
+    text.push_str(s);
+}
+
+/// Two documents rendered to be read side by side. Both have the same
+/// number of lines, and a block found in both starts on the same line of
+/// each.
+#[derive(Debug, Clone, Default)]
+pub struct SideBySide {
+    pub old: Text<'static>,
+    pub new: Text<'static>,
+    /// Lines where the documents differ, one range per run of changed
+    /// blocks.
+    pub changes: Vec<Range<usize>>,
+}
+
+/// Renders `old` and `new` for panes `options.width` columns wide and lines
+/// them up block by block, like [`diff`]. Removed blocks get a red bar at
+/// the left of the old pane, added ones a green bar in the new pane, and
+/// blank lines fill in across from them; blocks edited in place face each
+/// other.
+pub fn side_by_side(old: &str, new: &str, options: &RenderOptions) -> SideBySide {
+    let mut options = options.clone();
+    options.width = options.width.saturating_sub(GUTTER.len() as u16).max(1);
+    let old_blocks = blocks(old);
+    let new_blocks = blocks(new);
+    let old_text: Vec<&str> = old_blocks.iter().map(|r| old[r.clone()].trim_end()).collect();
+    let new_text: Vec<&str> = new_blocks.iter().map(|r| new[r.clone()].trim_end()).collect();
+    let old_lines = render_blocks(old, &old_blocks, &options);
+    let new_lines = render_blocks(new, &new_blocks, &options);
+
+    let mut view = SideBySide { old: Text::default(), new: Text::default(), changes: Vec::new() };
+    let (mut removed, mut added) = (Vec::new(), Vec::new());
+    let unchanged = |lines: &[Line<'static>]| lines.iter().map(|l| gutter(l, None)).collect();
+    for step in steps(&old_text, &new_text) {
+        match step {
+            Step::Remove(i) => removed.extend(marked(&old_lines[i], Color::Red)),
+            Step::Add(j) => added.extend(marked(&new_lines[j], Color::Green)),
+            Step::Keep(i, j) => {
+                view.push_change(&mut removed, &mut added);
+                view.push(unchanged(&old_lines[i]), unchanged(&new_lines[j]));
+            }
+        }
+    }
+    view.push_change(&mut removed, &mut added);
+    view
+}
+
+impl SideBySide {
+    /// Appends the blocks `removed` and `added` since the last unchanged
+    /// one, if any, as a change.
+    fn push_change(&mut self, removed: &mut Vec<Line<'static>>, added: &mut Vec<Line<'static>>) {
+        if removed.is_empty() && added.is_empty() {
+            return;
+        }
+        let start = self.old.lines.len();
+        self.push(std::mem::take(removed), std::mem::take(added));
+        self.changes.push(start..self.old.lines.len());
+    }
+
+    /// Appends `old` and `new` facing each other, the shorter padded with
+    /// blank lines.
+    fn push(&mut self, mut old: Vec<Line<'static>>, mut new: Vec<Line<'static>>) {
+        let len = old.len().max(new.len());
+        old.resize(len, Line::default());
+        new.resize(len, Line::default());
+        self.old.lines.extend(old);
+        self.new.lines.extend(new);
+    }
+}
+
+/// Columns in front of every line for the change bar.
+const GUTTER: &str = "  ";
+
+/// `lines` with a bar of `color` in front.
+fn marked(lines: &[Line<'static>], color: Color) -> Vec<Line<'static>> {
+    lines.iter().map(|line| gutter(line, Some(color))).collect()
+}
+
+/// `line` behind the gutter, with a bar in it when `color` is given.
+fn gutter(line: &Line<'static>, color: Option<Color>) -> Line<'static> {
+    let bar = match color {
+        Some(color) => Span::styled("▌ ", Style::default().fg(color)),
+        None => Span::raw(GUTTER),
+    };
+    let mut spans = vec![bar];
+    spans.extend(line.spans.iter().cloned());
+    Line::from(spans).style(line.style)
+}
+
+/// The byte ranges of the top-level blocks of `input`.
+fn blocks(input: &str) -> Vec<Range<usize>> {
+    let parser = Parser::new_ext(input, RenderOptions::new(0).extensions).into_offset_iter();
+    split_blocks(parser).map(|(range, _)| range).collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Step {
+    /// A block in both versions, by its index in the old and the new one.
+    Keep(usize, usize),
+    Remove(usize),
+    Add(usize),
+}
+
+/// The edits turning `old` into `new`, in document order, from their
+/// longest common subsequence. Removals come before additions at the same
+/// place, so an edited block reads old then new.
+fn steps(old: &[&str], new: &[&str]) -> Vec<Step> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix =
+        old[prefix..].iter().rev().zip(new[prefix..].iter().rev()).take_while(|(a, b)| a == b).count();
+    let (a, b) = (&old[prefix..old.len() - suffix], &new[prefix..new.len() - suffix]);
+
+    let mut steps: Vec<Step> = (0..prefix).map(|i| Step::Keep(i, i)).collect();
+    if a.len().saturating_mul(b.len()) > MAX_COMPARISONS {
+        steps.extend((0..a.len()).map(|i| Step::Remove(prefix + i)));
+        steps.extend((0..b.len()).map(|j| Step::Add(prefix + j)));
+    } else {
+        // lcs[i][j]: length of the longest common subsequence of a[i..] and b[j..].
+        let mut lcs = vec![vec![0u32; b.len() + 1]; a.len() + 1];
+        for i in (0..a.len()).rev() {
+            for j in (0..b.len()).rev() {
+                lcs[i][j] = match a[i] == b[j] {
+                    true => lcs[i + 1][j + 1] + 1,
+                    false => lcs[i + 1][j].max(lcs[i][j + 1]),
+                };
+            }
+        }
+        let (mut i, mut j) = (0, 0);
+        while i < a.len() || j < b.len() {
+            if i < a.len() && j < b.len() && a[i] == b[j] {
+                steps.push(Step::Keep(prefix + i, prefix + j));
+                (i, j) = (i + 1, j + 1);
+            } else if i < a.len() && (j == b.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+                steps.push(Step::Remove(prefix + i));
+                i += 1;
+            } else {
+                steps.push(Step::Add(prefix + j));
+                j += 1;
+            }
+        }
+    }
+    steps.extend((0..suffix).map(|k| Step::Keep(old.len() - suffix + k, new.len() - suffix + k)));
+    steps
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_blocks_added_and_removed() {
+        let old = "# Title\n\nKept.\n\nGone.\n\n```\ncode\n```\n";
+        let new = "# Title\n\nFresh\nparagraph.\n\nKept.\n\n```\ncode\n```\n";
+        let diff = diff(old, new);
+        assert_eq!(
+            diff.text,
+            "# Title\n\nFresh\nparagraph.\n\nKept.\n\n<!-- -->\n\nGone.\n\n<!-- -->\n\n```\ncode\n```\n"
+        );
+        assert_eq!(diff.blocks, [
+            ChangedBlock { change: Change::Added, lines: 2..4 },
+            ChangedBlock { change: Change::Removed, lines: 9..10 },
+        ]);
+    }
+
+    #[test]
+    fn test_side_by_side_lines_up_blocks() {
+        let old = "# Title\n\nOld text.\n\nSame.\n\nGone.\n";
+        let new = "# Title\n\nNew text\nover two lines.\n\nSame.\n";
+        let view = side_by_side(old, new, &RenderOptions::new(12));
+        let plain = |text: &Text| text.lines.iter().map(|l| l.to_string().trim_end().to_string()).collect::<Vec<_>>();
+        let (left, right) = (plain(&view.old), plain(&view.new));
+        assert_eq!(left.len(), right.len());
+        let same = left.iter().position(|l| l == "  Same.").unwrap();
+        assert_eq!(right[same], "  Same.");
+        assert_eq!(left[2..4], ["▌ Old text.", "▌"]);
+        assert_eq!(right[2..4], ["▌ New text", "▌ over two"]);
+        assert_eq!(view.changes.len(), 2);
+        assert_eq!(view.changes[1].start, same + 2);
+        assert!(right[same + 2..].iter().all(String::is_empty));
+    }
+
+    #[test]
+    fn test_identical_and_emptied() {
+        assert_eq!(diff("a\n\nb\n", "a\n\nb\n").blocks, []);
+        let removed = diff("a\n\nb\n", "");
+        assert_eq!(removed.text, "a\n\n<!-- -->\n\n<!-- -->\n\nb\n\n<!-- -->\n\n");
+        assert_eq!(removed.blocks.iter().map(|b| b.lines.clone()).collect::<Vec<_>>(), [0..1, 6..7]);
+    }
+}
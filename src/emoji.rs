@@ -0,0 +1,407 @@
+use std::borrow::Cow;
+
+/// Replaces GitHub-style `:shortcode:` emoji in `text` with the emoji
+/// itself. Unknown shortcodes are left as they are.
+pub(crate) fn replace_shortcodes(text: &str) -> Cow<'_, str> {
+    if !text.contains(':') {
+        return Cow::Borrowed(text);
+    }
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    let mut replaced = false;
+    while let Some(start) = rest.find(':') {
+        let after = &rest[start + 1..];
+        let end = after.find(|c: char| !is_shortcode_char(c)).filter(|&i| after[i..].starts_with(':'));
+        match end.and_then(|end| lookup(&after[..end]).map(|emoji| (end, emoji))) {
+            Some((end, emoji)) => {
+                out.push_str(&rest[..start]);
+                out.push_str(emoji);
+                rest = &after[end + 1..];
+                replaced = true;
+            }
+            None => {
+                out.push_str(&rest[..=start]);
+                rest = after;
+            }
+        }
+    }
+    if !replaced {
+        return Cow::Borrowed(text);
+    }
+    out.push_str(rest);
+    Cow::Owned(out)
+}
+
+fn is_shortcode_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, '_' | '+' | '-')
+}
+
+fn lookup(name: &str) -> Option<&'static str> {
+    EMOJI.binary_search_by_key(&name, |&(n, _)| n).ok().map(|i| EMOJI[i].1)
+}
+
+/// Shortcodes and their emoji, sorted by shortcode. A subset of GitHub's
+/// list covering the commonly used ones.
+const EMOJI: &[(&str, &str)] = &[
+    ("+1", "👍"),
+    ("-1", "👎"),
+    ("100", "💯"),
+    ("1234", "🔢"),
+    ("alarm_clock", "⏰"),
+    ("alien", "👽"),
+    ("ambulance", "🚑"),
+    ("anchor", "⚓"),
+    ("angry", "😠"),
+    ("apple", "🍎"),
+    ("arrow_down", "⬇\u{fe0f}"),
+    ("arrow_left", "⬅\u{fe0f}"),
+    ("arrow_right", "➡\u{fe0f}"),
+    ("arrow_up", "⬆\u{fe0f}"),
+    ("art", "🎨"),
+    ("astonished", "😲"),
+    ("baby", "👶"),
+    ("balloon", "🎈"),
+    ("bang", "💥"),
+    ("bar_chart", "📊"),
+    ("battery", "🔋"),
+    ("beer", "🍺"),
+    ("beers", "🍻"),
+    ("bell", "🔔"),
+    ("bike", "🚲"),
+    ("bird", "🐦"),
+    ("birthday", "🎂"),
+    ("black_heart", "🖤"),
+    ("blue_heart", "💙"),
+    ("blush", "😊"),
+    ("bomb", "💣"),
+    ("book", "📖"),
+    ("bookmark", "🔖"),
+    ("books", "📚"),
+    ("boom", "💥"),
+    ("bow", "🙇"),
+    ("brain", "🧠"),
+    ("bread", "🍞"),
+    ("briefcase", "💼"),
+    ("broken_heart", "💔"),
+    ("bug", "🐛"),
+    ("bulb", "💡"),
+    ("bus", "🚌"),
+    ("cake", "🍰"),
+    ("calendar", "📆"),
+    ("camera", "📷"),
+    ("car", "🚗"),
+    ("cat", "🐱"),
+    ("chart_with_downwards_trend", "📉"),
+    ("chart_with_upwards_trend", "📈"),
+    ("checkered_flag", "🏁"),
+    ("cherries", "🍒"),
+    ("chicken", "🐔"),
+    ("clap", "👏"),
+    ("clipboard", "📋"),
+    ("clock1", "🕐"),
+    ("closed_book", "📕"),
+    ("cloud", "☁\u{fe0f}"),
+    ("coffee", "☕"),
+    ("cold_sweat", "😰"),
+    ("computer", "💻"),
+    ("confetti_ball", "🎊"),
+    ("confused", "😕"),
+    ("construction", "🚧"),
+    ("cookie", "🍪"),
+    ("cool", "🆒"),
+    ("cow", "🐮"),
+    ("crab", "🦀"),
+    ("credit_card", "💳"),
+    ("crossed_fingers", "🤞"),
+    ("crown", "👑"),
+    ("cry", "😢"),
+    ("crystal_ball", "🔮"),
+    ("cupid", "💘"),
+    ("dart", "🎯"),
+    ("dash", "💨"),
+    ("date", "📅"),
+    ("disappointed", "😞"),
+    ("dizzy", "💫"),
+    ("dog", "🐶"),
+    ("dollar", "💵"),
+    ("door", "🚪"),
+    ("dragon", "🐉"),
+    ("droplet", "💧"),
+    ("earth_africa", "🌍"),
+    ("earth_americas", "🌎"),
+    ("egg", "🥚"),
+    ("eight", "8\u{fe0f}\u{20e3}"),
+    ("electric_plug", "🔌"),
+    ("email", "📧"),
+    ("envelope", "✉\u{fe0f}"),
+    ("exclamation", "❗"),
+    ("expressionless", "😑"),
+    ("eyes", "👀"),
+    ("face_with_thermometer", "🤒"),
+    ("facepalm", "🤦"),
+    ("fearful", "😨"),
+    ("file_folder", "📁"),
+    ("fire", "🔥"),
+    ("fireworks", "🎆"),
+    ("fish", "🐟"),
+    ("fist", "✊"),
+    ("five", "5\u{fe0f}\u{20e3}"),
+    ("flashlight", "🔦"),
+    ("floppy_disk", "💾"),
+    ("flushed", "😳"),
+    ("four", "4\u{fe0f}\u{20e3}"),
+    ("fox_face", "🦊"),
+    ("frog", "🐸"),
+    ("frowning", "😦"),
+    ("fuelpump", "⛽"),
+    ("gear", "⚙\u{fe0f}"),
+    ("gem", "💎"),
+    ("ghost", "👻"),
+    ("gift", "🎁"),
+    ("globe_with_meridians", "🌐"),
+    ("goal_net", "🥅"),
+    ("green_heart", "💚"),
+    ("grey_exclamation", "❕"),
+    ("grey_question", "❔"),
+    ("grimacing", "😬"),
+    ("grin", "😁"),
+    ("grinning", "😀"),
+    ("guitar", "🎸"),
+    ("hammer", "🔨"),
+    ("hammer_and_wrench", "🛠\u{fe0f}"),
+    ("hand", "✋"),
+    ("handshake", "🤝"),
+    ("hankey", "💩"),
+    ("headphones", "🎧"),
+    ("heart", "❤\u{fe0f}"),
+    ("heart_eyes", "😍"),
+    ("hearts", "♥\u{fe0f}"),
+    ("heavy_check_mark", "✔\u{fe0f}"),
+    ("heavy_minus_sign", "➖"),
+    ("heavy_multiplication_x", "✖\u{fe0f}"),
+    ("heavy_plus_sign", "➕"),
+    ("hibiscus", "🌺"),
+    ("hotsprings", "♨\u{fe0f}"),
+    ("hourglass", "⌛"),
+    ("house", "🏠"),
+    ("hugs", "🤗"),
+    ("hushed", "😯"),
+    ("ice_cream", "🍨"),
+    ("inbox_tray", "📥"),
+    ("information_source", "ℹ\u{fe0f}"),
+    ("innocent", "😇"),
+    ("jack_o_lantern", "🎃"),
+    ("joy", "😂"),
+    ("key", "🔑"),
+    ("keyboard", "⌨\u{fe0f}"),
+    ("kiss", "💋"),
+    ("kissing", "😗"),
+    ("label", "🏷\u{fe0f}"),
+    ("lady_beetle", "🐞"),
+    ("ladybug", "🐞"),
+    ("laptop", "💻"),
+    ("laughing", "😆"),
+    ("leaves", "🍃"),
+    ("ledger", "📒"),
+    ("link", "🔗"),
+    ("lipstick", "💄"),
+    ("lock", "🔒"),
+    ("loudspeaker", "📢"),
+    ("love_letter", "💌"),
+    ("mag", "🔍"),
+    ("mag_right", "🔎"),
+    ("mailbox", "📫"),
+    ("man_technologist", "👨\u{200d}💻"),
+    ("map", "🗺\u{fe0f}"),
+    ("mask", "😷"),
+    ("medal_sports", "🏅"),
+    ("mega", "📣"),
+    ("memo", "📝"),
+    ("microphone", "🎤"),
+    ("microscope", "🔬"),
+    ("money_with_wings", "💸"),
+    ("moneybag", "💰"),
+    ("monkey", "🐒"),
+    ("moon", "🌔"),
+    ("mortar_board", "🎓"),
+    ("mountain", "⛰\u{fe0f}"),
+    ("mouse", "🐭"),
+    ("muscle", "💪"),
+    ("mushroom", "🍄"),
+    ("musical_note", "🎵"),
+    ("nail_care", "💅"),
+    ("nerd_face", "🤓"),
+    ("neutral_face", "😐"),
+    ("new", "🆕"),
+    ("newspaper", "📰"),
+    ("nine", "9\u{fe0f}\u{20e3}"),
+    ("no_entry", "⛔"),
+    ("no_entry_sign", "🚫"),
+    ("notebook", "📓"),
+    ("notes", "🎶"),
+    ("ok", "🆗"),
+    ("ok_hand", "👌"),
+    ("one", "1\u{fe0f}\u{20e3}"),
+    ("open_book", "📖"),
+    ("open_mouth", "😮"),
+    ("orange_book", "📙"),
+    ("orange_heart", "🧡"),
+    ("outbox_tray", "📤"),
+    ("package", "📦"),
+    ("page_facing_up", "📄"),
+    ("page_with_curl", "📃"),
+    ("palm_tree", "🌴"),
+    ("paperclip", "📎"),
+    ("partying_face", "🥳"),
+    ("pencil", "📝"),
+    ("pencil2", "✏\u{fe0f}"),
+    ("penguin", "🐧"),
+    ("pensive", "😔"),
+    ("phone", "☎\u{fe0f}"),
+    ("pig", "🐷"),
+    ("pill", "💊"),
+    ("pizza", "🍕"),
+    ("point_down", "👇"),
+    ("point_left", "👈"),
+    ("point_right", "👉"),
+    ("point_up", "☝\u{fe0f}"),
+    ("police_car", "🚓"),
+    ("poop", "💩"),
+    ("popcorn", "🍿"),
+    ("pray", "🙏"),
+    ("purple_heart", "💜"),
+    ("pushpin", "📌"),
+    ("question", "❓"),
+    ("rabbit", "🐰"),
+    ("racehorse", "🐎"),
+    ("rage", "😡"),
+    ("rainbow", "🌈"),
+    ("raised_hands", "🙌"),
+    ("recycle", "♻\u{fe0f}"),
+    ("red_circle", "🔴"),
+    ("relaxed", "☺\u{fe0f}"),
+    ("relieved", "😌"),
+    ("repeat", "🔁"),
+    ("rewind", "⏪"),
+    ("ribbon", "🎀"),
+    ("robot", "🤖"),
+    ("rocket", "🚀"),
+    ("rofl", "🤣"),
+    ("rose", "🌹"),
+    ("rotating_light", "🚨"),
+    ("round_pushpin", "📍"),
+    ("runner", "🏃"),
+    ("sandwich", "🥪"),
+    ("satellite", "📡"),
+    ("scissors", "✂\u{fe0f}"),
+    ("scream", "😱"),
+    ("see_no_evil", "🙈"),
+    ("seedling", "🌱"),
+    ("seven", "7\u{fe0f}\u{20e3}"),
+    ("shield", "🛡\u{fe0f}"),
+    ("ship", "🚢"),
+    ("shipit", "🐿\u{fe0f}"),
+    ("shrug", "🤷"),
+    ("six", "6\u{fe0f}\u{20e3}"),
+    ("skull", "💀"),
+    ("sleeping", "😴"),
+    ("sleepy", "😪"),
+    ("slightly_frowning_face", "🙁"),
+    ("slightly_smiling_face", "🙂"),
+    ("smile", "😄"),
+    ("smiley", "😃"),
+    ("smirk", "😏"),
+    ("snail", "🐌"),
+    ("snake", "🐍"),
+    ("snowflake", "❄\u{fe0f}"),
+    ("snowman", "⛄"),
+    ("sob", "😭"),
+    ("soccer", "⚽"),
+    ("sparkles", "✨"),
+    ("sparkling_heart", "💖"),
+    ("speech_balloon", "💬"),
+    ("spiral_notepad", "🗒\u{fe0f}"),
+    ("squirrel", "🐿\u{fe0f}"),
+    ("star", "⭐"),
+    ("star2", "🌟"),
+    ("stars", "🌠"),
+    ("stop_sign", "🛑"),
+    ("stopwatch", "⏱\u{fe0f}"),
+    ("strawberry", "🍓"),
+    ("sun_with_face", "🌞"),
+    ("sunflower", "🌻"),
+    ("sunglasses", "😎"),
+    ("sunny", "☀\u{fe0f}"),
+    ("sweat", "😓"),
+    ("sweat_drops", "💦"),
+    ("sweat_smile", "😅"),
+    ("taco", "🌮"),
+    ("tada", "🎉"),
+    ("tea", "🍵"),
+    ("telescope", "🔭"),
+    ("ten", "🔟"),
+    ("tent", "⛺"),
+    ("test_tube", "🧪"),
+    ("thinking", "🤔"),
+    ("thought_balloon", "💭"),
+    ("three", "3\u{fe0f}\u{20e3}"),
+    ("thumbsdown", "👎"),
+    ("thumbsup", "👍"),
+    ("ticket", "🎫"),
+    ("tiger", "🐯"),
+    ("timer_clock", "⏲\u{fe0f}"),
+    ("tired_face", "😫"),
+    ("toolbox", "🧰"),
+    ("tophat", "🎩"),
+    ("tractor", "🚜"),
+    ("trophy", "🏆"),
+    ("truck", "🚚"),
+    ("tulip", "🌷"),
+    ("turtle", "🐢"),
+    ("two", "2\u{fe0f}\u{20e3}"),
+    ("umbrella", "☔"),
+    ("unamused", "😒"),
+    ("unicorn", "🦄"),
+    ("unlock", "🔓"),
+    ("upside_down_face", "🙃"),
+    ("v", "✌\u{fe0f}"),
+    ("vertical_traffic_light", "🚦"),
+    ("video_camera", "📹"),
+    ("video_game", "🎮"),
+    ("warning", "⚠\u{fe0f}"),
+    ("watch", "⌚"),
+    ("wave", "👋"),
+    ("whale", "🐳"),
+    ("wheelchair", "♿"),
+    ("white_check_mark", "✅"),
+    ("white_heart", "🤍"),
+    ("wink", "😉"),
+    ("wolf", "🐺"),
+    ("worried", "😟"),
+    ("wrench", "🔧"),
+    ("x", "❌"),
+    ("yellow_heart", "💛"),
+    ("yum", "😋"),
+    ("zap", "⚡"),
+    ("zero", "0\u{fe0f}\u{20e3}"),
+    ("zipper_mouth_face", "🤐"),
+    ("zzz", "💤"),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_table_is_sorted() {
+        assert!(EMOJI.windows(2).all(|w| w[0].0 < w[1].0));
+    }
+
+    #[test]
+    fn test_replace_shortcodes() {
+        assert_eq!(replace_shortcodes("Ship it :rocket::tada:!"), "Ship it 🚀🎉!");
+        assert_eq!(replace_shortcodes(":+1: and :white_check_mark:"), "👍 and ✅");
+        assert!(matches!(replace_shortcodes("at 10:30 or :nope:"), Cow::Borrowed(_)));
+        assert_eq!(replace_shortcodes("a:b :fire:"), "a:b 🔥");
+    }
+}
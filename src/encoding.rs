@@ -0,0 +1,116 @@
+//! Decoding documents that are not plain UTF-8, such as those saved by
+//! Windows tools: UTF-8 or UTF-16 with a byte order mark, UTF-16 without
+//! one, and anything else as Windows-1252 (a superset of Latin-1).
+//!
+//! ```
+//! let decoded = mdview::encoding::decode(b"caf\xe9");
+//! assert_eq!(decoded.text, "café");
+//! assert_eq!(decoded.encoding, Some("windows-1252"));
+//! ```
+
+use std::io;
+use std::path::Path;
+
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, WINDOWS_1252};
+
+/// Text read from a file, and how it was encoded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Decoded {
+    pub text: String,
+    /// The encoding's name, like `UTF-16LE`, with `UTF-8 BOM` for UTF-8
+    /// after a byte order mark. `None` for plain UTF-8, the usual case.
+    pub encoding: Option<&'static str>,
+}
+
+/// Reads the file at `path` and decodes it with [`decode`].
+pub fn read(path: &Path) -> io::Result<Decoded> {
+    Ok(decode(&std::fs::read(path)?))
+}
+
+/// Decodes `bytes` by their byte order mark, else as UTF-8 when they are
+/// valid UTF-8, else as UTF-16 when they look like it, else as
+/// Windows-1252. Every byte sequence decodes; malformed UTF-16 comes out
+/// with replacement characters.
+pub fn decode(bytes: &[u8]) -> Decoded {
+    if let Some((encoding, bom)) = Encoding::for_bom(bytes) {
+        let (text, _) = encoding.decode_without_bom_handling(&bytes[bom..]);
+        let name = match encoding.name() {
+            "UTF-8" => "UTF-8 BOM",
+            name => name,
+        };
+        return Decoded { text: text.into_owned(), encoding: Some(name) };
+    }
+    if let Ok(text) = std::str::from_utf8(bytes) {
+        return Decoded { text: text.to_string(), encoding: None };
+    }
+    let encoding = utf16_without_bom(bytes).unwrap_or(WINDOWS_1252);
+    let (text, _) = encoding.decode_without_bom_handling(bytes);
+    Decoded { text: text.into_owned(), encoding: Some(encoding.name()) }
+}
+
+/// UTF-16 without a byte order mark, told apart by the zero high bytes of
+/// ASCII characters: at odd offsets for little-endian, at even ones for
+/// big-endian. Most characters of a markdown file are ASCII.
+fn utf16_without_bom(bytes: &[u8]) -> Option<&'static Encoding> {
+    if bytes.is_empty() || !bytes.len().is_multiple_of(2) {
+        return None;
+    }
+    let zeros = |offset: usize| bytes.iter().skip(offset).step_by(2).filter(|&&b| b == 0).count();
+    let (even, odd) = (zeros(0), zeros(1));
+    let units = bytes.len() / 2;
+    if odd > units / 2 && even < odd / 4 {
+        Some(UTF_16LE)
+    } else if even > units / 2 && odd < even / 4 {
+        Some(UTF_16BE)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utf16(text: &str, big_endian: bool) -> Vec<u8> {
+        text.encode_utf16()
+            .flat_map(|unit| match big_endian {
+                true => unit.to_be_bytes(),
+                false => unit.to_le_bytes(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_decode() {
+        let text = "# Café\r\n\r\nNaïve text.\r\n";
+        assert_eq!(decode(text.as_bytes()), Decoded { text: text.to_string(), encoding: None });
+
+        let bom = [b"\xef\xbb\xbf".as_slice(), text.as_bytes()].concat();
+        assert_eq!(decode(&bom), Decoded { text: text.to_string(), encoding: Some("UTF-8 BOM") });
+
+        let le = [b"\xff\xfe".as_slice(), &utf16(text, false)].concat();
+        assert_eq!(decode(&le), Decoded { text: text.to_string(), encoding: Some("UTF-16LE") });
+        let be = [b"\xfe\xff".as_slice(), &utf16(text, true)].concat();
+        assert_eq!(decode(&be), Decoded { text: text.to_string(), encoding: Some("UTF-16BE") });
+        assert_eq!(decode(&utf16(text, false)).encoding, Some("UTF-16LE"));
+        assert_eq!(decode(&utf16(text, true)).encoding, Some("UTF-16BE"));
+
+        let latin1 = b"# Caf\xe9\n\nNa\xefve \x93quoted\x94 text.\n";
+        let decoded = decode(latin1);
+        assert_eq!(decoded.text, "# Café\n\nNaïve \u{201c}quoted\u{201d} text.\n");
+        assert_eq!(decoded.encoding, Some("windows-1252"));
+    }
+
+    #[test]
+    fn test_read() {
+        let dir = std::env::temp_dir().join(format!("mdview-encoding-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (utf8, utf16_file) = (dir.join("utf8.md"), dir.join("utf16.md"));
+        let text = "Some text, café.\n";
+        std::fs::write(&utf8, text).unwrap();
+        std::fs::write(&utf16_file, [b"\xff\xfe".as_slice(), &utf16(text, false)].concat()).unwrap();
+        assert_eq!(read(&utf8).unwrap(), Decoded { text: text.to_string(), encoding: None });
+        assert_eq!(read(&utf16_file).unwrap(), Decoded { text: text.to_string(), encoding: Some("UTF-16LE") });
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,293 @@
+use std::collections::HashMap;
+
+use pulldown_cmark::{CodeBlockKind, Event, Parser, Tag, TagEnd, TextMergeStream, html};
+use ratatui::style::Color;
+
+use crate::ast;
+use crate::emoji;
+use crate::frontmatter;
+use crate::highlight::{highlight_html, page_colors, page_rgb};
+use crate::pdf::write_pdf;
+use crate::render::{RenderOptions, expand_tabs, heading_text, render_document, unique_slug};
+
+/// Renders markdown as a pretty-printed JSON object whose `blocks` array is
+/// the document model from [`crate::ast::parse`].
+///
+/// ```
+/// let json = mdview::export::render_json("# Hi\n", &mdview::RenderOptions::new(80));
+/// assert!(json.contains("\"type\": \"heading\""));
+/// ```
+pub fn render_json(input: &str, options: &RenderOptions) -> String {
+    #[derive(serde::Serialize)]
+    struct Document {
+        blocks: Vec<ast::Block>,
+    }
+    let document = Document { blocks: ast::parse(input, options) };
+    let mut json = serde_json::to_string_pretty(&document).expect("the document model always serializes");
+    json.push('\n');
+    json
+}
+
+/// Renders markdown to a standalone HTML page styled with the options'
+/// theme. Code blocks are highlighted with the same syntect theme the
+/// terminal view uses, as inline styles, so the file has no external
+/// dependencies.
+pub fn render_html(input: &str, options: &RenderOptions, title: &str) -> String {
+    let syntax_theme = options.syntax_theme_name();
+    let mut events = Vec::new();
+    let mut code: Option<(Option<String>, String)> = None;
+
+    let body = frontmatter::split(input).1;
+    for event in TextMergeStream::new(Parser::new_ext(body, options.extensions)) {
+        match event {
+            Event::Start(Tag::CodeBlock(kind)) => {
+                let lang = match kind {
+                    CodeBlockKind::Fenced(info) => {
+                        info.split_whitespace().next().map(str::to_string)
+                    }
+                    CodeBlockKind::Indented => None,
+                };
+                code = Some((lang, String::new()));
+            }
+            Event::Text(text) if code.is_some() => {
+                if let Some((_, buf)) = &mut code {
+                    buf.push_str(&text);
+                }
+            }
+            Event::End(TagEnd::CodeBlock) => {
+                if let Some((lang, buf)) = code.take() {
+                    let buf = expand_tabs(&buf, options.tab_size);
+                    events.push(Event::Html(
+                        highlight_html(&buf, lang.as_deref(), syntax_theme).into(),
+                    ));
+                }
+            }
+            event => events.push(event),
+        }
+    }
+
+    // Slugs are taken from the headings as written, as the renderer takes
+    // them, before shortcodes turn into emoji.
+    add_heading_ids(&mut events);
+    if options.emoji {
+        for event in &mut events {
+            if let Event::Text(text) = event {
+                *text = emoji::replace_shortcodes(text).into_owned().into();
+            }
+        }
+    }
+    let mut body = String::new();
+    html::push_html(&mut body, events.into_iter());
+
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+         <meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n\
+         <title>{}</title>\n<style>\n{}</style>\n</head>\n<body>\n{body}</body>\n</html>\n",
+        escape(title),
+        stylesheet(options),
+    )
+}
+
+/// Renders markdown to a paginated A4 PDF with the styling of the terminal
+/// view: the rendered lines on a monospaced grid, in the theme's colors on
+/// its background. Headings become bookmarks and links can be clicked.
+///
+/// ```
+/// let pdf = mdview::export::render_pdf("# Hi\n", &mdview::RenderOptions::new(80), "hi.md");
+/// assert!(pdf.starts_with(b"%PDF-"));
+/// ```
+pub fn render_pdf(input: &str, options: &RenderOptions, title: &str) -> Vec<u8> {
+    let doc = render_document(input, options);
+    write_pdf(&doc, options.width, page_rgb(options.syntax_theme_name()), title)
+}
+
+/// Renders markdown to wrapped plain text: no styling, and ASCII bullets,
+/// rules and table borders, for tools that cannot handle escape codes.
+pub fn render_text(input: &str, options: &RenderOptions) -> String {
+    let doc = render_document(input, &options.clone().ascii(true));
+    let mut out = String::new();
+    for line in &doc.text.lines {
+        let text: String = line.spans.iter().map(|s| s.content.as_ref()).collect();
+        out.push_str(text.trim_end());
+        out.push('\n');
+    }
+    out
+}
+
+/// Gives every heading without an explicit id the same slug the terminal
+/// view registers, taken from the same [`heading_text`], so `#section`
+/// links work in the exported page.
+fn add_heading_ids(events: &mut [Event]) {
+    let mut seen: HashMap<String, usize> = HashMap::new();
+    for i in 0..events.len() {
+        let Event::Start(Tag::Heading { id: None, .. }) = &events[i] else {
+            continue;
+        };
+        let content = events[i + 1..].iter().take_while(|e| !matches!(e, Event::End(TagEnd::Heading(_))));
+        let title = heading_text(content);
+        let slug = unique_slug(&mut seen, &title);
+        if let Event::Start(Tag::Heading { id, .. }) = &mut events[i] {
+            *id = Some(slug.into());
+        }
+    }
+}
+
+fn stylesheet(options: &RenderOptions) -> String {
+    let theme = &options.theme;
+    let (bg, fg) = page_colors(options.syntax_theme_name());
+    let [h1, h2, h3, h4] = theme.headings.map(css_color);
+    let muted = css_color(theme.muted);
+    let alerts: String = ["note", "tip", "important", "warning", "caution"]
+        .iter()
+        .zip(["ℹ Note", "★ Tip", "‼ Important", "⚠ Warning", "✖ Caution"])
+        .zip(theme.alerts.map(css_color))
+        .map(|((kind, title), color)| {
+            format!(
+                ".markdown-alert-{kind} {{ border-left-color: {color}; }}\n\
+                 .markdown-alert-{kind}::before {{ content: \"{title}\"; color: {color}; font-weight: bold; }}\n"
+            )
+        })
+        .collect();
+    let css = format!(
+        "body {{ background: {bg}; color: {fg}; max-width: 50em; margin: 2em auto; padding: 0 1em; \
+         font-family: -apple-system, 'Segoe UI', Helvetica, Arial, sans-serif; line-height: 1.5; }}\n\
+         h1 {{ color: {h1}; }}\nh2 {{ color: {h2}; }}\nh3 {{ color: {h3}; }}\n\
+         h4, h5, h6 {{ color: {h4}; }}\n\
+         a {{ color: {link}; }}\n\
+         hr {{ border: none; border-top: 1px solid {muted}; }}\n\
+         blockquote {{ margin-left: 0; padding-left: 1em; border-left: 3px solid {bar}; }}\n\
+         code {{ font-family: ui-monospace, Menlo, Consolas, monospace; }}\n\
+         :not(pre) > code {{ background: {code_bg}; padding: 0.1em 0.3em; border-radius: 3px; }}\n\
+         pre {{ padding: 0.8em 1em; overflow-x: auto; border-radius: 4px; }}\n\
+         table {{ border-collapse: collapse; }}\n\
+         th, td {{ border: 1px solid {border}; padding: 0.3em 0.7em; }}\n\
+         tbody tr:nth-child(even) {{ background: {zebra}; }}\n\
+         input[type=checkbox] {{ accent-color: {done}; }}\n\
+         .footnote-definition {{ color: {muted}; }}\n\
+         .footnote-definition-label, .footnote-reference {{ color: {footnote}; }}\n",
+        link = css_color(theme.link),
+        bar = css_color(theme.blockquote_bar),
+        code_bg = css_color(theme.code_bg),
+        border = css_color(theme.table_border),
+        zebra = css_color(theme.zebra_bg),
+        done = css_color(theme.task_done),
+        footnote = css_color(theme.footnote),
+    );
+    css + &alerts
+}
+
+/// CSS equivalent of a terminal color.
+fn css_color(color: Color) -> String {
+    match rgb(color) {
+        Some((r, g, b)) => format!("#{r:02x}{g:02x}{b:02x}"),
+        None => "inherit".to_string(),
+    }
+}
+
+/// The RGB value of a terminal color, or `None` for the default color.
+/// Named colors use the xterm defaults; indexed colors follow the xterm
+/// 256-color palette.
+pub(crate) fn rgb(color: Color) -> Option<(u8, u8, u8)> {
+    Some(match color {
+        Color::Rgb(r, g, b) => (r, g, b),
+        Color::Indexed(i) => indexed_rgb(i),
+        Color::Black => (0x00, 0x00, 0x00),
+        Color::Red => (0xcd, 0x00, 0x00),
+        Color::Green => (0x00, 0xcd, 0x00),
+        Color::Yellow => (0xcd, 0xcd, 0x00),
+        Color::Blue => (0x00, 0x00, 0xee),
+        Color::Magenta => (0xcd, 0x00, 0xcd),
+        Color::Cyan => (0x00, 0xcd, 0xcd),
+        Color::Gray => (0xe5, 0xe5, 0xe5),
+        Color::DarkGray => (0x7f, 0x7f, 0x7f),
+        Color::LightRed => (0xff, 0x00, 0x00),
+        Color::LightGreen => (0x00, 0xff, 0x00),
+        Color::LightYellow => (0xff, 0xff, 0x00),
+        Color::LightBlue => (0x5c, 0x5c, 0xff),
+        Color::LightMagenta => (0xff, 0x00, 0xff),
+        Color::LightCyan => (0x00, 0xff, 0xff),
+        Color::White => (0xff, 0xff, 0xff),
+        Color::Reset => return None,
+    })
+}
+
+fn indexed_rgb(i: u8) -> (u8, u8, u8) {
+    match i {
+        0..=15 => rgb(crate::palette::ANSI[i as usize]).unwrap_or_default(),
+        16..=231 => {
+            let level = |v: u8| if v == 0 { 0 } else { 55 + v * 40 };
+            let i = i - 16;
+            (level(i / 36), level(i / 6 % 6), level(i % 6))
+        }
+        _ => {
+            let v = 8 + (i - 232) * 10;
+            (v, v, v)
+        }
+    }
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::theme;
+
+    #[test]
+    fn test_render_html_is_standalone() {
+        let html = render_html("# Title\n\nSome *text*.\n", &RenderOptions::new(80), "a <b>");
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("<title>a &lt;b&gt;</title>"));
+        assert!(html.contains("<h1 id=\"title\">Title</h1>"));
+        assert!(html.contains("<em>text</em>"));
+        assert!(html.trim_end().ends_with("</html>"));
+    }
+
+    #[test]
+    fn test_heading_ids_are_the_viewer_s() {
+        let md = "# Emoji :rocket: here\n\n[go](#emoji-rocket-here)\n";
+        let html = render_html(md, &RenderOptions::new(80), "t");
+        assert!(html.contains("<h1 id=\"emoji-rocket-here\">Emoji 🚀 here</h1>"), "{html}");
+        assert!(render_document(md, &RenderOptions::new(80)).anchors.contains_key("emoji-rocket-here"));
+        let md = "Line one\nline two\n===\n\nHard\\\nbreak\n---\n";
+        let html = render_html(md, &RenderOptions::new(80), "t");
+        assert!(html.contains("<h1 id=\"line-one-line-two\">"), "{html}");
+        assert!(html.contains("<h2 id=\"hard-break\">"), "{html}");
+        assert!(render_document(md, &RenderOptions::new(80)).anchors.contains_key("line-one-line-two"));
+    }
+
+    #[test]
+    fn test_code_blocks_are_highlighted() {
+        let md = "```rust\nfn main() {}\n```\n";
+        let html = render_html(md, &RenderOptions::new(80), "t");
+        assert!(html.contains("<pre style=\"background-color:"));
+        assert!(html.contains("<span style=\"color:"));
+        assert!(!html.contains("<code class=\"language-rust\">"));
+    }
+
+    #[test]
+    fn test_stylesheet_uses_theme_colors() {
+        let html = render_html("# Hi\n", &RenderOptions::new(80).theme(theme::DRACULA), "t");
+        assert!(html.contains("h1 { color: #bd93f9; }"));
+        assert!(html.contains(".markdown-alert-caution { border-left-color: #ff5555; }"));
+    }
+
+    #[test]
+    fn test_render_text_uses_ascii_markers() {
+        let md = "# Title\n\n- one\n- [x] done\n\n| a | b |\n|---|---|\n| 1 | 2 |\n";
+        let text = render_text(md, &RenderOptions::new(40));
+        assert!(text.starts_with("Title\n=====\n\n* one\n* [x] done\n"), "{text}");
+        assert!(text.contains("+-----+-----+\n| a   | b   |\n"), "{text}");
+        assert!(text.is_ascii());
+    }
+
+    #[test]
+    fn test_css_color_indexed_palette() {
+        assert_eq!(css_color(Color::Indexed(16)), "#000000");
+        assert_eq!(css_color(Color::Indexed(231)), "#ffffff");
+        assert_eq!(css_color(Color::Indexed(239)), "#4e4e4e");
+        assert_eq!(css_color(Color::Indexed(1)), "#cd0000");
+    }
+}
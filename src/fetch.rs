@@ -0,0 +1,210 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::{Context, Result, bail};
+use mdview::encoding::{self, Decoded};
+use serde::{Deserialize, Serialize};
+
+use crate::config::FileSize;
+
+/// Longest a fetch may take, connecting included.
+pub const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Where a document is read from: a file, or the URL it is fetched from.
+/// Saved as the path or URL.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(from = "PathBuf", into = "String")]
+pub enum Source {
+    File(PathBuf),
+    Url(String),
+}
+
+impl Source {
+    pub fn file(&self) -> Option<&Path> {
+        match self {
+            Source::File(path) => Some(path),
+            Source::Url(_) => None,
+        }
+    }
+
+    pub fn url(&self) -> Option<&str> {
+        match self {
+            Source::File(_) => None,
+            Source::Url(url) => Some(url),
+        }
+    }
+
+    /// The path naming the document, whose extension tells the format: the
+    /// file, or the URL without its query and fragment.
+    pub fn document_path(&self) -> &Path {
+        match self {
+            Source::File(path) => path,
+            Source::Url(url) => document_path(url),
+        }
+    }
+
+    /// The file name of the document, or all of its path or URL when that
+    /// has none.
+    pub fn name(&self) -> String {
+        self.document_path().file_name().map_or_else(|| self.to_string(), |n| n.to_string_lossy().into_owned())
+    }
+}
+
+/// A command-line argument or saved session entry: a URL when it starts
+/// with `http://` or `https://`, else a file.
+impl From<PathBuf> for Source {
+    fn from(path: PathBuf) -> Self {
+        match path.to_str().filter(|p| p.starts_with("https://") || p.starts_with("http://")) {
+            Some(url) => Source::Url(url.to_string()),
+            None => Source::File(path),
+        }
+    }
+}
+
+impl From<Source> for String {
+    fn from(source: Source) -> Self {
+        source.to_string()
+    }
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Source::File(path) => path.display().fmt(f),
+            Source::Url(url) => f.write_str(url),
+        }
+    }
+}
+
+/// `arg` as a URL, with `https://` put in front when it has no scheme.
+pub fn with_scheme(arg: &str) -> String {
+    match arg.contains("://") {
+        true => arg.to_string(),
+        false => format!("https://{arg}"),
+    }
+}
+
+/// The part of `url` naming the document, without its query and fragment,
+/// as a path whose extension tells the format.
+pub fn document_path(url: &str) -> &Path {
+    Path::new(url.split(['?', '#']).next().unwrap_or(url))
+}
+
+/// Downloads `url` as text, following redirects, decoded like a file on
+/// disk. Fails after `TIMEOUT`, on an HTTP error status, or when the body is
+/// larger than `limit`.
+pub fn get(url: &str, limit: FileSize) -> Result<Decoded> {
+    let agent: ureq::Agent = ureq::Agent::config_builder().timeout_global(Some(TIMEOUT)).build().into();
+    let mut response = match agent.get(url).call() {
+        Ok(response) => response,
+        Err(ureq::Error::StatusCode(code)) => bail!("Cannot fetch {url}: HTTP status {code}"),
+        Err(e) => return Err(e).with_context(|| format!("Cannot fetch {url}")),
+    };
+    let body = match response.body_mut().with_config().limit(limit.0).read_to_vec() {
+        Ok(body) => body,
+        Err(ureq::Error::BodyExceedsLimit(_)) => {
+            bail!("{url} is over max_file_size of {limit}; pass --force to open it anyway")
+        }
+        Err(e) => return Err(e).with_context(|| format!("Cannot fetch {url}")),
+    };
+    Ok(encoding::decode(&body))
+}
+
+/// `reference`, a link or image destination in the document at `base`, as
+/// an absolute URL, the way a browser resolves it.
+pub fn join(base: &str, reference: &str) -> String {
+    if reference.contains("://") || reference.starts_with("mailto:") {
+        return reference.to_string();
+    }
+    let (scheme, rest) = base.split_once("://").unwrap_or(("https", base));
+    if let Some(reference) = reference.strip_prefix("//") {
+        return format!("{scheme}://{reference}");
+    }
+    let host_end = rest.find(['/', '?', '#']).unwrap_or(rest.len());
+    let origin = &base[..scheme.len() + 3 + host_end];
+    let document = base.split('#').next().unwrap_or(base);
+    let path = document.split('?').next().unwrap_or(document);
+    if reference.is_empty() {
+        return document.to_string();
+    }
+    if reference.starts_with('#') {
+        return format!("{document}{reference}");
+    }
+    if reference.starts_with('?') {
+        return format!("{path}{reference}");
+    }
+    let (target, suffix) = match reference.find(['?', '#']) {
+        Some(i) => reference.split_at(i),
+        None => (reference, ""),
+    };
+    let merged = match target.starts_with('/') {
+        true => target.to_string(),
+        false => {
+            let dir = &path[origin.len()..];
+            let dir = &dir[..dir.rfind('/').map_or(0, |i| i + 1)];
+            match dir.is_empty() {
+                true => format!("/{target}"),
+                false => format!("{dir}{target}"),
+            }
+        }
+    };
+    format!("{origin}{}{suffix}", remove_dots(&merged))
+}
+
+/// `path` with its `.` and `..` segments applied.
+fn remove_dots(path: &str) -> String {
+    let segments: Vec<&str> = path.split('/').collect();
+    let mut out: Vec<&str> = Vec::new();
+    for (i, &segment) in segments.iter().enumerate() {
+        match segment {
+            "." | ".." => {
+                // The empty first segment is the root, which `..` stops at.
+                if segment == ".." && out.len() > 1 {
+                    out.pop();
+                }
+                if i + 1 == segments.len() {
+                    out.push("");
+                }
+            }
+            _ => out.push(segment),
+        }
+    }
+    out.join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_join() {
+        let base = "https://raw.githubusercontent.com/o/r/main/docs/README.md?x=1#top";
+        let cases = [
+            ("guide.md", "https://raw.githubusercontent.com/o/r/main/docs/guide.md"),
+            ("../LICENSE#mit", "https://raw.githubusercontent.com/o/r/main/LICENSE#mit"),
+            ("./img/logo.png?v=2", "https://raw.githubusercontent.com/o/r/main/docs/img/logo.png?v=2"),
+            ("/o/r/main/a.md", "https://raw.githubusercontent.com/o/r/main/a.md"),
+            ("../../../../../x", "https://raw.githubusercontent.com/x"),
+            ("#usage", "https://raw.githubusercontent.com/o/r/main/docs/README.md?x=1#usage"),
+            ("//example.com/a", "https://example.com/a"),
+            ("http://example.com/", "http://example.com/"),
+            ("sub/..", "https://raw.githubusercontent.com/o/r/main/docs/"),
+        ];
+        for (reference, expected) in cases {
+            assert_eq!(join(base, reference), expected, "{reference}");
+        }
+        assert_eq!(join("http://host", "a.md"), "http://host/a.md");
+    }
+
+    #[test]
+    fn test_urls() {
+        let url = Source::from(PathBuf::from("https://host/README.md?raw=1"));
+        assert_eq!(url.url(), Some("https://host/README.md?raw=1"));
+        assert_eq!(url.document_path(), Path::new("https://host/README.md"));
+        assert_eq!(Source::from(PathBuf::from("/home/README.md")), Source::File(PathBuf::from("/home/README.md")));
+        assert_eq!(with_scheme("host/a.md"), "https://host/a.md");
+        assert_eq!(with_scheme("http://host/a.md"), "http://host/a.md");
+        assert_eq!(document_path("https://host/notes.ipynb?raw=true"), Path::new("https://host/notes.ipynb"));
+    }
+}
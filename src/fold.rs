@@ -0,0 +1,194 @@
+use std::collections::{BTreeSet, HashMap};
+
+use mdview::{Document, Heading};
+use ratatui::style::{Color, Style};
+use ratatui::text::{Line, Span, Text};
+
+/// How the lines of a rendered document land on the rows of the view once
+/// folded sections are hidden. Row `r` shows document line `rows[r]`.
+#[derive(Debug, Default, Clone)]
+pub struct Projection {
+    rows: Vec<usize>,
+}
+
+impl Projection {
+    /// The document line shown at `row`. Rows past the end continue one
+    /// line per row, so positions beyond the text survive the round trip.
+    pub fn line(&self, row: usize) -> usize {
+        match (self.rows.get(row), self.rows.last()) {
+            (Some(&line), _) => line,
+            (None, Some(&last)) => last + 1 + row - self.rows.len(),
+            (None, None) => row,
+        }
+    }
+
+    /// The row showing document line `line`; a hidden line is at the row of
+    /// the folded heading it is under.
+    pub fn row(&self, line: usize) -> usize {
+        match self.rows.last() {
+            Some(&last) if line > last => self.rows.len() - 1 + line - last,
+            _ => self.rows.partition_point(|&l| l <= line).saturating_sub(1),
+        }
+    }
+}
+
+/// The end, exclusive, of the section under `headings[i]`: the line of the
+/// next heading at the same or a higher level, or `len`.
+pub fn section_end(headings: &[Heading], i: usize, len: usize) -> usize {
+    let level = headings[i].level;
+    headings[i + 1..].iter().find(|h| h.level <= level).map_or(len, |h| h.line)
+}
+
+/// The URL of the links over `<details>` summaries, which toggle them
+/// rather than lead anywhere.
+pub const TOGGLE_URL: &str = "mdview:toggle";
+
+/// What can fold in `doc`, in order: the sections under headings and
+/// `<details>` elements, each as its first line, the end of the range,
+/// exclusive, and the title its summary shows.
+fn foldable(doc: &Document) -> Vec<(usize, usize, &str)> {
+    let len = doc.text.lines.len();
+    let mut ranges: Vec<(usize, usize, &str)> = (doc.headings.iter().enumerate())
+        .map(|(i, h)| (h.line, section_end(&doc.headings, i, len), h.title.as_str()))
+        .collect();
+    ranges.extend(doc.details.iter().map(|d| (d.line, d.end, d.title.as_str())));
+    ranges.sort_by_key(|&(line, ..)| line);
+    ranges
+}
+
+/// Whether a heading or `<details>` of `doc` starts at `line`, so it can
+/// be folded.
+pub fn can_fold(doc: &Document, line: usize) -> bool {
+    doc.headings.binary_search_by_key(&line, |h| h.line).is_ok() || doc.details.iter().any(|d| d.line == line)
+}
+
+/// The folds in `folded` that hide line `line` of `doc`.
+pub fn hiding(doc: &Document, folded: &BTreeSet<usize>, line: usize) -> Vec<usize> {
+    (foldable(doc).into_iter())
+        .filter(|&(start, end, _)| folded.contains(&start) && start < line && line < end)
+        .map(|(start, ..)| start)
+        .collect()
+}
+
+/// `doc` as shown with the sections under the headings and the `<details>`
+/// at the lines in `folded` hidden. A folded one reads `▸ Title (n lines)`;
+/// the blank line that ends it stays, to keep headings apart. Each visible
+/// `<details>` summary gets a link to [`TOGGLE_URL`].
+pub fn project(doc: &Document, folded: &BTreeSet<usize>) -> (Document, Projection) {
+    let len = doc.text.lines.len();
+    let mut hidden = vec![false; len];
+    let mut summaries = HashMap::new();
+    for (line, end, title) in foldable(doc) {
+        if !folded.contains(&line) || hidden.get(line).is_none_or(|&h| h) {
+            continue;
+        }
+        let mut end = end.min(len);
+        if end > line + 1 && doc.text.lines[end - 1].width() == 0 {
+            end -= 1;
+        }
+        hidden[line + 1..end].iter_mut().for_each(|h| *h = true);
+        summaries.insert(line, summary(&doc.text.lines[line], title, end - line - 1));
+    }
+
+    let rows: Vec<usize> = (0..len).filter(|&line| !hidden[line]).collect();
+    let projection = Projection { rows };
+    let lines: Vec<Line> = projection
+        .rows
+        .iter()
+        .map(|line| summaries.remove(line).unwrap_or_else(|| doc.text.lines[*line].clone()))
+        .collect();
+    let shown = |line: usize| line < len && !hidden[line] && !folded.contains(&line);
+    let mut links: Vec<mdview::Link> = (doc.links.iter())
+        .filter(|l| shown(l.line))
+        .map(|l| mdview::Link { line: projection.row(l.line), ..l.clone() })
+        .collect();
+    for details in doc.details.iter().filter(|d| d.line < len && !hidden[d.line]) {
+        let line = projection.row(details.line);
+        let content = lines[line].to_string();
+        let start = content.len() - content.trim_start().len();
+        links.push(mdview::Link { line, start, end: content.trim_end().len(), url: TOGGLE_URL.to_string() });
+    }
+    links.sort_by_key(|l| (l.line, l.start));
+    let view = Document {
+        text: Text::from(lines),
+        links,
+        images: (doc.images.iter())
+            .filter(|i| shown(i.line))
+            .map(|i| mdview::ImagePlacement { line: projection.row(i.line), ..i.clone() })
+            .collect(),
+        headings: (doc.headings.iter())
+            .filter(|h| h.line < len && !hidden[h.line])
+            .map(|h| Heading { line: projection.row(h.line), ..h.clone() })
+            .collect(),
+        anchors: doc.anchors.iter().map(|(id, &line)| (id.clone(), projection.row(line))).collect(),
+        details: (doc.details.iter())
+            .filter(|d| d.line < len && !hidden[d.line])
+            .map(|d| mdview::Details { line: projection.row(d.line), end: projection.row(d.end), ..d.clone() })
+            .collect(),
+        tables: (doc.tables.iter())
+            .filter(|t| t.line < len && !hidden[t.line])
+            .map(|t| mdview::WideTable { line: projection.row(t.line), end: projection.row(t.end), ..t.clone() })
+            .collect(),
+    };
+    (view, projection)
+}
+
+fn summary(line: &Line<'static>, title: &str, hidden: usize) -> Line<'static> {
+    let style = line.spans.last().map_or_else(Style::default, |s| s.style);
+    let plural = if hidden == 1 { "" } else { "s" };
+    Line::from(vec![
+        Span::styled(format!("▸ {title}"), style),
+        Span::styled(format!(" ({hidden} line{plural})"), Style::default().fg(Color::DarkGray)),
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mdview::{RenderOptions, render_document};
+
+    fn plain(doc: &Document) -> Vec<String> {
+        doc.text.lines.iter().map(|l| l.to_string()).collect()
+    }
+
+    #[test]
+    fn test_folded_section_is_summarized() {
+        let doc = render_document("# A\n\nOne.\n\nTwo.\n\n## A.1\n\nThree.\n\n# B\n\n[Four](x)\n", &RenderOptions::new(40));
+        let folded = BTreeSet::from([doc.headings[0].line]);
+        let (view, projection) = project(&doc, &folded);
+        assert_eq!(plain(&view), ["▸ A (8 lines)", "", "# B", "", "Four (x)", ""]);
+        assert_eq!(view.headings.len(), 2);
+        assert_eq!(view.links[0].line, 4);
+        assert_eq!(view.anchors["a1"], 0);
+        // Hidden lines map to the summary and back to the heading.
+        assert_eq!(projection.row(doc.headings[1].line), 0);
+        assert_eq!(projection.line(2), doc.headings[2].line);
+        assert_eq!(projection.line(projection.row(9)), 9);
+    }
+
+    #[test]
+    fn test_nested_folds() {
+        let doc = render_document("# A\n\n## A.1\n\nOne.\n\n# B\n", &RenderOptions::new(40));
+        let all: BTreeSet<usize> = doc.headings.iter().map(|h| h.line).collect();
+        let (view, _) = project(&doc, &all);
+        assert_eq!(plain(&view), ["▸ A (4 lines)", "", "▸ B (0 lines)", ""]);
+        let (view, _) = project(&doc, &BTreeSet::from([doc.headings[1].line]));
+        assert_eq!(plain(&view), ["# A", "", "▸ A.1 (2 lines)", "", "# B", ""]);
+        assert_eq!(hiding(&doc, &all, doc.headings[1].line), [doc.headings[0].line]);
+        assert_eq!(hiding(&doc, &all, doc.headings[1].line + 2), [doc.headings[0].line, doc.headings[1].line]);
+    }
+
+    #[test]
+    fn test_folded_details() {
+        let md = "<details>\n<summary>More</summary>\n\nInside.\n\n</details>\n\n# B\n";
+        let doc = render_document(md, &RenderOptions::new(40));
+        let line = doc.details[0].line;
+        assert!(can_fold(&doc, line));
+        let (view, _) = project(&doc, &BTreeSet::from([line]));
+        assert_eq!(plain(&view), ["▸ More (2 lines)", "", "# B", ""]);
+        assert_eq!((view.links[0].start, view.links[0].url.as_str()), (0, TOGGLE_URL));
+        let (view, _) = project(&doc, &BTreeSet::new());
+        assert_eq!(plain(&view)[..3], ["▾ More", "", "Inside."]);
+        assert_eq!(view.links[0].end, "▾ More".len());
+    }
+}
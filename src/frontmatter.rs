@@ -0,0 +1,228 @@
+//! YAML frontmatter: the block of `key: value` lines between `---` fences
+//! that static site generators and note apps put at the top of a file.
+//!
+//! Only what the viewer shows is read, without a full YAML parser: plain,
+//! quoted and folded scalars, and lists, which are joined with commas.
+
+use std::borrow::Cow;
+
+/// The fields of a document's frontmatter shown in the status bar and the
+/// statistics.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Metadata {
+    pub title: Option<String>,
+    /// `author` or `authors`; several are joined with commas.
+    pub author: Option<String>,
+    /// As written, since frontmatter dates come in any format.
+    pub date: Option<String>,
+}
+
+impl Metadata {
+    pub fn is_empty(&self) -> bool {
+        self.title.is_none() && self.author.is_none() && self.date.is_none()
+    }
+
+    /// Label and value pairs of the fields that are set, in display order.
+    pub fn rows(&self) -> Vec<(&'static str, String)> {
+        [("Title", &self.title), ("Author", &self.author), ("Date", &self.date)]
+            .into_iter()
+            .filter_map(|(label, value)| Some((label, value.clone()?)))
+            .collect()
+    }
+
+    /// `Title (Author, Date)` with the fields that are set, empty when none
+    /// is.
+    pub fn summary(&self) -> String {
+        let byline = [&self.author, &self.date].into_iter().flatten().cloned().collect::<Vec<_>>().join(", ");
+        match &self.title {
+            Some(title) if byline.is_empty() => title.clone(),
+            Some(title) => format!("{title} ({byline})"),
+            None => byline,
+        }
+    }
+}
+
+/// Splits `input` into its frontmatter, without the fences, and the
+/// markdown after it. The frontmatter opens with `---` on the first line,
+/// with something other than a blank line under it so a leading thematic
+/// break is not taken for one, and closes with `---` or `...`.
+///
+/// ```
+/// let (frontmatter, body) = mdview::frontmatter::split("---\ntitle: Notes\n---\n# Notes\n");
+/// assert_eq!((frontmatter, body), (Some("title: Notes\n"), "# Notes\n"));
+/// ```
+pub fn split(input: &str) -> (Option<&str>, &str) {
+    let mut lines = input.split_inclusive('\n');
+    let Some(open) = lines.next().filter(|line| line.trim_end() == "---") else {
+        return (None, input);
+    };
+    let start = open.len();
+    if input[start..].lines().next().is_none_or(|line| line.trim().is_empty()) {
+        return (None, input);
+    }
+    let mut end = start;
+    for line in lines {
+        if matches!(line.trim_end(), "---" | "...") {
+            return (Some(&input[start..end]), &input[end + line.len()..]);
+        }
+        end += line.len();
+    }
+    (None, input)
+}
+
+/// `input` with its frontmatter, fences included, turned into blank lines,
+/// so it is not rendered as a thematic break and a heading while the lines
+/// after it keep their numbers.
+///
+/// ```
+/// let input = "---\ntitle: Notes\n---\n# Notes\n";
+/// assert_eq!(mdview::frontmatter::blank(input), "\n\n\n# Notes\n");
+/// ```
+pub fn blank(input: &str) -> Cow<'_, str> {
+    match split(input) {
+        (Some(_), body) => {
+            let head = &input[..input.len() - body.len()];
+            Cow::Owned("\n".repeat(head.matches('\n').count()) + body)
+        }
+        (None, _) => Cow::Borrowed(input),
+    }
+}
+
+/// The title, author and date in the frontmatter of `input`, if it has
+/// any.
+///
+/// ```
+/// let input = "---\ntitle: \"Release notes\"\nauthors: [Ada, Grace]\ndate: 2024-05-01\n---\n";
+/// let metadata = mdview::frontmatter::metadata(input);
+/// assert_eq!(metadata.summary(), "Release notes (Ada, Grace, 2024-05-01)");
+/// ```
+pub fn metadata(input: &str) -> Metadata {
+    let mut metadata = Metadata::default();
+    let Some(frontmatter) = split(input).0 else {
+        return metadata;
+    };
+    let lines: Vec<&str> = frontmatter.lines().collect();
+    for (i, line) in lines.iter().enumerate() {
+        // Keys of nested mappings are indented, and only top-level ones
+        // are read.
+        if line.starts_with([' ', '\t', '#', '-']) {
+            continue;
+        }
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let field = match key.trim() {
+            "title" => &mut metadata.title,
+            "author" | "authors" => &mut metadata.author,
+            "date" => &mut metadata.date,
+            _ => continue,
+        };
+        if field.is_none() {
+            *field = value_of(value.trim(), &lines[i + 1..]).filter(|v| !v.is_empty());
+        }
+    }
+    metadata
+}
+
+/// The `tags` (or `tag`) of the frontmatter of `input`: a list, or words
+/// separated by commas or spaces as Obsidian also takes them, without a
+/// leading `#`.
+///
+/// ```
+/// let input = "---\ntags: [rust, \"#cli\"]\n---\n";
+/// assert_eq!(mdview::frontmatter::tags(input), ["rust", "cli"]);
+/// assert_eq!(mdview::frontmatter::tags("---\ntags: notes, project/mdview\n---\n"), ["notes", "project/mdview"]);
+/// ```
+pub fn tags(input: &str) -> Vec<String> {
+    let Some(frontmatter) = split(input).0 else {
+        return Vec::new();
+    };
+    let lines: Vec<&str> = frontmatter.lines().collect();
+    let value = lines.iter().enumerate().find_map(|(i, line)| {
+        let (key, value) = line.split_once(':')?;
+        matches!(key, "tags" | "tag").then(|| value_of(value.trim(), &lines[i + 1..]))?
+    });
+    let words = value.iter().flat_map(|value| value.split([',', ' ']));
+    words.map(|tag| tag.trim_start_matches('#').to_string()).filter(|tag| !tag.is_empty()).collect()
+}
+
+/// The value of a key whose line ends with `value`; a block list or scalar
+/// goes on in the indented lines of `rest`.
+fn value_of(value: &str, rest: &[&str]) -> Option<String> {
+    let nested = rest.iter().take_while(|line| line.trim().is_empty() || line.starts_with([' ', '\t', '-']));
+    match value {
+        "" => {
+            let items: Vec<String> = nested.filter_map(|line| line.trim().strip_prefix('-')).map(scalar).collect();
+            (!items.is_empty()).then(|| items.join(", "))
+        }
+        _ if value.starts_with(['|', '>']) => {
+            let lines: Vec<&str> = nested.map(|line| line.trim()).filter(|line| !line.is_empty()).collect();
+            Some(lines.join(" "))
+        }
+        _ if value.starts_with('[') => {
+            let list = value[1..].split(']').next().unwrap_or_default();
+            Some(list.split(',').map(scalar).filter(|item| !item.is_empty()).collect::<Vec<_>>().join(", "))
+        }
+        _ => Some(scalar(value)),
+    }
+}
+
+/// A scalar without its quotes, or without a trailing comment when it has
+/// none.
+fn scalar(value: &str) -> String {
+    let value = value.trim();
+    if let Some(quoted) = value.strip_prefix('"') {
+        let mut out = String::new();
+        let mut chars = quoted.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '"' => break,
+                '\\' => out.extend(chars.next()),
+                c => out.push(c),
+            }
+        }
+        return out;
+    }
+    if let Some(quoted) = value.strip_prefix('\'') {
+        let parts: Vec<&str> = quoted.split("''").map(|part| part.split('\'').next().unwrap_or_default()).collect();
+        return parts.join("'");
+    }
+    value.split(" #").next().unwrap_or_default().trim().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_needs_both_fences() {
+        assert_eq!(split("---\na: 1\n...\nbody"), (Some("a: 1\n"), "body"));
+        assert_eq!(split("---\r\na: 1\r\n---\r\n"), (Some("a: 1\r\n"), ""));
+        assert_eq!(split("---\na: 1\n"), (None, "---\na: 1\n"));
+        assert_eq!(split("---\n\nText\n\n---\n"), (None, "---\n\nText\n\n---\n"));
+        assert_eq!(split("# Title\n---\na\n---\n").0, None);
+    }
+
+    #[test]
+    fn test_metadata_reads_scalars_and_lists() {
+        let input = "---\n\
+                     title: 'It''s here' # not a comment\n\
+                     layout: post\n\
+                     author:\n  - \"Ada \\\"A\\\" Lovelace\"\n  - Grace # a comment\n\
+                     tags: [a, b]\n\
+                     date: 2024-05-01 # a comment\n\
+                     seo:\n  title: Not this one\n\
+                     ---\n# Body\n";
+        let metadata = metadata(input);
+        assert_eq!(metadata.title.as_deref(), Some("It's here"));
+        assert_eq!(metadata.author.as_deref(), Some("Ada \"A\" Lovelace, Grace"));
+        assert_eq!(metadata.date.as_deref(), Some("2024-05-01"));
+        assert_eq!(metadata.rows().len(), 3);
+
+        let folded = super::metadata("---\ntitle: >\n  A long\n  title\n---\n");
+        assert_eq!(folded.summary(), "A long title");
+        let dated = super::metadata("---\ndate: 2024-05-01\ntitle:\n---\n");
+        assert_eq!((dated.title.as_deref(), dated.summary().as_str()), (None, "2024-05-01"));
+        assert!(super::metadata("# No frontmatter\n").is_empty());
+    }
+}
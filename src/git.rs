@@ -0,0 +1,81 @@
+use std::fmt;
+use std::path::Path;
+
+use anyhow::{Context, Result, anyhow};
+use git2::Repository;
+use mdview::encoding::{self, Decoded};
+
+/// The version of each file the viewer shows, when not the file as it is.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Revision {
+    /// The file as of a git revision (`--rev`).
+    At(String),
+    /// The file with the blocks added or removed since a git revision
+    /// marked (`--diff`).
+    Diff(String),
+}
+
+impl fmt::Display for Revision {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Revision::At(rev) => write!(f, "@{rev}"),
+            Revision::Diff(rev) => write!(f, "diff {rev}"),
+        }
+    }
+}
+
+/// The contents of the file at `path` as of `rev`, from the git repository
+/// holding it, decoded like a file on disk.
+pub fn show(path: &Path, rev: &str) -> Result<Decoded> {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let name = path.file_name().context("Not a file")?;
+    let cannot_read = |e: git2::Error| anyhow!("Cannot read {} at {rev}: {}", path.display(), e.message());
+    let repo = Repository::discover(dir).map_err(cannot_read)?;
+    let top = repo.workdir().context("The repository has no working tree")?;
+    // The file may be gone from the disk, its folder not.
+    let dir = dir.canonicalize().with_context(|| format!("Cannot open {}", dir.display()))?;
+    let relative = dir.join(name);
+    let relative = relative.strip_prefix(top.canonicalize()?).context("The file is outside the repository")?;
+    let tree = repo.revparse_single(rev).and_then(|object| object.peel_to_tree()).map_err(cannot_read)?;
+    let entry = tree.get_path(relative).map_err(cannot_read)?;
+    let blob = entry.to_object(&repo).and_then(|object| object.peel_to_blob()).map_err(cannot_read)?;
+    Ok(encoding::decode(blob.content()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_show_reads_old_versions() {
+        let dir = std::env::temp_dir().join(format!("mdview-git-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("docs")).unwrap();
+        let repo = Repository::init(&dir).unwrap();
+        let signature = git2::Signature::now("t", "t@t").unwrap();
+        let commit = |message: &str| {
+            let mut index = repo.index().unwrap();
+            index.add_path(Path::new("docs/guide.md")).unwrap();
+            let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+            let parent = repo.head().ok().map(|head| head.peel_to_commit().unwrap());
+            let parents: Vec<_> = parent.iter().collect();
+            repo.commit(Some("HEAD"), &signature, &signature, message, &tree, &parents).unwrap();
+        };
+        let file = dir.join("docs/guide.md");
+        std::fs::write(&file, "one\n").unwrap();
+        commit("one");
+        std::fs::write(&file, "two\n").unwrap();
+        commit("two");
+
+        assert_eq!(show(&file, "HEAD").unwrap().text, "two\n");
+        assert_eq!(show(&file, "HEAD~1").unwrap().text, "one\n");
+        assert!(show(&file, "HEAD~5").unwrap_err().to_string().starts_with("Cannot read"));
+        assert!(show(&dir.join("docs/new.md"), "HEAD").unwrap_err().to_string().starts_with("Cannot read"));
+        // A file deleted since is still read from history.
+        std::fs::remove_file(&file).unwrap();
+        assert_eq!(show(&file, "HEAD~1").unwrap().text, "one\n");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,78 @@
+//! Cutting a document down to the blocks that match something, for
+//! `mdview dump --grep`.
+
+use pulldown_cmark::{Event, Parser, Tag};
+use ratatui::text::Text;
+
+use crate::frontmatter;
+use crate::incremental::{render_blocks, split_blocks};
+use crate::render::RenderOptions;
+
+/// `input` rendered with only the top-level blocks `matches` accepts, each
+/// after the headings of the sections it is in, in document order. A
+/// heading shows once however many blocks under it match. `matches` is
+/// given the rendered lines of each block; the result is empty when it
+/// accepts none.
+///
+/// ```
+/// use mdview::{RenderOptions, grep};
+///
+/// let input = "# Notes\n\nNothing here.\n\n## Later\n\nTODO: write this.\n";
+/// let text = grep::filter(input, &RenderOptions::new(80), |block| block.to_string().contains("TODO"));
+/// let lines: Vec<String> = text.lines.iter().map(|l| l.to_string()).collect();
+/// assert_eq!(lines, ["# Notes", "", "## Later", "", "TODO: write this."]);
+/// ```
+pub fn filter(input: &str, options: &RenderOptions, matches: impl Fn(&Text<'static>) -> bool) -> Text<'static> {
+    let input = &*frontmatter::blank(input);
+    let parser = Parser::new_ext(input, options.extensions).into_offset_iter();
+    let (ranges, levels): (Vec<_>, Vec<_>) = split_blocks(parser)
+        .map(|(range, events)| {
+            let level = match events.first() {
+                Some(Event::Start(Tag::Heading { level, .. })) => Some(*level as u8),
+                _ => None,
+            };
+            (range, level)
+        })
+        .unzip();
+    let blocks: Vec<Text<'static>> = render_blocks(input, &ranges, options).into_iter().map(Text::from).collect();
+
+    let mut shown = vec![false; blocks.len()];
+    // The headings of the sections the block at hand is in, outermost
+    // first, by level and block index.
+    let mut sections: Vec<(u8, usize)> = Vec::new();
+    for (i, block) in blocks.iter().enumerate() {
+        if let Some(level) = levels[i] {
+            while sections.last().is_some_and(|&(l, _)| l >= level) {
+                sections.pop();
+            }
+        }
+        if matches(block) {
+            sections.iter().for_each(|&(_, heading)| shown[heading] = true);
+            shown[i] = true;
+        }
+        if let Some(level) = levels[i] {
+            sections.push((level, i));
+        }
+    }
+    let mut lines: Vec<_> =
+        blocks.into_iter().zip(shown).filter(|(_, shown)| *shown).flat_map(|(block, _)| block.lines).collect();
+    // The blank lines after the last block shown.
+    while lines.last().is_some_and(|line| line.width() == 0) {
+        lines.pop();
+    }
+    Text::from(lines)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_filter_keeps_enclosing_headings() {
+        let input = "# A\n\nx one\n\n## B\n\nnothing\n\n### C\n\nx two\n\n## D\n\nx three\n\nx four\n\n# E\n\nnone\n";
+        let text = filter(input, &RenderOptions::new(40), |block| block.to_string().contains('x'));
+        let lines: Vec<String> = text.lines.iter().map(|l| l.to_string()).filter(|l| !l.is_empty()).collect();
+        assert_eq!(lines, ["# A", "x one", "## B", "### C", "x two", "## D", "x three", "x four"]);
+        assert!(filter(input, &RenderOptions::new(40), |_| false).lines.is_empty());
+    }
+}
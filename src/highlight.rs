@@ -1,37 +1,433 @@
-use std::sync::LazyLock;
+use std::collections::HashMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::sync::{LazyLock, Mutex, OnceLock};
 
-use ansi_to_tui::IntoText;
-use ratatui::text::Line;
+use ratatui::style::{Color, Modifier, Style};
+use rayon::prelude::*;
+use ratatui::text::{Line, Span};
 use syntect::easy::HighlightLines;
-use syntect::highlighting::ThemeSet;
-use syntect::parsing::SyntaxSet;
-use syntect::util::as_24_bit_terminal_escaped;
+use syntect::highlighting::{Theme, ThemeSet};
+use syntect::html::highlighted_html_for_string;
+use syntect::parsing::{SyntaxDefinition, SyntaxSet};
 
-static SYNTAX_SET: LazyLock<SyntaxSet> = LazyLock::new(SyntaxSet::load_defaults_newlines);
+use crate::bench;
+
+/// The directory of extra syntax definitions and the cache directory, from
+/// `use_syntaxes`.
+static SYNTAX_DIRS: OnceLock<(PathBuf, Option<PathBuf>)> = OnceLock::new();
+
+static SYNTAX_SET: LazyLock<SyntaxSet> = LazyLock::new(|| match SYNTAX_DIRS.get() {
+    Some((dir, cache_dir)) => load_syntaxes(dir, cache_dir.as_deref()),
+    None => SyntaxSet::load_defaults_newlines(),
+});
 static THEME_SET: LazyLock<ThemeSet> = LazyLock::new(ThemeSet::load_defaults);
 
-pub fn highlight_code(code: &str, lang: Option<&str>) -> Vec<Line<'static>> {
+const DEFAULT_THEME: &str = "base16-ocean.dark";
+
+/// Languages that are plain text, drawn without the syntax definitions.
+const PLAIN: &[&str] = &["text", "txt", "plain", "plaintext"];
+
+/// Highlighted blocks kept across renders. Enough for every code block of a
+/// large document, so a resize or reload re-highlights only edited blocks.
+const CACHE_CAPACITY: usize = 512;
+
+static CACHE: LazyLock<Mutex<LruCache>> = LazyLock::new(|| Mutex::new(LruCache::new(CACHE_CAPACITY)));
+
+/// Language, hash of the code and syntect theme name.
+type CacheKey = (Option<String>, u64, String);
+
+/// Least-recently-used cache of highlighted code blocks.
+struct LruCache {
+    capacity: usize,
+    entries: HashMap<CacheKey, (u64, Vec<Line<'static>>)>,
+    /// Bumped on every access; an entry's stamp is when it was last used.
+    clock: u64,
+}
+
+impl LruCache {
+    fn new(capacity: usize) -> Self {
+        Self { capacity, entries: HashMap::new(), clock: 0 }
+    }
+
+    fn get(&mut self, key: &CacheKey) -> Option<Vec<Line<'static>>> {
+        self.clock += 1;
+        let (used, lines) = self.entries.get_mut(key)?;
+        *used = self.clock;
+        Some(lines.clone())
+    }
+
+    fn contains(&self, key: &CacheKey) -> bool {
+        self.entries.contains_key(key)
+    }
+
+    fn insert(&mut self, key: CacheKey, lines: Vec<Line<'static>>) {
+        if self.entries.len() >= self.capacity && !self.entries.contains_key(&key) {
+            let oldest = self.entries.iter().min_by_key(|(_, (used, _))| *used).map(|(k, _)| k.clone());
+            if let Some(oldest) = oldest {
+                self.entries.remove(&oldest);
+            }
+        }
+        self.clock += 1;
+        self.entries.insert(key, (self.clock, lines));
+    }
+}
+
+fn syntect_theme(name: &str) -> &'static Theme {
+    THEME_SET
+        .themes
+        .get(name)
+        .unwrap_or_else(|| &THEME_SET.themes[DEFAULT_THEME])
+}
+
+/// Highlights with the `.sublime-syntax` files in `dir` as well as syntect's
+/// defaults, winning over them for the same name or extension. The set
+/// built with them is cached in `cache_dir` until they change. Only takes
+/// effect before the first highlight.
+pub fn use_syntaxes(dir: PathBuf, cache_dir: Option<PathBuf>) {
+    let _ = SYNTAX_DIRS.set((dir, cache_dir));
+}
+
+/// syntect's default syntaxes plus the definitions in `dir`, read from the
+/// cache when one was written for the same files. Definitions that fail to
+/// load are logged and left out.
+fn load_syntaxes(dir: &Path, cache_dir: Option<&Path>) -> SyntaxSet {
+    let mut files: Vec<PathBuf> = std::fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "sublime-syntax"))
+        .collect();
+    if files.is_empty() {
+        return SyntaxSet::load_defaults_newlines();
+    }
+    files.sort();
+    // The cache is named by the version, whose syntect writes it, and the
+    // name, size and modification time of every file.
+    let mut hasher = DefaultHasher::new();
+    env!("CARGO_PKG_VERSION").hash(&mut hasher);
+    for path in &files {
+        let meta = std::fs::metadata(path).ok();
+        (path, meta.as_ref().map(|m| m.len()), meta.and_then(|m| m.modified().ok())).hash(&mut hasher);
+    }
+    let name = format!("syntaxes-{:016x}.packdump", hasher.finish());
+    let cache = cache_dir.map(|dir| dir.join(name));
+    if let Some(set) = cache.as_ref().and_then(|path| syntect::dumps::from_uncompressed_dump_file(path).ok()) {
+        return set;
+    }
+
+    let mut builder = SyntaxSet::load_defaults_newlines().into_builder();
+    for path in &files {
+        let fallback = path.file_stem().and_then(|stem| stem.to_str());
+        let loaded = std::fs::read_to_string(path)
+            .map_err(|e| e.to_string())
+            .and_then(|source| SyntaxDefinition::load_from_str(&source, true, fallback).map_err(|e| e.to_string()));
+        match loaded {
+            Ok(syntax) => builder.add(syntax),
+            Err(error) => tracing::warn!(path = %path.display(), %error, "syntax definition not loaded"),
+        }
+    }
+    let set = builder.build();
+    if let (Some(cache_dir), Some(cache)) = (cache_dir, &cache)
+        && let Err(error) = write_cache(&set, cache_dir, cache)
+    {
+        tracing::warn!(path = %cache.display(), %error, "syntax cache not written");
+    }
+    set
+}
+
+/// Writes `set` to `path` in `dir`, replacing the caches of earlier files.
+fn write_cache(set: &SyntaxSet, dir: &Path, path: &Path) -> anyhow::Result<()> {
+    std::fs::create_dir_all(dir)?;
+    for entry in std::fs::read_dir(dir)?.flatten() {
+        let name = entry.file_name();
+        if name.to_string_lossy().starts_with("syntaxes-") {
+            let _ = std::fs::remove_file(entry.path());
+        }
+    }
+    let tmp = path.with_extension("packdump.tmp");
+    syntect::dumps::dump_to_uncompressed_file(set, &tmp)?;
+    std::fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+/// Loads the syntax definitions and themes, which otherwise happens on the
+/// first highlight.
+pub(crate) fn load() {
+    LazyLock::force(&SYNTAX_SET);
+    LazyLock::force(&THEME_SET);
+}
+
+/// Forgets every highlighted block.
+pub(crate) fn clear_cache() {
+    if let Ok(mut cache) = CACHE.lock() {
+        *cache = LruCache::new(CACHE_CAPACITY);
+    }
+}
+
+/// Highlights `code` into one styled line per source line. Results are
+/// cached by language, code and theme.
+pub fn highlight_code(code: &str, lang: Option<&str>, theme: &str) -> Vec<Line<'static>> {
+    let key = cache_key(code, lang, theme);
+    if let Some(lines) = CACHE.lock().ok().and_then(|mut cache| cache.get(&key)) {
+        return lines;
+    }
+    let lines = highlight_uncached(code, lang, theme);
+    if let Ok(mut cache) = CACHE.lock() {
+        cache.insert(key, lines.clone());
+    }
+    lines
+}
+
+/// Highlights the `(language, code)` blocks not cached yet in parallel and
+/// caches them, so a render that then highlights them one by one in order
+/// finds them all done. No more than the cache holds are highlighted, and
+/// nothing on one core or while a profile runs, which times each block as
+/// it is drawn.
+pub(crate) fn highlight_blocks(blocks: &[(Option<String>, String)], theme: &str) {
+    if bench::profiling() || rayon::current_num_threads() < 2 {
+        return;
+    }
+    let mut keys = std::collections::HashSet::new();
+    let todo: Vec<_> = {
+        let Ok(cache) = CACHE.lock() else { return };
+        blocks
+            .iter()
+            .map(|(lang, code)| (cache_key(code, lang.as_deref(), theme), lang, code))
+            .filter(|(key, ..)| !cache.contains(key) && keys.insert(key.clone()))
+            .take(CACHE_CAPACITY)
+            .collect()
+    };
+    // A lone block is as quick to highlight when it is drawn.
+    if todo.len() < 2 {
+        return;
+    }
+    let highlighted: Vec<_> = todo
+        .into_par_iter()
+        .map(|(key, lang, code)| (key, highlight_uncached(code, lang.as_deref(), theme)))
+        .collect();
+    if let Ok(mut cache) = CACHE.lock() {
+        for (key, lines) in highlighted {
+            cache.insert(key, lines);
+        }
+    }
+}
+
+fn cache_key(code: &str, lang: Option<&str>, theme: &str) -> CacheKey {
+    let mut hasher = DefaultHasher::new();
+    code.hash(&mut hasher);
+    (lang.map(str::to_string), hasher.finish(), theme.to_string())
+}
+
+fn highlight_uncached(code: &str, lang: Option<&str>, theme: &str) -> Vec<Line<'static>> {
+    let _span = bench::span("highlight");
+    if lang.is_some_and(|l| l.eq_ignore_ascii_case("diff") || l.eq_ignore_ascii_case("patch")) {
+        return diff_lines(code);
+    }
+    if lang.is_none_or(|l| PLAIN.iter().any(|p| l.eq_ignore_ascii_case(p))) {
+        return plain_lines(code, theme);
+    }
     let ss = &*SYNTAX_SET;
     let syntax = lang
         .and_then(|l| ss.find_syntax_by_token(l))
         .unwrap_or_else(|| ss.find_syntax_plain_text());
 
-    let theme = &THEME_SET.themes["base16-ocean.dark"];
+    let theme = syntect_theme(theme);
     let mut h = HighlightLines::new(syntax, theme);
 
-    let mut ansi = String::new();
-    for line in code.lines() {
-        let ranges = h.highlight_line(line, ss).unwrap_or_default();
-        ansi.push_str(&as_24_bit_terminal_escaped(&ranges, false));
-        ansi.push('\n');
-    }
-    ansi.push_str("\x1b[0m");
+    // Spans are built from the ranges directly rather than going through
+    // ANSI, which would act on escape sequences in the code itself.
+    code.lines()
+        .map(|line| match h.highlight_line(line, ss) {
+            Ok(ranges) => ranges
+                .into_iter()
+                .map(|(style, text)| {
+                    let fg = style.foreground;
+                    Span::styled(text.to_string(), Style::default().fg(Color::Rgb(fg.r, fg.g, fg.b)))
+                })
+                .collect(),
+            Err(_) => Line::raw(line.to_string()),
+        })
+        .collect()
+}
 
-    ansi.into_text()
-        .map(|t| t.lines.into_iter().collect())
-        .unwrap_or_else(|_| {
-            code.lines()
-                .map(|l| Line::raw(l.to_string()))
-                .collect()
+/// `code` in the theme's foreground, as syntect colors plain text, without
+/// loading the syntax definitions: a document whose code blocks have no
+/// language never does.
+fn plain_lines(code: &str, theme: &str) -> Vec<Line<'static>> {
+    let fg = syntect_theme(theme).settings.foreground.unwrap_or(syntect::highlighting::Color::BLACK);
+    let style = Style::default().fg(Color::Rgb(fg.r, fg.g, fg.b));
+    code.lines()
+        .map(|line| match line {
+            "" => Line::default(),
+            line => Line::from(Span::styled(line.to_string(), style)),
         })
+        .collect()
+}
+
+/// Colors a diff by line, as `git diff` does, in place of syntect's diff
+/// grammar, whose scopes most themes leave uncolored: added lines green,
+/// removed lines red, hunk headers dim and file headers bold. Inside a hunk
+/// its line counts tell a removed `--- x` from a file header; diffs without
+/// hunk headers, as often written by hand, go by the first character.
+fn diff_lines(code: &str) -> Vec<Line<'static>> {
+    let added = Style::default().fg(Color::Green);
+    let removed = Style::default().fg(Color::Red);
+    let dim = Style::default().add_modifier(Modifier::DIM);
+    let bold = Style::default().add_modifier(Modifier::BOLD);
+    // Old and new lines left in the current hunk.
+    let mut left = (0, 0);
+    code.lines()
+        .map(|line| {
+            let header = ["+++ ", "--- ", "diff "].iter().any(|p| line.starts_with(p)) && left == (0, 0);
+            let style = match line.as_bytes().first() {
+                _ if line.starts_with("@@") => {
+                    left = hunk_counts(line).unwrap_or_default();
+                    dim
+                }
+                Some(b'\\') => dim,
+                _ if header => bold,
+                Some(b'+') => {
+                    left.1 = left.1.saturating_sub(1);
+                    added
+                }
+                Some(b'-') => {
+                    left.0 = left.0.saturating_sub(1);
+                    removed
+                }
+                _ => {
+                    left = (left.0.saturating_sub(1), left.1.saturating_sub(1));
+                    Style::default()
+                }
+            };
+            Line::from(Span::styled(line.to_string(), style))
+        })
+        .collect()
+}
+
+/// The old and new line counts of a hunk header, `@@ -1,5 +1,6 @@`; a
+/// count left out is 1.
+fn hunk_counts(header: &str) -> Option<(usize, usize)> {
+    let mut ranges = header.strip_prefix("@@ ")?.split_whitespace();
+    let count = |range: &str| match range.split_once(',') {
+        Some((_, count)) => count.parse().ok(),
+        None => Some(1),
+    };
+    let old = count(ranges.next()?.strip_prefix('-')?)?;
+    let new = count(ranges.next()?.strip_prefix('+')?)?;
+    Some((old, new))
+}
+
+/// Highlights `code` as a `<pre>` block with inline styles.
+pub fn highlight_html(code: &str, lang: Option<&str>, theme: &str) -> String {
+    let ss = &*SYNTAX_SET;
+    let syntax = lang
+        .and_then(|l| ss.find_syntax_by_token(l))
+        .unwrap_or_else(|| ss.find_syntax_plain_text());
+    highlighted_html_for_string(code, ss, syntax, syntect_theme(theme)).unwrap_or_default()
+}
+
+/// Background and foreground of a syntect theme as CSS hex colors.
+pub fn page_colors(theme: &str) -> (String, String) {
+    let hex = |(r, g, b): (u8, u8, u8)| format!("#{r:02x}{g:02x}{b:02x}");
+    let (bg, fg) = page_rgb(theme);
+    (hex(bg), hex(fg))
+}
+
+/// Background and foreground of a syntect theme; white and black where the
+/// theme leaves them unset.
+pub fn page_rgb(theme: &str) -> ((u8, u8, u8), (u8, u8, u8)) {
+    let settings = &syntect_theme(theme).settings;
+    let rgb = |c: syntect::highlighting::Color| (c.r, c.g, c.b);
+    (
+        settings.background.map_or((0xff, 0xff, 0xff), rgb),
+        settings.foreground.map_or((0x00, 0x00, 0x00), rgb),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(n: u64) -> CacheKey {
+        (None, n, DEFAULT_THEME.to_string())
+    }
+
+    #[test]
+    fn test_cache_evicts_least_recently_used() {
+        let mut cache = LruCache::new(2);
+        cache.insert(key(1), vec![Line::raw("one")]);
+        cache.insert(key(2), vec![Line::raw("two")]);
+        assert!(cache.get(&key(1)).is_some());
+        cache.insert(key(3), vec![Line::raw("three")]);
+        assert!(cache.get(&key(2)).is_none());
+        assert_eq!(cache.get(&key(1)).unwrap(), [Line::raw("one")]);
+        assert!(cache.get(&key(3)).is_some());
+    }
+
+    #[test]
+    fn test_diff_lines() {
+        let code = "diff --git a/x b/x\n--- a/x\n+++ b/x\n@@ -1,3 +1,2 @@\n keep\n--- gone\n-also\n+new\n\
+                    \\ No newline\n";
+        let styles: Vec<Style> = highlight_code(code, Some("diff"), DEFAULT_THEME)
+            .iter()
+            .map(|line| line.spans[0].style)
+            .collect();
+        let bold = Style::default().add_modifier(Modifier::BOLD);
+        let dim = Style::default().add_modifier(Modifier::DIM);
+        let (added, removed) = (Style::default().fg(Color::Green), Style::default().fg(Color::Red));
+        assert_eq!(styles, [bold, bold, bold, dim, Style::default(), removed, removed, added, dim]);
+        let by_hand = highlight_code("-old\n+new\n same\n", Some("patch"), DEFAULT_THEME);
+        assert_eq!(by_hand[0].spans[0].style, removed);
+        assert_eq!(by_hand[1].spans[0].style, added);
+        assert_eq!(hunk_counts("@@ -3 +3,0 @@ fn x()"), Some((1, 0)));
+        assert_eq!(hunk_counts("@@@ -1 -1 +1 @@@"), None);
+    }
+
+    #[test]
+    fn test_cached_result_matches_fresh_highlight() {
+        let code = "fn main() {\n    println!(\"hi\");\n}\n";
+        let first = highlight_code(code, Some("rust"), DEFAULT_THEME);
+        assert_eq!(first, highlight_uncached(code, Some("rust"), DEFAULT_THEME));
+        assert_eq!(highlight_code(code, Some("rust"), DEFAULT_THEME), first);
+    }
+
+    #[test]
+    fn test_plain_lines_match_plain_text_syntax() {
+        let code = "one\n\n  two <b>\n";
+        for theme in THEME_SET.themes.keys() {
+            let mut h = HighlightLines::new(SYNTAX_SET.find_syntax_plain_text(), syntect_theme(theme));
+            let syntect: Vec<Line> = code
+                .lines()
+                .map(|line| {
+                    let ranges = h.highlight_line(line, &SYNTAX_SET).unwrap();
+                    let fg = |c: syntect::highlighting::Color| Style::default().fg(Color::Rgb(c.r, c.g, c.b));
+                    ranges.into_iter().map(|(s, t)| Span::styled(t.to_string(), fg(s.foreground))).collect()
+                })
+                .collect();
+            assert_eq!(plain_lines(code, theme), syntect, "{theme}");
+        }
+        assert_eq!(highlight_uncached("x", Some("TXT"), DEFAULT_THEME), plain_lines("x", DEFAULT_THEME));
+    }
+
+    #[test]
+    fn test_load_syntaxes() {
+        let dir = std::env::temp_dir().join(format!("mdview-syntaxes-{}", std::process::id()));
+        let cache_dir = dir.join("cache");
+        std::fs::create_dir_all(&dir).unwrap();
+        let syntax = "name: Knot\nfile_extensions: [knot]\nscope: source.knot\ncontexts:\n  main: []\n";
+        std::fs::write(dir.join("Knot.sublime-syntax"), syntax).unwrap();
+        std::fs::write(dir.join("Broken.sublime-syntax"), "contexts: [").unwrap();
+        let set = load_syntaxes(&dir, Some(&cache_dir));
+        assert_eq!(set.find_syntax_by_token("knot").map(|s| s.name.as_str()), Some("Knot"));
+        assert!(set.find_syntax_by_token("rust").is_some());
+        // The second load comes from the cache.
+        let caches: Vec<_> = std::fs::read_dir(&cache_dir).unwrap().flatten().map(|e| e.path()).collect();
+        assert_eq!(caches.len(), 1);
+        let cached: SyntaxSet = syntect::dumps::from_uncompressed_dump_file(&caches[0]).unwrap();
+        assert!(cached.find_syntax_by_token("knot").is_some());
+        assert_eq!(load_syntaxes(&dir, Some(&cache_dir)).syntaxes().len(), set.syntaxes().len());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
 }
@@ -0,0 +1,69 @@
+/// A place in the viewer: a buffer and its scroll position.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Location {
+    pub buffer: usize,
+    pub scroll: usize,
+}
+
+/// Browser-style back/forward stacks of the places links were followed from.
+#[derive(Debug, Default)]
+pub struct History {
+    back: Vec<Location>,
+    forward: Vec<Location>,
+}
+
+impl History {
+    /// Records leaving `from` by following a link, which forgets anything
+    /// that was forward of it.
+    pub fn visit(&mut self, from: Location) {
+        self.back.push(from);
+        self.forward.clear();
+    }
+
+    /// The location to go back to from `current`, if any.
+    pub fn back(&mut self, current: Location) -> Option<Location> {
+        let to = self.back.pop()?;
+        self.forward.push(current);
+        Some(to)
+    }
+
+    /// The location to go forward to from `current`, if any.
+    pub fn forward(&mut self, current: Location) -> Option<Location> {
+        let to = self.forward.pop()?;
+        self.back.push(current);
+        Some(to)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(buffer: usize, scroll: usize) -> Location {
+        Location { buffer, scroll }
+    }
+
+    #[test]
+    fn test_back_and_forward() {
+        let mut history = History::default();
+        assert_eq!(history.back(at(0, 0)), None);
+        history.visit(at(0, 10));
+        history.visit(at(1, 5));
+        assert_eq!(history.back(at(2, 0)), Some(at(1, 5)));
+        assert_eq!(history.back(at(1, 7)), Some(at(0, 10)));
+        assert_eq!(history.back(at(0, 10)), None);
+        assert_eq!(history.forward(at(0, 12)), Some(at(1, 7)));
+        assert_eq!(history.forward(at(1, 7)), Some(at(2, 0)));
+        assert_eq!(history.forward(at(2, 0)), None);
+    }
+
+    #[test]
+    fn test_visit_clears_forward() {
+        let mut history = History::default();
+        history.visit(at(0, 0));
+        history.back(at(1, 0));
+        history.visit(at(0, 3));
+        assert_eq!(history.forward(at(2, 0)), None);
+        assert_eq!(history.back(at(2, 0)), Some(at(0, 3)));
+    }
+}
@@ -0,0 +1,249 @@
+use crate::entities::decode;
+
+/// A piece of raw HTML: a tag or the text between tags, with character
+/// references decoded. Comments and doctypes are dropped.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Token {
+    /// An opening tag. `name` and attribute names are lower case.
+    Open { name: String, attrs: Vec<(String, String)> },
+    Close(String),
+    Text(String),
+}
+
+impl Token {
+    pub(crate) fn attr(&self, key: &str) -> Option<&str> {
+        match self {
+            Token::Open { attrs, .. } => attrs.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str()),
+            _ => None,
+        }
+    }
+}
+
+/// Block-level elements, which start a new line of a table cell.
+pub(crate) const BLOCK: &[&str] = &[
+    "p", "div", "center", "section", "blockquote", "figure", "details", "summary", "ul", "ol", "li", "h1", "h2",
+    "h3", "h4", "h5", "h6", "hr",
+];
+
+/// Elements whose content is not shown.
+pub(crate) const HIDDEN: &[&str] = &["script", "style", "head", "title", "template"];
+
+/// Splits `html` into tags and text. Anything that does not parse as a tag
+/// is kept as text, so stray `<` characters survive.
+pub(crate) fn tokenize(html: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut text = String::new();
+    let mut rest = html;
+    while let Some(at) = rest.find('<') {
+        text.push_str(&rest[..at]);
+        rest = &rest[at..];
+        if let Some(after) = rest.strip_prefix("<!--") {
+            rest = after.find("-->").map_or("", |end| &after[end + 3..]);
+            continue;
+        }
+        if rest.starts_with("<!") || rest.starts_with("<?") {
+            rest = rest.find('>').map_or("", |end| &rest[end + 1..]);
+            continue;
+        }
+        match parse_tag(rest) {
+            Some((token, len)) => {
+                if !text.is_empty() {
+                    tokens.push(Token::Text(decode(&std::mem::take(&mut text)).into_owned()));
+                }
+                tokens.push(token);
+                rest = &rest[len..];
+            }
+            None => {
+                text.push('<');
+                rest = &rest[1..];
+            }
+        }
+    }
+    text.push_str(rest);
+    if !text.is_empty() {
+        tokens.push(Token::Text(decode(&text).into_owned()));
+    }
+    tokens
+}
+
+/// Parses the tag at the start of `s`, returning it and its length in bytes.
+fn parse_tag(s: &str) -> Option<(Token, usize)> {
+    let close = s.starts_with("</");
+    let body = &s[if close { 2 } else { 1 }..];
+    let name_len = body.find(|c: char| !c.is_ascii_alphanumeric() && c != '-').unwrap_or(body.len());
+    if name_len == 0 || !body.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        return None;
+    }
+    let name = body[..name_len].to_ascii_lowercase();
+    let mut attrs = Vec::new();
+    let mut rest = &body[name_len..];
+    loop {
+        rest = rest.trim_start();
+        if let Some(after) = rest.strip_prefix("/>").or_else(|| rest.strip_prefix('>')) {
+            let len = s.len() - after.len();
+            let token = if close { Token::Close(name) } else { Token::Open { name, attrs } };
+            return Some((token, len));
+        }
+        if rest.is_empty() || rest.starts_with('<') {
+            return None;
+        }
+        let key_len = rest.find(|c: char| c.is_whitespace() || "=>/<".contains(c)).unwrap_or(rest.len()).max(1);
+        let key = rest[..key_len].to_ascii_lowercase();
+        rest = rest[key_len..].trim_start();
+        let mut value = String::new();
+        if let Some(after) = rest.strip_prefix('=') {
+            let after = after.trim_start();
+            let (raw, next) = match after.chars().next() {
+                Some(quote @ ('"' | '\'')) => {
+                    let end = after[1..].find(quote)? + 1;
+                    (&after[1..end], &after[end + 1..])
+                }
+                _ => {
+                    let end = after.find(|c: char| c.is_whitespace() || c == '>').unwrap_or(after.len());
+                    (&after[..end], &after[end..])
+                }
+            };
+            value = decode(raw).into_owned();
+            rest = next;
+        }
+        attrs.push((key, value));
+    }
+}
+
+/// The index of the token closing the element opened just before
+/// `tokens`, counting nested elements of the same name; `tokens.len()` if
+/// it is never closed.
+pub(crate) fn closing(tokens: &[Token], name: &str) -> usize {
+    let mut depth = 0;
+    for (i, token) in tokens.iter().enumerate() {
+        match token {
+            Token::Open { name: n, .. } if n == name => depth += 1,
+            Token::Close(n) if n == name => {
+                if depth == 0 {
+                    return i;
+                }
+                depth -= 1;
+            }
+            _ => {}
+        }
+    }
+    tokens.len()
+}
+
+/// The rows of the table whose content is `tokens`, each a list of cells
+/// holding the tokens inside them, trimmed of surrounding whitespace.
+pub(crate) fn table_rows(tokens: &[Token]) -> Vec<Vec<Vec<Token>>> {
+    let mut rows: Vec<Vec<Vec<Token>>> = Vec::new();
+    let mut i = 0;
+    while i < tokens.len() {
+        match &tokens[i] {
+            Token::Open { name, .. } if name == "tr" => rows.push(Vec::new()),
+            Token::Open { name, .. } if name == "td" || name == "th" => {
+                let end = (i + 1 + closing(&tokens[i + 1..], name)).min(tokens.len());
+                // A cell outside any row starts one.
+                if rows.is_empty() {
+                    rows.push(Vec::new());
+                }
+                rows.last_mut().expect("a row was just ensured").push(trim(&tokens[i + 1..end]));
+                i = end;
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    rows.retain(|row| !row.is_empty());
+    rows
+}
+
+/// `tokens` without whitespace at either end.
+fn trim(tokens: &[Token]) -> Vec<Token> {
+    let mut tokens = tokens.to_vec();
+    if let Some(Token::Text(text)) = tokens.first_mut() {
+        *text = text.trim_start().to_string();
+    }
+    if let Some(Token::Text(text)) = tokens.last_mut() {
+        *text = text.trim_end().to_string();
+    }
+    tokens.retain(|t| !matches!(t, Token::Text(text) if text.is_empty()));
+    tokens
+}
+
+/// Raised or lowered text, from `<sup>` and `<sub>`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Script {
+    Super,
+    Sub,
+}
+
+/// `text` in Unicode superscript or subscript characters, or unchanged if
+/// any of its characters has none.
+pub(crate) fn script(text: &str, script: Script) -> String {
+    const SUPER: &[(char, char)] = &[
+        ('0', '⁰'), ('1', '¹'), ('2', '²'), ('3', '³'), ('4', '⁴'), ('5', '⁵'), ('6', '⁶'), ('7', '⁷'),
+        ('8', '⁸'), ('9', '⁹'), ('+', '⁺'), ('-', '⁻'), ('=', '⁼'), ('(', '⁽'), (')', '⁾'), ('a', 'ᵃ'),
+        ('b', 'ᵇ'), ('c', 'ᶜ'), ('d', 'ᵈ'), ('e', 'ᵉ'), ('f', 'ᶠ'), ('g', 'ᵍ'), ('h', 'ʰ'), ('i', 'ⁱ'),
+        ('j', 'ʲ'), ('k', 'ᵏ'), ('l', 'ˡ'), ('m', 'ᵐ'), ('n', 'ⁿ'), ('o', 'ᵒ'), ('p', 'ᵖ'), ('r', 'ʳ'),
+        ('s', 'ˢ'), ('t', 'ᵗ'), ('u', 'ᵘ'), ('v', 'ᵛ'), ('w', 'ʷ'), ('x', 'ˣ'), ('y', 'ʸ'), ('z', 'ᶻ'),
+        (' ', ' '),
+    ];
+    const SUB: &[(char, char)] = &[
+        ('0', '₀'), ('1', '₁'), ('2', '₂'), ('3', '₃'), ('4', '₄'), ('5', '₅'), ('6', '₆'), ('7', '₇'),
+        ('8', '₈'), ('9', '₉'), ('+', '₊'), ('-', '₋'), ('=', '₌'), ('(', '₍'), (')', '₎'), ('a', 'ₐ'),
+        ('e', 'ₑ'), ('h', 'ₕ'), ('i', 'ᵢ'), ('j', 'ⱼ'), ('k', 'ₖ'), ('l', 'ₗ'), ('m', 'ₘ'), ('n', 'ₙ'),
+        ('o', 'ₒ'), ('p', 'ₚ'), ('r', 'ᵣ'), ('s', 'ₛ'), ('t', 'ₜ'), ('u', 'ᵤ'), ('v', 'ᵥ'), ('x', 'ₓ'),
+        (' ', ' '),
+    ];
+    let table = match script {
+        Script::Super => SUPER,
+        Script::Sub => SUB,
+    };
+    let mapped: Option<String> =
+        text.chars().map(|c| table.iter().find(|(from, _)| *from == c).map(|&(_, to)| to)).collect();
+    mapped.unwrap_or_else(|| text.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn open(name: &str, attrs: &[(&str, &str)]) -> Token {
+        let attrs = attrs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+        Token::Open { name: name.to_string(), attrs }
+    }
+
+    #[test]
+    fn test_tokenize() {
+        let html = "<p align=\"center\">a &amp; b<br/><!-- hi --><IMG SRC=x.png alt='A &lt;B'> 1 < 2</p>";
+        let tokens = tokenize(html);
+        assert_eq!(
+            tokens,
+            [
+                open("p", &[("align", "center")]),
+                Token::Text("a & b".to_string()),
+                open("br", &[]),
+                open("img", &[("src", "x.png"), ("alt", "A <B")]),
+                Token::Text(" 1 < 2".to_string()),
+                Token::Close("p".to_string()),
+            ]
+        );
+        assert_eq!(tokens[3].attr("alt"), Some("A <B"));
+        assert_eq!(decode("&#x2713; &#65; &bogus; &"), "✓ A &bogus; &");
+    }
+
+    #[test]
+    fn test_table_rows() {
+        let tokens = tokenize("<tr><th>A</th><th>B</th></tr><tr><td> <b>1</b> </td><td>2</td></tr>");
+        let rows = table_rows(&tokens);
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0], [[Token::Text("A".to_string())], [Token::Text("B".to_string())]]);
+        assert_eq!(rows[1][0], [open("b", &[]), Token::Text("1".to_string()), Token::Close("b".to_string())]);
+    }
+
+    #[test]
+    fn test_script() {
+        assert_eq!(script("2", Script::Super), "²");
+        assert_eq!(script("beta", Script::Super), "ᵇᵉᵗᵃ");
+        assert_eq!(script("H2O", Script::Sub), "H2O");
+        assert_eq!(script("2", Script::Sub), "₂");
+    }
+}
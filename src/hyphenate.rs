@@ -0,0 +1,84 @@
+//! Where words may be hyphenated, by the hyphenation patterns of their
+//! language.
+//!
+//! ```
+//! use mdview::hyphenate::Language;
+//! let english = Language::parse("en_US.UTF-8").unwrap();
+//! assert_eq!(english.syllables("hyphenation"), ["hy", "phen", "ation"]);
+//! assert_eq!(Language::parse("xx"), None);
+//! ```
+
+use serde::Deserialize;
+
+/// A language with hyphenation patterns.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Language(hypher::Lang);
+
+impl Language {
+    /// The language of an ISO 639-1 code like `en` or `de`, also as the
+    /// start of a locale like `de_CH.UTF-8`.
+    pub fn parse(s: &str) -> Option<Self> {
+        let code = s.split(['_', '-', '.']).next()?.to_ascii_lowercase();
+        let code: [u8; 2] = code.as_bytes().try_into().ok()?;
+        hypher::Lang::from_iso(code).map(Self)
+    }
+
+    /// `word` split where it may be hyphenated.
+    pub fn syllables(self, word: &str) -> Vec<&str> {
+        hypher::hyphenate(word, self.0).collect()
+    }
+}
+
+impl TryFrom<String> for Language {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, String> {
+        Self::parse(&s).ok_or_else(|| format!("no hyphenation patterns for '{s}', expected a code like en or de"))
+    }
+}
+
+/// The char offsets in `word` where it may be hyphenated. Only runs of
+/// letters are hyphenated, so punctuation and digits stay whole.
+pub(crate) fn breaks(word: &[char], language: Language) -> Vec<usize> {
+    let mut breaks = Vec::new();
+    let mut i = 0;
+    while i < word.len() {
+        let len = word[i..].iter().take_while(|c| c.is_alphabetic()).count();
+        if len == 0 {
+            i += 1;
+            continue;
+        }
+        let run: String = word[i..i + len].iter().collect();
+        let mut at = i;
+        for syllable in language.syllables(&run) {
+            at += syllable.chars().count();
+            if at < i + len {
+                breaks.push(at);
+            }
+        }
+        i += len;
+    }
+    breaks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_language() {
+        assert!(Language::parse("de").is_some());
+        assert_eq!(Language::parse("EN"), Language::parse("en-GB"));
+        assert_eq!(Language::parse("english"), None);
+        assert_eq!(Language::parse(""), None);
+    }
+
+    #[test]
+    fn test_breaks_skip_punctuation() {
+        let english = Language::parse("en").unwrap();
+        let word: Vec<char> = "(hyphenation),".chars().collect();
+        assert_eq!(breaks(&word, english), [3, 7]);
+        assert!(breaks(&['a', '1', 'b'], english).is_empty());
+    }
+}
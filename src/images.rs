@@ -0,0 +1,350 @@
+use std::collections::HashMap;
+use std::io::{self, Cursor, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::SystemTime;
+
+use anyhow::{Context, Result};
+use base64::Engine as _;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use image::imageops::FilterType;
+use image::{DynamicImage, GenericImageView as _, ImageFormat, RgbaImage};
+use serde::Deserialize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Protocol {
+    Kitty,
+    Iterm2,
+    Sixel,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImageMode {
+    Auto,
+    Kitty,
+    Iterm2,
+    Sixel,
+    /// A detected graphics protocol, or else half-block art.
+    Blocks,
+    Off,
+}
+
+impl ImageMode {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "auto" => Some(Self::Auto),
+            "kitty" => Some(Self::Kitty),
+            "iterm2" => Some(Self::Iterm2),
+            "sixel" => Some(Self::Sixel),
+            "blocks" => Some(Self::Blocks),
+            "off" => Some(Self::Off),
+            _ => None,
+        }
+    }
+
+    pub fn protocol(self) -> Option<Protocol> {
+        match self {
+            Self::Auto | Self::Blocks => detect(),
+            Self::Kitty => Some(Protocol::Kitty),
+            Self::Iterm2 => Some(Protocol::Iterm2),
+            Self::Sixel => Some(Protocol::Sixel),
+            Self::Off => None,
+        }
+    }
+}
+
+/// Guesses the graphics protocol from environment variables set by the
+/// terminal emulator. Sixel cannot be detected this way and must be requested
+/// explicitly.
+pub fn detect() -> Option<Protocol> {
+    let var = |k: &str| std::env::var(k).unwrap_or_default();
+    if std::env::var_os("KITTY_WINDOW_ID").is_some()
+        || var("TERM") == "xterm-kitty"
+        || var("TERM_PROGRAM") == "ghostty"
+    {
+        return Some(Protocol::Kitty);
+    }
+    if matches!(var("TERM_PROGRAM").as_str(), "iTerm.app" | "WezTerm") {
+        return Some(Protocol::Iterm2);
+    }
+    None
+}
+
+/// Pixel size of a terminal cell, falling back to 8x16 when the terminal
+/// does not report its pixel dimensions.
+pub fn cell_size() -> (u16, u16) {
+    crossterm::terminal::window_size()
+        .ok()
+        .filter(|ws| ws.width > 0 && ws.height > 0 && ws.columns > 0 && ws.rows > 0)
+        .map(|ws| (ws.width / ws.columns, ws.height / ws.rows))
+        .filter(|&(w, h)| w > 0 && h > 0)
+        .unwrap_or((8, 16))
+}
+
+/// Reads the pixel dimensions from the image header without decoding it.
+pub fn dimensions(path: &Path) -> Option<(u32, u32)> {
+    image::image_dimensions(path).ok()
+}
+
+/// Cell footprint of a `px_w`×`px_h` image scaled down (never up) to fit
+/// within `max_cols`×`max_rows`, preserving aspect ratio.
+pub fn fit(
+    (px_w, px_h): (u32, u32),
+    (cell_w, cell_h): (u16, u16),
+    max_cols: u16,
+    max_rows: u16,
+) -> (u16, u16) {
+    if px_w == 0 || px_h == 0 || max_cols == 0 || max_rows == 0 {
+        return (0, 0);
+    }
+    let (cell_w, cell_h) = (cell_w.max(1) as f64, cell_h.max(1) as f64);
+    let natural_cols = px_w as f64 / cell_w;
+    let natural_rows = px_h as f64 / cell_h;
+    let scale = (max_cols as f64 / natural_cols)
+        .min(max_rows as f64 / natural_rows)
+        .min(1.0);
+    let cols = (natural_cols * scale).ceil().clamp(1.0, max_cols as f64) as u16;
+    let rows = (natural_rows * scale).ceil().clamp(1.0, max_rows as f64) as u16;
+    (cols, rows)
+}
+
+/// An image drawn with half blocks: per row of cells, the colors of the
+/// upper and lower half of each cell, `None` where it is transparent.
+pub type HalfBlocks = Vec<Vec<[Option<[u8; 3]>; 2]>>;
+
+/// Half-block images keyed by file, modification time and size limits.
+/// Failed decodes are kept as `None`. Cleared when it grows past
+/// [`HALF_BLOCKS_CAPACITY`], as resizing adds an entry per width.
+static HALF_BLOCKS: LazyLock<Mutex<HashMap<HalfBlocksKey, Option<Arc<HalfBlocks>>>>> = LazyLock::new(Mutex::default);
+
+type HalfBlocksKey = (PathBuf, Option<SystemTime>, u16, u16);
+
+const HALF_BLOCKS_CAPACITY: usize = 64;
+
+/// The image at `path` scaled down (never up) to at most `max_cols` cells
+/// wide and `max_rows` high as half blocks, two pixels to a cell, one above
+/// the other. None when the file cannot be decoded.
+pub fn half_blocks(path: &Path, max_cols: u16, max_rows: u16) -> Option<Arc<HalfBlocks>> {
+    let modified = std::fs::metadata(path).and_then(|m| m.modified()).ok();
+    let key = (path.to_path_buf(), modified, max_cols, max_rows);
+    let mut cache = HALF_BLOCKS.lock().ok()?;
+    if let Some(blocks) = cache.get(&key) {
+        return blocks.clone();
+    }
+    if cache.len() >= HALF_BLOCKS_CAPACITY {
+        cache.clear();
+    }
+    let blocks = image::open(path).ok().map(|img| Arc::new(to_half_blocks(&img, max_cols, max_rows)));
+    cache.insert(key, blocks.clone());
+    blocks
+}
+
+fn to_half_blocks(img: &DynamicImage, max_cols: u16, max_rows: u16) -> HalfBlocks {
+    let (cols, rows) = fit(img.dimensions(), (1, 2), max_cols, max_rows);
+    if cols == 0 || rows == 0 {
+        return Vec::new();
+    }
+    let img = img.resize_exact(cols as u32, rows as u32 * 2, FilterType::Triangle).to_rgba8();
+    let pixel = |x: u32, y: u32| {
+        let p = img.get_pixel(x, y);
+        (p[3] >= 128).then_some([p[0], p[1], p[2]])
+    };
+    (0..rows as u32)
+        .map(|row| (0..cols as u32).map(|x| [pixel(x, row * 2), pixel(x, row * 2 + 1)]).collect())
+        .collect()
+}
+
+/// Encoded escape sequences keyed by file and cell footprint. Failed
+/// encodings are cached as `None` so broken files are not retried per frame.
+#[derive(Default)]
+pub struct Cache {
+    entries: HashMap<(PathBuf, u16, u16), Option<Vec<u8>>>,
+}
+
+impl Cache {
+    pub fn get(
+        &mut self,
+        protocol: Protocol,
+        path: &Path,
+        cols: u16,
+        rows: u16,
+        cell: (u16, u16),
+    ) -> Option<&[u8]> {
+        self.entries
+            .entry((path.to_path_buf(), cols, rows))
+            .or_insert_with(|| encode(protocol, path, cols, rows, cell).ok())
+            .as_deref()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+fn encode(protocol: Protocol, path: &Path, cols: u16, rows: u16, cell: (u16, u16)) -> Result<Vec<u8>> {
+    if protocol == Protocol::Iterm2 {
+        let bytes = std::fs::read(path).with_context(|| format!("Cannot read {}", path.display()))?;
+        return Ok(encode_iterm2(&bytes, cols, rows));
+    }
+
+    let img = image::open(path).with_context(|| format!("Cannot decode {}", path.display()))?;
+    let max_w = cols as u32 * cell.0 as u32;
+    let max_h = rows as u32 * cell.1 as u32;
+    let img = if img.width() > max_w || img.height() > max_h {
+        img.resize(max_w, max_h, FilterType::Triangle)
+    } else {
+        img
+    };
+
+    match protocol {
+        Protocol::Kitty => encode_kitty(&img, cols, rows),
+        Protocol::Sixel => Ok(encode_sixel(&img.to_rgba8())),
+        Protocol::Iterm2 => unreachable!(),
+    }
+}
+
+fn encode_kitty(img: &DynamicImage, cols: u16, rows: u16) -> Result<Vec<u8>> {
+    let mut png = Vec::new();
+    img.write_to(&mut Cursor::new(&mut png), ImageFormat::Png)?;
+    let data = BASE64.encode(png);
+
+    let mut out = Vec::new();
+    let chunks: Vec<&[u8]> = data.as_bytes().chunks(4096).collect();
+    for (i, chunk) in chunks.iter().enumerate() {
+        let more = u8::from(i + 1 < chunks.len());
+        if i == 0 {
+            write!(out, "\x1b_Gf=100,a=T,t=d,c={cols},r={rows},C=1,q=2,m={more};")?;
+        } else {
+            write!(out, "\x1b_Gm={more};")?;
+        }
+        out.extend_from_slice(chunk);
+        out.extend_from_slice(b"\x1b\\");
+    }
+    Ok(out)
+}
+
+fn encode_iterm2(bytes: &[u8], cols: u16, rows: u16) -> Vec<u8> {
+    format!(
+        "\x1b]1337;File=inline=1;size={};width={cols};height={rows};preserveAspectRatio=1:{}\x07",
+        bytes.len(),
+        BASE64.encode(bytes)
+    )
+    .into_bytes()
+}
+
+/// Encodes an image as sixel using a fixed 6×6×6 color cube. Pixels with
+/// alpha below 50% are left transparent.
+fn encode_sixel(img: &RgbaImage) -> Vec<u8> {
+    let (w, h) = img.dimensions();
+    let level = |c: u8| (c as u16 * 5 + 127) / 255;
+    let index: Vec<Option<u8>> = img
+        .pixels()
+        .map(|p| {
+            (p[3] >= 128).then(|| (level(p[0]) * 36 + level(p[1]) * 6 + level(p[2])) as u8)
+        })
+        .collect();
+
+    let mut out = format!("\x1bPq\"1;1;{w};{h}").into_bytes();
+    for i in 0..216u16 {
+        let pct = |v: u16| v * 100 / 5;
+        out.extend(format!("#{i};2;{};{};{}", pct(i / 36), pct(i / 6 % 6), pct(i % 6)).bytes());
+    }
+
+    for band in (0..h).step_by(6) {
+        let rows = band as usize * w as usize..(band + 6).min(h) as usize * w as usize;
+        let mut colors: Vec<u8> = index[rows].iter().flatten().copied().collect();
+        colors.sort_unstable();
+        colors.dedup();
+
+        for (n, &color) in colors.iter().enumerate() {
+            if n > 0 {
+                out.push(b'$');
+            }
+            out.extend(format!("#{color}").bytes());
+            let mut run = (0u8, 0usize);
+            for x in 0..w {
+                let mut bits = 0u8;
+                for dy in 0..6 {
+                    let y = band + dy;
+                    if y < h && index[(y * w + x) as usize] == Some(color) {
+                        bits |= 1 << dy;
+                    }
+                }
+                let ch = b'?' + bits;
+                if run.1 > 0 && run.0 != ch {
+                    push_sixel_run(&mut out, run.0, run.1);
+                    run.1 = 0;
+                }
+                run = (ch, run.1 + 1);
+            }
+            push_sixel_run(&mut out, run.0, run.1);
+        }
+        out.push(b'-');
+    }
+    out.extend_from_slice(b"\x1b\\");
+    out
+}
+
+fn push_sixel_run(out: &mut Vec<u8>, ch: u8, len: usize) {
+    if len > 3 {
+        out.extend(format!("!{len}").bytes());
+        out.push(ch);
+    } else {
+        out.extend(std::iter::repeat_n(ch, len));
+    }
+}
+
+/// Removes every kitty image placement from the screen.
+pub fn clear_kitty(out: &mut impl Write) -> io::Result<()> {
+    out.write_all(b"\x1b_Ga=d,d=A,q=2\x1b\\")
+}
+
+/// Draws an encoded image with its top-left corner at screen cell (`x`, `y`).
+pub fn draw(out: &mut impl Write, x: u16, y: u16, encoded: &[u8]) -> io::Result<()> {
+    write!(out, "\x1b7\x1b[{};{}H", y + 1, x + 1)?;
+    out.write_all(encoded)?;
+    out.write_all(b"\x1b8")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fit_keeps_small_images_at_natural_size() {
+        assert_eq!(fit((80, 32), (8, 16), 100, 20), (10, 2));
+    }
+
+    #[test]
+    fn test_fit_scales_down_preserving_aspect() {
+        // 1600×800 px at 8×16 cells is 200×50 cells; width is the constraint.
+        assert_eq!(fit((1600, 800), (8, 16), 100, 40), (100, 25));
+        // Height is the constraint.
+        assert_eq!(fit((1600, 800), (8, 16), 100, 10), (40, 10));
+    }
+
+    #[test]
+    fn test_half_blocks_pair_rows() {
+        let mut img = RgbaImage::from_pixel(4, 4, image::Rgba([255, 0, 0, 255]));
+        for x in 0..4 {
+            img.put_pixel(x, 1, image::Rgba([0, 0, 255, 255]));
+            img.put_pixel(x, 3, image::Rgba([0, 0, 0, 0]));
+        }
+        let blocks = to_half_blocks(&DynamicImage::ImageRgba8(img), 80, 10);
+        let (red, blue) = (Some([255, 0, 0]), Some([0, 0, 255]));
+        assert_eq!(blocks, [vec![[red, blue]; 4], vec![[red, None]; 4]]);
+        // Scaled down to fit, two pixels to a row.
+        let wide = DynamicImage::ImageRgba8(RgbaImage::new(400, 100));
+        let blocks = to_half_blocks(&wide, 40, 20);
+        assert_eq!((blocks[0].len(), blocks.len()), (40, 5));
+    }
+
+    #[test]
+    fn test_sixel_is_terminated() {
+        let img = RgbaImage::from_pixel(3, 7, image::Rgba([255, 0, 0, 255]));
+        let out = encode_sixel(&img);
+        assert!(out.starts_with(b"\x1bPq"));
+        assert!(out.ends_with(b"\x1b\\"));
+    }
+}
@@ -0,0 +1,196 @@
+use std::path::{Path, PathBuf};
+
+use crate::encoding;
+use crate::section;
+use crate::vault;
+
+/// Markdown with its includes inlined.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Expanded {
+    pub text: String,
+    /// Every file inlined into `text`, directly or through another include,
+    /// in the order first seen.
+    pub includes: Vec<PathBuf>,
+    /// How the file was encoded, when it was not plain UTF-8; see
+    /// [`Decoded`](crate::encoding::Decoded).
+    pub encoding: Option<&'static str>,
+}
+
+/// Inlines other markdown files into `input`, the contents of the file at
+/// `path`. A line consisting only of `<!-- include: other.md -->` or
+/// `![[other.md]]` is replaced by that file, itself expanded. Include paths
+/// are relative to the file that contains them. Lines inside fenced code
+/// blocks are left alone. While a [vault](crate::vault) is in use, an embed
+/// that names no such file shows the note it names, or with
+/// `![[Note#Heading]]` the section under that heading.
+///
+/// An include that cannot be read, or that would include a file already
+/// being expanded, is replaced by a caution alert saying why.
+///
+/// ```
+/// use std::path::Path;
+/// use mdview::include::expand_includes;
+///
+/// let expanded = expand_includes("# Notes\n\nNo includes here.\n", Path::new("notes.md"));
+/// assert_eq!(expanded.text, "# Notes\n\nNo includes here.\n");
+/// assert!(expanded.includes.is_empty());
+/// ```
+pub fn expand_includes(input: &str, path: &Path) -> Expanded {
+    let mut expanded = Expanded { text: String::new(), includes: Vec::new(), encoding: None };
+    let mut stack = vec![path.canonicalize().unwrap_or_else(|_| path.to_path_buf())];
+    expand_into(input, path, &mut stack, &mut expanded);
+    expanded
+}
+
+fn expand_into(input: &str, path: &Path, stack: &mut Vec<PathBuf>, out: &mut Expanded) {
+    let base = path.parent().unwrap_or(Path::new(""));
+    let mut fence: Option<(char, usize)> = None;
+
+    for line in input.split_inclusive('\n') {
+        if let Some(marker) = fence_marker(line) {
+            match fence {
+                None => fence = Some(marker),
+                Some((c, len)) if marker.0 == c && marker.1 >= len => fence = None,
+                Some(_) => {}
+            }
+        }
+        let target = match fence {
+            None => include_target(line),
+            Some(_) => None,
+        };
+        let Some(target) = target else {
+            out.text.push_str(line);
+            continue;
+        };
+
+        let mut file = base.join(target);
+        let mut heading = None;
+        if !file.exists()
+            && line.trim_start().starts_with("![[")
+            && let Some((note, section)) = vault::current().and_then(|vault| vault.embed(target))
+        {
+            (file, heading) = (note, section);
+        }
+        let canonical = match file.canonicalize() {
+            Ok(canonical) => canonical,
+            Err(e) => {
+                push_error(&mut out.text, target, &e.to_string());
+                continue;
+            }
+        };
+        if stack.contains(&canonical) {
+            push_error(&mut out.text, target, "include cycle");
+            continue;
+        }
+        let mut content = match encoding::read(&canonical) {
+            Ok(decoded) => decoded.text,
+            Err(e) => {
+                push_error(&mut out.text, target, &e.to_string());
+                continue;
+            }
+        };
+        if let Some(heading) = heading {
+            match section::find(&content, heading) {
+                Some(range) => content = content[range].to_string(),
+                None => {
+                    push_error(&mut out.text, target, &format!("no heading '{heading}'"));
+                    continue;
+                }
+            }
+        }
+        if !out.includes.contains(&canonical) {
+            out.includes.push(canonical.clone());
+        }
+
+        stack.push(canonical);
+        expand_into(&content, &file, stack, out);
+        stack.pop();
+        if !out.text.ends_with('\n') {
+            out.text.push('\n');
+        }
+    }
+}
+
+/// The path named by an include line, if `line` is one.
+fn include_target(line: &str) -> Option<&str> {
+    let line = line.trim();
+    let target = line
+        .strip_prefix("<!--")
+        .and_then(|l| l.strip_suffix("-->"))
+        .and_then(|l| l.trim().strip_prefix("include:"))
+        .or_else(|| line.strip_prefix("![[").and_then(|l| l.strip_suffix("]]")))?
+        .trim();
+    (!target.is_empty()).then_some(target)
+}
+
+/// The fence character and length if `line` opens or closes a fenced code
+/// block.
+pub(crate) fn fence_marker(line: &str) -> Option<(char, usize)> {
+    let trimmed = line.trim_start_matches(' ');
+    if line.len() - trimmed.len() > 3 {
+        return None;
+    }
+    let c = trimmed.chars().next().filter(|c| matches!(c, '`' | '~'))?;
+    let len = trimmed.chars().take_while(|&ch| ch == c).count();
+    (len >= 3).then_some((c, len))
+}
+
+fn push_error(out: &mut String, target: &str, reason: &str) {
+    out.push_str(&format!("> [!CAUTION]\n> Cannot include `{target}`: {reason}\n\n"));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture_dir(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("mdview-include-{name}-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("sub")).unwrap();
+        for (file, content) in files {
+            std::fs::write(dir.join(file), content).unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn test_include_target() {
+        assert_eq!(include_target("<!-- include: a.md -->\n"), Some("a.md"));
+        assert_eq!(include_target("  ![[sub/b.md]]"), Some("sub/b.md"));
+        assert_eq!(include_target("See ![[a.md]] inline\n"), None);
+        assert_eq!(include_target("<!-- a comment -->\n"), None);
+    }
+
+    #[test]
+    fn test_nested_includes_resolve_relative_paths() {
+        let dir = fixture_dir("nested", &[
+            ("main.md", "# Main\n\n<!-- include: sub/part.md -->\n\nEnd.\n"),
+            ("sub/part.md", "Part.\n\n![[leaf.md]]\n"),
+            ("sub/leaf.md", "Leaf."),
+        ]);
+        let main = dir.join("main.md");
+        let expanded = expand_includes(&std::fs::read_to_string(&main).unwrap(), &main);
+        assert_eq!(expanded.text, "# Main\n\nPart.\n\nLeaf.\n\nEnd.\n");
+        assert_eq!(expanded.includes.len(), 2);
+        assert!(expanded.includes[1].ends_with("sub/leaf.md"));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_cycles_and_missing_files_are_reported() {
+        let dir = fixture_dir("cycle", &[
+            ("a.md", "A.\n\n![[b.md]]\n"),
+            ("b.md", "B.\n\n![[a.md]]\n\n![[missing.md]]\n"),
+        ]);
+        let a = dir.join("a.md");
+        let expanded = expand_includes(&std::fs::read_to_string(&a).unwrap(), &a);
+        assert!(expanded.text.contains("Cannot include `a.md`: include cycle"));
+        assert!(expanded.text.contains("Cannot include `missing.md`"));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_code_blocks_are_not_expanded() {
+        let md = "```\n![[a.md]]\n```\n";
+        assert_eq!(expand_includes(md, Path::new("x.md")).text, md);
+    }
+}
@@ -0,0 +1,621 @@
+use std::collections::HashMap;
+use std::ops::Range;
+
+use pulldown_cmark::{Event, Parser, RefDefs, Tag};
+use ratatui::text::{Line, Text};
+
+use crate::admonitions;
+use crate::ast::{self, Block};
+use crate::frontmatter;
+use crate::html::{self, Token};
+use crate::render::{Document, Heading, LinkStyle, RenderOptions, add_heading_anchors, layout, prehighlight};
+
+/// Re-renders a document after edits, reusing the output of top-level blocks
+/// whose source is unchanged.
+///
+/// Each top-level block (paragraph, list, code block, table, ...) renders
+/// independently of its neighbours, so only blocks that were added or edited
+/// since the last call go through the renderer. Each block's parsed model is
+/// kept too, so new options (a resize, another theme) only lay blocks out
+/// again, and rendering the same input again does not parse it at all. Link
+/// reference definitions
+/// can change how any block renders; when they change, every block is
+/// rendered again. Documents with footnotes are always rendered in full,
+/// since their numbering spans the whole document.
+///
+/// ```
+/// use mdview::{IncrementalRenderer, RenderOptions};
+///
+/// let mut renderer = IncrementalRenderer::new(RenderOptions::new(80));
+/// renderer.render("# Title\n\nFirst paragraph.\n");
+/// let doc = renderer.render("# Title\n\nEdited paragraph.\n");
+/// assert_eq!(doc.text.lines[0].to_string(), "# Title");
+/// ```
+pub struct IncrementalRenderer {
+    options: RenderOptions,
+    /// Blocks from the last call, keyed by their source text.
+    cache: HashMap<String, CachedBlock>,
+    ref_defs: Vec<(String, String, Option<String>)>,
+    layout: Vec<BlockLayout>,
+    previous_layout: Vec<BlockLayout>,
+    /// Headings of the latest and the previous render, for `map_line`.
+    headings: Vec<Heading>,
+    previous_headings: Vec<Heading>,
+    /// The input of the last full render, which `layout` describes.
+    input: String,
+    /// The last render laid the whole input out as one block, for its
+    /// footnotes.
+    whole: bool,
+}
+
+/// A top-level block's parsed model and, once laid out with the current
+/// options, its output.
+#[derive(Debug, Clone)]
+struct CachedBlock {
+    blocks: Vec<Block>,
+    doc: Option<Document>,
+}
+
+/// Where a block's lines landed in the assembled document.
+#[derive(Debug, Clone)]
+struct BlockLayout {
+    source: String,
+    /// Byte range of `source` in the input.
+    range: Range<usize>,
+    start: usize,
+    len: usize,
+    /// First source line of the block, and how many lines it spans.
+    source_line: usize,
+    source_lines: usize,
+}
+
+impl IncrementalRenderer {
+    pub fn new(options: RenderOptions) -> Self {
+        Self {
+            options,
+            cache: HashMap::new(),
+            ref_defs: Vec::new(),
+            layout: Vec::new(),
+            previous_layout: Vec::new(),
+            headings: Vec::new(),
+            previous_headings: Vec::new(),
+            input: String::new(),
+            whole: false,
+        }
+    }
+
+    pub fn options(&self) -> &RenderOptions {
+        &self.options
+    }
+
+    /// Replaces the options. Every block is laid out again on the next
+    /// render, but parsed again only if the markdown extensions changed.
+    pub fn set_options(&mut self, options: RenderOptions) {
+        if options.extensions == self.options.extensions {
+            self.cache.values_mut().for_each(|block| block.doc = None);
+        } else {
+            self.cache.clear();
+        }
+        self.options = options;
+    }
+
+    /// Forgets every rendered block so the next `render` starts from scratch.
+    pub fn clear_cache(&mut self) {
+        self.cache.clear();
+    }
+
+    pub fn render(&mut self, input: &str) -> Document {
+        let input = frontmatter::blank(input);
+        let input = &*admonitions::to_fences(&input);
+        if input == self.input && self.layout.iter().all(|b| self.cache.contains_key(&b.source)) {
+            return self.relayout();
+        }
+        let parser = Parser::new_ext(input, self.options.extensions).into_offset_iter();
+        self.update_ref_defs(parser.reference_definitions());
+        let blocks: Vec<_> = split_blocks(parser).collect();
+        // Footnotes, links numbered in a list at the end and numbered
+        // headings are counted across blocks.
+        let numbered_links = matches!(self.options.link_style, LinkStyle::Footnote | LinkStyle::Section);
+        let whole = blocks.iter().flat_map(|(_, events)| events).any(|e| match e {
+            Event::FootnoteReference(_) | Event::Start(Tag::FootnoteDefinition(_)) => true,
+            Event::Start(Tag::Link { .. }) => numbered_links,
+            Event::Start(Tag::Heading { .. }) => self.options.number_headings,
+            Event::Html(html) | Event::InlineHtml(html) => {
+                (numbered_links && html.contains("<a")) || (self.options.number_headings && html.contains("<h"))
+            }
+            _ => false,
+        });
+        self.start_layout();
+        self.input = input.to_string();
+        self.whole = whole;
+        if whole {
+            self.cache.clear();
+            return self.render_whole(input, blocks.into_iter().flat_map(|(_, events)| events));
+        }
+
+        self.prehighlight(input, &blocks);
+        let (doc, cache) = self.assemble(input, blocks.into_iter(), usize::MAX);
+        self.cache = cache;
+        doc
+    }
+
+    /// Highlights the code of the `blocks` of `input` that are not laid out
+    /// yet all at once, in parallel, rather than block by block as `assemble`
+    /// lays them out.
+    fn prehighlight(&self, input: &str, blocks: &[(Range<usize>, Vec<Event>)]) {
+        let mut events = Vec::new();
+        for (range, block) in blocks {
+            match self.cache.get(&input[range.clone()]) {
+                Some(CachedBlock { doc: Some(_), .. }) => {}
+                Some(cached) => events.extend(ast::events(&cached.blocks)),
+                None => events.extend(block.iter().cloned()),
+            }
+        }
+        prehighlight(&events, &self.options);
+    }
+
+    /// Keeps the latest layout as the previous one, for `map_line`, before
+    /// a new one is built.
+    fn start_layout(&mut self) {
+        self.previous_layout = std::mem::take(&mut self.layout);
+        self.previous_headings = std::mem::take(&mut self.headings);
+    }
+
+    /// Lays the blocks of the last render out again from their cached
+    /// models, for when only the options changed since.
+    fn relayout(&mut self) -> Document {
+        self.start_layout();
+        let input = std::mem::take(&mut self.input);
+        let doc = match self.whole {
+            true => self.render_whole(&input, std::iter::empty()),
+            false => {
+                let blocks: Vec<_> =
+                    self.previous_layout.iter().map(|b| (b.range.clone(), Vec::new())).collect();
+                self.prehighlight(&input, &blocks);
+                let (doc, cache) = self.assemble(&input, blocks.into_iter(), usize::MAX);
+                self.cache = cache;
+                doc
+            }
+        };
+        self.input = input;
+        doc
+    }
+
+    /// Renders `input` as a single block, so footnote numbering spans the
+    /// whole document. `events` are its events, needed unless it is cached.
+    fn render_whole<'a>(&mut self, input: &str, events: impl Iterator<Item = Event<'a>>) -> Document {
+        let mut cached = self
+            .cache
+            .remove(input)
+            .unwrap_or_else(|| CachedBlock { blocks: ast::from_events(events), doc: None });
+        let doc = cached.doc.get_or_insert_with(|| layout(&cached.blocks, &self.options)).clone();
+        self.cache.insert(input.to_string(), cached);
+        self.headings = doc.headings.clone();
+        self.layout.push(BlockLayout {
+            source: input.to_string(),
+            range: 0..input.len(),
+            start: 0,
+            len: doc.text.lines.len(),
+            source_line: 0,
+            source_lines: line_count(input),
+        });
+        doc
+    }
+
+    /// Renders only as many leading blocks of `input` as it takes to fill
+    /// `lines` lines, so the top of a large document can be shown at once
+    /// while a full `render` runs. The parser still scans the block structure
+    /// of the whole input first, but blocks past that point get no events,
+    /// layout or highlighting, and the ones rendered stay cached for the
+    /// full render.
+    pub fn render_head(&mut self, input: &str, lines: usize) -> Document {
+        let input = frontmatter::blank(input);
+        let input = &*admonitions::to_fences(&input);
+        let parser = Parser::new_ext(input, self.options.extensions).into_offset_iter();
+        self.update_ref_defs(parser.reference_definitions());
+        self.start_layout();
+        // The layout will cover only part of the input.
+        self.input.clear();
+        let (doc, cache) = self.assemble(input, split_blocks(parser), lines);
+        self.cache.extend(cache);
+        doc
+    }
+
+    /// Drops every cached block when the link reference definitions changed,
+    /// since any block may use them.
+    fn update_ref_defs(&mut self, defs: &RefDefs) {
+        let mut ref_defs: Vec<(String, String, Option<String>)> = defs
+            .iter()
+            .map(|(label, def)| {
+                (label.to_string(), def.dest.to_string(), def.title.as_ref().map(|t| t.to_string()))
+            })
+            .collect();
+        ref_defs.sort();
+        if ref_defs != self.ref_defs {
+            self.ref_defs = ref_defs;
+            self.cache.clear();
+        }
+    }
+
+    /// Joins `blocks` into one document, taking unchanged blocks from the
+    /// cache, until it is at least `lines` lines long. Returns the blocks
+    /// used, to be cached for the next render.
+    fn assemble<'a>(
+        &mut self,
+        input: &str,
+        blocks: impl Iterator<Item = (Range<usize>, Vec<Event<'a>>)>,
+        lines: usize,
+    ) -> (Document, HashMap<String, CachedBlock>) {
+        let mut cache = HashMap::new();
+        let mut doc = Document {
+            text: Text::default(),
+            links: Vec::new(),
+            images: Vec::new(),
+            headings: Vec::new(),
+            anchors: HashMap::new(),
+            details: Vec::new(),
+            tables: Vec::new(),
+        };
+        let (mut source_line, mut counted) = (0, 0);
+        for (range, events) in blocks {
+            source_line += input[counted..range.start].matches('\n').count();
+            counted = range.start;
+            let source = &input[range.clone()];
+            let mut cached = match self.cache.remove(source) {
+                Some(cached) => cached,
+                None => match cache.get(source) {
+                    Some(cached) => CachedBlock::clone(cached),
+                    None => CachedBlock { blocks: ast::from_events(events.into_iter()), doc: None },
+                },
+            };
+            let block = cached.doc.get_or_insert_with(|| layout(&cached.blocks, &self.options));
+
+            let base = doc.text.lines.len();
+            doc.links.extend(block.links.iter().cloned().map(|mut l| {
+                l.line += base;
+                l
+            }));
+            doc.images.extend(block.images.iter().cloned().map(|mut i| {
+                i.line += base;
+                i
+            }));
+            doc.headings.extend(block.headings.iter().cloned().map(|mut h| {
+                h.line += base;
+                h
+            }));
+            doc.details.extend(block.details.iter().cloned().map(|mut d| {
+                d.line += base;
+                d.end += base;
+                d
+            }));
+            doc.tables.extend(block.tables.iter().cloned().map(|mut t| {
+                t.line += base;
+                t.end += base;
+                t
+            }));
+            doc.text.lines.extend(block.text.lines.iter().cloned());
+            self.layout.push(BlockLayout {
+                source: source.to_string(),
+                range,
+                start: base,
+                len: block.text.lines.len(),
+                source_line,
+                source_lines: line_count(source),
+            });
+            cache.insert(source.to_string(), cached);
+            if doc.text.lines.len() >= lines {
+                break;
+            }
+        }
+        // Without footnotes, every anchor comes from a heading. Slugs are
+        // numbered across the whole document, so they are assigned here
+        // rather than per block.
+        add_heading_anchors(&mut doc.anchors, &doc.headings);
+        self.headings = doc.headings.clone();
+        (doc, cache)
+    }
+
+    /// Where `line` of the previous render is in the latest one. Lines in an
+    /// unchanged block follow that block. A line in an edited block keeps its
+    /// distance below the nearest heading above it, found again by title, so
+    /// editing a section leaves the reader in it; without such a heading it
+    /// goes to the same source line.
+    pub fn map_line(&self, line: usize) -> usize {
+        let Some(old) = self.previous_layout.iter().find(|b| line < b.start + b.len) else {
+            return line;
+        };
+        if let Some(new) = self.layout.iter().find(|b| b.source == old.source) {
+            return new.start + (line - old.start).min(new.len.saturating_sub(1));
+        }
+        if let Some(mapped) = self.map_by_heading(line) {
+            return mapped;
+        }
+        let source_line = source_line_in(&self.previous_layout, line);
+        self.rendered_line(source_line).min(self.line_count().saturating_sub(1))
+    }
+
+    /// `line` at the same distance below the heading above it, once that
+    /// heading (the same title, level and occurrence) is found in the latest
+    /// render. Stays above the heading that follows it there.
+    fn map_by_heading(&self, line: usize) -> Option<usize> {
+        let i = self.previous_headings.partition_point(|h| h.line <= line).checked_sub(1)?;
+        let old = &self.previous_headings[i];
+        let same = |h: &&Heading| h.level == old.level && h.title == old.title;
+        let occurrence = self.previous_headings[..i].iter().filter(same).count();
+        let (j, new) = self.headings.iter().enumerate().filter(|(_, h)| same(h)).nth(occurrence)?;
+        let end = self.headings.get(j + 1).map_or(self.line_count(), |h| h.line);
+        Some((new.line + line - old.line).min(end.saturating_sub(1)).max(new.line))
+    }
+
+    /// Lines in the latest render.
+    fn line_count(&self) -> usize {
+        self.layout.last().map_or(0, |b| b.start + b.len)
+    }
+
+    /// The rendered line showing source line `line` of the latest render, both
+    /// counted from 0. Lines inside a block are spread proportionally over
+    /// its output; a blank line between blocks maps to the end of the block
+    /// before.
+    pub fn rendered_line(&self, line: usize) -> usize {
+        let i = self.layout.partition_point(|b| b.source_line <= line);
+        let Some(block) = i.checked_sub(1).map(|i| &self.layout[i]) else {
+            return 0;
+        };
+        match line - block.source_line {
+            offset if offset < block.source_lines => block.start + offset * block.len / block.source_lines,
+            _ => block.start + block.len,
+        }
+    }
+
+    /// The source line shown at rendered line `line`; the inverse of
+    /// `rendered_line`.
+    pub fn source_line(&self, line: usize) -> usize {
+        source_line_in(&self.layout, line)
+    }
+}
+
+/// The source line shown at rendered line `line` of `layout`.
+fn source_line_in(layout: &[BlockLayout], line: usize) -> usize {
+    let i = layout.partition_point(|b| b.start <= line);
+    let Some(block) = i.checked_sub(1).map(|i| &layout[i]) else {
+        return 0;
+    };
+    match line - block.start {
+        offset if offset < block.len => block.source_line + offset * block.source_lines / block.len,
+        _ => block.source_line + block.source_lines,
+    }
+}
+
+/// Number of lines `source` spans, not counting a final newline.
+fn line_count(source: &str) -> usize {
+    source.trim_end_matches('\n').matches('\n').count() + 1
+}
+
+/// Groups the event stream into top-level blocks with their source ranges.
+/// Events are pulled only as blocks are asked for.
+pub(crate) fn split_blocks<'a>(
+    mut events: impl Iterator<Item = (Event<'a>, Range<usize>)>,
+) -> impl Iterator<Item = (Range<usize>, Vec<Event<'a>>)> {
+    std::iter::from_fn(move || {
+        let mut depth = 0usize;
+        // `<details>` elements opened by raw HTML and not closed yet. The
+        // blocks up to the closing tag join the one that opened it, so the
+        // renderer sees the whole element.
+        let mut details = 0usize;
+        let mut current: Option<(Range<usize>, Vec<Event<'a>>)> = None;
+        for (event, range) in events.by_ref() {
+            match &event {
+                Event::Start(_) => depth += 1,
+                Event::End(_) => depth = depth.saturating_sub(1),
+                Event::Html(html) => {
+                    for token in html::tokenize(html) {
+                        match token {
+                            Token::Open { name, .. } if name == "details" => details += 1,
+                            Token::Close(name) if name == "details" => details = details.saturating_sub(1),
+                            _ => {}
+                        }
+                    }
+                }
+                _ => {}
+            }
+            let block = current.get_or_insert_with(|| (range.clone(), Vec::new()));
+            block.0.end = block.0.end.max(range.end);
+            block.1.push(event);
+            if depth == 0 && details == 0 {
+                break;
+            }
+        }
+        current
+    })
+}
+
+/// The rendered lines of each of `blocks` of `input`, with the blank lines
+/// after it.
+pub(crate) fn render_blocks(
+    input: &str,
+    blocks: &[Range<usize>],
+    options: &RenderOptions,
+) -> Vec<Vec<Line<'static>>> {
+    let mut renderer = IncrementalRenderer::new(options.clone());
+    let lines = renderer.render(input).text.lines;
+    let mut starts = Vec::with_capacity(blocks.len());
+    let mut source_line = 0;
+    let mut counted = 0;
+    for (i, range) in blocks.iter().enumerate() {
+        source_line += input[counted..range.start].matches('\n').count();
+        counted = range.start;
+        let start = if i == 0 { 0 } else { renderer.rendered_line(source_line) };
+        starts.push(start.max(starts.last().copied().unwrap_or(0)).min(lines.len()));
+    }
+    (0..blocks.len()).map(|i| lines[starts[i]..starts.get(i + 1).copied().unwrap_or(lines.len())].to_vec()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render::render_document;
+
+    fn plain(doc: &Document) -> Vec<String> {
+        doc.text.lines.iter().map(|l| l.to_string()).collect()
+    }
+
+    #[test]
+    fn test_matches_full_render() {
+        for fixture in ["alerts", "blockquotes", "code-blocks", "edge-cases", "headings", "inline", "lists", "tables"] {
+            let path = format!("{}/tests/fixtures/{fixture}.md", env!("CARGO_MANIFEST_DIR"));
+            let md = std::fs::read_to_string(path).unwrap();
+            let options = RenderOptions::new(60);
+            let full = render_document(&md, &options);
+            let mut renderer = IncrementalRenderer::new(options);
+            renderer.render("");
+            let incremental = renderer.render(&md);
+            assert_eq!(plain(&incremental), plain(&full), "{fixture}");
+            assert_eq!(incremental.links, full.links, "{fixture}");
+            assert_eq!(incremental.anchors, full.anchors, "{fixture}");
+        }
+    }
+
+    #[test]
+    fn test_numbers_counted_across_blocks_match_full_render() {
+        let md = "# One\n\nSee [a](https://a.io).\n\n## Two\n\n[b](https://b.io)[^1]\n\n[^1]: Note.\n";
+        let options = RenderOptions::new(60).number_headings(true).link_style(LinkStyle::Section);
+        let full = render_document(md, &options);
+        let mut renderer = IncrementalRenderer::new(options);
+        renderer.render("# One\n");
+        let incremental = renderer.render(md);
+        assert_eq!(plain(&incremental), plain(&full));
+        assert_eq!(incremental.headings, full.headings);
+    }
+
+    #[test]
+    fn test_unchanged_blocks_are_reused() {
+        let mut renderer = IncrementalRenderer::new(RenderOptions::new(80));
+        renderer.render("# Title\n\nOne.\n\nTwo.\n");
+        assert_eq!(renderer.cache.len(), 3);
+
+        // A cached block is spliced in as-is rather than rendered again.
+        let marker = render_document("Cached.", renderer.options());
+        renderer.cache.insert("Two.\n".to_string(), CachedBlock { blocks: Vec::new(), doc: Some(marker) });
+        let doc = renderer.render("# Title\n\nOne, edited.\n\nTwo.\n");
+        let lines = plain(&doc);
+        assert!(lines.contains(&"One, edited.".to_string()));
+        assert!(lines.contains(&"Cached.".to_string()));
+    }
+
+    #[test]
+    fn test_new_options_reuse_parsed_blocks() {
+        let mut renderer = IncrementalRenderer::new(RenderOptions::new(80));
+        renderer.render("One.\n\nTwo.\n");
+        // Laid out again from the cached model, not the source.
+        let swapped = ast::parse("Swapped.", renderer.options());
+        renderer.cache.get_mut("Two.\n").unwrap().blocks = swapped;
+        renderer.set_options(RenderOptions::new(40));
+        assert_eq!(plain(&renderer.render("One.\n\nTwo.\n")), ["One.", "", "Swapped.", ""]);
+
+        renderer.set_options(RenderOptions::new(40).tables(false));
+        assert!(renderer.cache.is_empty());
+    }
+
+    #[test]
+    fn test_same_input_is_laid_out_without_parsing() {
+        for md in ["# Title\n\nOne.\n\n- two\n", "Note.[^1]\n\n[^1]: Footnote.\n"] {
+            let mut renderer = IncrementalRenderer::new(RenderOptions::new(80));
+            renderer.render(md);
+            // Stale reference definitions would make a parse drop the cache.
+            renderer.ref_defs.push(("stale".into(), "x".into(), None));
+            renderer.set_options(RenderOptions::new(30));
+            let doc = renderer.render(md);
+            assert_eq!(plain(&doc), plain(&render_document(md, renderer.options())), "{md}");
+            assert!(!renderer.cache.is_empty(), "{md}");
+            assert_eq!(renderer.ref_defs.len(), 1, "{md}");
+        }
+    }
+
+    #[test]
+    fn test_link_lines_are_offset() {
+        let mut renderer = IncrementalRenderer::new(RenderOptions::new(80));
+        let doc = renderer.render("Intro.\n\nSee [docs](docs.md).\n");
+        let link = &doc.links[0];
+        assert_eq!(&doc.text.lines[link.line].to_string()[link.start..link.end], "docs");
+    }
+
+    #[test]
+    fn test_details_render_as_one_block() {
+        let md = "Intro.\n\n<details>\n<summary>More</summary>\n\nInside.\n\n- a\n\n</details>\n\nAfter.\n";
+        let full = render_document(md, &RenderOptions::new(80));
+        let mut renderer = IncrementalRenderer::new(RenderOptions::new(80));
+        let doc = renderer.render(md);
+        assert_eq!(plain(&doc), plain(&full));
+        assert_eq!(doc.details, full.details);
+        assert_eq!(renderer.layout.len(), 3);
+        let details = &doc.details[0];
+        assert_eq!((details.line, details.title.as_str(), details.open), (2, "More", false));
+        assert_eq!(plain(&doc)[details.end], "After.");
+    }
+
+    #[test]
+    fn test_map_line_follows_moved_block() {
+        let mut renderer = IncrementalRenderer::new(RenderOptions::new(80));
+        let before = renderer.render("Alpha.\n\nBeta.\n");
+        let beta = plain(&before).iter().position(|l| l == "Beta.").unwrap();
+
+        let after = renderer.render("New.\n\nLines.\n\nAlpha.\n\nBeta.\n");
+        let mapped = renderer.map_line(beta);
+        assert_eq!(plain(&after)[mapped], "Beta.");
+    }
+
+    #[test]
+    fn test_map_line_keeps_place_under_heading() {
+        // Footnotes make the whole document one block, so any edit changes it.
+        let mut renderer = IncrementalRenderer::new(RenderOptions::new(80));
+        let before = renderer.render("# A\n\nOne.[^1]\n\n# B\n\nFirst.\n\nSecond.\n\n[^1]: Note.\n");
+        let second = plain(&before).iter().position(|l| l == "Second.").unwrap();
+
+        let md = "# A\n\nOne.[^1]\n\nMore.\n\nAnd more.\n\n# B\n\nFirst.\n\nSecond.\n\n[^1]: Note.\n";
+        let after = renderer.render(md);
+        assert_eq!(plain(&after)[renderer.map_line(second)], "Second.");
+    }
+
+    #[test]
+    fn test_reference_definition_change_rerenders() {
+        let mut renderer = IncrementalRenderer::new(RenderOptions::new(80));
+        renderer.render("See [docs].\n\n[docs]: a.md\n");
+        let doc = renderer.render("See [docs].\n\n[docs]: b.md\n");
+        assert_eq!(doc.links[0].url, "b.md");
+    }
+
+    #[test]
+    fn test_source_and_rendered_lines_correspond() {
+        let mut renderer = IncrementalRenderer::new(RenderOptions::new(80));
+        let md = "# Title\n\n```\na\nb\n```\n\nEnd.\n";
+        let doc = plain(&renderer.render(md));
+        let end = doc.iter().position(|l| l == "End.").unwrap();
+        assert_eq!(renderer.rendered_line(7), end);
+        assert_eq!(renderer.source_line(end), 7);
+        assert_eq!(renderer.source_line(0), 0);
+        let b = renderer.rendered_line(4);
+        assert!(doc[b].contains('b'), "{doc:?}");
+    }
+
+    #[test]
+    fn test_frontmatter_is_skipped_and_lines_keep_their_numbers() {
+        let mut renderer = IncrementalRenderer::new(RenderOptions::new(80));
+        let md = "---\ntitle: Hello\nauthor: Me\n---\n# Body\n\nText.\n";
+        let doc = plain(&renderer.render(md));
+        assert_eq!(doc, plain(&render_document(md, renderer.options())));
+        assert_eq!(doc, ["# Body", "", "Text.", ""]);
+        assert_eq!((renderer.rendered_line(4), renderer.rendered_line(6)), (0, 2));
+        assert_eq!(renderer.source_line(2), 6);
+    }
+
+    #[test]
+    fn test_render_head_stops_early_and_caches() {
+        let mut renderer = IncrementalRenderer::new(RenderOptions::new(80));
+        let md = "One.\n\nTwo.\n\nThree.\n\nFour.\n";
+        let head = renderer.render_head(md, 3);
+        assert_eq!(plain(&head), ["One.", "", "Two.", ""]);
+        assert_eq!(renderer.cache.len(), 2);
+        let full = renderer.render(md);
+        assert_eq!(plain(&full), plain(&render_document(md, renderer.options())));
+    }
+}
@@ -0,0 +1,69 @@
+use std::path::Path;
+
+use anyhow::Result;
+
+use crate::{csv, notebook, org, rst};
+
+/// A kind of file mdview can show, told by its extension. Everything but
+/// markdown is converted to markdown before rendering.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Markdown,
+    Csv,
+    Tsv,
+    Notebook,
+    Rst,
+    Org,
+}
+
+impl Format {
+    /// The format of the file at `path`, or `None` when its extension is not
+    /// one mdview knows.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+        Some(match ext.as_str() {
+            "md" | "markdown" | "mdown" | "mkd" => Format::Markdown,
+            "csv" => Format::Csv,
+            "tsv" => Format::Tsv,
+            "ipynb" => Format::Notebook,
+            "rst" | "rest" => Format::Rst,
+            "org" => Format::Org,
+            _ => return None,
+        })
+    }
+}
+
+/// `input` in `format` as markdown: a csv or tsv file as one fenced table,
+/// a notebook's cells, or a reStructuredText or Org document converted.
+/// Only notebooks can fail, when they are not valid JSON.
+///
+/// ```
+/// use mdview::input::{Format, to_markdown};
+///
+/// assert_eq!(to_markdown("a,b\n1,2\n", Format::Csv).unwrap(), "```csv\na,b\n1,2\n```\n");
+/// assert_eq!(to_markdown("* Notes\n", Format::Org).unwrap(), "# Notes\n");
+/// ```
+pub fn to_markdown(input: &str, format: Format) -> Result<String> {
+    Ok(match format {
+        Format::Markdown => input.to_string(),
+        Format::Csv => csv::fenced(input, "csv"),
+        Format::Tsv => csv::fenced(input, "tsv"),
+        Format::Notebook => notebook::to_markdown(input)?,
+        Format::Rst => rst::to_markdown(input),
+        Format::Org => org::to_markdown(input),
+    })
+}
+
+/// A markdown table of `rows`, the first being the header. Short rows are
+/// padded and `|` in cells escaped.
+pub(crate) fn markdown_table(rows: &[Vec<String>]) -> String {
+    let columns = rows.iter().map(Vec::len).max().unwrap_or(0).max(1);
+    let row = |cells: &[String]| {
+        let cells = (0..columns).map(|i| cells.get(i).map_or(String::new(), |c| c.replace('|', "\\|")));
+        format!("| {} |\n", cells.collect::<Vec<_>>().join(" | "))
+    };
+    let mut out = row(rows.first().map_or(&[], Vec::as_slice));
+    out.push_str(&format!("|{}\n", "---|".repeat(columns)));
+    rows.iter().skip(1).for_each(|cells| out.push_str(&row(cells)));
+    out
+}
@@ -0,0 +1,139 @@
+//! Control characters in document text. Written to the terminal as they
+//! are, an escape sequence in a file could recolor the screen, move the
+//! cursor or set the window title, and a carriage return or form feed
+//! would break the layout. The renderer either drops them or, with
+//! `--show-invisibles`, shows each as a dim symbol.
+//!
+//! ```
+//! use mdview::invisibles::{pictures, strip};
+//! assert_eq!(strip("\x1b[31mred\x1b[0m\r"), "red");
+//! let runs: Vec<(String, bool)> =
+//!     pictures("\x1b[31mred", false).into_iter().map(|(run, symbol)| (run.into_owned(), symbol)).collect();
+//! assert_eq!(runs, [("␛".to_string(), true), ("[31mred".to_string(), false)]);
+//! ```
+
+use std::borrow::Cow;
+
+/// Whether `c` is a control character the terminal would act on. Tabs and
+/// line feeds are layout, handled before text gets here.
+pub fn is_control(c: char) -> bool {
+    c.is_control() && c != '\t' && c != '\n'
+}
+
+/// Drops the control characters from `text`, with the rest of any escape
+/// sequence they start: `ESC [ 31 m` goes whole, not just the `ESC`.
+pub fn strip(text: &str) -> Cow<'_, str> {
+    if !text.contains(is_control) {
+        return Cow::Borrowed(text);
+    }
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\x1b' => match chars.next() {
+                Some('[') => skip_csi(&mut chars),
+                Some(']' | 'P' | 'X' | '^' | '_') => skip_string(&mut chars),
+                _ => {}
+            },
+            '\u{9b}' => skip_csi(&mut chars),
+            '\u{90}' | '\u{98}' | '\u{9d}' | '\u{9e}' | '\u{9f}' => skip_string(&mut chars),
+            c if is_control(c) => {}
+            c => out.push(c),
+        }
+    }
+    Cow::Owned(out)
+}
+
+/// Skips the parameters and final byte of a control sequence (`ESC [`).
+fn skip_csi(chars: &mut impl Iterator<Item = char>) {
+    for c in chars.by_ref() {
+        if ('@'..='~').contains(&c) {
+            break;
+        }
+    }
+}
+
+/// Skips the body of an operating system command or another string
+/// sequence, up to the bell or string terminator that ends it.
+fn skip_string(chars: &mut std::iter::Peekable<impl Iterator<Item = char>>) {
+    while let Some(c) = chars.next() {
+        match c {
+            '\x07' | '\u{9c}' => break,
+            '\x1b' if chars.peek() == Some(&'\\') => {
+                chars.next();
+                break;
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Splits `text` into runs of plain text and symbols standing for its
+/// control characters, each flagged `true` for a symbol. Escape sequences
+/// keep their parameters as plain text after the symbol for `ESC`, so they
+/// can be read: `␛[31m`. Symbols are the Unicode control pictures, or
+/// caret notation (`^[`) with `ascii`; C1 controls, which have no picture,
+/// show as their code (`<9b>`).
+pub fn pictures(text: &str, ascii: bool) -> Vec<(Cow<'_, str>, bool)> {
+    let mut runs = Vec::new();
+    let mut start = 0;
+    for (i, c) in text.char_indices().filter(|&(_, c)| is_control(c)) {
+        if start < i {
+            runs.push((Cow::Borrowed(&text[start..i]), false));
+        }
+        runs.push((Cow::Owned(picture(c, ascii)), true));
+        start = i + c.len_utf8();
+    }
+    if start < text.len() {
+        runs.push((Cow::Borrowed(&text[start..]), false));
+    }
+    runs
+}
+
+fn picture(c: char, ascii: bool) -> String {
+    let code = c as u32;
+    match (code, ascii) {
+        (0x00..=0x1f, false) => char::from_u32(0x2400 + code).map_or_else(String::new, String::from),
+        (0x00..=0x1f, true) => format!("^{}", char::from(code as u8 + b'@')),
+        (0x7f, false) => "␡".to_string(),
+        (0x7f, true) => "^?".to_string(),
+        _ => format!("<{code:02x}>"),
+    }
+}
+
+/// The symbol for a space at the end of a code line with
+/// `--show-invisibles`.
+pub fn trailing_space(ascii: bool) -> &'static str {
+    if ascii { "." } else { "·" }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_drops_escape_sequences() {
+        assert_eq!(strip("plain\ttext\n"), "plain\ttext\n");
+        assert!(matches!(strip("plain"), Cow::Borrowed(_)));
+        assert_eq!(strip("a\x1b[1;31mb\x1b[0m c"), "ab c");
+        assert_eq!(strip("\x1b]0;title\x07x\x1b]8;;url\x1b\\y"), "xy");
+        assert_eq!(strip("page\x0cbreak\r\x7f\u{9b}2Jend"), "pagebreakend");
+        assert_eq!(strip("\x1b7saved\x1b"), "saved");
+    }
+
+    #[test]
+    fn test_pictures() {
+        let runs = |text, ascii| -> Vec<(String, bool)> {
+            pictures(text, ascii).into_iter().map(|(s, control)| (s.into_owned(), control)).collect()
+        };
+        let expected = [("a".into(), false), ("␌".into(), true), ("b".into(), false), ("␍".into(), true)];
+        assert_eq!(runs("a\x0cb\r", false), expected);
+        assert_eq!(runs("\x1b[0m\x7f\u{85}", true), [
+            ("^[".into(), true),
+            ("[0m".into(), false),
+            ("^?".into(), true),
+            ("<85>".into(), true)
+        ]);
+        assert_eq!(runs("tab\there", false), [("tab\there".into(), false)]);
+    }
+}
@@ -0,0 +1,276 @@
+use std::collections::{BTreeMap, HashMap};
+
+use anyhow::{Result, bail};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+/// What a key press does in normal mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Quit,
+    /// Quit with exit status 1.
+    QuitWithError,
+    /// Deselect the active link, then clear the search, then quit.
+    Cancel,
+    ScrollDown,
+    ScrollUp,
+    HalfPageDown,
+    HalfPageUp,
+    PageDown,
+    PageUp,
+    Top,
+    Bottom,
+    /// Scroll to the percentage given as a count.
+    Percent,
+    Search,
+    NextMatch,
+    PrevMatch,
+    NextHeading,
+    PrevHeading,
+    /// Start selecting lines; in the selection, movement keys move the cursor.
+    Visual,
+    /// Copy the selected lines to the clipboard.
+    Yank,
+    /// Wait for a letter and mark the current position with it.
+    SetMark,
+    /// Wait for a letter and jump to that mark.
+    JumpToMark,
+    NextLink,
+    PrevLink,
+    OpenLink,
+    /// Return to where the last link was followed from.
+    Back,
+    Forward,
+    NextBuffer,
+    PrevBuffer,
+    /// Show the next slide when presenting; otherwise scroll the wide table
+    /// in view right.
+    NextSlide,
+    PrevSlide,
+    /// Show word, heading, code block and link counts and the reading time.
+    Info,
+    ToggleFollow,
+    ToggleStatusBar,
+    /// Show the markdown source beside the rendered view.
+    ToggleSplit,
+    /// Re-read the current file from disk.
+    Reload,
+    /// Open the current file in `$EDITOR` at the line on screen.
+    Edit,
+    /// Wait for a key and fold or unfold sections: `a` toggles, `o` opens
+    /// and `c` closes the one at the top of the view, `R` opens all and `M`
+    /// closes all.
+    Fold,
+    /// Open the heading palette: type to fuzzy-match heading titles, `Enter`
+    /// jumps to the highlighted one.
+    Outline,
+    /// Open the search results panel: every match of the query in the
+    /// document with its section, `Enter` jumps to the highlighted one.
+    SearchResults,
+    /// Open the quick switcher of `--vault`: type to fuzzy-match the names
+    /// of the vault's notes, `Enter` opens the highlighted one.
+    QuickSwitch,
+    /// List the lines of other notes of `--vault` that link to the current
+    /// one; `Enter` opens the highlighted one there.
+    Backlinks,
+    /// Open the quick switcher on `:tag `, to list the notes with a tag.
+    FilterTags,
+}
+
+/// Config names and default keys for every action.
+const DEFAULTS: &[(&str, Action, &[&str])] = &[
+    ("quit", Action::Quit, &["q", "C-c"]),
+    ("quit_with_error", Action::QuitWithError, &["Q"]),
+    ("cancel", Action::Cancel, &["Esc"]),
+    ("scroll_down", Action::ScrollDown, &["j", "Down"]),
+    ("scroll_up", Action::ScrollUp, &["k", "Up"]),
+    ("half_page_down", Action::HalfPageDown, &["d", "C-d"]),
+    ("half_page_up", Action::HalfPageUp, &["u", "C-u"]),
+    ("page_down", Action::PageDown, &["Space", "PageDown"]),
+    ("page_up", Action::PageUp, &["PageUp", "C-b"]),
+    ("top", Action::Top, &["g"]),
+    ("bottom", Action::Bottom, &["G"]),
+    ("percent", Action::Percent, &["%"]),
+    ("search", Action::Search, &["/"]),
+    ("next_match", Action::NextMatch, &["n"]),
+    ("prev_match", Action::PrevMatch, &["N"]),
+    ("next_heading", Action::NextHeading, &["}"]),
+    ("prev_heading", Action::PrevHeading, &["{"]),
+    ("visual", Action::Visual, &["v"]),
+    ("yank", Action::Yank, &["y"]),
+    ("set_mark", Action::SetMark, &["m"]),
+    ("jump_to_mark", Action::JumpToMark, &["'"]),
+    ("next_link", Action::NextLink, &["Tab"]),
+    ("prev_link", Action::PrevLink, &["BackTab"]),
+    ("open_link", Action::OpenLink, &["Enter"]),
+    ("back", Action::Back, &["Backspace", "C-o"]),
+    ("forward", Action::Forward, &["C-i"]),
+    ("next_buffer", Action::NextBuffer, &["]"]),
+    ("prev_buffer", Action::PrevBuffer, &["["]),
+    ("next_slide", Action::NextSlide, &["Right"]),
+    ("prev_slide", Action::PrevSlide, &["Left"]),
+    ("info", Action::Info, &["C-g"]),
+    ("toggle_follow", Action::ToggleFollow, &["F"]),
+    ("toggle_status_bar", Action::ToggleStatusBar, &["s"]),
+    ("toggle_split", Action::ToggleSplit, &["S"]),
+    ("reload", Action::Reload, &["r"]),
+    ("edit", Action::Edit, &["e"]),
+    ("fold", Action::Fold, &["z"]),
+    ("outline", Action::Outline, &["p", "C-j"]),
+    ("search_results", Action::SearchResults, &["C-f"]),
+    ("quick_switch", Action::QuickSwitch, &["o"]),
+    ("backlinks", Action::Backlinks, &["b"]),
+    ("filter_tags", Action::FilterTags, &[":"]),
+];
+
+pub struct Keymap {
+    bindings: HashMap<(KeyCode, KeyModifiers), Action>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Self::new(&BTreeMap::new()).expect("default bindings are valid")
+    }
+}
+
+impl Keymap {
+    /// Builds the keymap from the `[keys]` config table. An action listed
+    /// there gets exactly the given keys; unlisted actions keep their defaults.
+    pub fn new(overrides: &BTreeMap<String, Vec<String>>) -> Result<Self> {
+        for name in overrides.keys() {
+            if !DEFAULTS.iter().any(|(n, _, _)| n == name) {
+                let names: Vec<&str> = DEFAULTS.iter().map(|(n, _, _)| *n).collect();
+                bail!("Unknown action '{name}' in [keys] (available: {})", names.join(", "));
+            }
+        }
+
+        // Overrides go in last so they win over a default on the same key.
+        let mut bindings = HashMap::new();
+        for &(name, action, defaults) in DEFAULTS {
+            if !overrides.contains_key(name) {
+                for key in defaults {
+                    bindings.insert(parse_key(key)?, action);
+                }
+            }
+        }
+        for &(name, action, _) in DEFAULTS {
+            for key in overrides.get(name).into_iter().flatten() {
+                bindings.insert(parse_key(key)?, action);
+            }
+        }
+        Ok(Self { bindings })
+    }
+
+    pub fn action(&self, key: KeyEvent) -> Option<Action> {
+        self.bindings.get(&normalize(key.code, key.modifiers)).copied()
+    }
+}
+
+/// Shift is implied by the character itself (`G`, `{`) and by `BackTab`, and
+/// terminals disagree on whether they report it, so it is dropped there.
+fn normalize(code: KeyCode, modifiers: KeyModifiers) -> (KeyCode, KeyModifiers) {
+    match code {
+        KeyCode::Char(_) | KeyCode::BackTab => (code, modifiers - KeyModifiers::SHIFT),
+        _ => (code, modifiers),
+    }
+}
+
+/// Parses a key like `j`, `G`, `Down`, `Space`, `C-d` or `Alt-Enter`.
+pub fn parse_key(s: &str) -> Result<(KeyCode, KeyModifiers)> {
+    let mut modifiers = KeyModifiers::NONE;
+    let mut rest = s;
+    loop {
+        if rest.chars().count() <= 1 {
+            break;
+        }
+        if let Some(r) = rest.strip_prefix("C-").or_else(|| rest.strip_prefix("Ctrl-")) {
+            modifiers |= KeyModifiers::CONTROL;
+            rest = r;
+        } else if let Some(r) = rest.strip_prefix("A-").or_else(|| rest.strip_prefix("Alt-")) {
+            modifiers |= KeyModifiers::ALT;
+            rest = r;
+        } else {
+            break;
+        }
+    }
+
+    let mut chars = rest.chars();
+    let code = match (chars.next(), chars.next()) {
+        (Some(c), None) => KeyCode::Char(c),
+        _ => match rest.to_ascii_lowercase().as_str() {
+            "up" => KeyCode::Up,
+            "down" => KeyCode::Down,
+            "left" => KeyCode::Left,
+            "right" => KeyCode::Right,
+            "pageup" => KeyCode::PageUp,
+            "pagedown" => KeyCode::PageDown,
+            "home" => KeyCode::Home,
+            "end" => KeyCode::End,
+            "enter" => KeyCode::Enter,
+            "esc" => KeyCode::Esc,
+            "tab" => KeyCode::Tab,
+            "backtab" => KeyCode::BackTab,
+            "backspace" => KeyCode::Backspace,
+            "space" => KeyCode::Char(' '),
+            name => match name.strip_prefix('f').map(str::parse::<u8>) {
+                Some(Ok(n @ 1..=12)) => KeyCode::F(n),
+                _ => bail!("Invalid key '{s}'"),
+            },
+        },
+    };
+    Ok(normalize(code, modifiers))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(code: KeyCode, modifiers: KeyModifiers) -> KeyEvent {
+        KeyEvent::new(code, modifiers)
+    }
+
+    #[test]
+    fn test_parse_key() {
+        assert_eq!(parse_key("j").unwrap(), (KeyCode::Char('j'), KeyModifiers::NONE));
+        assert_eq!(parse_key("Space").unwrap(), (KeyCode::Char(' '), KeyModifiers::NONE));
+        assert_eq!(parse_key("pagedown").unwrap(), (KeyCode::PageDown, KeyModifiers::NONE));
+        assert_eq!(parse_key("C-d").unwrap(), (KeyCode::Char('d'), KeyModifiers::CONTROL));
+        assert_eq!(parse_key("Alt-Enter").unwrap(), (KeyCode::Enter, KeyModifiers::ALT));
+        assert_eq!(parse_key("-").unwrap(), (KeyCode::Char('-'), KeyModifiers::NONE));
+        assert_eq!(parse_key("F5").unwrap(), (KeyCode::F(5), KeyModifiers::NONE));
+        assert!(parse_key("Hyper-x").is_err());
+    }
+
+    #[test]
+    fn test_default_bindings() {
+        let keymap = Keymap::default();
+        assert_eq!(keymap.action(key(KeyCode::Char('j'), KeyModifiers::NONE)), Some(Action::ScrollDown));
+        assert_eq!(keymap.action(key(KeyCode::Char('G'), KeyModifiers::SHIFT)), Some(Action::Bottom));
+        assert_eq!(keymap.action(key(KeyCode::Char('c'), KeyModifiers::CONTROL)), Some(Action::Quit));
+        assert_eq!(keymap.action(key(KeyCode::Char('f'), KeyModifiers::CONTROL)), Some(Action::SearchResults));
+        assert_eq!(keymap.action(key(KeyCode::Char('Q'), KeyModifiers::SHIFT)), Some(Action::QuitWithError));
+        assert_eq!(keymap.action(key(KeyCode::Char('x'), KeyModifiers::NONE)), None);
+    }
+
+    #[test]
+    fn test_overrides_replace_defaults() {
+        let overrides = BTreeMap::from([("scroll_down".to_string(), vec!["e".to_string(), "C-n".to_string()])]);
+        let keymap = Keymap::new(&overrides).unwrap();
+        assert_eq!(keymap.action(key(KeyCode::Char('e'), KeyModifiers::NONE)), Some(Action::ScrollDown));
+        assert_eq!(keymap.action(key(KeyCode::Char('n'), KeyModifiers::CONTROL)), Some(Action::ScrollDown));
+        assert_eq!(keymap.action(key(KeyCode::Char('j'), KeyModifiers::NONE)), None);
+        assert_eq!(keymap.action(key(KeyCode::Char('k'), KeyModifiers::NONE)), Some(Action::ScrollUp));
+    }
+
+    #[test]
+    fn test_override_wins_over_other_default() {
+        let overrides = BTreeMap::from([("quit".to_string(), vec!["k".to_string()])]);
+        let keymap = Keymap::new(&overrides).unwrap();
+        assert_eq!(keymap.action(key(KeyCode::Char('k'), KeyModifiers::NONE)), Some(Action::Quit));
+    }
+
+    #[test]
+    fn test_unknown_action_is_rejected() {
+        let overrides = BTreeMap::from([("scrol_down".to_string(), vec!["j".to_string()])]);
+        assert!(Keymap::new(&overrides).is_err());
+    }
+}
@@ -0,0 +1,50 @@
+//! Markdown rendering for ratatui.
+//!
+//! ```
+//! let text = mdview::render_markdown("# Title\n\nSome *markdown*.", 80, &mdview::Theme::default());
+//! assert_eq!(text.lines[0].to_string(), "# Title");
+//! ```
+
+mod admonitions;
+pub mod ast;
+pub mod bench;
+mod bidi;
+mod csv;
+pub mod diff;
+mod emoji;
+pub mod encoding;
+mod entities;
+pub mod export;
+pub mod frontmatter;
+pub mod grep;
+mod highlight;
+mod html;
+pub mod hyphenate;
+pub mod images;
+pub mod include;
+pub mod input;
+pub mod invisibles;
+pub mod lint;
+pub mod man;
+pub mod notebook;
+pub mod org;
+pub mod palette;
+pub mod rst;
+pub mod section;
+mod incremental;
+mod mermaid;
+mod pdf;
+mod render;
+pub mod stats;
+pub mod tags;
+pub mod theme;
+pub mod vault;
+mod wikilinks;
+
+pub use render::{
+    Details, Document, Heading, ImageOptions, ImagePlacement, Link, LinkStyle, RenderOptions, TableOverflow,
+    WideTable, layout, render_document, render_markdown, slugify, split_slides,
+};
+pub use highlight::use_syntaxes;
+pub use incremental::IncrementalRenderer;
+pub use theme::Theme;
@@ -0,0 +1,148 @@
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use mdview::input::Format;
+
+use crate::fetch::{self, Source};
+use crate::fold;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Target {
+    /// A document in a format mdview reads, to open inside mdview, with the
+    /// fragment to jump to once it is open: a local file, or the URL of one
+    /// linked from a fetched document.
+    File(Source, Option<String>),
+    /// A fragment within the current document, without the leading `#`.
+    Anchor(String),
+    /// Anything else: handed off to the system opener.
+    External(String),
+}
+
+/// Classifies a link destination relative to the document `base` it
+/// appears in.
+pub fn resolve(base: &Source, url: &str) -> Target {
+    if let Some(anchor) = url.strip_prefix('#') {
+        return Target::Anchor(anchor.to_string());
+    }
+    if url.contains("://") || url.starts_with("mailto:") {
+        return Target::External(url.to_string());
+    }
+
+    let (file, fragment) = match url.split_once('#') {
+        Some((file, fragment)) => (file, Some(fragment.to_string()).filter(|f| !f.is_empty())),
+        None => (url, None),
+    };
+    let base = match base {
+        Source::File(path) => path,
+        Source::Url(base) => {
+            let target = fetch::join(base, file);
+            return match Format::from_path(fetch::document_path(&target)) {
+                Some(_) => Target::File(Source::Url(target), fragment),
+                None => Target::External(fetch::join(base, url)),
+            };
+        }
+    };
+    let dir = base.parent().unwrap_or(Path::new("."));
+    if Format::from_path(Path::new(file)).is_some() {
+        Target::File(Source::File(dir.join(file)), fragment)
+    } else {
+        Target::External(dir.join(file).display().to_string())
+    }
+}
+
+/// The URL a terminal hyperlink to `url` opens: web and mail links as
+/// they are, and local files as `file://` URLs. Links within the document,
+/// which only the viewer can follow, have none.
+pub fn hyperlink(base: &Source, url: &str) -> Option<String> {
+    if url == fold::TOGGLE_URL {
+        return None;
+    }
+    let (path, fragment) = match resolve(base, url) {
+        Target::Anchor(_) => return None,
+        Target::External(url) if url.contains("://") || url.starts_with("mailto:") => return Some(url),
+        Target::External(path) => (PathBuf::from(path), None),
+        Target::File(Source::File(path), fragment) => (path, fragment),
+        Target::File(Source::Url(url), fragment) => {
+            return Some(url + &fragment.map_or_else(String::new, |f| format!("#{f}")));
+        }
+    };
+    let fragment = fragment.map_or_else(String::new, |f| format!("#{f}"));
+    let path = std::path::absolute(&path).ok()?;
+    let mut out = String::from("file://");
+    for byte in path.to_string_lossy().bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'/' | b'-' | b'_' | b'.' | b'~' => out.push(byte as char),
+            _ => out.push_str(&format!("%{byte:02X}")),
+        }
+    }
+    Some(out + &fragment)
+}
+
+pub fn open_external(url: &str) -> io::Result<()> {
+    let mut cmd = if cfg!(target_os = "macos") {
+        Command::new("open")
+    } else if cfg!(target_os = "windows") {
+        let mut c = Command::new("cmd");
+        c.args(["/C", "start", ""]);
+        c
+    } else {
+        Command::new("xdg-open")
+    };
+    cmd.arg(url)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map(|_| ())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_targets() {
+        let base = &Source::File(PathBuf::from("/docs/README.md"));
+        assert_eq!(resolve(base, "#usage"), Target::Anchor("usage".into()));
+        assert_eq!(
+            resolve(base, "https://example.com"),
+            Target::External("https://example.com".into())
+        );
+        assert_eq!(
+            resolve(base, "guide/intro.md#setup"),
+            Target::File(Source::File(PathBuf::from("/docs/guide/intro.md")), Some("setup".into()))
+        );
+        assert_eq!(
+            resolve(base, "CHANGELOG.md"),
+            Target::File(Source::File(PathBuf::from("/docs/CHANGELOG.md")), None)
+        );
+        assert_eq!(
+            resolve(base, "data/sales.csv"),
+            Target::File(Source::File(PathBuf::from("/docs/data/sales.csv")), None)
+        );
+        assert_eq!(
+            resolve(base, "logo.png"),
+            Target::External("/docs/logo.png".into())
+        );
+
+        let base = &Source::Url("https://host/o/r/docs/README.md".into());
+        assert_eq!(
+            resolve(base, "../CHANGELOG.md#v2"),
+            Target::File(Source::Url("https://host/o/r/CHANGELOG.md".into()), Some("v2".into()))
+        );
+        assert_eq!(resolve(base, "logo.png"), Target::External("https://host/o/r/docs/logo.png".into()));
+        assert_eq!(resolve(base, "#usage"), Target::Anchor("usage".into()));
+    }
+
+    #[test]
+    fn test_hyperlink_urls() {
+        let base = &Source::File(PathBuf::from("/docs/README.md"));
+        assert_eq!(hyperlink(base, "https://example.com").as_deref(), Some("https://example.com"));
+        assert_eq!(hyperlink(base, "my notes.md#a").as_deref(), Some("file:///docs/my%20notes.md#a"));
+        assert_eq!(hyperlink(base, "#usage"), None);
+        assert_eq!(hyperlink(base, fold::TOGGLE_URL), None);
+        let base = &Source::Url("https://host/docs/README.md".into());
+        assert_eq!(hyperlink(base, "a.md#b").as_deref(), Some("https://host/docs/a.md#b"));
+    }
+}
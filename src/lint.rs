@@ -0,0 +1,186 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use pulldown_cmark::{Event, Parser, Tag, TagEnd};
+
+use crate::render::RenderOptions;
+
+/// A problem found by [`lint`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    /// 1-based line number.
+    pub line: usize,
+    /// Short name of the check, like `bare-url`.
+    pub rule: &'static str,
+    pub message: String,
+}
+
+/// Checks `input`, the contents of the file at `path`, for common problems:
+/// heading levels that skip a level, bare URLs, trailing whitespace,
+/// relative links and images to files that do not exist, and headings
+/// repeated word for word. Findings are sorted by line.
+///
+/// ```
+/// use std::path::Path;
+///
+/// let findings = mdview::lint::lint("# Title\n\n### Skipped\n", Path::new("doc.md"));
+/// assert_eq!(findings[0].line, 3);
+/// assert_eq!(findings[0].rule, "heading-increment");
+/// ```
+pub fn lint(input: &str, path: &Path) -> Vec<Finding> {
+    let line_starts: Vec<usize> =
+        std::iter::once(0).chain(input.match_indices('\n').map(|(i, _)| i + 1)).collect();
+    let line_of = |offset: usize| line_starts.partition_point(|&start| start <= offset);
+    let dir = path.parent().unwrap_or(Path::new(""));
+
+    let mut findings = trailing_whitespace(input);
+    let mut previous_level = None;
+    let mut headings: HashMap<String, usize> = HashMap::new();
+    let mut heading: Option<(String, usize)> = None;
+    let mut in_code_block = false;
+    let mut link_depth = 0usize;
+
+    for (event, range) in Parser::new_ext(input, RenderOptions::new(0).extensions).into_offset_iter() {
+        let line = line_of(range.start);
+        match event {
+            Event::Start(Tag::Heading { level, .. }) => {
+                let level = level as usize;
+                if let Some(previous) = previous_level
+                    && level > previous + 1
+                {
+                    findings.push(Finding {
+                        line,
+                        rule: "heading-increment",
+                        message: format!("H{level} follows H{previous}, skipping a level"),
+                    });
+                }
+                previous_level = Some(level);
+                heading = Some((String::new(), line));
+            }
+            Event::End(TagEnd::Heading(_)) => {
+                if let Some((title, line)) = heading.take() {
+                    let title = title.trim().to_string();
+                    match headings.get(&title) {
+                        Some(first) => findings.push(Finding {
+                            line,
+                            rule: "duplicate-heading",
+                            message: format!("Heading \"{title}\" already used on line {first}"),
+                        }),
+                        None => {
+                            headings.insert(title, line);
+                        }
+                    }
+                }
+            }
+            Event::Start(Tag::CodeBlock(_)) => in_code_block = true,
+            Event::End(TagEnd::CodeBlock) => in_code_block = false,
+            Event::Start(Tag::Link { dest_url, .. } | Tag::Image { dest_url, .. }) => {
+                link_depth += 1;
+                if let Some(target) = local_target(&dest_url)
+                    && !dir.join(target).exists()
+                    && !dir.join(target.replace("%20", " ")).exists()
+                {
+                    findings.push(Finding {
+                        line,
+                        rule: "broken-link",
+                        message: format!("{target} does not exist"),
+                    });
+                }
+            }
+            Event::End(TagEnd::Link | TagEnd::Image) => link_depth = link_depth.saturating_sub(1),
+            Event::Code(code) => {
+                if let Some((title, _)) = &mut heading {
+                    title.push_str(&code);
+                }
+            }
+            Event::Text(text) => {
+                if let Some((title, _)) = &mut heading {
+                    title.push_str(&text);
+                }
+                if !in_code_block
+                    && link_depth == 0
+                    && let Some(at) = text.find("http://").or_else(|| text.find("https://"))
+                {
+                    let url: String = text[at..].chars().take_while(|c| !c.is_whitespace()).collect();
+                    findings.push(Finding {
+                        line,
+                        rule: "bare-url",
+                        message: format!("Bare URL {url}; wrap it in <> or make it a link"),
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+    findings.sort_by_key(|f| f.line);
+    findings
+}
+
+/// Lines ending in spaces or tabs, other than the two spaces of a hard line
+/// break.
+fn trailing_whitespace(input: &str) -> Vec<Finding> {
+    input
+        .lines()
+        .enumerate()
+        .filter_map(|(i, line)| {
+            let trimmed = line.trim_end_matches([' ', '\t']);
+            let trailing = &line[trimmed.len()..];
+            let hard_break = trailing == "  " && !trimmed.trim().is_empty();
+            (!trailing.is_empty() && !hard_break).then(|| Finding {
+                line: i + 1,
+                rule: "trailing-whitespace",
+                message: "Trailing whitespace".to_string(),
+            })
+        })
+        .collect()
+}
+
+/// The file part of a link destination that points at a local file.
+fn local_target(url: &str) -> Option<&str> {
+    if url.is_empty() || url.starts_with('#') || url.starts_with('/') || url.contains(':') {
+        return None;
+    }
+    let file = url.split(['#', '?']).next().unwrap_or(url);
+    (!file.is_empty()).then_some(file)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules(input: &str) -> Vec<(usize, &'static str)> {
+        lint(input, Path::new("/nonexistent/doc.md")).into_iter().map(|f| (f.line, f.rule)).collect()
+    }
+
+    #[test]
+    fn test_clean_document() {
+        let md = "# Title\n\nText with a  \nhard break and <https://example.com>.\n\n\
+                  ## Section\n\n[Top](#title)\n";
+        assert!(rules(md).is_empty());
+    }
+
+    #[test]
+    fn test_findings() {
+        let md = "# Title \n\n#### Deep\n\nSee https://example.com now.\n\n## Usage\n\n## Usage\n\n\
+                  [guide](guide.md#setup) and ![logo](img/logo.png)\n\n```\nhttps://in.code\n```\n";
+        assert_eq!(
+            rules(md),
+            [
+                (1, "trailing-whitespace"),
+                (3, "heading-increment"),
+                (5, "bare-url"),
+                (9, "duplicate-heading"),
+                (11, "broken-link"),
+                (11, "broken-link"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_local_target() {
+        assert_eq!(local_target("docs/a.md#intro"), Some("docs/a.md"));
+        assert_eq!(local_target("https://example.com/a.md"), None);
+        assert_eq!(local_target("mailto:me@example.com"), None);
+        assert_eq!(local_target("#anchor"), None);
+    }
+}
@@ -0,0 +1,29 @@
+//! `--debug`: a log of what the viewer does, for reports of reloads that go
+//! missing or renders that take too long.
+
+use std::fs::File;
+use std::path::Path;
+use std::sync::Mutex;
+
+use anyhow::{Context, Result};
+use tracing_subscriber::fmt::format::FmtSpan;
+use tracing_subscriber::fmt::time::Uptime;
+
+/// Writes debug-level events to a new file at `path`. The viewer owns the
+/// terminal, so nothing can go to stderr while it runs. Each line is
+/// stamped with the time since start, and spans log the time spent in them
+/// when they close.
+pub fn init(path: &Path) -> Result<()> {
+    let file = File::create(path).with_context(|| format!("Cannot create log file {}", path.display()))?;
+    tracing_subscriber::fmt()
+        .with_writer(Mutex::new(file))
+        .with_ansi(false)
+        .with_max_level(tracing::Level::DEBUG)
+        .with_timer(Uptime::default())
+        .with_span_events(FmtSpan::CLOSE)
+        .with_thread_ids(true)
+        .init();
+    let args: Vec<String> = std::env::args().collect();
+    tracing::info!(version = env!("CARGO_PKG_VERSION"), ?args, "started");
+    Ok(())
+}
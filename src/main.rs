@@ -1,243 +1,2705 @@
-mod highlight;
-mod render;
+mod cli;
+mod clipboard;
+mod compare;
+mod config;
+mod fetch;
+mod fold;
+mod git;
+mod history;
+mod keymap;
+mod links;
+mod logging;
+mod notes;
+mod outline;
+mod pager;
+mod search;
+mod session;
+mod source;
+mod state;
+mod terminal;
 mod watch;
+mod worker;
 
-use std::io::{self, Write as _};
-use std::path::PathBuf;
-use std::sync::mpsc;
-use std::time::Duration;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::io::{self, IsTerminal as _, Write as _};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::{Arc, mpsc};
+use std::time::{Duration, Instant};
 
 use anyhow::{Context, Result};
-use crossterm::event::{self, Event, KeyCode, KeyModifiers};
+use crossterm::event::{
+    self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEvent, KeyModifiers,
+    KeyboardEnhancementFlags, MouseButton, MouseEventKind, PopKeyboardEnhancementFlags,
+    PushKeyboardEnhancementFlags,
+};
 use crossterm::terminal::{
     EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode,
+    supports_keyboard_enhancement,
 };
 use crossterm::ExecutableCommand;
 use ratatui::backend::CrosstermBackend;
-use ratatui::layout::Rect;
-use ratatui::widgets::{Paragraph, Wrap};
+use ratatui::layout::{Margin, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::widgets::{Block, Borders, Clear, Padding, Paragraph};
 use ratatui::Terminal;
-use ratatui::text::Text;
+use ratatui::text::{Line, Span, Text};
+use unicode_segmentation::UnicodeSegmentation as _;
+use unicode_width::UnicodeWidthStr;
+
+use fetch::Source;
+use links::Target;
+use notes::{Backlinks, Switcher};
+use outline::Outline;
+use cli::{Cli, Command, DumpArgs, ExportArgs, Format, LintArgs, RenderArgs, ViewArgs};
+use config::{Config, Width};
+use history::{History, Location};
+use keymap::{Action, Keymap};
+use mdview::diff::{self, Change, ChangedBlock};
+use mdview::encoding::{self, Decoded};
+use mdview::export;
+use mdview::frontmatter::{self, Metadata};
+use mdview::images;
+use mdview::include::{Expanded, expand_includes};
+use mdview::man::{ManOptions, render_man};
+use mdview::input;
+use mdview::palette::{self, ColorDepth};
+use mdview::section::Section;
+use mdview::stats::{Stats, document_stats};
+use mdview::tags::{self, TagIndex};
+use mdview::theme::{self, Theme};
+use mdview::vault::{self, Vault};
+use mdview::{
+    Document, Heading, ImageOptions, ImagePlacement, IncrementalRenderer, Link, LinkStyle, RenderOptions, TableOverflow,
+    WideTable, render_document, split_slides,
+};
+use search::{Results, Search};
+use session::Session;
+use state::{FileState, State};
 
-use render::render_markdown;
+const FLASH_DURATION: Duration = Duration::from_millis(1500);
+/// Blank columns and rows kept around a slide.
+const SLIDE_PADDING: Margin = Margin { horizontal: 4, vertical: 1 };
+/// Columns a wide table moves per Left/Right press.
+const TABLE_SCROLL_STEP: usize = 8;
+/// Rows an error banner may take, the rest of its message cut off.
+const ERROR_BANNER_ROWS: usize = 3;
+/// Slowest blocks listed by `--bench`.
+const BENCH_BLOCKS: usize = 5;
 
-const MAX_FILE_SIZE: u64 = 10 * 1024 * 1024;
+struct TerminalGuard {
+    /// Key disambiguation was pushed and must be popped again.
+    keyboard_enhanced: bool,
+}
 
-struct TerminalGuard;
 impl Drop for TerminalGuard {
     fn drop(&mut self) {
+        if self.keyboard_enhanced {
+            let _ = io::stdout().execute(PopKeyboardEnhancementFlags);
+        }
         let _ = disable_raw_mode();
+        let _ = io::stdout().execute(DisableMouseCapture);
         let _ = io::stdout().execute(LeaveAlternateScreen);
     }
 }
 
-struct App {
-    text: Text<'static>,
-    scroll: u16,
-    viewport_height: u16,
+#[derive(PartialEq, Eq)]
+enum Mode {
+    Normal,
+    Search,
+    /// Waiting for the letter of a mark to set.
+    SetMark,
+    /// Waiting for the letter of a mark to jump to.
+    JumpToMark,
+    /// Waiting for the key after `z`.
+    Fold,
+    /// Typing in the heading palette.
+    Outline,
+    /// Typing in the search results panel.
+    Results,
+    /// Typing in the quick switcher of `--vault`.
+    Switcher,
+    /// Choosing in the backlinks panel of `--vault`.
+    Backlinks,
+    /// Selecting rendered lines between `anchor` and `cursor`, inclusive.
+    Visual { anchor: usize, cursor: usize },
+}
+
+/// What the next render of a buffer has to take into account. Requests made
+/// while a render is running add up here and go out as one render when it
+/// finishes.
+#[derive(Default)]
+struct PendingRender {
+    /// A render was asked for and has not started yet.
+    queued: bool,
+    options: Option<RenderOptions>,
+    clear_cache: bool,
+    reset_scroll: bool,
+}
+
+/// An open file with its rendered document and view state.
+struct Buffer {
+    source: Source,
+    /// The file's markdown with includes inlined.
+    content: String,
+    /// `None` while the renderer is away rendering on the worker thread.
+    renderer: Option<IncrementalRenderer>,
+    /// Changes for the next render.
+    pending: PendingRender,
+    /// The rendered document; `text` to `anchors` are it with the folded
+    /// sections hidden.
+    doc: Document,
+    /// Lines of `doc` holding the headings of folded sections and the
+    /// summaries of folded `<details>`.
+    folds: BTreeSet<usize>,
+    /// Summary lines of `<details>` that start closed but were opened.
+    opened: BTreeSet<usize>,
+    /// Maps the rows of `text` to the lines of `doc`.
+    projection: fold::Projection,
+    text: Text<'static>,
+    links: Vec<Link>,
+    images: Vec<ImagePlacement>,
+    headings: Vec<Heading>,
+    anchors: HashMap<String, usize>,
+    tables: Vec<WideTable>,
+    /// How far each wide table is scrolled sideways, by its line in `doc`.
+    table_scroll: HashMap<usize, usize>,
+    active_link: Option<usize>,
+    scroll: usize,
+    /// Positions saved with `m{a-z}`, as lines of `doc` so they hold when
+    /// sections are folded.
+    marks: BTreeMap<char, usize>,
+    /// The slide shown when presenting; `None` shows the whole file.
+    slide: Option<usize>,
+    /// With `--section`, the heading asked for and the part of `content` its
+    /// section spans.
+    section: Option<(String, Range<usize>)>,
+    /// The title, author and date in the frontmatter of `content`.
+    metadata: Metadata,
+    /// The tags of `content`, from its frontmatter and `#tag` words.
+    tags: Vec<String>,
+    /// How the file is encoded, when it is not plain UTF-8.
+    encoding: Option<&'static str>,
+    /// An anchor to jump to once the render in progress is in.
+    jump_to: Option<String>,
+    /// A source line, from 0, to scroll to once the render in progress is in.
+    goto_line: Option<usize>,
+    /// Why the file could not be reloaded after it changed on disk, so the
+    /// view shows an earlier version.
+    reload_error: Option<String>,
+    /// Why the last render failed, so the view shows the one before.
+    render_error: Option<String>,
+    /// With `--diff`, the blocks of `content` added or removed since the
+    /// revision, by source line.
+    changes: Vec<ChangedBlock>,
+    /// The same as lines of `doc`, once rendered.
+    gutter: Vec<(Range<usize>, Change)>,
+    watcher: watch::Watcher,
+}
+
+impl Buffer {
+    fn set_document(&mut self, doc: Document) {
+        self.folds.retain(|&line| fold::can_fold(&doc, line));
+        self.opened.retain(|&line| doc.details.iter().any(|d| d.line == line && !d.open));
+        self.table_scroll.retain(|&line, _| doc.tables.iter().any(|t| t.line == line));
+        self.doc = doc;
+        self.fold_closed_details();
+        self.refold();
+    }
+
+    /// Folds the `<details>` that start closed, unless they were opened.
+    fn fold_closed_details(&mut self) {
+        let closed = self.doc.details.iter().filter(|d| !d.open).map(|d| d.line);
+        self.folds.extend(closed.filter(|line| !self.opened.contains(line)));
+    }
+
+    /// Folds or unfolds the heading or `<details>` at line `line` of `doc`.
+    fn set_fold(&mut self, line: usize, close: bool) {
+        if close {
+            self.folds.insert(line);
+            self.opened.remove(&line);
+        } else {
+            self.folds.remove(&line);
+            if self.doc.details.iter().any(|d| d.line == line && !d.open) {
+                self.opened.insert(line);
+            }
+        }
+    }
+
+    /// Unfolds whatever hides line `line` of `doc`.
+    fn reveal(&mut self, line: usize) {
+        for fold in fold::hiding(&self.doc, &self.folds, line) {
+            self.set_fold(fold, false);
+        }
+    }
+
+    /// Rebuilds the view of `doc` after `folds` changed.
+    fn refold(&mut self) {
+        let (view, projection) = fold::project(&self.doc, &self.folds);
+        self.text = view.text;
+        self.links = view.links;
+        self.images = view.images;
+        self.headings = view.headings;
+        self.anchors = view.anchors;
+        self.tables = view.tables;
+        self.projection = projection;
+        self.active_link = None;
+    }
+
+    /// The line of `doc` shown at the top of the view.
+    fn top_line(&self) -> usize {
+        self.projection.line(self.scroll)
+    }
+
+    /// The row of the view showing line `line` of `doc`, or the folded
+    /// heading hiding it.
+    fn row(&self, line: usize) -> usize {
+        self.projection.row(line)
+    }
+
+    /// Columns row `row` of the view is scrolled sideways: the offset of
+    /// the wide table it is part of, if any.
+    fn table_shift(&self, row: usize) -> usize {
+        let Some(table) = self.tables.iter().find(|t| t.line <= row && row < t.end) else {
+            return 0;
+        };
+        let offset = self.table_scroll.get(&self.projection.line(table.line)).copied().unwrap_or(0);
+        offset.min(table.overflow)
+    }
+
+    /// The part of `content` viewed: the section with `--section`, else all
+    /// of it.
+    fn body(&self) -> &str {
+        match &self.section {
+            Some((_, range)) => &self.content[range.clone()],
+            None => &self.content,
+        }
+    }
+
+    /// The part of `content` on screen: the current slide of `body` when
+    /// presenting, else all of it.
+    fn shown(&self) -> &str {
+        shown_source(self.body(), self.slide)
+    }
+
+    /// Lines of `content` before the part on screen.
+    fn lines_before_shown(&self) -> usize {
+        let offset = self.shown().as_ptr() as usize - self.content.as_ptr() as usize;
+        self.content[..offset].matches('\n').count()
+    }
+
+    fn slide_count(&self) -> usize {
+        split_slides(self.body()).len()
+    }
+
+    fn name(&self) -> String {
+        self.source.name()
+    }
+}
+
+/// A link on screen, drawn again inside OSC 8 escapes after ratatui has
+/// drawn the frame: the URL it opens and its cells on row `y` from `x`.
+#[derive(PartialEq)]
+struct Hyperlink {
+    url: String,
+    x: u16,
+    y: u16,
+    cells: Vec<ratatui::buffer::Cell>,
+}
+
+struct App {
+    buffers: Vec<Buffer>,
+    current: usize,
+    /// Image index and screen position of each image drawn last frame.
+    drawn_images: Vec<(usize, u16, u16)>,
+    /// Links drawn as hyperlinks last frame, with `--link-style osc8`.
+    drawn_links: Vec<Hyperlink>,
+    viewport_height: u16,
+    mode: Mode,
+    search: Search,
+    /// The heading palette's query and selection, while `Mode::Outline`.
+    outline: Outline,
+    /// The results panel's query, matches and selection, while
+    /// `Mode::Results`.
+    results: Results,
+    message: Option<String>,
+    /// Jump to the bottom whenever the current file changes, like `tail -f`.
+    follow: bool,
+    status_bar: bool,
+    /// Show the markdown source in a pane left of the rendered view.
+    split: bool,
+    /// Short-lived confirmation shown in the status area, cleared after
+    /// `FLASH_DURATION`.
+    flash: Option<(String, Instant)>,
+    worker: worker::Worker,
+    /// Where followed links were followed from, for back/forward.
+    history: History,
+    /// Number typed before a key, like `10` in `10j`.
+    count: Option<usize>,
+    /// Statistics of the current buffer, shown in a popup until the next key.
+    info: Option<Stats>,
+    /// The git revision every buffer is shown at, or diffed against.
+    revision: Option<git::Revision>,
+    /// Largest file reloaded, from the config.
+    max_file_size: config::FileSize,
+    /// The vault of `--vault`, indexed anew whenever one of its panels opens.
+    vault: Option<Arc<Vault>>,
+    /// The tags of the vault's notes, brought up to date with the vault and
+    /// as watched files change.
+    tag_index: TagIndex,
+    /// The quick switcher's query, notes and selection, while
+    /// `Mode::Switcher`.
+    switcher: Switcher,
+    /// The backlinks of the current note, while `Mode::Backlinks`.
+    backlinks: Backlinks,
+    /// Where `y` copies to.
+    clipboard: clipboard::Clipboard,
+}
+
+impl App {
+    fn buf(&self) -> &Buffer {
+        &self.buffers[self.current]
+    }
+
+    fn buf_mut(&mut self) -> &mut Buffer {
+        &mut self.buffers[self.current]
+    }
+
+    /// Updates the view after buffer `index` got a new document or was
+    /// refolded, keeping its scroll position within the new content.
+    fn set_document(&mut self, index: usize) {
+        if index == self.current {
+            self.drawn_images.clear();
+            if self.search.is_active() {
+                self.search.update(&self.buffers[index].text);
+            }
+            if self.mode == Mode::Results {
+                self.results.update(&self.buffers[index].doc.text);
+            }
+            self.clamp_scroll();
+        }
+    }
+
+    /// Makes buffer `index` current, restoring its scroll position.
+    fn switch_to(&mut self, index: usize) {
+        if index == self.current || index >= self.buffers.len() {
+            return;
+        }
+        self.current = index;
+        self.drawn_images.clear();
+        if self.search.is_active() {
+            self.search.update(&self.buffers[index].text);
+        }
+        self.clamp_scroll();
+        self.message = Some(format!("[{}/{}] {}", index + 1, self.buffers.len(), self.buf().name()));
+    }
+
+    /// Switches to the next buffer, or the previous one when `forward` is
+    /// false, wrapping around at either end.
+    fn location(&self) -> Location {
+        Location { buffer: self.current, scroll: self.buf().top_line() }
+    }
+
+    /// Returns to the place the last followed link was followed from, or
+    /// (with `forward`) undoes that.
+    fn go_back(&mut self, forward: bool) {
+        let current = self.location();
+        let to = match forward {
+            false => self.history.back(current),
+            true => self.history.forward(current),
+        };
+        let Some(to) = to else {
+            self.message = Some(format!("Nothing to go {}", if forward { "forward to" } else { "back to" }));
+            return;
+        };
+        self.switch_to(to.buffer);
+        self.scroll_to(self.buf().row(to.scroll));
+    }
+
+    fn cycle_buffer(&mut self, forward: bool) {
+        let len = self.buffers.len();
+        if len < 2 {
+            self.message = Some("Only one buffer open".to_string());
+            return;
+        }
+        let next = if forward { (self.current + 1) % len } else { (self.current + len - 1) % len };
+        self.switch_to(next);
+    }
+
+    fn toggle_follow(&mut self) {
+        self.follow = !self.follow;
+        if self.follow {
+            self.scroll_to(usize::MAX);
+        }
+        self.message = Some(format!("Follow mode {}", if self.follow { "on" } else { "off" }));
+    }
+
+    /// Renders buffer `index` on the worker thread, or once the render
+    /// already running there finishes. The result is applied by
+    /// `finish_render`.
+    fn request_render(&mut self, index: usize) {
+        let buf = &mut self.buffers[index];
+        buf.pending.queued = true;
+        let Some(mut renderer) = buf.renderer.take() else {
+            tracing::debug!(buffer = index, "render queued behind the running one");
+            return;
+        };
+        let pending = std::mem::take(&mut buf.pending);
+        if let Some(options) = pending.options {
+            renderer.set_options(options);
+        }
+        if pending.clear_cache {
+            renderer.clear_cache();
+        }
+        let source = buf.shown().to_string();
+        self.worker.submit(worker::Job {
+            buffer: index,
+            renderer,
+            source,
+            reset_scroll: pending.reset_scroll,
+        });
+    }
+
+    /// Shows a finished render, keeping the view on the same content, and
+    /// starts the next one if more changes came in meanwhile.
+    fn finish_render(&mut self, done: worker::Done) {
+        let index = done.buffer;
+        let buf = &mut self.buffers[index];
+        let renderer = buf.renderer.insert(done.renderer);
+        let doc = match done.doc {
+            Ok(doc) => doc,
+            Err(message) => {
+                tracing::warn!(buffer = index, %message, "render failed");
+                buf.render_error = Some(message);
+                if buf.pending.queued {
+                    self.request_render(index);
+                }
+                return;
+            }
+        };
+        buf.render_error = None;
+        // Positions are carried over as lines of the document, folded or not.
+        let mut top = 0;
+        if done.reset_scroll {
+            buf.folds.clear();
+            buf.opened.clear();
+        } else {
+            top = renderer.map_line(buf.projection.line(buf.scroll));
+            for line in buf.marks.values_mut() {
+                *line = renderer.map_line(*line);
+            }
+            buf.folds = buf.folds.iter().map(|&line| renderer.map_line(line)).collect();
+            buf.opened = buf.opened.iter().map(|&line| renderer.map_line(line)).collect();
+        }
+        buf.set_document(doc);
+        if let Some(renderer) = &buf.renderer {
+            // Changes are by line of `content`, of which a section may be shown.
+            let before = buf.lines_before_shown();
+            let rendered = |lines: &Range<usize>| {
+                renderer.rendered_line(lines.start.saturating_sub(before))
+                    ..renderer.rendered_line(lines.end.saturating_sub(before))
+            };
+            buf.gutter = buf
+                .changes
+                .iter()
+                .filter(|block| block.lines.end > before)
+                .map(|block| (rendered(&block.lines), block.change))
+                .collect();
+        }
+        buf.scroll = buf.row(top);
+        self.set_document(index);
+        if self.follow && index == self.current {
+            self.scroll_to(usize::MAX);
+        }
+        if let Some(anchor) = self.buffers[index].jump_to.take()
+            && index == self.current
+        {
+            self.jump_to_anchor(&anchor);
+        }
+        if let Some(line) = self.buffers[index].goto_line.take()
+            && index == self.current
+        {
+            self.scroll_to_source_line(line);
+        }
+        if self.buffers[index].pending.queued {
+            self.request_render(index);
+        }
+    }
+
+    /// The error over the top of the view while the current buffer shows an
+    /// earlier version of its file than the one on disk.
+    fn error_banner(&self) -> Option<String> {
+        let buf = self.buf();
+        match (&buf.reload_error, &buf.render_error) {
+            (Some(e), _) => Some(format!("Reload failed: {e}. Showing the last version read; r retries.")),
+            (None, Some(e)) => Some(format!("Render failed: {e}. Showing the last render; r retries.")),
+            (None, None) => None,
+        }
+    }
+
+    /// Whether the current buffer is being rendered in the background.
+    fn rendering(&self) -> bool {
+        self.buf().renderer.is_none()
+    }
+
+    /// Scrolls the current buffer to the rendering of `line` of its content,
+    /// from 0.
+    fn scroll_to_source_line(&mut self, line: usize) {
+        let line = line.saturating_sub(self.buf().lines_before_shown());
+        let line = self.buf().renderer.as_ref().map_or(0, |r| r.rendered_line(line));
+        self.scroll_to(self.buf().row(line));
+    }
+
+    /// Re-reads buffer `index` from disk and re-renders it, keeping the view
+    /// on the same content. On failure the buffer keeps showing the old
+    /// content, under a banner with the error.
+    fn reload(&mut self, index: usize) -> Result<()> {
+        let buf = &mut self.buffers[index];
+        let _span = tracing::debug_span!("reload", source = %buf.source).entered();
+        let (source, changes) = match read_version(&buf.source, self.revision.as_ref(), self.max_file_size) {
+            Ok(read) => read,
+            Err(e) => {
+                tracing::warn!(error = format!("{e:#}"), "reload failed");
+                buf.reload_error = Some(format!("{e:#}"));
+                return Err(e);
+            }
+        };
+        buf.reload_error = None;
+        buf.changes = changes;
+        buf.watcher.set_includes(&source.includes);
+        // A section that is gone leaves the whole file in view.
+        if let Some((heading, range)) = &mut buf.section {
+            *range = mdview::section::find(&source.text, heading).unwrap_or(0..source.text.len());
+        }
+        buf.metadata = frontmatter::metadata(&source.text);
+        buf.tags = tags::tags(&source.text);
+        buf.encoding = source.encoding;
+        buf.content = source.text;
+        let count = buf.slide_count();
+        if let Some(slide) = &mut buf.slide {
+            *slide = (*slide).min(count - 1);
+        }
+        self.request_render(index);
+        Ok(())
+    }
+
+    /// Moves `delta` slides forward (or back) in the current buffer when
+    /// presenting, showing the new slide from its top.
+    fn go_to_slide(&mut self, delta: isize) {
+        let index = self.current;
+        let count = self.buf().slide_count();
+        let buf = self.buf_mut();
+        let Some(slide) = buf.slide else {
+            return;
+        };
+        let next = slide.saturating_add_signed(delta).min(count - 1);
+        if next == slide {
+            return;
+        }
+        buf.slide = Some(next);
+        buf.pending.reset_scroll = true;
+        self.request_render(index);
+        self.message = Some(format!("Slide {}/{count}", next + 1));
+    }
+
+    /// Scrolls the first wide table in view `delta` steps right (or left),
+    /// between its left edge and its right border.
+    fn scroll_table(&mut self, delta: isize) {
+        let height = self.viewport_height as usize;
+        let buf = self.buf_mut();
+        let top = buf.scroll;
+        let Some(table) = buf.tables.iter().find(|t| t.end > top && t.line < top + height) else {
+            self.message = Some("No wide table in view".to_string());
+            return;
+        };
+        let overflow = table.overflow;
+        let offset = buf.table_scroll.entry(buf.projection.line(table.line)).or_default();
+        *offset = (*offset).min(overflow).saturating_add_signed(delta * TABLE_SCROLL_STEP as isize).min(overflow);
+    }
+
+    /// Where the current slide goes in `area`: centered inside the padding,
+    /// and only as large as its content.
+    fn slide_area(&self, area: Rect) -> Rect {
+        let inner = area.inner(SLIDE_PADDING);
+        let text = &self.buf().text;
+        let width = text.width().min(inner.width as usize) as u16;
+        let height = text.height().min(inner.height as usize) as u16;
+        Rect {
+            x: inner.x + (inner.width - width) / 2,
+            y: inner.y + (inner.height - height) / 2,
+            width,
+            height,
+        }
+    }
+
+    fn start_visual(&mut self) {
+        let line = self.buf().scroll;
+        self.mode = Mode::Visual { anchor: line, cursor: line };
+    }
+
+    /// Moves the visual-mode cursor by `delta` lines, scrolling to keep it
+    /// in view.
+    fn move_cursor(&mut self, delta: isize) {
+        let last = self.buf().text.lines.len().saturating_sub(1);
+        if let Mode::Visual { cursor, .. } = &mut self.mode {
+            *cursor = cursor.saturating_add_signed(delta).min(last);
+            let line = *cursor;
+            self.show_line(line);
+        }
+    }
+
+    /// The selected range of rendered lines in visual mode.
+    fn selection(&self) -> Option<std::ops::RangeInclusive<usize>> {
+        match self.mode {
+            Mode::Visual { anchor, cursor } => Some(anchor.min(cursor)..=anchor.max(cursor)),
+            _ => None,
+        }
+    }
+
+    /// Copies the selected lines as plain text and leaves visual mode.
+    fn yank(&mut self) -> io::Result<()> {
+        let Some(range) = self.selection() else {
+            return Ok(());
+        };
+        let count = range.clone().count();
+        let text: Vec<String> = self.buf().text.lines[range]
+            .iter()
+            .map(|line| {
+                let content: String = line.spans.iter().map(|s| s.content.as_ref()).collect();
+                content.trim_end().to_string()
+            })
+            .collect();
+        let copied = self.clipboard.copy(&mut io::stdout(), &(text.join("\n") + "\n"))?;
+        self.mode = Mode::Normal;
+        let plural = if count == 1 { "" } else { "s" };
+        // Only the system clipboard says whether it took the text.
+        self.message = Some(match copied {
+            clipboard::Copied::System => format!("Copied {count} line{plural}"),
+            clipboard::Copied::Terminal => format!("Sent {count} line{plural} to the terminal clipboard"),
+        });
+        Ok(())
+    }
+
+    fn set_mark(&mut self, mark: char) {
+        let line = self.buf().top_line();
+        self.buf_mut().marks.insert(mark, line);
+        self.message = Some(format!("Mark '{mark}' set"));
+    }
+
+    fn jump_to_mark(&mut self, mark: char) {
+        match self.buf().marks.get(&mark) {
+            Some(&line) => self.scroll_to(self.buf().row(line)),
+            None => self.message = Some(format!("Mark '{mark}' not set")),
+        }
+    }
+
+    /// Reloads the current buffer on request, rendering every block afresh.
+    fn force_reload(&mut self) {
+        let index = self.current;
+        self.buf_mut().pending.clear_cache = true;
+        // A failure shows in the error banner.
+        if self.reload(index).is_ok() {
+            self.flash = Some((format!("Reloaded {}", self.buf().name()), Instant::now()));
+        }
+    }
+
+    fn max_scroll(&self) -> usize {
+        self.buf().text.height().saturating_sub(self.viewport_height as usize)
+    }
+
+    fn scroll_down(&mut self, n: usize) {
+        let max = self.max_scroll();
+        let buf = self.buf_mut();
+        buf.scroll = buf.scroll.saturating_add(n).min(max);
+    }
+
+    fn scroll_up(&mut self, n: usize) {
+        let buf = self.buf_mut();
+        buf.scroll = buf.scroll.saturating_sub(n);
+    }
+
+    fn scroll_to(&mut self, line: usize) {
+        let max = self.max_scroll();
+        self.buf_mut().scroll = line.min(max);
+    }
+
+    fn half_page(&self) -> usize {
+        self.viewport_height as usize / 2
+    }
+
+    /// A page, less two lines kept from the previous one for context.
+    fn page(&self) -> usize {
+        (self.viewport_height as usize).saturating_sub(2)
+    }
+
+    /// Scrolls `percent` of the way down, like less's `%`.
+    fn scroll_to_percent(&mut self, percent: usize) {
+        let line = self.max_scroll() * percent.min(100) / 100;
+        self.scroll_to(line);
+    }
+
+    fn clamp_scroll(&mut self) {
+        self.scroll_to(self.buf().scroll);
+    }
+
+    fn show_line(&mut self, line: usize) {
+        let scroll = self.buf().scroll;
+        if line < scroll || line >= scroll.saturating_add(self.viewport_height as usize) {
+            self.scroll_to(line);
+        }
+    }
+
+    /// Scrolls to the first heading below the top of the viewport.
+    fn next_heading(&mut self) {
+        let top = self.buf().scroll;
+        let headings = &self.buf().headings;
+        let i = headings.partition_point(|h| h.line <= top);
+        if let Some(line) = headings.get(i).map(|h| h.line) {
+            self.scroll_to(line);
+        }
+    }
+
+    /// Scrolls to the last heading above the top of the viewport.
+    fn prev_heading(&mut self) {
+        let top = self.buf().scroll;
+        let headings = &self.buf().headings;
+        let i = headings.partition_point(|h| h.line < top);
+        if let Some(line) = i.checked_sub(1).map(|i| headings[i].line) {
+            self.scroll_to(line);
+        }
+    }
+
+    /// Folds (`Some(true)`), unfolds (`Some(false)`) or toggles the section
+    /// under the last heading at or above the top of the view.
+    fn fold(&mut self, close: Option<bool>) {
+        let buf = self.buf();
+        let top = buf.scroll;
+        let i = buf.headings.partition_point(|h| h.line <= top);
+        let Some(row) = i.checked_sub(1).map(|i| buf.headings[i].line) else {
+            self.message = Some("No section here".to_string());
+            return;
+        };
+        let line = buf.projection.line(row);
+        let buf = self.buf_mut();
+        buf.set_fold(line, close.unwrap_or(!buf.folds.contains(&line)));
+        self.refold();
+    }
+
+    /// Folds every section and `<details>`, or unfolds them all.
+    fn fold_all(&mut self, close: bool) {
+        let buf = self.buf_mut();
+        let headings = buf.doc.headings.iter().map(|h| h.line);
+        let lines: Vec<usize> = headings.chain(buf.doc.details.iter().map(|d| d.line)).collect();
+        for line in lines {
+            buf.set_fold(line, close);
+        }
+        self.refold();
+    }
+
+    /// Opens or closes the `<details>` whose summary is the selected link,
+    /// keeping it selected.
+    fn toggle_details(&mut self) {
+        let buf = self.buf();
+        let Some(row) = buf.active_link.and_then(|i| buf.links.get(i)).map(|l| l.line) else {
+            return;
+        };
+        let line = buf.projection.line(row);
+        let buf = self.buf_mut();
+        buf.set_fold(line, !buf.folds.contains(&line));
+        self.refold();
+        let buf = self.buf_mut();
+        let row = buf.projection.row(line);
+        buf.active_link = buf.links.iter().position(|l| l.line == row && l.url == fold::TOGGLE_URL);
+    }
+
+    /// Shows the current buffer with its changed folds, keeping the line at
+    /// the top of the view there, or the fold that now hides it.
+    fn refold(&mut self) {
+        let buf = self.buf_mut();
+        let top = buf.top_line();
+        buf.refold();
+        buf.scroll = buf.row(top);
+        self.set_document(self.current);
+    }
+
+    fn open_outline(&mut self) {
+        self.outline = Outline::default();
+        self.mode = Mode::Outline;
+    }
+
+    /// Jumps to the heading selected in the palette, unfolding the sections
+    /// hiding it, and closes the palette.
+    fn submit_outline(&mut self) {
+        self.mode = Mode::Normal;
+        let entries = self.outline.matches(&self.buf().doc.headings);
+        let Some(entry) = entries.get(self.outline.selected) else {
+            return;
+        };
+        let line = self.buf().doc.headings[entry.heading].line;
+        self.history.visit(self.location());
+        self.buf_mut().reveal(line);
+        self.refold();
+        self.scroll_to(self.buf().row(line));
+    }
+
+    /// Opens the results panel on the current search, if any.
+    fn open_results(&mut self) {
+        self.results = Results { input: self.search.query.clone(), regex: self.search.regex, ..Results::default() };
+        self.results.update(&self.buffers[self.current].doc.text);
+        self.mode = Mode::Results;
+    }
+
+    /// Jumps to the match selected in the results panel, unfolding the
+    /// sections hiding it, and makes its query the search so `n` and `N`
+    /// go on from there.
+    fn submit_results(&mut self) {
+        self.mode = Mode::Normal;
+        let Some(&m) = self.results.matches.get(self.results.selected) else {
+            return;
+        };
+        self.history.visit(self.location());
+        self.buf_mut().reveal(m.line);
+        self.refold();
+        let row = self.buf().row(m.line);
+        self.search.query = std::mem::take(&mut self.results.input);
+        self.search.regex = self.results.regex;
+        self.search.update(&self.buffers[self.current].text);
+        self.search.select_from(row);
+        if let Some(i) = self.search.matches.iter().position(|s| s.line == row && s.start == m.start) {
+            self.search.current = i;
+        }
+        self.scroll_to(row);
+    }
+
+    /// Indexes the vault anew, so notes added since are found, and makes it
+    /// the one links resolve in. `None` without `--vault`.
+    fn reindex_vault(&mut self) -> Option<Arc<Vault>> {
+        let Some(root) = self.vault.as_ref().map(|vault| vault.root().to_path_buf()) else {
+            self.message = Some("Not in a vault; open one with --vault DIR".to_string());
+            return None;
+        };
+        match Vault::open(&root) {
+            Ok(vault) => {
+                self.tag_index.update(&vault);
+                self.vault = Some(Arc::new(vault));
+                vault::use_vault(self.vault.clone());
+            }
+            Err(e) => self.message = Some(format!("{e:#}")),
+        }
+        self.vault.clone()
+    }
+
+    /// Opens the quick switcher with `input` typed in, such as `:tag ` to
+    /// filter by tag.
+    fn open_switcher(&mut self, input: &str) {
+        let Some(vault) = self.reindex_vault() else {
+            return;
+        };
+        let names = vault.notes().iter().map(|note| vault::name(note)).collect();
+        let tags = vault.notes().iter().map(|note| self.tag_index.get(&vault.root().join(note)).to_vec()).collect();
+        self.switcher = Switcher { input: input.to_string(), names, tags, selected: 0 };
+        self.mode = Mode::Switcher;
+    }
+
+    /// Closes the quick switcher, returning the note selected in it for the
+    /// caller to open.
+    fn submit_switcher(&mut self) -> Option<Source> {
+        self.mode = Mode::Normal;
+        let vault = self.vault.as_ref()?;
+        let (note, _) = self.switcher.matches().into_iter().nth(self.switcher.selected)?;
+        Some(Source::File(vault.root().join(&vault.notes()[note])))
+    }
+
+    fn open_backlinks(&mut self) {
+        let Some(vault) = self.reindex_vault() else {
+            return;
+        };
+        let links = self.buf().source.file().map_or_else(Vec::new, |note| vault.backlinks(note));
+        self.backlinks = Backlinks { links, selected: 0 };
+        self.mode = Mode::Backlinks;
+    }
+
+    /// Closes the backlinks panel, returning the note and line of the
+    /// backlink selected in it for the caller to open.
+    fn submit_backlinks(&mut self) -> Option<(Source, usize)> {
+        self.mode = Mode::Normal;
+        let vault = self.vault.as_ref()?;
+        let link = self.backlinks.links.get(self.backlinks.selected)?;
+        Some((Source::File(vault.root().join(&link.note)), link.line))
+    }
+
+    fn submit_search(&mut self) {
+        self.mode = Mode::Normal;
+        self.search.query = std::mem::take(&mut self.search.input);
+        self.search.update(&self.buffers[self.current].text);
+        if let Some(m) = self.search.select_from(self.buf().scroll) {
+            self.show_line(m.line);
+        }
+    }
+
+    fn search_next(&mut self) {
+        if let Some(m) = self.search.next() {
+            self.show_line(m.line);
+        }
+    }
+
+    fn search_prev(&mut self) {
+        if let Some(m) = self.search.prev() {
+            self.show_line(m.line);
+        }
+    }
+
+    /// Images fully inside the viewport, with their screen position.
+    fn visible_images(&self, area: Rect) -> Vec<(usize, u16, u16)> {
+        let top = self.buf().scroll;
+        let bottom = top + self.viewport_height as usize;
+        self.buf()
+            .images
+            .iter()
+            .enumerate()
+            .filter(|(_, img)| img.line >= top && img.line + img.rows as usize <= bottom)
+            .map(|(i, img)| (i, area.x + img.col, area.y + (img.line - top) as u16))
+            .collect()
+    }
+
+    /// The links in view in `area`, as drawn to `screen`, cut off at the
+    /// edge of the area.
+    fn visible_links(&self, area: Rect, screen: &ratatui::buffer::Buffer) -> Vec<Hyperlink> {
+        let buf = self.buf();
+        let top = buf.scroll;
+        let visible = buf.links.iter().filter(|link| self.is_visible(link.line));
+        visible
+            .filter_map(|link| {
+                let url = links::hyperlink(&buf.source, &link.url)?;
+                let content: String = buf.text.lines.get(link.line)?.spans.iter().map(|s| s.content.as_ref()).collect();
+                let shift = buf.table_shift(link.line);
+                let start = content.get(..link.start)?.width().saturating_sub(shift);
+                let end = content.get(..link.end)?.width().saturating_sub(shift).min(area.width as usize);
+                let (x, y) = (area.x + start as u16, area.y + (link.line - top) as u16);
+                let cells = (x..area.x + end as u16).map(|x| screen[(x, y)].clone()).collect::<Vec<_>>();
+                (!cells.is_empty()).then_some(Hyperlink { url, x, y, cells })
+            })
+            .collect()
+    }
+
+    fn is_visible(&self, line: usize) -> bool {
+        let top = self.buf().scroll;
+        line >= top && line < top + self.viewport_height as usize
+    }
+
+    /// Moves the active link to the next (or previous) link inside the
+    /// viewport, starting from the first visible one when none is active.
+    fn cycle_link(&mut self, forward: bool) {
+        let buf = self.buf();
+        let visible: Vec<usize> = (0..buf.links.len())
+            .filter(|&i| self.is_visible(buf.links[i].line))
+            .collect();
+        if visible.is_empty() {
+            self.buf_mut().active_link = None;
+            return;
+        }
+        let pos = buf
+            .active_link
+            .and_then(|a| visible.iter().position(|&i| i == a));
+        let next = match (pos, forward) {
+            (None, true) => 0,
+            (None, false) => visible.len() - 1,
+            (Some(p), true) => (p + 1) % visible.len(),
+            (Some(p), false) => p.checked_sub(1).unwrap_or(visible.len() - 1),
+        };
+        self.buf_mut().active_link = Some(visible[next]);
+    }
+
+    /// Index of the link under screen cell (`x`, `y`), if any.
+    fn link_at(&self, area: Rect, x: u16, y: u16) -> Option<usize> {
+        if !area.contains(ratatui::layout::Position { x, y }) {
+            return None;
+        }
+        let buf = self.buf();
+        let line_idx = buf.scroll + (y - area.y) as usize;
+        let col = (x - area.x) as usize + buf.table_shift(line_idx);
+        let line = buf.text.lines.get(line_idx)?;
+        let content: String = line.spans.iter().map(|s| s.content.as_ref()).collect();
+        buf.links.iter().position(|l| {
+            l.line == line_idx
+                && content.get(..l.start).map(UnicodeWidthStr::width).is_some_and(|s| s <= col)
+                && content.get(..l.end).map(UnicodeWidthStr::width).is_some_and(|e| col < e)
+        })
+    }
+
+    /// Scrolls so the scrollbar thumb lands on row `y` of a track `height` rows tall.
+    fn scroll_to_track(&mut self, y: u16, height: u16) {
+        let track = height.saturating_sub(1).max(1) as usize;
+        let y = (y as usize).min(track);
+        self.scroll_to(y * self.max_scroll() / track);
+    }
+
+    /// Acts on a link destination. External URLs are handed to the system
+    /// opener; a local markdown file (and anchor within it) is returned for
+    /// the caller to open.
+    fn follow_link(&mut self, url: &str) -> Option<(Source, Option<String>)> {
+        if url == fold::TOGGLE_URL {
+            self.toggle_details();
+            return None;
+        }
+        match links::resolve(&self.buf().source, url) {
+            Target::File(target, anchor) => return Some((target, anchor)),
+            Target::Anchor(anchor) => {
+                if self.buf().anchors.contains_key(&anchor) {
+                    self.history.visit(self.location());
+                }
+                self.jump_to_anchor(&anchor);
+            }
+            Target::External(url) => {
+                if let Err(e) = links::open_external(&url) {
+                    self.message = Some(format!("Cannot open {url}: {e}"));
+                }
+            }
+        }
+        None
+    }
+
+    /// Scrolls the current buffer to a heading slug or footnote anchor.
+    fn jump_to_anchor(&mut self, anchor: &str) {
+        match self.buf().anchors.get(anchor) {
+            Some(&line) => self.scroll_to(line),
+            None => self.message = Some(format!("No such anchor: #{anchor}")),
+        }
+    }
+
+    fn active_link_url(&self) -> Option<String> {
+        let buf = self.buf();
+        buf.active_link
+            .and_then(|i| buf.links.get(i))
+            .filter(|l| self.is_visible(l.line))
+            .map(|l| l.url.clone())
+    }
+
+    /// The rows of the view in a viewport `height` rows tall, from the
+    /// scroll position down, with search matches, the active link and the
+    /// selection highlighted.
+    fn display_text(&self, height: u16) -> Text<'static> {
+        let buf = self.buf();
+        let mut text = if self.search.is_active() {
+            let mut text = search::highlight(&buf.text, &self.search.matches, Some(self.search.current));
+            search::highlight_captures(&mut text, &self.search.captures);
+            text
+        } else {
+            buf.text.clone()
+        };
+        if let Some(link) = buf.active_link.and_then(|i| buf.links.get(i)) {
+            search::highlight_range(
+                &mut text,
+                link.line,
+                link.start,
+                link.end,
+                Style::default().add_modifier(Modifier::REVERSED),
+            );
+        }
+        if let Some(range) = self.selection() {
+            for line in text.lines.iter_mut().take(range.end() + 1).skip(*range.start()) {
+                line.style = line.style.add_modifier(Modifier::REVERSED);
+            }
+        }
+        for table in &buf.tables {
+            let shift = buf.table_shift(table.line);
+            if shift > 0 {
+                text.lines.iter_mut().take(table.end).skip(table.line).for_each(|line| shift_line(line, shift));
+            }
+        }
+        // Drawn from a window on the view: ratatui scrolls a paragraph by
+        // at most `u16::MAX` rows.
+        text.lines.drain(..buf.scroll.min(text.lines.len()));
+        text.lines.truncate(height as usize);
+        text
+    }
+
+    fn status_line(&self, width: u16) -> Option<Line<'static>> {
+        if let Some((msg, _)) = &self.flash {
+            let style = Style::default().fg(Color::Green).add_modifier(Modifier::BOLD);
+            return Some(Line::from(Span::styled(msg.clone(), style)));
+        }
+        if let Some(msg) = &self.message {
+            return Some(Line::from(Span::styled(msg.clone(), Style::default().fg(Color::DarkGray))));
+        }
+        match self.mode {
+            Mode::Search => {
+                let mut prompt = Line::from(format!("/{}", self.search.input));
+                if self.search.regex {
+                    prompt.push_span(Span::styled("  (regex)", Style::default().fg(Color::DarkGray)));
+                }
+                Some(prompt)
+            }
+            Mode::SetMark => Some(Line::from("m")),
+            Mode::JumpToMark => Some(Line::from("'")),
+            Mode::Fold => Some(Line::from("z")),
+            Mode::Outline | Mode::Results | Mode::Switcher | Mode::Backlinks => None,
+            Mode::Visual { .. } => {
+                let count = self.selection().map_or(0, |r| r.count());
+                let plural = if count == 1 { "" } else { "s" };
+                Some(Line::from(format!("-- VISUAL -- {count} line{plural}")))
+            }
+            Mode::Normal if self.count.is_some() => {
+                Some(Line::from(self.count.unwrap_or_default().to_string()))
+            }
+            Mode::Normal if self.status_bar => Some(self.status_bar_line(width)),
+            Mode::Normal if self.rendering() => {
+                Some(Line::from(Span::styled("Rendering…", Style::default().fg(Color::DarkGray))))
+            }
+            Mode::Normal if self.search.is_active() => {
+                let status = if let Some(error) = &self.search.error {
+                    error.clone()
+                } else if self.search.matches.is_empty() {
+                    format!("Pattern not found: {}", self.search.query)
+                } else {
+                    format!(
+                        "/{} [{}/{}]",
+                        self.search.query,
+                        self.search.current + 1,
+                        self.search.matches.len()
+                    )
+                };
+                Some(Line::from(Span::styled(status, Style::default().fg(Color::DarkGray))))
+            }
+            Mode::Normal => None,
+        }
+    }
+
+    /// `SEARCH docs/guide.md [UTF-16LE] [modified] § Setup ...... [1/2] L120  42%`
+    fn status_bar_line(&self, width: u16) -> Line<'static> {
+        let buf = self.buf();
+        let mode = if self.search.is_active() {
+            "SEARCH"
+        } else if buf.active_link.is_some() {
+            "LINK"
+        } else if self.follow {
+            "FOLLOW"
+        } else {
+            "NORMAL"
+        };
+
+        let name = match &buf.source {
+            Source::File(path) => std::env::current_dir()
+                .ok()
+                .and_then(|cwd| path.strip_prefix(cwd).ok().map(|p| p.display().to_string()))
+                .unwrap_or_else(|| path.display().to_string()),
+            Source::Url(url) => url.clone(),
+        };
+        let mut left = format!(" {mode}  {name}");
+        if let Some(revision) = &self.revision {
+            left.push_str(&format!(" [{revision}]"));
+        }
+        if let Some(encoding) = buf.encoding {
+            left.push_str(&format!(" [{encoding}]"));
+        }
+        if buf.reload_error.is_some() {
+            left.push_str(" [modified]");
+        }
+        if self.rendering() {
+            left.push_str(" [rendering…]");
+        }
+        if !buf.metadata.is_empty() {
+            left.push_str(&format!("  {}", buf.metadata.summary()));
+        }
+        for tag in &buf.tags {
+            left.push_str(&format!(" #{tag}"));
+        }
+        let top = buf.scroll;
+        let current = buf.headings.partition_point(|h| h.line <= top);
+        if let Some(heading) = current.checked_sub(1).map(|i| &buf.headings[i]) {
+            left.push_str(&format!("  § {}", heading.title));
+        }
+
+        let mut right = String::new();
+        if self.search.is_active() {
+            right.push_str(&format!(
+                "/{} [{}/{}]  ",
+                self.search.query,
+                (self.search.current + 1).min(self.search.matches.len()),
+                self.search.matches.len()
+            ));
+        }
+        if !buf.marks.is_empty() {
+            let marks: String = buf.marks.keys().collect();
+            right.push_str(&format!("'{marks}  "));
+        }
+        if self.buffers.len() > 1 {
+            right.push_str(&format!("[{}/{}] ", self.current + 1, self.buffers.len()));
+        }
+        if let Some(slide) = buf.slide {
+            right.push_str(&format!("Slide {}/{}  ", slide + 1, buf.slide_count()));
+        }
+        if buf.renderer.is_some() {
+            right.push_str(&format!("L{}  ", self.top_source_line() + 1));
+        }
+        let max = self.max_scroll();
+        right.push_str(&match buf.scroll {
+            _ if max == 0 => "All ".to_string(),
+            0 => "Top ".to_string(),
+            s if s >= max => "Bot ".to_string(),
+            s => format!("{}% ", s * 100 / max),
+        });
+
+        let room = (width as usize).saturating_sub(right.width());
+        let mut left_width = left.width();
+        if left_width > room {
+            while left_width + 1 > room && left.pop().is_some() {
+                left_width = left.width();
+            }
+            left.push('…');
+            left_width += 1;
+        }
+        let pad = " ".repeat(room.saturating_sub(left_width));
+        Line::from(Span::styled(
+            format!("{left}{pad}{right}"),
+            Style::default().add_modifier(Modifier::REVERSED),
+        ))
+    }
+
+    /// The rows of a view `height` rows tall showing a block changed since
+    /// the `--diff` revision, with how it changed.
+    fn changed_rows(&self, height: u16) -> Vec<(u16, Change)> {
+        let buf = self.buf();
+        (0..height)
+            .filter_map(|y| {
+                let line = buf.projection.line(buf.scroll + y as usize);
+                let (_, change) = buf.gutter.iter().find(|(lines, _)| lines.contains(&line))?;
+                Some((y, *change))
+            })
+            .collect()
+    }
+
+    fn toggle_status_bar(&mut self) {
+        self.status_bar = !self.status_bar;
+    }
+
+    /// The line of the file, from 0, shown at the top of the view.
+    fn top_source_line(&self) -> usize {
+        let buf = self.buf();
+        let top = buf.renderer.as_ref().map_or(0, |r| r.source_line(buf.top_line()));
+        buf.lines_before_shown() + top
+    }
+
+    /// The source pane's text for a pane `height` rows tall, starting at the
+    /// source line shown at the top of the rendered view.
+    fn source_text(&self, height: u16, tab_size: usize) -> Text<'static> {
+        let buf = self.buf();
+        let top = buf.renderer.as_ref().map_or(0, |r| r.source_line(buf.top_line()));
+        source::source_text(buf.shown(), top, height as usize, tab_size)
+    }
+}
+
+fn main() -> Result<ExitCode> {
+    let cli = Cli::parse_args(std::env::args_os());
+    if let Some(path) = &cli.debug {
+        logging::init(path)?;
+    }
+    match cli.command.unwrap_or(Command::View(cli.view)) {
+        Command::View(args) => view(args),
+        Command::Dump(args) => dump(args),
+        Command::Export(args) => export(args).map(|()| ExitCode::SUCCESS),
+        Command::Lint(args) => lint(args),
+    }
+}
+
+/// Loads the config, applies the command-line overrides (`--force` lifting
+/// `max_file_size`) and resolves the theme, `auto` by the terminal
+/// background, and the color depth.
+fn load_config(args: &RenderArgs) -> Result<(Config, Theme)> {
+    let mut config = Config::load(args.config.as_deref())?;
+    if let Some(w) = args.width {
+        config.width = Some(w);
+    }
+    if let Some(w) = args.max_width {
+        config.max_width = Some(w);
+    }
+    if let Some(m) = args.margin {
+        config.margin = m;
+    }
+    if let Some(t) = &args.theme {
+        config.theme = t.clone();
+    }
+    if let Some(t) = &args.syntax_theme {
+        config.syntax_theme = Some(t.clone());
+    }
+    if let Some(t) = args.tab_size {
+        config.tab_size = t;
+    }
+    config.line_numbers |= args.line_numbers;
+    config.language_labels |= args.language_labels;
+    config.bidi |= args.bidi;
+    if let Some(language) = args.hyphenate {
+        config.hyphenate = Some(language);
+    }
+    config.show_invisibles |= args.show_invisibles;
+    config.number_headings |= args.number_headings;
+    config.ascii |= args.ascii;
+    if let Some(style) = args.link_style {
+        config.link_style = style;
+    }
+    config.color_depth = args.color_depth.unwrap_or(config.color_depth).resolve();
+    if args.force {
+        config.max_file_size = config::FileSize(u64::MAX);
+    }
+    if let Some(dir) = config::syntaxes_dir() {
+        mdview::use_syntaxes(dir, config::cache_dir());
+    }
+
+    if config.theme.eq_ignore_ascii_case("auto") {
+        let light = terminal::background() == Some(terminal::Background::Light);
+        config.theme = if light { theme::LIGHT.name } else { theme::DARK.name }.to_string();
+    }
+    let theme = theme::by_name(&config.theme).with_context(|| {
+        let names: Vec<&str> = theme::THEMES.iter().map(|t| t.name).collect();
+        format!("Unknown theme '{}' (available: auto, {})", config.theme, names.join(", "))
+    })?;
+    Ok((config, theme))
+}
+
+/// Writes each file rendered, or with `--grep` the blocks matching, then
+/// exits 1 if none did. With `--watch`, writes them again whenever a file
+/// changes, until interrupted.
+fn dump(args: DumpArgs) -> Result<ExitCode> {
+    let (config, theme) = load_config(&args.render)?;
+    let mut files = open_files(&args.files, config.max_file_size)?;
+    if !args.watch {
+        let (out, found) = dump_files(&args, &config, theme, &files)?;
+        pager::write(&out, args.pager)?;
+        return Ok(if found { ExitCode::SUCCESS } else { ExitCode::FAILURE });
+    }
+
+    // Fetched documents are written once; there is no file to watch.
+    let (tx, rx) = mpsc::channel();
+    let mut watchers = Vec::new();
+    for (i, (from, source)) in files.iter().enumerate() {
+        if let Source::File(path) = from {
+            let mut watcher = watch::setup(path, tx.clone())?;
+            watcher.set_includes(&source.includes);
+            watchers.push((i, watcher));
+        }
+    }
+    loop {
+        // A file that does not render now may after the next save.
+        match dump_files(&args, &config, theme, &files) {
+            Ok((mut out, _)) => {
+                if args.clear {
+                    out.splice(0..0, *b"\x1b[H\x1b[2J");
+                }
+                pager::write(&out, false)?;
+            }
+            Err(e) => eprintln!("Error: {e:#}"),
+        }
+        let changed: BTreeSet<Source> = std::iter::once(rx.recv()?).chain(rx.try_iter()).collect();
+        for (i, watcher) in &mut watchers {
+            let (from, source) = &mut files[*i];
+            if !changed.contains(from) {
+                continue;
+            }
+            match read_source(from, config.max_file_size) {
+                Ok(new) => {
+                    watcher.set_includes(&new.includes);
+                    *source = new;
+                }
+                Err(e) => eprintln!("Error: {e:#}"),
+            }
+        }
+        if !args.clear {
+            println!();
+        }
+    }
+}
+
+/// The output of `dump` for `files`, and whether `--grep` found anything.
+fn dump_files(args: &DumpArgs, config: &Config, theme: Theme, files: &[(Source, Expanded)]) -> Result<(Vec<u8>, bool)> {
+    let options = render_options(config, theme, dump_width(config));
+    let color = args.color.enabled().then_some(config.color_depth);
+    let bodies = files
+        .iter()
+        .map(|(from, source)| match &args.section {
+            Some(heading) => Ok(&source.text[find_section(&source.text, heading, from)?]),
+            None => Ok(source.text.as_str()),
+        })
+        .collect::<Result<Vec<_>>>()?;
+    // Written whole at the end, when it is known whether it fits on screen.
+    let mut out = Vec::new();
+    let Some(pattern) = &args.grep else {
+        for (i, ((from, _), body)) in files.iter().zip(&bodies).enumerate() {
+            if i > 0 {
+                writeln!(out)?;
+            }
+            // Local images are read for their sizes and half blocks.
+            let doc = match from {
+                Source::File(path) => {
+                    render_document(body, &options.clone().base_dir(path.parent().map(Path::to_path_buf)))
+                }
+                Source::Url(_) => render_document(body, &options.clone().block_images(None)),
+            };
+            let links = match config.link_style {
+                LinkStyle::Osc8 => hyperlinks(from, doc.links),
+                _ => Vec::new(),
+            };
+            write_ansi(&mut out, &doc.text, &links, config.margin, color)?;
+        }
+        return Ok((out, true));
+    };
+
+    let matcher = search::Matcher::new(pattern, false)?;
+    let mut found = false;
+    for (path, body) in args.files.iter().zip(bodies) {
+        let text = mdview::grep::filter(body, &options, |block| !matcher.find(block).0.is_empty());
+        if text.lines.is_empty() {
+            continue;
+        }
+        if found {
+            writeln!(out)?;
+        }
+        found = true;
+        let (matches, captures) = matcher.find(&text);
+        let mut text = search::highlight(&text, &matches, None);
+        search::highlight_captures(&mut text, &captures);
+        // Like grep, name the file each match is from when there are several.
+        if files.len() > 1 {
+            let style = Style::default().fg(Color::Magenta).add_modifier(Modifier::BOLD);
+            text.lines.insert(0, Line::styled(path.display().to_string(), style));
+            text.lines.insert(1, Line::default());
+        }
+        write_ansi(&mut out, &text, &[], config.margin, color)?;
+    }
+    Ok((out, found))
+}
+
+fn export(args: ExportArgs) -> Result<()> {
+    let (config, theme) = load_config(&args.render)?;
+    let files = open_files(&args.files, config.max_file_size)?;
+    let mut out = Vec::new();
+    match args.format {
+        Format::Html => {
+            anyhow::ensure!(files.len() == 1, "HTML export takes a single file");
+            let (from, source) = &files[0];
+            let title = from.name();
+            let html = export::render_html(&source.text, &render_options(&config, theme, u16::MAX), &title);
+            out.write_all(html.as_bytes())?;
+        }
+        Format::Text => {
+            let options = render_options(&config, theme, dump_width(&config));
+            let margin = " ".repeat(config.margin as usize);
+            for (i, (_, source)) in files.iter().enumerate() {
+                if i > 0 {
+                    writeln!(out)?;
+                }
+                for line in export::render_text(&source.text, &options).lines() {
+                    match line.is_empty() {
+                        true => writeln!(out)?,
+                        false => writeln!(out, "{margin}{line}")?,
+                    }
+                }
+            }
+        }
+        Format::Json => {
+            anyhow::ensure!(files.len() == 1, "JSON export takes a single file");
+            let json = export::render_json(&files[0].1.text, &render_options(&config, theme, u16::MAX));
+            out.write_all(json.as_bytes())?;
+        }
+        Format::Pdf => {
+            anyhow::ensure!(files.len() == 1, "PDF export takes a single file");
+            anyhow::ensure!(!io::stdout().is_terminal(), "PDF output is binary; redirect it to a file");
+            let (from, source) = &files[0];
+            let title = from.name();
+            // A page holds 80 columns unless the width says otherwise.
+            let columns = match config.width {
+                Some(Width::Columns(w)) => w,
+                _ => 80,
+            };
+            let options = render_options(&config, theme, columns.saturating_add(config.margin.saturating_mul(2)));
+            out.write_all(&export::render_pdf(&source.text, &options, &title))?;
+        }
+        Format::Man => {
+            let mut options =
+                ManOptions::new(content_width(&config, dump_width(&config))).tab_size(config.tab_size);
+            if let Some(indent) = args.man_indent {
+                options = options.indent(indent);
+            }
+            let color = args.color.enabled().then_some(config.color_depth);
+            for (i, (_, source)) in files.iter().enumerate() {
+                if i > 0 {
+                    writeln!(out)?;
+                }
+                write_ansi(&mut out, &render_man(&source.text, &options), &[], config.margin, color)?;
+            }
+        }
+    }
+    pager::write(&out, false)
+}
+
+/// Prints each finding as `path:line: message (rule)`, compiler style, and
+/// exits 1 if there were any.
+fn lint(args: LintArgs) -> Result<ExitCode> {
+    let mut found = false;
+    for path in &args.files {
+        // Lint reads no config, so the default limit holds.
+        let input = read_file(path, config::FileSize::DEFAULT)?.text;
+        let mut out = Vec::new();
+        for finding in mdview::lint::lint(&input, path) {
+            found = true;
+            writeln!(out, "{}:{}: {} ({})", path.display(), finding.line, finding.message, finding.rule)?;
+        }
+        pager::write(&out, false)?;
+    }
+    Ok(if found { ExitCode::FAILURE } else { ExitCode::SUCCESS })
 }
 
-impl App {
-    fn max_scroll(&self) -> u16 {
-        let content_height = (self.text.height() as u32).min(u16::MAX as u32) as u16;
-        content_height.saturating_sub(self.viewport_height)
-    }
+/// Puts the terminal in raw mode on the alternate screen with mouse
+/// capture, until the returned guard is dropped.
+fn enter_terminal() -> Result<(TerminalGuard, Terminal<CrosstermBackend<io::Stdout>>)> {
+    enable_raw_mode()?;
+    // Where the terminal can report them, `C-i` and `Tab` (and `C-m` and
+    // `Enter`) arrive as different keys.
+    let keyboard_enhanced = supports_keyboard_enhancement().unwrap_or(false)
+        && io::stdout()
+            .execute(PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::DISAMBIGUATE_ESCAPE_CODES))
+            .is_ok();
+    let guard = TerminalGuard { keyboard_enhanced };
+    io::stdout().execute(EnterAlternateScreen)?;
+    io::stdout().execute(EnableMouseCapture)?;
+    let terminal = Terminal::new(CrosstermBackend::new(io::stdout()))?;
+    Ok((guard, terminal))
+}
 
-    fn scroll_down(&mut self, n: u16) {
-        self.scroll = self.scroll.saturating_add(n).min(self.max_scroll());
+/// Runs the interactive viewer. Quitting with `quit_with_error` exits 1,
+/// so scripts can tell it apart from a normal quit.
+fn view(mut args: ViewArgs) -> Result<ExitCode> {
+    let goto_line = args.take_goto_line();
+    let mut current = 0;
+    let mut sources: Vec<Source> = match args.last {
+        true => {
+            let session = Session::load()?;
+            current = session.current;
+            session.files
+        }
+        false => args.files.iter().cloned().map(Source::from).collect(),
+    };
+    sources.extend(args.url.iter().map(|url| Source::Url(fetch::with_scheme(url))));
+    let vault = args.vault.as_deref().map(Vault::open).transpose()?.map(Arc::new);
+    if let Some(vault) = &vault {
+        vault::use_vault(Some(vault.clone()));
+        // A vault alone opens at the note last written.
+        if sources.is_empty() {
+            let notes = vault.notes().iter().map(|note| vault.root().join(note));
+            let latest = notes.max_by_key(|path| path.metadata().and_then(|m| m.modified()).ok());
+            sources.extend(latest.map(Source::File));
+        }
+        anyhow::ensure!(!sources.is_empty(), "No notes in vault {}", vault.root().display());
     }
-
-    fn scroll_up(&mut self, n: u16) {
-        self.scroll = self.scroll.saturating_sub(n);
+    anyhow::ensure!(!sources.is_empty() || args.diff_files.is_some(), "No file to open");
+    let (mut config, theme) = load_config(&args.render)?;
+    config.status_bar |= args.status_bar;
+    if let Some(mode) = args.images {
+        config.images = mode;
     }
-
-    fn clamp_scroll(&mut self) {
-        self.scroll = self.scroll.min(self.max_scroll());
+    if let Some(paths) = &args.diff_files {
+        let limit = config.max_file_size;
+        let paths = [canonical(&paths[0])?, canonical(&paths[1])?];
+        let keymap = Keymap::new(&config.keys).context("Invalid [keys] in config")?;
+        let (_guard, mut terminal) = enter_terminal()?;
+        let read = |path: &Path| read_source(&Source::File(path.to_path_buf()), limit).map(|source| source.text);
+        let options = |width| render_options(&config, theme, width);
+        return compare::run(&mut terminal, &keymap, paths, read, options, config.color_depth);
     }
-}
-
-fn main() -> Result<()> {
-    let args: Vec<String> = std::env::args().collect();
-
-    if args.iter().any(|a| a == "--version" || a == "-v") {
-        println!("mdview {}", env!("CARGO_PKG_VERSION"));
-        return Ok(());
+    let follow = args.follow;
+    let slides = args.slides;
+    let poll = args.poll.map(Duration::from_secs);
+    let revision = args.revision();
+    if let Some(git::Revision::Diff(_)) = revision {
+        // Room for the gutter.
+        config.margin = config.margin.max(2);
     }
-
-    let dump = args.iter().any(|a| a == "--dump");
-    let width_override = args.iter()
-        .position(|a| a == "-w" || a == "--width")
-        .and_then(|i| args.get(i + 1))
-        .and_then(|v| v.parse::<u16>().ok());
-    let skip_args: Vec<&str> = ["-w", "--width"].into();
-    let mut skip_next = false;
-    let path = args
-        .iter()
-        .skip(1)
-        .filter(|a| {
-            if skip_next { skip_next = false; return false; }
-            if skip_args.contains(&a.as_str()) { skip_next = true; return false; }
-            !a.starts_with('-')
-        })
-        .next()
-        .map(PathBuf::from)
-        .context("Usage: mdview [--dump] [-w WIDTH] <file.md>")?;
-
-    let path = path
-        .canonicalize()
-        .with_context(|| format!("Cannot resolve path: {}", path.display()))?;
-
-    let meta = std::fs::metadata(&path)
-        .with_context(|| format!("Cannot stat {}", path.display()))?;
-    anyhow::ensure!(
-        meta.len() <= MAX_FILE_SIZE,
-        "File too large ({} bytes, max {} bytes)",
-        meta.len(),
-        MAX_FILE_SIZE
-    );
-
-    let mut content = std::fs::read_to_string(&path)
-        .with_context(|| format!("Cannot read {}", path.display()))?;
-
-    if dump {
-        return dump_text(&content, width_override);
+    // Slides always start at the first one, and lines of a revision or diff
+    // are not those of the file.
+    let restore = !args.no_restore && !slides && revision.is_none();
+    let files = sources.iter().map(|s| open_file(s.clone(), config.max_file_size)).collect::<Result<Vec<_>>>()?;
+    if args.stats {
+        print_stats(&sources, &files)?;
+        return Ok(ExitCode::SUCCESS);
+    }
+    if args.outline {
+        print_outline(&sources, &files)?;
+        return Ok(ExitCode::SUCCESS);
+    }
+    if args.bench {
+        print_bench(&sources, &files, &render_options(&config, theme, dump_width(&config)))?;
+        return Ok(ExitCode::SUCCESS);
     }
 
-    enable_raw_mode()?;
-    let _guard = TerminalGuard;
-    io::stdout().execute(EnterAlternateScreen)?;
-    let backend = CrosstermBackend::new(io::stdout());
-    let mut terminal = Terminal::new(backend)?;
+    let keymap = Keymap::new(&config.keys).context("Invalid [keys] in config")?;
+    let (guard, mut terminal) = enter_terminal()?;
 
     let size = terminal.size()?;
+    let protocol = config.images.protocol();
+    let image_options = protocol.map(|_| ImageOptions {
+        cell_size: images::cell_size(),
+        max_rows: size.height.saturating_sub(2).max(1),
+    });
+    let options_for = |source: &Source, width: u16| {
+        let width = match slides {
+            true => width.saturating_sub(SLIDE_PADDING.horizontal.saturating_mul(2)),
+            false => width,
+        };
+        // Left and Right turn slides, so slides squeeze their tables.
+        let overflow = if slides { TableOverflow::Wrap } else { config.table_overflow };
+        // The images of a fetched document are not fetched with it, so they
+        // stay placeholders.
+        let options = render_options(&config, theme, width).table_overflow(overflow);
+        match source {
+            Source::File(path) => options.images(image_options).base_dir(path.parent().map(Path::to_path_buf)),
+            Source::Url(_) => options.block_images(None),
+        }
+    };
+    let mut image_cache = images::Cache::default();
+
+    let (tx, rx) = mpsc::channel();
     let mut render_width = size.width;
+    let state = State::load();
+    // Files given on the command line open at `--section`; those links lead
+    // to open whole.
+    let open_buffer = |from: Source, source: Expanded, width: u16, section: Option<&str>| -> Result<Buffer> {
+        let (source, changes) = match &revision {
+            Some(_) => read_version(&from, revision.as_ref(), config.max_file_size)?,
+            None => (source, Vec::new()),
+        };
+        let section = match section {
+            Some(heading) => Some((heading.to_string(), find_section(&source.text, heading, &from)?)),
+            None => None,
+        };
+        let body = section.as_ref().map_or(source.text.as_str(), |(_, range)| &source.text[range.clone()]);
+        let watcher = match (&from, poll) {
+            (Source::File(path), _) => {
+                let mut watcher = watch::setup(path, tx.clone())?;
+                watcher.set_includes(&source.includes);
+                watch::Watcher::File(watcher)
+            }
+            (Source::Url(_), Some(every)) => {
+                let url = from.clone();
+                let limit = config.max_file_size;
+                let fetch = move || read_source(&url, limit).map(|source| source.text);
+                let poller = watch::poll(from.clone(), source.text.clone(), every, tx.clone(), fetch);
+                watch::Watcher::Poll { _poller: poller }
+            }
+            (Source::Url(_), None) => watch::Watcher::Off,
+        };
+        // Positions past the end are clamped once the full render is in.
+        let (scroll, marks) = match state.get(&from) {
+            Some(saved) if restore => {
+                let marks = saved
+                    .marks
+                    .iter()
+                    .filter_map(|(m, &line)| Some((m.chars().next()?, line)))
+                    .collect();
+                (saved.scroll, marks)
+            }
+            _ => (0, BTreeMap::new()),
+        };
+        // Only the first screens are rendered here, so even a huge file opens
+        // at once; the caller queues the full render.
+        let mut renderer = IncrementalRenderer::new(options_for(&from, width));
+        let slide = slides.then_some(0);
+        let lines = scroll + 2 * size.height as usize;
+        let doc = renderer.render_head(shown_source(body, slide), lines);
+        let mut buffer = Buffer {
+            watcher,
+            source: from,
+            metadata: frontmatter::metadata(&source.text),
+            tags: tags::tags(&source.text),
+            encoding: source.encoding,
+            content: source.text,
+            renderer: Some(renderer),
+            pending: PendingRender::default(),
+            doc,
+            folds: BTreeSet::new(),
+            opened: BTreeSet::new(),
+            projection: fold::Projection::default(),
+            text: Text::default(),
+            links: Vec::new(),
+            images: Vec::new(),
+            headings: Vec::new(),
+            anchors: HashMap::new(),
+            tables: Vec::new(),
+            table_scroll: HashMap::new(),
+            active_link: None,
+            scroll,
+            marks,
+            slide,
+            section,
+            jump_to: None,
+            goto_line: None,
+            reload_error: None,
+            render_error: None,
+            changes,
+            gutter: Vec::new(),
+        };
+        buffer.fold_closed_details();
+        buffer.refold();
+        Ok(buffer)
+    };
+    // Re-renders every buffer for a terminal `width` columns wide, after it
+    // was resized or the split view toggled.
+    let rerender = |app: &mut App, width: u16| {
+        let width = preview_width(width, app.split);
+        for i in 0..app.buffers.len() {
+            let buf = &mut app.buffers[i];
+            buf.pending.options = Some(options_for(&buf.source, width));
+            app.request_render(i);
+        }
+    };
     let mut app = App {
-        text: render_markdown(&content, render_width),
-        scroll: 0,
+        buffers: files
+            .into_iter()
+            .map(|(path, source)| open_buffer(path, source, render_width, args.section.as_deref()))
+            .collect::<Result<_>>()?,
+        current,
+        drawn_images: Vec::new(),
+        drawn_links: Vec::new(),
         viewport_height: size.height,
+        mode: Mode::Normal,
+        search: Search::default(),
+        outline: Outline::default(),
+        results: Results::default(),
+        message: None,
+        follow,
+        status_bar: config.status_bar,
+        split: false,
+        flash: None,
+        worker: worker::spawn(),
+        history: History::default(),
+        count: None,
+        info: None,
+        revision: revision.clone(),
+        max_file_size: config.max_file_size,
+        tag_index: TagIndex::default(),
+        vault,
+        switcher: Switcher::default(),
+        backlinks: Backlinks::default(),
+        clipboard: clipboard::Clipboard::default(),
     };
+    if let Some(vault) = &app.vault {
+        app.tag_index.update(vault);
+    }
+    app.buffers[0].goto_line = goto_line.map(|line| line.saturating_sub(1));
+    for i in 0..app.buffers.len() {
+        app.request_render(i);
+    }
+    if follow {
+        app.scroll_to(usize::MAX);
+    }
 
-    let (tx, rx) = mpsc::channel();
-    let _watcher = watch::setup(&path, tx)?;
-
+    let mut exit = ExitCode::SUCCESS;
     loop {
-        app.viewport_height = terminal.size()?.height;
+        let status = app.status_line(terminal.size()?.width);
+        let size = terminal.size()?;
+        let rows = size.height.saturating_sub(u16::from(status.is_some()));
+        let (source_area, preview_area) = if app.split {
+            let left = size.width / 2;
+            let right = preview_width(size.width, true);
+            (Some(Rect::new(0, 0, left, rows)), Rect::new(size.width - right, 0, right, rows))
+        } else {
+            (None, Rect::new(0, 0, size.width, rows))
+        };
+        let mut text_area = match app.buf().slide {
+            Some(_) => app.slide_area(preview_area),
+            None => {
+                let inner = preview_area.width.saturating_sub(config.margin.saturating_mul(2));
+                // Content capped by `max_width` sits in the middle; `width`
+                // alone keeps it at the left margin.
+                let (indent, width) = match config.max_width {
+                    Some(_) => {
+                        let width = content_width(&config, preview_area.width);
+                        ((inner - width) / 2, width)
+                    }
+                    None => (0, inner),
+                };
+                Rect {
+                    x: preview_area.x + config.margin.min(preview_area.width) + indent,
+                    y: 0,
+                    width,
+                    height: rows,
+                }
+            }
+        };
+        // The view starts below an error banner.
+        let banner = app.error_banner().map_or_else(Vec::new, |message| banner_rows(&message, preview_area.width));
+        let banner_area = Rect { height: (banner.len() as u16).min(rows), ..preview_area };
+        let bottom = text_area.bottom();
+        text_area.y = text_area.y.max(banner_area.height).min(bottom);
+        text_area.height = bottom - text_area.y;
+        app.viewport_height = text_area.height;
+
+        // Images would be drawn over the popup.
+        let popup = app.info.is_some()
+            || matches!(app.mode, Mode::Outline | Mode::Results | Mode::Switcher | Mode::Backlinks);
+        let visible_images = match popup {
+            true => Vec::new(),
+            false => app.visible_images(text_area),
+        };
+        if visible_images != app.drawn_images && protocol.is_some_and(|p| p != images::Protocol::Kitty) {
+            // Sixel and iTerm2 images live in the cell grid; clear so ratatui
+            // repaints over the stale ones.
+            terminal.clear()?;
+            app.drawn_links.clear();
+        }
+
+        let frame = terminal.draw(|f| {
+            if let Some(status) = status {
+                let status_area = Rect::new(0, rows, size.width, 1);
+                f.render_widget(Paragraph::new(status), status_area);
+            }
 
-        terminal.draw(|f| {
-            let area = f.area();
+            if let Some(area) = source_area {
+                let block = Block::default()
+                    .borders(Borders::RIGHT)
+                    .border_style(Style::default().fg(Color::DarkGray));
+                let source = Paragraph::new(app.source_text(rows, config.tab_size)).block(block);
+                f.render_widget(source, Rect { width: area.width + 1, ..area });
+            }
+
+            let paragraph = Paragraph::new(app.display_text(text_area.height));
 
-            let paragraph = Paragraph::new(app.text.clone())
-                .wrap(Wrap { trim: false })
-                .scroll((app.scroll, 0));
+            f.render_widget(paragraph, text_area);
+            if !banner.is_empty() {
+                let style = Style::default().fg(Color::White).bg(Color::Red).add_modifier(Modifier::BOLD);
+                let lines: Vec<Line> = banner.iter().map(|row| Line::from(row.as_str())).collect();
+                f.render_widget(Paragraph::new(lines).style(style), banner_area);
+            }
 
-            f.render_widget(paragraph, area);
+            let gutter = text_area.x.saturating_sub(2).max(preview_area.x);
+            for (y, change) in app.changed_rows(text_area.height) {
+                let color = match change {
+                    Change::Added => Color::Green,
+                    Change::Removed => Color::Red,
+                };
+                let mark = Paragraph::new(Span::styled("▌", Style::default().fg(color)));
+                f.render_widget(mark, Rect::new(gutter, text_area.y + y, 1, 1));
+            }
 
             let max = app.max_scroll();
             if max > 0 {
-                render_scrollbar(f, area, app.scroll, max);
+                render_scrollbar(f, Rect { height: rows, ..f.area() }, app.buf().scroll, max);
+            }
+
+            if let Some(stats) = &app.info {
+                render_info(f, Rect { height: rows, ..f.area() }, &app.buf().name(), stats);
+            }
+            if app.mode == Mode::Outline {
+                render_outline(f, Rect { height: rows, ..f.area() }, &app.outline, &app.buf().doc.headings);
+            }
+            if app.mode == Mode::Results {
+                render_results(f, Rect { height: rows, ..f.area() }, &app.results, &app.buf().doc);
             }
+            if app.mode == Mode::Switcher {
+                render_switcher(f, Rect { height: rows, ..f.area() }, &app.switcher);
+            }
+            if app.mode == Mode::Backlinks {
+                render_backlinks(f, Rect { height: rows, ..f.area() }, &app.backlinks, &app.buf().name());
+            }
+            palette::quantize_buffer(f.buffer_mut(), config.color_depth);
         })?;
 
-        if rx.try_recv().is_ok() {
-            while rx.try_recv().is_ok() {}
-            let size_ok = std::fs::metadata(&path)
-                .map(|m| m.len() <= MAX_FILE_SIZE)
-                .unwrap_or(false);
-            if size_ok {
-                if let Ok(new_content) = std::fs::read_to_string(&path) {
-                    content = new_content;
-                    render_width = terminal.size()?.width;
-                    app.text = render_markdown(&content, render_width);
-                    app.clamp_scroll();
+        // ratatui has no hyperlinks, so the cells of each link are drawn
+        // again between the escapes that open and close one. Cells it
+        // redraws later lose the link, and differ from the ones kept.
+        let visible_links = match config.link_style == LinkStyle::Osc8 && !popup {
+            true => app.visible_links(text_area, frame.buffer),
+            false => Vec::new(),
+        };
+        if visible_links != app.drawn_links {
+            let backend = terminal.backend_mut();
+            for link in visible_links.iter().filter(|link| !app.drawn_links.contains(link)) {
+                write!(backend, "\x1b]8;;{}\x1b\\", link.url)?;
+                let cells = link.cells.iter().enumerate().map(|(i, cell)| (link.x + i as u16, link.y, cell));
+                ratatui::backend::Backend::draw(backend, cells)?;
+                write!(backend, "\x1b]8;;\x1b\\")?;
+            }
+            backend.flush()?;
+            app.drawn_links = visible_links;
+        }
+
+        if let Some(protocol) = protocol
+            && visible_images != app.drawn_images
+        {
+            let mut out = io::stdout().lock();
+            if protocol == images::Protocol::Kitty {
+                images::clear_kitty(&mut out)?;
+            }
+            for &(i, x, y) in &visible_images {
+                let img = &app.buf().images[i];
+                if let Some(encoded) =
+                    image_cache.get(protocol, &img.path, img.cols, img.rows, images::cell_size())
+                {
+                    images::draw(&mut out, x, y, encoded)?;
+                }
+            }
+            out.flush()?;
+            app.drawn_images = visible_images;
+        }
+
+        let rendered: Vec<worker::Done> = app.worker.done.try_iter().collect();
+        for done in rendered {
+            app.finish_render(done);
+        }
+
+        let changed: Vec<Source> = rx.try_iter().collect();
+        if !changed.is_empty() {
+            tracing::debug!(?changed, "files changed");
+            image_cache.clear();
+            changed.iter().filter_map(Source::file).for_each(|path| app.tag_index.refresh(path));
+            for i in 0..app.buffers.len() {
+                if changed.contains(&app.buffers[i].source) {
+                    let _ = app.reload(i);
                 }
             }
         }
+        if app.flash.as_ref().is_some_and(|(_, at)| at.elapsed() >= FLASH_DURATION) {
+            app.flash = None;
+        }
 
         if event::poll(Duration::from_millis(50))? {
-            match event::read()? {
-                Event::Key(key) => match key.code {
-                    KeyCode::Char('q') | KeyCode::Esc => break,
+            let mut open_target = None;
+            // The source line to show once `open_target` is open.
+            let mut open_line = None;
+            let event = event::read()?;
+            if !matches!(&event, Event::Mouse(mouse) if mouse.kind == MouseEventKind::Moved) {
+                tracing::debug!(?event, "input");
+            }
+            // Any key but another digit uses up the count.
+            let mut count = None;
+            if matches!(event, Event::Key(_)) {
+                app.message = None;
+                app.info = None;
+                count = app.count.take();
+            }
+            let times = count.unwrap_or(1);
+            match event {
+                Event::Key(key) if app.mode == Mode::Search => match key.code {
+                    KeyCode::Esc => {
+                        app.mode = Mode::Normal;
+                        app.search.input.clear();
+                    }
+                    KeyCode::Enter => app.submit_search(),
+                    KeyCode::Backspace => {
+                        let popped = app.search.input.pop();
+                        if popped.is_none() {
+                            app.mode = Mode::Normal;
+                        }
+                    }
                     KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-                        break
+                        app.mode = Mode::Normal;
+                        app.search.input.clear();
+                    }
+                    KeyCode::Char('r') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                        app.search.regex = !app.search.regex
+                    }
+                    KeyCode::Char(c) => app.search.input.push(c),
+                    _ => {}
+                },
+                Event::Key(key) if app.mode == Mode::Outline => {
+                    let count = app.outline.matches(&app.buf().doc.headings).len();
+                    let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+                    match key.code {
+                        KeyCode::Esc => app.mode = Mode::Normal,
+                        KeyCode::Char('c') if ctrl => app.mode = Mode::Normal,
+                        KeyCode::Enter => app.submit_outline(),
+                        KeyCode::Up => app.outline.select(-1, count),
+                        KeyCode::Char('p' | 'k') if ctrl => app.outline.select(-1, count),
+                        KeyCode::Down | KeyCode::Tab => app.outline.select(1, count),
+                        KeyCode::Char('n' | 'j') if ctrl => app.outline.select(1, count),
+                        KeyCode::BackTab => app.outline.select(-1, count),
+                        KeyCode::Backspace => {
+                            if app.outline.input.pop().is_none() {
+                                app.mode = Mode::Normal;
+                            }
+                            app.outline.selected = 0;
+                        }
+                        KeyCode::Char(c) if !ctrl => {
+                            app.outline.input.push(c);
+                            app.outline.selected = 0;
+                        }
+                        _ => {}
+                    }
+                }
+                Event::Key(key) if app.mode == Mode::Results => {
+                    let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+                    match key.code {
+                        KeyCode::Esc => app.mode = Mode::Normal,
+                        KeyCode::Char('c') if ctrl => app.mode = Mode::Normal,
+                        KeyCode::Enter => app.submit_results(),
+                        KeyCode::Char('r') if ctrl => {
+                            app.results.regex = !app.results.regex;
+                            app.results.update(&app.buffers[app.current].doc.text);
+                        }
+                        KeyCode::Up => app.results.select(-1),
+                        KeyCode::Char('p' | 'k') if ctrl => app.results.select(-1),
+                        KeyCode::Down | KeyCode::Tab => app.results.select(1),
+                        KeyCode::Char('n' | 'j') if ctrl => app.results.select(1),
+                        KeyCode::BackTab => app.results.select(-1),
+                        KeyCode::Backspace => {
+                            if app.results.input.pop().is_none() {
+                                app.mode = Mode::Normal;
+                            }
+                            app.results.selected = 0;
+                            app.results.update(&app.buffers[app.current].doc.text);
+                        }
+                        KeyCode::Char(c) if !ctrl => {
+                            app.results.input.push(c);
+                            app.results.selected = 0;
+                            app.results.update(&app.buffers[app.current].doc.text);
+                        }
+                        _ => {}
+                    }
+                }
+                Event::Key(key) if app.mode == Mode::Switcher => {
+                    let count = app.switcher.matches().len();
+                    let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+                    match key.code {
+                        KeyCode::Esc => app.mode = Mode::Normal,
+                        KeyCode::Char('c') if ctrl => app.mode = Mode::Normal,
+                        KeyCode::Enter => open_target = app.submit_switcher().map(|note| (note, None)),
+                        KeyCode::Up => app.switcher.select(-1, count),
+                        KeyCode::Char('p' | 'k') if ctrl => app.switcher.select(-1, count),
+                        KeyCode::Down | KeyCode::Tab => app.switcher.select(1, count),
+                        KeyCode::Char('n' | 'j') if ctrl => app.switcher.select(1, count),
+                        KeyCode::BackTab => app.switcher.select(-1, count),
+                        KeyCode::Backspace => {
+                            if app.switcher.input.pop().is_none() {
+                                app.mode = Mode::Normal;
+                            }
+                            app.switcher.selected = 0;
+                        }
+                        KeyCode::Char(c) if !ctrl => {
+                            app.switcher.input.push(c);
+                            app.switcher.selected = 0;
+                        }
+                        _ => {}
+                    }
+                }
+                Event::Key(key) if app.mode == Mode::Backlinks => {
+                    let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+                    match key.code {
+                        KeyCode::Esc | KeyCode::Char('q') => app.mode = Mode::Normal,
+                        KeyCode::Char('c') if ctrl => app.mode = Mode::Normal,
+                        KeyCode::Enter => {
+                            if let Some((note, line)) = app.submit_backlinks() {
+                                open_target = Some((note, None));
+                                open_line = Some(line);
+                            }
+                        }
+                        KeyCode::Up | KeyCode::BackTab | KeyCode::Char('k') => app.backlinks.select(-1),
+                        KeyCode::Char('p') if ctrl => app.backlinks.select(-1),
+                        KeyCode::Down | KeyCode::Tab | KeyCode::Char('j') => app.backlinks.select(1),
+                        KeyCode::Char('n') if ctrl => app.backlinks.select(1),
+                        _ => {}
+                    }
+                }
+                Event::Key(key) if matches!(app.mode, Mode::Visual { .. }) => {
+                    let page = app.page() as isize;
+                    match keymap.action(key) {
+                        Some(Action::ScrollDown) => app.move_cursor(1),
+                        Some(Action::ScrollUp) => app.move_cursor(-1),
+                        Some(Action::HalfPageDown) => app.move_cursor(page / 2 + 1),
+                        Some(Action::HalfPageUp) => app.move_cursor(-(page / 2 + 1)),
+                        Some(Action::PageDown) => app.move_cursor(page),
+                        Some(Action::PageUp) => app.move_cursor(-page),
+                        Some(Action::Top) => app.move_cursor(isize::MIN),
+                        Some(Action::Bottom) => app.move_cursor(isize::MAX),
+                        Some(Action::Yank) => app.yank()?,
+                        Some(Action::Visual | Action::Cancel | Action::Quit) => app.mode = Mode::Normal,
+                        _ => {}
+                    }
+                }
+                Event::Key(key) if matches!(app.mode, Mode::SetMark | Mode::JumpToMark) => {
+                    // Any key other than a letter cancels.
+                    if let KeyCode::Char(c @ 'a'..='z') = key.code {
+                        match app.mode {
+                            Mode::SetMark => app.set_mark(c),
+                            _ => app.jump_to_mark(c),
+                        }
                     }
-                    KeyCode::Char('j') | KeyCode::Down => app.scroll_down(1),
-                    KeyCode::Char('k') | KeyCode::Up => app.scroll_up(1),
-                    KeyCode::Char('d') => app.scroll_down(app.viewport_height / 2),
-                    KeyCode::Char('u') => app.scroll_up(app.viewport_height / 2),
-                    KeyCode::Char('g') => app.scroll = 0,
-                    KeyCode::Char('G') => app.scroll = app.max_scroll(),
-                    KeyCode::Char(' ') | KeyCode::PageDown => {
-                        app.scroll_down(app.viewport_height.saturating_sub(2))
+                    app.mode = Mode::Normal;
+                }
+                Event::Key(key) if app.mode == Mode::Fold => {
+                    app.mode = Mode::Normal;
+                    match key.code {
+                        KeyCode::Char('a') => app.fold(None),
+                        KeyCode::Char('o') => app.fold(Some(false)),
+                        KeyCode::Char('c') => app.fold(Some(true)),
+                        KeyCode::Char('R') => app.fold_all(false),
+                        KeyCode::Char('M') => app.fold_all(true),
+                        _ => {}
+                    }
+                }
+                Event::Key(key)
+                    if keymap.action(key).is_none() && count_digit(key, count).is_some() =>
+                {
+                    let digit = count_digit(key, count).unwrap_or_default();
+                    app.count = Some(count.unwrap_or(0).saturating_mul(10).saturating_add(digit));
+                }
+                Event::Key(key) => match keymap.action(key) {
+                    Some(Action::Cancel) if app.buf().active_link.is_some() => {
+                        app.buf_mut().active_link = None
+                    }
+                    Some(Action::Cancel) if app.search.is_active() => app.search.clear(),
+                    Some(Action::Quit | Action::Cancel) => break,
+                    Some(Action::QuitWithError) => {
+                        exit = ExitCode::FAILURE;
+                        break;
+                    }
+                    Some(Action::ScrollDown) => app.scroll_down(count.unwrap_or(config.scroll_step.into())),
+                    Some(Action::ScrollUp) => app.scroll_up(count.unwrap_or(config.scroll_step.into())),
+                    Some(Action::HalfPageDown) => app.scroll_down(app.half_page().saturating_mul(times)),
+                    Some(Action::HalfPageUp) => app.scroll_up(app.half_page().saturating_mul(times)),
+                    Some(Action::PageDown) => app.scroll_down(app.page().saturating_mul(times)),
+                    Some(Action::PageUp) => app.scroll_up(app.page().saturating_mul(times)),
+                    // With a count, `g` and `G` go to that line.
+                    Some(Action::Top) => app.scroll_to(count.map_or(0, |n| n.saturating_sub(1))),
+                    Some(Action::Bottom) => app.scroll_to(count.map_or(usize::MAX, |n| n.saturating_sub(1))),
+                    Some(Action::Percent) => app.scroll_to_percent(count.unwrap_or(0)),
+                    Some(Action::Search) => {
+                        app.mode = Mode::Search;
+                        app.search.input.clear();
+                    }
+                    Some(Action::NextMatch) => (0..times).for_each(|_| app.search_next()),
+                    Some(Action::PrevMatch) => (0..times).for_each(|_| app.search_prev()),
+                    Some(Action::Visual) => app.start_visual(),
+                    Some(Action::Yank) => {}
+                    Some(Action::SetMark) => app.mode = Mode::SetMark,
+                    Some(Action::JumpToMark) => app.mode = Mode::JumpToMark,
+                    Some(Action::Fold) => app.mode = Mode::Fold,
+                    Some(Action::Outline) => app.open_outline(),
+                    Some(Action::SearchResults) => app.open_results(),
+                    Some(Action::QuickSwitch) => app.open_switcher(""),
+                    Some(Action::FilterTags) => app.open_switcher(":tag "),
+                    Some(Action::Backlinks) => app.open_backlinks(),
+                    Some(Action::NextHeading) => (0..times).for_each(|_| app.next_heading()),
+                    Some(Action::PrevHeading) => (0..times).for_each(|_| app.prev_heading()),
+                    Some(Action::NextLink) => app.cycle_link(true),
+                    Some(Action::PrevLink) => app.cycle_link(false),
+                    Some(Action::OpenLink) => {
+                        if let Some(url) = app.active_link_url() {
+                            open_target = app.follow_link(&url);
+                        }
+                    }
+                    Some(Action::Back) => app.go_back(false),
+                    Some(Action::Forward) => app.go_back(true),
+                    Some(Action::NextBuffer) => app.cycle_buffer(true),
+                    Some(Action::PrevBuffer) => app.cycle_buffer(false),
+                    Some(Action::NextSlide) if app.buf().slide.is_some() => app.go_to_slide(1),
+                    Some(Action::PrevSlide) if app.buf().slide.is_some() => app.go_to_slide(-1),
+                    Some(Action::NextSlide) => app.scroll_table(1),
+                    Some(Action::PrevSlide) => app.scroll_table(-1),
+                    Some(Action::Info) => app.info = Some(document_stats(&app.buf().content)),
+                    Some(Action::ToggleFollow) => app.toggle_follow(),
+                    Some(Action::ToggleStatusBar) => app.toggle_status_bar(),
+                    Some(Action::ToggleSplit) => {
+                        app.split = !app.split;
+                        rerender(&mut app, render_width);
+                        app.clamp_scroll();
                     }
-                    KeyCode::PageUp => {
-                        app.scroll_up(app.viewport_height.saturating_sub(2))
+                    Some(Action::Reload) => {
+                        image_cache.clear();
+                        app.force_reload();
+                    }
+                    Some(Action::Edit) => {
+                        let line = app.top_source_line() + 1;
+                        let edited = edit(&mut terminal, &app.buf().source, line, guard.keyboard_enhanced)
+                            .and_then(|()| app.reload(app.current));
+                        app.drawn_images.clear();
+                        if let Err(e) = edited {
+                            app.message = Some(format!("{e:#}"));
+                        }
+                    }
+                    None => {}
+                },
+                Event::Mouse(mouse) => match mouse.kind {
+                    MouseEventKind::ScrollDown => app.scroll_down(3),
+                    MouseEventKind::ScrollUp => app.scroll_up(3),
+                    MouseEventKind::Down(MouseButton::Left) | MouseEventKind::Drag(MouseButton::Left)
+                        if mouse.column + 1 == size.width
+                            && mouse.row < app.viewport_height
+                            && app.max_scroll() > 0 =>
+                    {
+                        app.scroll_to_track(mouse.row, app.viewport_height);
+                    }
+                    MouseEventKind::Down(MouseButton::Left) => {
+                        if let Some(i) = app.link_at(text_area, mouse.column, mouse.row) {
+                            app.buf_mut().active_link = Some(i);
+                            let url = app.buf().links[i].url.clone();
+                            open_target = app.follow_link(&url);
+                        }
                     }
                     _ => {}
                 },
                 Event::Resize(w, h) => {
-                    app.viewport_height = h;
+                    app.drawn_images.clear();
+                    app.drawn_links.clear();
+                    app.viewport_height = h.saturating_sub(u16::from(app.status_line(w).is_some()));
                     if w != render_width {
                         render_width = w;
-                        app.text = render_markdown(&content, render_width);
+                        rerender(&mut app, render_width);
                     }
                     app.clamp_scroll();
                 }
                 _ => {}
             }
+
+            if let Some((target, anchor)) = open_target {
+                let from = app.location();
+                let open = open_file(target, config.max_file_size).and_then(|(source, expanded)| {
+                    match app.buffers.iter().position(|b| b.source == source) {
+                        Some(i) => Ok(i),
+                        None => {
+                            let width = preview_width(render_width, app.split);
+                            app.buffers.push(open_buffer(source, expanded, width, None)?);
+                            app.request_render(app.buffers.len() - 1);
+                            Ok(app.buffers.len() - 1)
+                        }
+                    }
+                });
+                match open {
+                    Ok(i) => {
+                        app.history.visit(from);
+                        app.switch_to(i);
+                        match anchor {
+                            Some(anchor) if app.rendering() => app.buf_mut().jump_to = Some(anchor),
+                            Some(anchor) => app.jump_to_anchor(&anchor),
+                            None => {}
+                        }
+                        match open_line {
+                            Some(line) if app.rendering() => app.buf_mut().goto_line = Some(line),
+                            Some(line) => app.scroll_to_source_line(line),
+                            None => {}
+                        }
+                    }
+                    Err(e) => app.message = Some(format!("{e:#}")),
+                }
+            }
+        }
+    }
+
+    drop(guard);
+    // Re-read so positions saved by other sessions meanwhile are kept.
+    let mut state = State::load();
+    for buf in app.buffers.iter().filter(|b| b.slide.is_none() && app.revision.is_none()) {
+        let marks = buf.marks.iter().map(|(m, &line)| (m.to_string(), line)).collect();
+        let scroll = buf.top_line();
+        state.set(&buf.source, FileState { scroll, marks });
+    }
+    state.save().context("Cannot save view state")?;
+    if !slides && app.revision.is_none() {
+        let files = app.buffers.iter().map(|b| b.source.clone()).collect();
+        Session { files, current: app.current }.save().context("Cannot save the session")?;
+    }
+    Ok(exit)
+}
+
+/// The digit `key` adds to a count, if it is one. A leading `0` is not.
+fn count_digit(key: KeyEvent, count: Option<usize>) -> Option<usize> {
+    match key.code {
+        KeyCode::Char(c @ '0'..='9') if key.modifiers.is_empty() && (c != '0' || count.is_some()) => {
+            Some(c as usize - '0' as usize)
+        }
+        _ => None,
+    }
+}
+
+/// Writes the statistics of each file, under the path it was given as.
+fn print_stats(sources: &[Source], files: &[(Source, Expanded)]) -> Result<()> {
+    let mut out = Vec::new();
+    for (i, (given, (_, source))) in sources.iter().zip(files).enumerate() {
+        if i > 0 {
+            writeln!(out)?;
+        }
+        writeln!(out, "{given}")?;
+        let rows = document_stats(&source.text).rows();
+        let label_width = rows.iter().map(|(label, _)| label.len()).max().unwrap_or(0);
+        for (label, value) in rows {
+            writeln!(out, "  {label:label_width$}  {value}")?;
+        }
+    }
+    pager::write(&out, false)
+}
+
+/// Writes where the render time of each file goes, under the path it was
+/// given as, with its slowest blocks.
+fn print_bench(sources: &[Source], files: &[(Source, Expanded)], options: &RenderOptions) -> Result<()> {
+    let mut out = Vec::new();
+    for (i, (given, (_, source))) in sources.iter().zip(files).enumerate() {
+        if i > 0 {
+            writeln!(out)?;
+        }
+        writeln!(out, "{given}")?;
+        let profile = mdview::bench::profile(&source.text, options);
+        let rows = profile.rows();
+        let label_width = rows.iter().map(|(label, _)| label.len()).max().unwrap_or(0);
+        for (label, value) in rows {
+            writeln!(out, "  {label:label_width$}  {value}")?;
+        }
+        let slowest = &profile.blocks[..profile.blocks.len().min(BENCH_BLOCKS)];
+        if !slowest.is_empty() {
+            writeln!(out, "  Slowest blocks")?;
+        }
+        let line_width = slowest.iter().map(|b| b.line.to_string().len()).max().unwrap_or(0);
+        let kind_width = slowest.iter().map(|b| b.kind.width()).max().unwrap_or(0);
+        for block in slowest {
+            let (line, kind, time) = (block.line, &block.kind, mdview::bench::millis(block.time));
+            writeln!(out, "    line {line:>line_width$}  {kind:kind_width$}  {time:>9}")?;
+        }
+    }
+    pager::write(&out, false)
+}
+
+/// Writes the heading tree of each file, under the path it was given as,
+/// with the number of lines of each section in a column on the right.
+fn print_outline(sources: &[Source], files: &[(Source, Expanded)]) -> Result<()> {
+    let mut out = Vec::new();
+    for (i, (given, (_, source))) in sources.iter().zip(files).enumerate() {
+        if i > 0 {
+            writeln!(out)?;
+        }
+        writeln!(out, "{given}")?;
+        let entries: Vec<(String, usize)> =
+            mdview::section::outline(&source.text).iter().map(|s| (outline_entry(s), s.lines)).collect();
+        let title_width = entries.iter().map(|(title, _)| title.width()).max().unwrap_or(0);
+        let count_width = entries.iter().map(|(_, lines)| lines.to_string().len()).max().unwrap_or(0);
+        for (title, lines) in entries {
+            let pad = " ".repeat(title_width - title.width());
+            writeln!(out, "  {title}{pad}  {lines:>count_width$}")?;
         }
     }
+    pager::write(&out, false)
+}
+
+/// Columns left for the rendered view of a terminal `width` columns wide:
+/// all of them, or the right half when the source pane takes the left half
+/// and the column dividing them.
+fn preview_width(width: u16, split: bool) -> u16 {
+    match split {
+        true => width.saturating_sub(width / 2 + 1),
+        false => width,
+    }
+}
+
+/// Content width for a terminal `available` columns wide: the terminal minus
+/// margins, narrowed to the configured width and capped at `max_width`.
+fn content_width(config: &Config, available: u16) -> u16 {
+    let width = available.saturating_sub(config.margin.saturating_mul(2));
+    let width = config.width.map_or(width, |w| w.columns(width).min(width));
+    config.max_width.map_or(width, |max| max.min(width))
+}
+
+fn render_options(config: &Config, theme: Theme, available: u16) -> RenderOptions {
+    RenderOptions::new(content_width(config, available))
+        .block_images(Some(config.block_image_rows).filter(|_| config.images == images::ImageMode::Blocks))
+        .tab_size(config.tab_size)
+        .theme(theme)
+        .syntax_theme(config.syntax_theme.clone())
+        .line_numbers(config.line_numbers)
+        .language_labels(config.language_labels)
+        .table_min_column_width(config.table_min_column_width)
+        .table_max_cell_lines(config.table_max_cell_lines)
+        .emoji(config.emoji)
+        .wiki_links(config.wiki_links)
+        .bidi(config.bidi)
+        .hyphenate(config.hyphenate)
+        .show_invisibles(config.show_invisibles)
+        .link_style(config.link_style)
+        .number_headings(config.number_headings)
+        .ascii(config.ascii)
+}
+
+/// Reads and decodes the file at `path`, refusing it when it is larger than
+/// `limit`.
+fn read_file(path: &Path, limit: config::FileSize) -> Result<Decoded> {
+    let meta = std::fs::metadata(path)
+        .with_context(|| format!("Cannot stat {}", path.display()))?;
+    anyhow::ensure!(
+        meta.len() <= limit.0,
+        "{} is too large ({}, over max_file_size of {}); pass --force to open it anyway",
+        path.display(),
+        config::FileSize(meta.len()),
+        limit
+    );
+    encoding::read(path).with_context(|| format!("Cannot read {}", path.display()))
+}
+
+/// Hands the terminal to `$VISUAL` or `$EDITOR` (else `vi`), opened at
+/// `line` of `source`, and takes it back once the editor exits.
+fn edit(
+    terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
+    source: &Source,
+    line: usize,
+    keyboard_enhanced: bool,
+) -> Result<()> {
+    let Source::File(path) = source else {
+        anyhow::bail!("{source} was fetched and has no file to edit");
+    };
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .unwrap_or_else(|_| "vi".to_string());
+    // Like git, allow arguments in the variable (`code --wait`).
+    let mut words = editor.split_whitespace();
+    let program = words.next().context("$EDITOR is empty")?;
+
+    if keyboard_enhanced {
+        io::stdout().execute(PopKeyboardEnhancementFlags)?;
+    }
+    io::stdout().execute(DisableMouseCapture)?;
+    io::stdout().execute(LeaveAlternateScreen)?;
+    disable_raw_mode()?;
+    let status = std::process::Command::new(program).args(words).arg(format!("+{line}")).arg(path).status();
+    enable_raw_mode()?;
+    io::stdout().execute(EnterAlternateScreen)?;
+    io::stdout().execute(EnableMouseCapture)?;
+    if keyboard_enhanced {
+        io::stdout()
+            .execute(PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::DISAMBIGUATE_ESCAPE_CODES))?;
+    }
+    terminal.clear()?;
 
-    drop(_guard);
+    let status = status.with_context(|| format!("Cannot run {program}"))?;
+    anyhow::ensure!(status.success(), "{program} exited with {status}");
     Ok(())
 }
 
-fn dump_text(content: &str, width_override: Option<u16>) -> Result<()> {
-    let width = width_override
-        .unwrap_or_else(|| crossterm::terminal::size().map(|(w, _)| w).unwrap_or(80));
-    let text = render_markdown(content, width);
-    let mut out = io::stdout().lock();
+/// Drops the first `columns` columns of `line`, for a table scrolled
+/// sideways. A wide character cut in half becomes a space.
+fn shift_line(line: &mut Line<'static>, columns: usize) {
+    let mut skip = columns;
+    let mut spans = Vec::new();
+    for span in line.spans.drain(..) {
+        if skip == 0 {
+            spans.push(span);
+            continue;
+        }
+        let mut kept = String::new();
+        for cluster in span.content.graphemes(true) {
+            let width = cluster.width();
+            if skip == 0 {
+                kept.push_str(cluster);
+            } else if width > skip {
+                kept.push_str(&" ".repeat(width - skip));
+                skip = 0;
+            } else {
+                skip -= width;
+            }
+        }
+        if !kept.is_empty() {
+            spans.push(Span::styled(kept, span.style));
+        }
+    }
+    line.spans = spans;
+}
+
+/// The part of `content` on screen: slide `slide` when presenting, else all
+/// of it.
+fn shown_source(content: &str, slide: Option<usize>) -> &str {
+    match slide {
+        Some(i) => {
+            let slides = split_slides(content);
+            slides[i.min(slides.len() - 1)]
+        }
+        None => content,
+    }
+}
+
+/// The part of `text`, the markdown of `source`, that the section under
+/// `heading` spans, or an error listing the headings there are.
+fn find_section(text: &str, heading: &str, source: &Source) -> Result<Range<usize>> {
+    mdview::section::find(text, heading).with_context(|| {
+        let titles: Vec<String> = mdview::section::outline(text).iter().map(outline_entry).collect();
+        match titles.is_empty() {
+            true => format!("No section '{heading}' in {source}, which has no headings"),
+            false => format!("No section '{heading}' in {source}; its headings are:\n  {}", titles.join("\n  ")),
+        }
+    })
+}
+
+/// The title of `section` indented two columns per level below the top.
+fn outline_entry(section: &Section) -> String {
+    format!("{}{}", "  ".repeat(section.level.saturating_sub(1) as usize), section.title)
+}
 
-    for line in &text.lines {
-        for span in &line.spans {
-            let mut preamble = String::new();
-            let mut has_style = false;
+/// Reads `source` as markdown: a markdown file (or one of an unknown kind)
+/// with its includes inlined, anything else converted. A URL is fetched
+/// instead, and its includes are left alone since they would be read from
+/// the local disk. Either is refused when larger than `limit`.
+fn read_source(source: &Source, limit: config::FileSize) -> Result<Expanded> {
+    match source {
+        Source::File(path) => convert(read_file(path, limit)?, source),
+        Source::Url(url) => convert(fetch::get(url, limit)?, source),
+    }
+}
+
+/// `decoded`, the contents of `source`, as markdown.
+fn convert(decoded: Decoded, source: &Source) -> Result<Expanded> {
+    let Decoded { text, encoding } = decoded;
+    let format = input::Format::from_path(source.document_path()).unwrap_or(input::Format::Markdown);
+    match (format, source) {
+        (input::Format::Markdown, Source::File(path)) => Ok(Expanded { encoding, ..expand_includes(&text, path) }),
+        (format, _) => {
+            let text = input::to_markdown(&text, format).with_context(|| format!("Cannot read {source}"))?;
+            Ok(Expanded { text, includes: Vec::new(), encoding })
+        }
+    }
+}
+
+/// Reads `source` the way the viewer shows it: as of a git revision with
+/// `--rev`, merged with the blocks removed since one with `--diff` (along
+/// with the blocks that changed), else as it is. Includes are always read
+/// from the working tree.
+fn read_version(
+    source: &Source,
+    revision: Option<&git::Revision>,
+    limit: config::FileSize,
+) -> Result<(Expanded, Vec<ChangedBlock>)> {
+    let committed = |rev| match source {
+        Source::File(path) => convert(git::show(path, rev)?, source),
+        Source::Url(url) => anyhow::bail!("{url} was fetched and has no git history"),
+    };
+    match revision {
+        None => Ok((read_source(source, limit)?, Vec::new())),
+        Some(git::Revision::At(rev)) => Ok((committed(rev)?, Vec::new())),
+        Some(git::Revision::Diff(rev)) => {
+            let old = committed(rev)?;
+            let new = read_source(source, limit)?;
+            let diff = diff::diff(&old.text, &new.text);
+            Ok((Expanded { text: diff.text, ..new }, diff.blocks))
+        }
+    }
+}
+
+/// Reads the files, or URLs, given on the command line.
+fn open_files(args: &[PathBuf], limit: config::FileSize) -> Result<Vec<(Source, Expanded)>> {
+    args.iter().map(|arg| open_file(Source::from(arg.clone()), limit)).collect()
+}
+
+/// Reads `source`, a file by its canonical path so it is known under one
+/// name however it was reached.
+fn open_file(source: Source, limit: config::FileSize) -> Result<(Source, Expanded)> {
+    let source = match source {
+        Source::File(path) => Source::File(canonical(&path)?),
+        url => url,
+    };
+    let expanded = read_source(&source, limit)?;
+    Ok((source, expanded))
+}
+
+fn canonical(path: &Path) -> Result<PathBuf> {
+    path.canonicalize().with_context(|| format!("Cannot resolve path: {}", path.display()))
+}
+
+/// Total width, margins included, for output written to stdout.
+fn dump_width(config: &Config) -> u16 {
+    // An explicit width is taken as-is since the output may not go to a
+    // terminal; a percentage is of the terminal (or 80 columns).
+    match config.width {
+        Some(Width::Columns(w)) => w.saturating_add(config.margin.saturating_mul(2)),
+        _ => crossterm::terminal::size().map(|(w, _)| w).unwrap_or(80),
+    }
+}
 
-            if let Some(fg) = span.style.fg {
-                if let Some(seq) = color_to_ansi_fg(fg) {
-                    preamble.push_str(&seq);
-                    has_style = true;
+/// The links of a document read from `source` that a terminal can open,
+/// with the URL it opens.
+fn hyperlinks(source: &Source, links: Vec<Link>) -> Vec<Link> {
+    links.into_iter().filter_map(|link| Some(Link { url: links::hyperlink(source, &link.url)?, ..link })).collect()
+}
+
+/// Writes rendered text to `out`, styled with ANSI escapes in the colors of
+/// the `color` depth when it is set. `links` are wrapped in OSC 8 hyperlink escapes, which terminals that
+/// support them make clickable and the others ignore.
+fn write_ansi(
+    out: &mut impl io::Write,
+    text: &Text,
+    links: &[Link],
+    margin: u16,
+    color: Option<ColorDepth>,
+) -> Result<()> {
+    let margin = " ".repeat(margin as usize);
+
+    for (i, line) in text.lines.iter().enumerate() {
+        if !line.spans.is_empty() {
+            write!(out, "{margin}")?;
+        }
+        let links: Vec<&Link> = links.iter().filter(|l| color.is_some() && l.line == i && l.start < l.end).collect();
+        let mut at = 0;
+        for span in &line.spans {
+            // A span is cut where a link starts or ends inside it.
+            let content = span.content.as_ref();
+            let mut cuts: Vec<usize> = links
+                .iter()
+                .flat_map(|l| [l.start, l.end])
+                .filter(|&c| c > at && c < at + content.len() && content.is_char_boundary(c - at))
+                .map(|c| c - at)
+                .collect();
+            cuts.sort_unstable();
+            cuts.dedup();
+            cuts.push(content.len());
+            let mut from = 0;
+            for to in cuts {
+                if let Some(link) = links.iter().find(|l| l.start == at + from) {
+                    write!(out, "\x1b]8;;{}\x1b\\", link.url)?;
                 }
-            }
-            if let Some(bg) = span.style.bg {
-                if let Some(seq) = color_to_ansi_bg(bg) {
-                    if has_style { preamble.push(';'); }
-                    preamble.push_str(&seq);
-                    has_style = true;
+                write_styled(out, span.style, &content[from..to], color)?;
+                if links.iter().any(|l| l.end == at + to) {
+                    write!(out, "\x1b]8;;\x1b\\")?;
                 }
+                from = to;
             }
+            at += content.len();
+        }
+        writeln!(out)?;
+    }
+    Ok(())
+}
 
-            let mods = span.style.add_modifier;
-            for (flag, code) in [
-                (ratatui::style::Modifier::BOLD, "1"),
-                (ratatui::style::Modifier::DIM, "2"),
-                (ratatui::style::Modifier::ITALIC, "3"),
-                (ratatui::style::Modifier::UNDERLINED, "4"),
-                (ratatui::style::Modifier::CROSSED_OUT, "9"),
-            ] {
-                if mods.contains(flag) {
-                    if has_style { preamble.push(';'); }
-                    preamble.push_str(code);
-                    has_style = true;
-                }
-            }
+fn write_styled(out: &mut impl io::Write, style: Style, content: &str, color: Option<ColorDepth>) -> Result<()> {
+    let Some(depth) = color else {
+        write!(out, "{content}")?;
+        return Ok(());
+    };
+    let mut preamble = String::new();
+    let mut has_style = false;
 
-            if has_style {
-                write!(out, "\x1b[{preamble}m{}\x1b[0m", span.content)?;
-            } else {
-                write!(out, "{}", span.content)?;
-            }
+    if let Some(fg) = style.fg
+        && let Some(seq) = color_to_ansi_fg(palette::quantize(fg, depth))
+    {
+        preamble.push_str(&seq);
+        has_style = true;
+    }
+    if let Some(bg) = style.bg
+        && let Some(seq) = color_to_ansi_bg(palette::quantize(bg, depth))
+    {
+        if has_style { preamble.push(';'); }
+        preamble.push_str(&seq);
+        has_style = true;
+    }
+
+    let mods = style.add_modifier;
+    for (flag, code) in [
+        (ratatui::style::Modifier::BOLD, "1"),
+        (ratatui::style::Modifier::DIM, "2"),
+        (ratatui::style::Modifier::ITALIC, "3"),
+        (ratatui::style::Modifier::UNDERLINED, "4"),
+        (ratatui::style::Modifier::CROSSED_OUT, "9"),
+    ] {
+        if mods.contains(flag) {
+            if has_style { preamble.push(';'); }
+            preamble.push_str(code);
+            has_style = true;
         }
-        writeln!(out)?;
+    }
+
+    if has_style {
+        write!(out, "\x1b[{preamble}m{content}\x1b[0m")?;
+    } else {
+        write!(out, "{content}")?;
     }
     Ok(())
 }
@@ -290,7 +2752,164 @@ fn color_to_ansi_bg(color: ratatui::style::Color) -> Option<String> {
     }
 }
 
-fn render_scrollbar(f: &mut ratatui::Frame, area: Rect, scroll: u16, max_scroll: u16) {
+/// Draws the `Info` popup with the statistics of the file `name` in the
+/// middle of `area`.
+fn render_info(f: &mut ratatui::Frame, area: Rect, name: &str, stats: &Stats) {
+    let rows = stats.rows();
+    let label_width = rows.iter().map(|(label, _)| label.len()).max().unwrap_or(0);
+    let lines: Vec<Line> = rows
+        .into_iter()
+        .map(|(label, value)| {
+            Line::from(vec![
+                Span::styled(format!("{label:label_width$}  "), Style::default().fg(Color::DarkGray)),
+                Span::raw(value),
+            ])
+        })
+        .collect();
+    let content_width = lines.iter().map(Line::width).chain([name.width()]).max().unwrap_or(0) as u16;
+    let width = (content_width + 4).min(area.width);
+    let height = (lines.len() as u16 + 2).min(area.height);
+    let popup = Rect {
+        x: area.x + (area.width - width) / 2,
+        y: area.y + (area.height - height) / 2,
+        width,
+        height,
+    };
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::DarkGray))
+        .title(format!(" {name} "))
+        .padding(Padding::horizontal(1));
+    f.render_widget(Clear, popup);
+    f.render_widget(Paragraph::new(lines).block(block), popup);
+}
+
+/// `message` broken into rows of `width` columns for the error banner, at
+/// most `ERROR_BANNER_ROWS` of them.
+fn banner_rows(message: &str, width: u16) -> Vec<String> {
+    let mut rows = Vec::new();
+    let mut row = String::new();
+    for g in format!(" {message}").graphemes(true) {
+        if !row.is_empty() && row.width() + g.width() > width as usize {
+            rows.push(std::mem::take(&mut row));
+        }
+        row.push_str(g);
+    }
+    rows.push(row);
+    rows.truncate(ERROR_BANNER_ROWS);
+    rows
+}
+
+/// Draws the heading palette over the top of `area`: the query, then as
+/// many matching headings as fit, scrolled to keep the highlighted one shown.
+fn render_outline(f: &mut ratatui::Frame, area: Rect, outline: &Outline, headings: &[Heading]) {
+    let entries = outline.matches(headings);
+    let width = (area.width * 2 / 3).clamp(40, 80).min(area.width);
+    let height = (entries.len() as u16 + 3).clamp(4, (area.height * 2 / 3).max(4)).min(area.height);
+    let popup = Rect { x: area.x + (area.width - width) / 2, y: area.y + area.height / 6, width, height };
+    let room = height.saturating_sub(3) as usize;
+    let skip = (outline.selected + 1).saturating_sub(room);
+    let mut lines = vec![Line::from(format!("> {}", outline.input))];
+    match entries.is_empty() {
+        true => lines.push(Line::from(Span::styled("No matching heading", Style::default().fg(Color::DarkGray)))),
+        false => lines.extend(outline.lines(headings, &entries).into_iter().skip(skip).take(room)),
+    }
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::DarkGray))
+        .title(" Go to heading ")
+        .padding(Padding::horizontal(1));
+    f.render_widget(Clear, popup);
+    f.render_widget(Paragraph::new(lines).block(block), popup);
+}
+
+/// Draws the search results panel over the top of `area`: the query, then
+/// as many matches as fit, scrolled to keep the highlighted one shown.
+fn render_results(f: &mut ratatui::Frame, area: Rect, results: &Results, doc: &Document) {
+    let width = (area.width * 4 / 5).clamp(40, 100).min(area.width);
+    let height = (results.matches.len() as u16 + 3).clamp(4, (area.height * 2 / 3).max(4)).min(area.height);
+    let popup = Rect { x: area.x + (area.width - width) / 2, y: area.y + area.height / 6, width, height };
+    let room = height.saturating_sub(3) as usize;
+    let skip = (results.selected + 1).saturating_sub(room);
+    let mut lines = vec![Line::from(format!("> {}", results.input))];
+    match results.matches.is_empty() {
+        true => {
+            let message = match &results.error {
+                Some(error) => error.as_str(),
+                None if results.input.is_empty() => "Type to search",
+                None => "No match",
+            };
+            lines.push(Line::from(Span::styled(message.to_string(), Style::default().fg(Color::DarkGray))));
+        }
+        false => {
+            let list = results.lines(&doc.text, &doc.headings, width.saturating_sub(4) as usize);
+            lines.extend(list.into_iter().skip(skip).take(room));
+        }
+    }
+    let kind = if results.regex { "Regex search" } else { "Search" };
+    let title = match results.matches.len() {
+        0 => format!(" {kind} "),
+        1 => format!(" {kind}: 1 match "),
+        n => format!(" {kind}: {n} matches "),
+    };
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::DarkGray))
+        .title(title)
+        .padding(Padding::horizontal(1));
+    f.render_widget(Clear, popup);
+    f.render_widget(Paragraph::new(lines).block(block), popup);
+}
+
+/// Draws the quick switcher over the top of `area`: the query, then as many
+/// matching notes as fit, scrolled to keep the highlighted one shown.
+fn render_switcher(f: &mut ratatui::Frame, area: Rect, switcher: &Switcher) {
+    let entries = switcher.matches();
+    let width = (area.width * 2 / 3).clamp(40, 80).min(area.width);
+    let height = (entries.len() as u16 + 3).clamp(4, (area.height * 2 / 3).max(4)).min(area.height);
+    let popup = Rect { x: area.x + (area.width - width) / 2, y: area.y + area.height / 6, width, height };
+    let room = height.saturating_sub(3) as usize;
+    let skip = (switcher.selected + 1).saturating_sub(room);
+    let mut lines = vec![Line::from(format!("> {}", switcher.input))];
+    match entries.is_empty() {
+        true => lines.push(Line::from(Span::styled("No matching note", Style::default().fg(Color::DarkGray)))),
+        false => lines.extend(switcher.lines(&entries).into_iter().skip(skip).take(room)),
+    }
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::DarkGray))
+        .title(" Open note ")
+        .padding(Padding::horizontal(1));
+    f.render_widget(Clear, popup);
+    f.render_widget(Paragraph::new(lines).block(block), popup);
+}
+
+/// Draws the backlinks of the note `name` over the top of `area`, as many as
+/// fit, scrolled to keep the highlighted one shown.
+fn render_backlinks(f: &mut ratatui::Frame, area: Rect, backlinks: &Backlinks, name: &str) {
+    let width = (area.width * 4 / 5).clamp(40, 100).min(area.width);
+    let height = (backlinks.links.len() as u16 + 2).clamp(3, (area.height * 2 / 3).max(3)).min(area.height);
+    let popup = Rect { x: area.x + (area.width - width) / 2, y: area.y + area.height / 6, width, height };
+    let room = height.saturating_sub(2) as usize;
+    let skip = (backlinks.selected + 1).saturating_sub(room);
+    let lines = match backlinks.links.is_empty() {
+        true => vec![Line::from(Span::styled("No note links here", Style::default().fg(Color::DarkGray)))],
+        false => backlinks.lines(width.saturating_sub(4) as usize).into_iter().skip(skip).take(room).collect(),
+    };
+    let title = match backlinks.links.len() {
+        1 => format!(" 1 backlink to {name} "),
+        n => format!(" {n} backlinks to {name} "),
+    };
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::DarkGray))
+        .title(title)
+        .padding(Padding::horizontal(1));
+    f.render_widget(Clear, popup);
+    f.render_widget(Paragraph::new(lines).block(block), popup);
+}
+
+fn render_scrollbar(f: &mut ratatui::Frame, area: Rect, scroll: usize, max_scroll: usize) {
     let track_height = area.height.saturating_sub(1) as f64;
     let pos = if max_scroll == 0 {
         0
@@ -309,3 +2928,50 @@ fn render_scrollbar(f: &mut ratatui::Frame, area: Rect, scroll: u16, max_scroll:
         );
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser as _;
+
+    use super::*;
+
+    fn dump_args(args: &[&str]) -> DumpArgs {
+        match Cli::parse_from(["mdview", "dump"].iter().chain(args)).command {
+            Some(Command::Dump(args)) => args,
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_banner_rows_wrap_and_truncate() {
+        assert_eq!(banner_rows("Cannot read a.md", 40), [" Cannot read a.md"]);
+        assert_eq!(banner_rows("abcdefgh", 4), [" abc", "defg", "h"]);
+        // Wide characters are not split across rows.
+        assert_eq!(banner_rows("日本語", 4), [" 日", "本語"]);
+        let long = banner_rows(&"x".repeat(100), 10);
+        assert_eq!(long.len(), ERROR_BANNER_ROWS);
+        assert!(long.iter().all(|row| row.width() == 10));
+    }
+
+    #[test]
+    fn test_read_file_refuses_files_over_the_limit() {
+        let path = std::env::temp_dir().join(format!("mdview-limit-{}.md", std::process::id()));
+        std::fs::write(&path, "# Title\n").unwrap();
+        assert_eq!(read_file(&path, config::FileSize(8)).unwrap().text, "# Title\n");
+        let error = read_file(&path, config::FileSize(7)).unwrap_err().to_string();
+        assert!(error.contains("too large (8 bytes, over max_file_size of 7 bytes)"), "{error}");
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_dump_leaves_frontmatter_out() {
+        let text = "---\ntitle: Hello\nauthor: Me\n---\n# Body\n\nText.\n".to_string();
+        let expanded = Expanded { text, includes: Vec::new(), encoding: None };
+        let files = [(Source::File(PathBuf::from("notes.md")), expanded)];
+        let args = dump_args(&["--color", "never", "notes.md"]);
+        let (out, _) = dump_files(&args, &Config::default(), Theme::default(), &files).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = out.lines().map(str::trim_end).collect();
+        assert_eq!(lines, ["# Body", "", "Text.", ""]);
+    }
+}
@@ -0,0 +1,380 @@
+use pulldown_cmark::{BlockQuoteKind, Event, Parser, Tag, TagEnd};
+use ratatui::style::{Modifier, Style};
+use ratatui::text::{Line, Span, Text};
+use unicode_width::UnicodeWidthStr;
+
+use crate::render::{RenderOptions, coalesce_chars, expand_tabs, wrap_paragraph};
+
+/// Layout for [`render_man`].
+#[derive(Debug, Clone)]
+pub struct ManOptions {
+    width: u16,
+    indent: u16,
+    tab_size: usize,
+}
+
+impl ManOptions {
+    pub fn new(width: u16) -> Self {
+        Self { width, indent: 7, tab_size: 4 }
+    }
+
+    /// Columns body text is indented from section headings.
+    pub fn indent(mut self, indent: u16) -> Self {
+        self.indent = indent;
+        self
+    }
+
+    /// Columns per tab stop when expanding tabs in code blocks.
+    pub fn tab_size(mut self, tab_size: usize) -> Self {
+        self.tab_size = tab_size;
+        self
+    }
+}
+
+/// Renders markdown laid out like a man page: `#` and `##` headings become
+/// bold, upper-case section headings at the left margin, deeper headings
+/// subsections, and body text is indented beneath them. List items hang
+/// from their bullet, emphasis is underlined and code is bold. Only bold,
+/// underline and strikethrough are used, never colors.
+///
+/// ```
+/// use mdview::man::{ManOptions, render_man};
+///
+/// let text = render_man("# Name\n\nmdview - view markdown\n", &ManOptions::new(80));
+/// assert_eq!(text.lines[0].to_string(), "NAME");
+/// assert_eq!(text.lines[1].to_string(), "       mdview - view markdown");
+/// ```
+pub fn render_man(input: &str, options: &ManOptions) -> Text<'static> {
+    let extensions = RenderOptions::new(options.width).extensions;
+    let mut writer = ManWriter {
+        options,
+        lines: Vec::new(),
+        spans: Vec::new(),
+        styles: Vec::new(),
+        indents: vec![options.indent as usize],
+        lists: Vec::new(),
+        tag: None,
+        link: None,
+        code: None,
+        table: None,
+        heading_end: 0,
+    };
+    for event in Parser::new_ext(input, extensions) {
+        writer.event(event);
+    }
+    writer.flush();
+    while writer.lines.last().is_some_and(|l| l.spans.is_empty()) {
+        writer.lines.pop();
+    }
+    Text::from(writer.lines)
+}
+
+struct ManWriter<'o> {
+    options: &'o ManOptions,
+    lines: Vec<Line<'static>>,
+    /// Inline content of the paragraph being built.
+    spans: Vec<Span<'static>>,
+    styles: Vec<Style>,
+    /// Left margin of each open block; the last one applies.
+    indents: Vec<usize>,
+    /// Next number of each open list, `None` for bullet lists.
+    lists: Vec<Option<u64>>,
+    /// Bullet or number hanging to the left of the next paragraph.
+    tag: Option<String>,
+    /// Destination and first span of the open link.
+    link: Option<(String, usize)>,
+    code: Option<String>,
+    /// Rows of plain cell text; the first row is the header.
+    table: Option<Vec<Vec<String>>>,
+    /// Number of lines when the last heading or alert title was written.
+    /// Body text follows these without a blank line, as in man pages.
+    heading_end: usize,
+}
+
+impl ManWriter<'_> {
+    fn event(&mut self, event: Event) {
+        match event {
+            Event::Start(tag) => self.start(tag),
+            Event::End(tag) => self.end(tag),
+            Event::Text(text) | Event::InlineMath(text) => self.text(&text, Style::default()),
+            Event::Code(code) => self.text(&code, Style::default().add_modifier(Modifier::BOLD)),
+            Event::SoftBreak => self.text(" ", Style::default()),
+            Event::HardBreak => self.flush(),
+            Event::Rule => {
+                self.flush();
+                self.blank();
+            }
+            Event::TaskListMarker(checked) => {
+                self.text(if checked { "[x] " } else { "[ ] " }, Style::default());
+            }
+            Event::FootnoteReference(label) => self.text(&format!("[{label}]"), Style::default()),
+            Event::DisplayMath(math) => {
+                self.flush();
+                self.blank();
+                self.text(&math, Style::default());
+                self.flush();
+            }
+            Event::Html(_) | Event::InlineHtml(_) => {}
+        }
+    }
+
+    fn start(&mut self, tag: Tag) {
+        match tag {
+            Tag::Heading { .. } => {
+                self.flush();
+                self.blank();
+                self.styles.push(Style::default().add_modifier(Modifier::BOLD));
+            }
+            Tag::Paragraph if self.table.is_none() && self.tag.is_none() => {
+                self.flush();
+                self.blank();
+            }
+            Tag::BlockQuote(kind) => {
+                self.flush();
+                self.blank();
+                self.indents.push(self.indent() + 4);
+                if let Some(kind) = kind {
+                    let title = match kind {
+                        BlockQuoteKind::Note => "Note:",
+                        BlockQuoteKind::Tip => "Tip:",
+                        BlockQuoteKind::Important => "Important:",
+                        BlockQuoteKind::Warning => "Warning:",
+                        BlockQuoteKind::Caution => "Caution:",
+                    };
+                    self.text(title, Style::default().add_modifier(Modifier::BOLD));
+                    self.flush();
+                    self.heading_end = self.lines.len();
+                }
+            }
+            Tag::CodeBlock(_) => {
+                self.flush();
+                self.blank();
+                self.code = Some(String::new());
+            }
+            Tag::List(start) => {
+                self.flush();
+                if self.lists.is_empty() {
+                    self.blank();
+                }
+                self.lists.push(start);
+            }
+            Tag::Item => {
+                self.flush();
+                let tag = match self.lists.last_mut() {
+                    Some(Some(n)) => {
+                        *n += 1;
+                        format!("{}.", *n - 1)
+                    }
+                    _ => "•".to_string(),
+                };
+                let width = (tag.width() + 1).max(4);
+                self.indents.push(self.indent() + width);
+                self.tag = Some(tag);
+            }
+            Tag::FootnoteDefinition(label) => {
+                self.flush();
+                self.blank();
+                let tag = format!("[{label}]");
+                self.indents.push(self.indent() + tag.width() + 1);
+                self.tag = Some(tag);
+            }
+            Tag::Table(_) => {
+                self.flush();
+                self.blank();
+                self.table = Some(Vec::new());
+            }
+            Tag::TableHead | Tag::TableRow => {
+                if let Some(rows) = &mut self.table {
+                    rows.push(Vec::new());
+                }
+            }
+            Tag::TableCell => {
+                if let Some(row) = self.table.as_mut().and_then(|rows| rows.last_mut()) {
+                    row.push(String::new());
+                }
+            }
+            Tag::Emphasis => self.push_style(Modifier::UNDERLINED),
+            Tag::Strong => self.push_style(Modifier::BOLD),
+            Tag::Strikethrough => self.push_style(Modifier::CROSSED_OUT),
+            Tag::Link { dest_url, .. } => {
+                self.push_style(Modifier::UNDERLINED);
+                self.link = Some((dest_url.to_string(), self.spans.len()));
+            }
+            Tag::Image { .. } => self.text("[", Style::default()),
+            _ => {}
+        }
+    }
+
+    fn end(&mut self, tag: TagEnd) {
+        match tag {
+            TagEnd::Heading(level) => {
+                self.styles.pop();
+                let title: String = self.spans.drain(..).map(|s| s.content.into_owned()).collect();
+                let style = Style::default().add_modifier(Modifier::BOLD);
+                let line = match level {
+                    pulldown_cmark::HeadingLevel::H1 | pulldown_cmark::HeadingLevel::H2 => {
+                        Line::styled(title.to_uppercase(), style)
+                    }
+                    _ => {
+                        let indent = " ".repeat(self.options.indent as usize / 2);
+                        Line::from(vec![Span::raw(indent), Span::styled(title, style)])
+                    }
+                };
+                self.lines.push(line);
+                self.heading_end = self.lines.len();
+            }
+            TagEnd::Paragraph if self.table.is_none() => self.flush(),
+            TagEnd::BlockQuote(_) => {
+                self.flush();
+                self.indents.pop();
+            }
+            TagEnd::CodeBlock => {
+                let code = expand_tabs(&self.code.take().unwrap_or_default(), self.options.tab_size);
+                let indent = " ".repeat(self.indent() + 4);
+                for line in code.lines() {
+                    self.lines.push(Line::raw(format!("{indent}{line}")));
+                }
+            }
+            TagEnd::List(_) => {
+                self.flush();
+                self.lists.pop();
+            }
+            TagEnd::Item | TagEnd::FootnoteDefinition => {
+                self.flush();
+                self.tag = None;
+                self.indents.pop();
+            }
+            TagEnd::Table => self.table(),
+            TagEnd::Emphasis | TagEnd::Strong | TagEnd::Strikethrough => {
+                self.styles.pop();
+            }
+            TagEnd::Link => {
+                self.styles.pop();
+                if let Some((url, start)) = self.link.take() {
+                    let text: String = self.spans[start..].iter().map(|s| s.content.as_ref()).collect();
+                    if !url.starts_with('#') && text != url {
+                        self.text(&format!(" <{url}>"), Style::default());
+                    }
+                }
+            }
+            TagEnd::Image => self.text("]", Style::default()),
+            _ => {}
+        }
+    }
+
+    fn text(&mut self, text: &str, style: Style) {
+        if let Some(code) = &mut self.code {
+            code.push_str(text);
+            return;
+        }
+        if let Some(cell) = self.table.as_mut().and_then(|rows| rows.last_mut()?.last_mut()) {
+            cell.push_str(text);
+            return;
+        }
+        let style = self.styles.last().map_or(style, |s| s.patch(style));
+        self.spans.push(Span::styled(text.to_string(), style));
+    }
+
+    fn push_style(&mut self, modifier: Modifier) {
+        let style = self.styles.last().copied().unwrap_or_default().add_modifier(modifier);
+        self.styles.push(style);
+    }
+
+    fn indent(&self) -> usize {
+        self.indents.last().copied().unwrap_or(0)
+    }
+
+    /// Wraps the pending inline content into lines at the current indent,
+    /// hanging the list tag, if any, to its left.
+    fn flush(&mut self) {
+        if self.spans.is_empty() {
+            return;
+        }
+        let indent = self.indent();
+        let width = (self.options.width as usize).saturating_sub(indent).max(10);
+        let spans = std::mem::take(&mut self.spans);
+        for (i, row) in wrap_paragraph(&spans, width, width, None).into_iter().enumerate() {
+            let chars: Vec<_> = row.into_iter().map(|(c, style, _)| (c, style)).collect();
+            let margin = match self.tag.take() {
+                Some(tag) if i == 0 => {
+                    let start = self.indents.len().checked_sub(2).map_or(0, |i| self.indents[i]);
+                    format!("{}{tag:<w$}", " ".repeat(start), w = indent - start)
+                }
+                _ => " ".repeat(indent),
+            };
+            let mut line = vec![Span::raw(margin)];
+            line.extend(coalesce_chars(&chars));
+            self.lines.push(Line::from(line));
+        }
+    }
+
+    fn blank(&mut self) {
+        let after_text = self.lines.last().is_some_and(|l| !l.spans.is_empty());
+        if after_text && self.lines.len() != self.heading_end {
+            self.lines.push(Line::default());
+        }
+    }
+
+    /// Lays out the collected table as borderless, space-separated columns
+    /// with a bold header.
+    fn table(&mut self) {
+        let rows = self.table.take().unwrap_or_default();
+        let columns = rows.iter().map(Vec::len).max().unwrap_or(0);
+        let widths: Vec<usize> = (0..columns)
+            .map(|c| rows.iter().filter_map(|r| r.get(c)).map(|s| s.width()).max().unwrap_or(0))
+            .collect();
+        let indent = " ".repeat(self.indent());
+        for (i, row) in rows.iter().enumerate() {
+            let cells: Vec<String> = widths
+                .iter()
+                .enumerate()
+                .map(|(c, &w)| {
+                    let cell = row.get(c).map_or("", String::as_str);
+                    format!("{cell}{}", " ".repeat(w - cell.width()))
+                })
+                .collect();
+            let text = format!("{indent}{}", cells.join("  ").trim_end());
+            let style = match i {
+                0 => Style::default().add_modifier(Modifier::BOLD),
+                _ => Style::default(),
+            };
+            self.lines.push(Line::styled(text, style));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn plain(text: &Text) -> Vec<String> {
+        text.lines.iter().map(|l| l.to_string()).collect()
+    }
+
+    #[test]
+    fn test_sections_and_indented_paragraphs() {
+        let md = "# Name\n\nmdview - view markdown\n\n### Flags\n\nSome text.\n";
+        let lines = plain(&render_man(md, &ManOptions::new(80).indent(4)));
+        assert_eq!(lines, ["NAME", "    mdview - view markdown", "", "  Flags", "    Some text."]);
+    }
+
+    #[test]
+    fn test_list_items_hang_from_their_tag() {
+        let md = "- first item that wraps onto a second line\n- second\n\n1. one\n";
+        let lines = plain(&render_man(md, &ManOptions::new(30).indent(2)));
+        assert_eq!(lines[0], "  •   first item that wraps");
+        assert_eq!(lines[1], "      onto a second line");
+        assert_eq!(lines[2], "  •   second");
+        assert_eq!(lines[4], "  1.  one");
+    }
+
+    #[test]
+    fn test_inline_styles() {
+        let text = render_man("Run `mdview` *now*.\n", &ManOptions::new(80));
+        let spans = &text.lines[0].spans;
+        let code = spans.iter().find(|s| s.content == "mdview").unwrap();
+        assert!(code.style.add_modifier.contains(Modifier::BOLD));
+        let em = spans.iter().find(|s| s.content == "now").unwrap();
+        assert!(em.style.add_modifier.contains(Modifier::UNDERLINED));
+    }
+}
@@ -0,0 +1,837 @@
+use unicode_segmentation::UnicodeSegmentation as _;
+use unicode_width::UnicodeWidthStr;
+
+use crate::bench;
+
+/// Draws a mermaid diagram with box-drawing characters (or ASCII when
+/// `ascii` is set). Supports flowcharts (`graph`/`flowchart`, top-down or
+/// left-right, without cycles) and sequence diagrams made of participants
+/// and messages. Returns `None` for anything else, and for diagrams wider
+/// than `width` columns, so the caller can show the source instead.
+pub(crate) fn render_diagram(code: &str, width: usize, ascii: bool) -> Option<Vec<String>> {
+    let _span = bench::span("diagrams");
+    let mut lines = code
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with("%%"));
+    let header: Vec<&str> = lines.next()?.trim_end_matches(';').split_whitespace().collect();
+    let canvas = match header[..] {
+        ["graph" | "flowchart"] => flowchart("TD", lines)?,
+        ["graph" | "flowchart", direction] => flowchart(direction, lines)?,
+        ["sequenceDiagram"] => sequence(lines)?,
+        _ => return None,
+    };
+    let diagram = canvas.into_lines(ascii);
+    diagram.iter().all(|l| l.width() <= width).then_some(diagram)
+}
+
+const UP: u8 = 1;
+const DOWN: u8 = 2;
+const LEFT: u8 = 4;
+const RIGHT: u8 = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Cell {
+    /// Line segments leaving the cell, as `UP | DOWN | LEFT | RIGHT` bits,
+    /// so crossing and touching lines join up.
+    Lines(u8),
+    Char(char),
+    /// A grapheme cluster of several chars, such as an emoji ZWJ sequence,
+    /// by its index in `Canvas::clusters`.
+    Cluster(usize),
+    /// The right half of a double-width character.
+    Covered,
+}
+
+struct Canvas {
+    cells: Vec<Vec<Cell>>,
+    clusters: Vec<String>,
+}
+
+impl Canvas {
+    fn new(width: usize, height: usize) -> Self {
+        Self { cells: vec![vec![Cell::Lines(0); width]; height], clusters: Vec::new() }
+    }
+
+    fn line(&mut self, x: usize, y: usize, bits: u8) {
+        if let Some(cell) = self.cells.get_mut(y).and_then(|row| row.get_mut(x)) {
+            *cell = match *cell {
+                Cell::Lines(old) => Cell::Lines(old | bits),
+                _ => Cell::Lines(bits),
+            };
+        }
+    }
+
+    fn hline(&mut self, x0: usize, x1: usize, y: usize) {
+        for x in x0..=x1 {
+            self.line(x, y, LEFT | RIGHT);
+        }
+    }
+
+    fn vline(&mut self, x: usize, y0: usize, y1: usize) {
+        for y in y0..=y1 {
+            self.line(x, y, UP | DOWN);
+        }
+    }
+
+    fn put(&mut self, x: usize, y: usize, c: char) {
+        self.set(x, y, Cell::Char(c));
+    }
+
+    fn set(&mut self, x: usize, y: usize, new: Cell) {
+        if let Some(cell) = self.cells.get_mut(y).and_then(|row| row.get_mut(x)) {
+            *cell = new;
+        }
+    }
+
+    /// Writes `text` from `x` on, a cell per column it takes.
+    fn text(&mut self, mut x: usize, y: usize, text: &str) {
+        for cluster in text.graphemes(true) {
+            let mut chars = cluster.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => self.put(x, y, c),
+                _ => {
+                    self.set(x, y, Cell::Cluster(self.clusters.len()));
+                    self.clusters.push(cluster.to_string());
+                }
+            }
+            for covered in 1..cluster.width() {
+                self.set(x + covered, y, Cell::Covered);
+            }
+            x += cluster.width().max(1);
+        }
+    }
+
+    fn draw_box(&mut self, x: usize, y: usize, width: usize, label: &str, shape: Shape) {
+        let right = x + width - 1;
+        self.hline(x + 1, right - 1, y);
+        self.hline(x + 1, right - 1, y + 2);
+        self.line(x, y + 1, UP | DOWN);
+        self.line(right, y + 1, UP | DOWN);
+        self.text(x + 2, y + 1, label);
+        match shape {
+            Shape::Box => {
+                self.line(x, y, RIGHT | DOWN);
+                self.line(right, y, LEFT | DOWN);
+                self.line(x, y + 2, RIGHT | UP);
+                self.line(right, y + 2, LEFT | UP);
+            }
+            Shape::Round => {
+                self.put(x, y, '╭');
+                self.put(right, y, '╮');
+                self.put(x, y + 2, '╰');
+                self.put(right, y + 2, '╯');
+            }
+            Shape::Diamond => {
+                self.put(x, y, '╱');
+                self.put(right, y, '╲');
+                self.put(x, y + 2, '╲');
+                self.put(right, y + 2, '╱');
+            }
+        }
+    }
+
+    fn into_lines(self, ascii: bool) -> Vec<String> {
+        let clusters = self.clusters;
+        self.cells
+            .into_iter()
+            .map(|row| {
+                let line: String = row
+                    .into_iter()
+                    .filter_map(|cell| match cell {
+                        Cell::Lines(bits) => Some(line_char(bits, ascii).to_string()),
+                        Cell::Char(c) if ascii => Some(ascii_char(c).to_string()),
+                        Cell::Char(c) => Some(c.to_string()),
+                        Cell::Cluster(i) => Some(clusters[i].clone()),
+                        Cell::Covered => None,
+                    })
+                    .collect();
+                line.trim_end().to_string()
+            })
+            .collect()
+    }
+}
+
+fn line_char(bits: u8, ascii: bool) -> char {
+    const VERTICAL: u8 = UP | DOWN;
+    const HORIZONTAL: u8 = LEFT | RIGHT;
+    match (bits, ascii) {
+        (0, _) => ' ',
+        (UP | DOWN | VERTICAL, false) => '│',
+        (LEFT | RIGHT | HORIZONTAL, false) => '─',
+        (UP | DOWN | VERTICAL, true) => '|',
+        (LEFT | RIGHT | HORIZONTAL, true) => '-',
+        (_, true) => '+',
+        (b, false) if b == DOWN | RIGHT => '┌',
+        (b, false) if b == DOWN | LEFT => '┐',
+        (b, false) if b == UP | RIGHT => '└',
+        (b, false) if b == UP | LEFT => '┘',
+        (b, false) if b == VERTICAL | RIGHT => '├',
+        (b, false) if b == VERTICAL | LEFT => '┤',
+        (b, false) if b == HORIZONTAL | DOWN => '┬',
+        (b, false) if b == HORIZONTAL | UP => '┴',
+        (_, false) => '┼',
+    }
+}
+
+fn ascii_char(c: char) -> char {
+    match c {
+        '╭' | '╮' | '╰' | '╯' => '+',
+        '╱' => '/',
+        '╲' => '\\',
+        '╌' => '-',
+        '▼' => 'v',
+        '▶' => '>',
+        '◀' => '<',
+        c => c,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Shape {
+    Box,
+    Round,
+    Diamond,
+}
+
+#[derive(Debug)]
+struct Node {
+    id: String,
+    label: String,
+    shape: Shape,
+}
+
+#[derive(Debug)]
+struct Edge {
+    from: usize,
+    to: usize,
+    label: Option<String>,
+    arrow: bool,
+}
+
+#[derive(Debug, Default)]
+struct Graph {
+    nodes: Vec<Node>,
+    edges: Vec<Edge>,
+}
+
+fn flowchart<'a>(direction: &str, lines: impl Iterator<Item = &'a str>) -> Option<Canvas> {
+    let vertical = match direction {
+        "TD" | "TB" => true,
+        "LR" => false,
+        _ => return None,
+    };
+    let graph = parse_flowchart(lines)?;
+    let layout = Layout::new(&graph)?;
+    Some(match vertical {
+        true => layout.draw_vertical(&graph),
+        false => layout.draw_horizontal(&graph),
+    })
+}
+
+fn parse_flowchart<'a>(lines: impl Iterator<Item = &'a str>) -> Option<Graph> {
+    let mut graph = Graph::default();
+    for statement in lines.flat_map(|l| l.split(';')).map(str::trim).filter(|s| !s.is_empty()) {
+        let keyword = statement.split_whitespace().next().unwrap_or_default();
+        if matches!(
+            keyword,
+            "classDef" | "class" | "style" | "linkStyle" | "click" | "direction" | "subgraph" | "end"
+        ) {
+            continue;
+        }
+        parse_chain(statement, &mut graph)?;
+    }
+    (!graph.nodes.is_empty()).then_some(graph)
+}
+
+/// Parses `A[Label] --> B -->|text| C` into nodes and edges.
+fn parse_chain(statement: &str, graph: &mut Graph) -> Option<()> {
+    let mut rest = statement;
+    let mut link: Option<(Option<String>, bool)> = None;
+    let mut prev = None;
+    loop {
+        let (node, after) = parse_node(rest, graph)?;
+        if let Some((label, arrow)) = link.take() {
+            graph.edges.push(Edge { from: prev?, to: node, label, arrow });
+        }
+        prev = Some(node);
+        rest = after.trim_start();
+        if rest.is_empty() {
+            return Some(());
+        }
+        let (parsed, after) = parse_link(rest)?;
+        link = Some(parsed);
+        rest = after.trim_start();
+    }
+}
+
+/// Bracket pairs around node labels, longest opener first.
+const NODE_BRACKETS: &[(&str, &str, Shape)] = &[
+    ("(((", ")))", Shape::Round),
+    ("((", "))", Shape::Round),
+    ("([", "])", Shape::Round),
+    ("[(", ")]", Shape::Box),
+    ("[[", "]]", Shape::Box),
+    ("[/", "/]", Shape::Box),
+    ("[\\", "\\]", Shape::Box),
+    ("{{", "}}", Shape::Diamond),
+    ("(", ")", Shape::Round),
+    ("[", "]", Shape::Box),
+    ("{", "}", Shape::Diamond),
+    (">", "]", Shape::Box),
+];
+
+fn parse_node<'s>(s: &'s str, graph: &mut Graph) -> Option<(usize, &'s str)> {
+    let end = s.find(|c: char| !(c.is_alphanumeric() || c == '_')).unwrap_or(s.len());
+    if end == 0 {
+        return None;
+    }
+    let (id, mut rest) = s.split_at(end);
+    let mut label = None;
+    if let Some(&(open, close, shape)) = NODE_BRACKETS.iter().find(|(open, _, _)| rest.starts_with(open)) {
+        let inner = &rest[open.len()..];
+        let close_at = inner.find(close)?;
+        label = Some((inner[..close_at].trim().trim_matches('"').to_string(), shape));
+        rest = &inner[close_at + close.len()..];
+    }
+
+    let index = match graph.nodes.iter().position(|n| n.id == id) {
+        Some(index) => index,
+        None => {
+            graph.nodes.push(Node { id: id.to_string(), label: id.to_string(), shape: Shape::Box });
+            graph.nodes.len() - 1
+        }
+    };
+    if let Some((text, shape)) = label {
+        graph.nodes[index].label = text;
+        graph.nodes[index].shape = shape;
+    }
+    Some((index, rest))
+}
+
+fn is_link_char(c: char) -> bool {
+    matches!(c, '-' | '=' | '.' | '>' | '<')
+}
+
+/// Parses a link (`-->`, `---`, `==>`, `-.->`, ...) with an optional label
+/// written as `-->|label|` or `-- label -->`. Returns the label and whether
+/// the link has an arrowhead.
+fn parse_link(s: &str) -> Option<((Option<String>, bool), &str)> {
+    let len = s.find(|c| !is_link_char(c)).unwrap_or(s.len());
+    let token = &s[..len];
+    if token.len() < 2 || token.starts_with('.') {
+        return None;
+    }
+    let mut rest = &s[len..];
+    let mut arrow = token.ends_with('>');
+    let mut label = None;
+    if matches!(token, "--" | "==" | "-.") {
+        let close = ["-->", "---", "==>", "===", ".->", "-.-"]
+            .iter()
+            .filter_map(|pat| rest.find(pat))
+            .min()?;
+        label = Some(rest[..close].trim());
+        let after = &rest[close..];
+        let len = after.find(|c| !is_link_char(c)).unwrap_or(after.len());
+        arrow = after[..len].ends_with('>');
+        rest = &after[len..];
+    }
+    let trimmed = rest.trim_start();
+    if let Some(inner) = trimmed.strip_prefix('|') {
+        let end = inner.find('|')?;
+        label = Some(inner[..end].trim());
+        rest = &inner[end + 1..];
+    }
+    let label = label.filter(|l| !l.is_empty()).map(str::to_string);
+    Some(((label, arrow), rest))
+}
+
+/// A node, or a point an edge passes through on a rank between its ends.
+#[derive(Debug, Clone, Copy)]
+enum Item {
+    Node(usize),
+    Dummy,
+}
+
+/// One rank-to-rank piece of an edge.
+#[derive(Debug)]
+struct Segment {
+    from: usize,
+    to: usize,
+    edge: usize,
+    first: bool,
+}
+
+/// Nodes assigned to ranks (rows top-down, columns left-right) and ordered
+/// within them, with long edges split into one segment per rank.
+struct Layout {
+    items: Vec<Item>,
+    ranks: Vec<Vec<usize>>,
+    segments: Vec<Segment>,
+}
+
+impl Layout {
+    fn new(graph: &Graph) -> Option<Self> {
+        let count = graph.nodes.len();
+        let mut incoming = vec![0usize; count];
+        for edge in &graph.edges {
+            incoming[edge.to] += 1;
+        }
+        // Longest-path ranking in topological order; a cycle leaves nodes
+        // unvisited.
+        let mut rank = vec![0usize; count];
+        let mut ready: Vec<usize> = (0..count).rev().filter(|&n| incoming[n] == 0).collect();
+        let mut visited = 0;
+        while let Some(node) = ready.pop() {
+            visited += 1;
+            for edge in graph.edges.iter().filter(|e| e.from == node) {
+                rank[edge.to] = rank[edge.to].max(rank[node] + 1);
+                incoming[edge.to] -= 1;
+                if incoming[edge.to] == 0 {
+                    ready.push(edge.to);
+                }
+            }
+        }
+        if visited < count {
+            return None;
+        }
+
+        let mut items: Vec<Item> = (0..count).map(Item::Node).collect();
+        let mut item_rank = rank.clone();
+        let mut segments = Vec::new();
+        for (index, edge) in graph.edges.iter().enumerate() {
+            let mut prev = edge.from;
+            for r in rank[edge.from] + 1..rank[edge.to] {
+                items.push(Item::Dummy);
+                item_rank.push(r);
+                let dummy = items.len() - 1;
+                segments.push(Segment { from: prev, to: dummy, edge: index, first: prev == edge.from });
+                prev = dummy;
+            }
+            segments.push(Segment { from: prev, to: edge.to, edge: index, first: prev == edge.from });
+        }
+
+        let depth = rank.iter().max().map_or(0, |r| r + 1);
+        let mut ranks = vec![Vec::new(); depth];
+        for (item, &r) in item_rank.iter().enumerate() {
+            ranks[r].push(item);
+        }
+        // Order each rank by the mean position of its predecessors to cut
+        // down crossings.
+        for _ in 0..2 {
+            for r in 1..depth {
+                let mut position = vec![0.0; items.len()];
+                for (i, &item) in ranks[r - 1].iter().enumerate() {
+                    position[item] = i as f64;
+                }
+                let keys: Vec<(usize, f64)> = ranks[r]
+                    .iter()
+                    .enumerate()
+                    .map(|(i, &item)| {
+                        let preds: Vec<f64> =
+                            segments.iter().filter(|s| s.to == item).map(|s| position[s.from]).collect();
+                        let key = match preds.len() {
+                            0 => i as f64,
+                            n => preds.iter().sum::<f64>() / n as f64,
+                        };
+                        (item, key)
+                    })
+                    .collect();
+                let mut sorted = keys;
+                sorted.sort_by(|a, b| a.1.total_cmp(&b.1));
+                ranks[r] = sorted.into_iter().map(|(item, _)| item).collect();
+            }
+        }
+        Some(Self { items, ranks, segments })
+    }
+
+    fn box_width(&self, graph: &Graph, item: usize) -> usize {
+        match self.items[item] {
+            // Odd widths keep every box's center on a whole column, so
+            // stems between boxes of different widths line up.
+            Item::Node(n) => (graph.nodes[n].label.width() + 4) | 1,
+            Item::Dummy => 1,
+        }
+    }
+
+    fn node(&self, item: usize) -> Option<usize> {
+        match self.items[item] {
+            Item::Node(n) => Some(n),
+            Item::Dummy => None,
+        }
+    }
+
+    /// Ranks as rows, edges running down.
+    fn draw_vertical(&self, graph: &Graph) -> Canvas {
+        const GAP: usize = 3;
+        const ROW: usize = 3 + 3;
+        let rank_width = |rank: &Vec<usize>| {
+            rank.iter().map(|&i| self.box_width(graph, i)).sum::<usize>() + GAP * rank.len().saturating_sub(1)
+        };
+        let label_room = graph.edges.iter().filter_map(|e| e.label.as_ref()).map(|l| l.width() + 2).max();
+        let width = self.ranks.iter().map(rank_width).max().unwrap_or(0) + label_room.unwrap_or(0);
+        let mut canvas = Canvas::new(width, self.ranks.len() * ROW - GAP);
+
+        let mut x = vec![0; self.items.len()];
+        let mut y = vec![0; self.items.len()];
+        for (r, rank) in self.ranks.iter().enumerate() {
+            let mut left = (width - label_room.unwrap_or(0) - rank_width(rank)) / 2;
+            for &item in rank {
+                x[item] = left;
+                y[item] = r * ROW;
+                let w = self.box_width(graph, item);
+                match self.node(item) {
+                    Some(n) => canvas.draw_box(left, r * ROW, w, &graph.nodes[n].label, graph.nodes[n].shape),
+                    None => canvas.vline(left, r * ROW, r * ROW + 2),
+                }
+                left += w + GAP;
+            }
+        }
+        let center = |item: usize| x[item] + self.box_width(graph, item) / 2;
+
+        for segment in &self.segments {
+            let edge = &graph.edges[segment.edge];
+            let (sx, tx) = (center(segment.from), center(segment.to));
+            let bottom = y[segment.from] + 2;
+            canvas.line(sx, bottom, DOWN);
+            canvas.line(sx, bottom + 1, UP | DOWN);
+            if sx == tx {
+                canvas.line(sx, bottom + 2, UP | DOWN);
+            } else {
+                let (toward, back) = if tx > sx { (RIGHT, LEFT) } else { (LEFT, RIGHT) };
+                canvas.line(sx, bottom + 2, UP | toward);
+                canvas.hline(sx.min(tx) + 1, sx.max(tx) - 1, bottom + 2);
+                canvas.line(tx, bottom + 2, back | DOWN);
+            }
+            match self.node(segment.to) {
+                Some(_) if edge.arrow => canvas.put(tx, bottom + 3, '▼'),
+                Some(_) => {
+                    canvas.line(tx, bottom + 3, UP | DOWN);
+                    canvas.line(tx, bottom + 4, UP);
+                }
+                None => canvas.line(tx, bottom + 3, UP | DOWN),
+            }
+            if segment.first
+                && let Some(label) = &edge.label
+            {
+                canvas.text(tx + 2, bottom + 3, label);
+            }
+        }
+        canvas
+    }
+
+    /// Ranks as columns, edges running right.
+    fn draw_horizontal(&self, graph: &Graph) -> Canvas {
+        let column_width: Vec<usize> = self
+            .ranks
+            .iter()
+            .map(|rank| rank.iter().map(|&i| self.box_width(graph, i)).max().unwrap_or(1))
+            .collect();
+        let item_height = |item: usize| if self.node(item).is_some() { 3 } else { 1 };
+        let rank_height = |rank: &Vec<usize>| {
+            rank.iter().map(|&i| item_height(i)).sum::<usize>() + rank.len().saturating_sub(1)
+        };
+        // Each gap leaves room for the longest label of an edge leaving the
+        // column before it.
+        let gaps: Vec<usize> = (0..self.ranks.len())
+            .map(|r| {
+                let label = self
+                    .segments
+                    .iter()
+                    .filter(|s| s.first && self.ranks[r].contains(&s.from))
+                    .filter_map(|s| graph.edges[s.edge].label.as_ref())
+                    .map(|l| l.width() + 1)
+                    .max()
+                    .unwrap_or(0);
+                5 + label
+            })
+            .collect();
+        let height = self.ranks.iter().map(rank_height).max().unwrap_or(0);
+        let width = column_width.iter().sum::<usize>() + gaps[..gaps.len() - 1].iter().sum::<usize>();
+        let mut canvas = Canvas::new(width, height);
+
+        let mut x = vec![0; self.items.len()];
+        let mut y = vec![0; self.items.len()];
+        let mut column_x = Vec::new();
+        let mut left = 0;
+        for (r, rank) in self.ranks.iter().enumerate() {
+            column_x.push(left);
+            let mut top = (height - rank_height(rank)) / 2;
+            for &item in rank {
+                x[item] = left;
+                y[item] = top;
+                match self.node(item) {
+                    Some(n) => {
+                        let node = &graph.nodes[n];
+                        canvas.draw_box(left, top, self.box_width(graph, item), &node.label, node.shape);
+                    }
+                    None => canvas.hline(left, left + column_width[r] - 1, top),
+                }
+                top += item_height(item) + 1;
+            }
+            left += column_width[r] + gaps[r];
+        }
+        let rank_of = |item: usize| self.ranks.iter().position(|rank| rank.contains(&item)).unwrap_or(0);
+        let middle = |item: usize| y[item] + item_height(item) / 2;
+
+        for segment in &self.segments {
+            let edge = &graph.edges[segment.edge];
+            let r = rank_of(segment.from);
+            let (sy, ty) = (middle(segment.from), middle(segment.to));
+            let start = match self.node(segment.from) {
+                Some(_) => x[segment.from] + self.box_width(graph, segment.from) - 1,
+                None => x[segment.from] + column_width[r] - 1,
+            };
+            let bend = column_x[r] + column_width[r] + 1;
+            let target = x[segment.to];
+            canvas.line(start, sy, RIGHT);
+            if start + 1 < bend {
+                canvas.hline(start + 1, bend - 1, sy);
+            }
+            if sy == ty {
+                canvas.line(bend, sy, LEFT | RIGHT);
+            } else {
+                let (toward, back) = if ty > sy { (DOWN, UP) } else { (UP, DOWN) };
+                canvas.line(bend, sy, LEFT | toward);
+                canvas.vline(bend, sy.min(ty) + 1, sy.max(ty) - 1);
+                canvas.line(bend, ty, back | RIGHT);
+            }
+            canvas.hline(bend + 1, target - 1, ty);
+            match self.node(segment.to) {
+                Some(_) if edge.arrow => canvas.put(target - 1, ty, '▶'),
+                Some(_) => canvas.line(target, ty, LEFT),
+                None => {}
+            }
+            if segment.first
+                && let Some(label) = &edge.label
+            {
+                canvas.text(bend + 2, ty.saturating_sub(1), label);
+            }
+        }
+        canvas
+    }
+}
+
+struct Message {
+    from: usize,
+    to: usize,
+    text: String,
+    dashed: bool,
+    head: Option<char>,
+}
+
+/// Message arrows, longest first so `-->>` is not read as `-->`.
+const ARROWS: &[(&str, bool, Option<char>)] = &[
+    ("-->>", true, Some('▶')),
+    ("->>", false, Some('▶')),
+    ("-->", true, None),
+    ("->", false, None),
+    ("--x", true, Some('x')),
+    ("-x", false, Some('x')),
+    ("--)", true, Some('▶')),
+    ("-)", false, Some('▶')),
+];
+
+fn sequence<'a>(lines: impl Iterator<Item = &'a str>) -> Option<Canvas> {
+    let mut participants: Vec<(String, String)> = Vec::new();
+    let mut messages = Vec::new();
+    let participant = |id: &str, label: Option<&str>, participants: &mut Vec<(String, String)>| {
+        let index = match participants.iter().position(|(p, _)| p == id) {
+            Some(index) => index,
+            None => {
+                participants.push((id.to_string(), id.to_string()));
+                participants.len() - 1
+            }
+        };
+        if let Some(label) = label {
+            participants[index].1 = label.to_string();
+        }
+        index
+    };
+
+    for line in lines {
+        let keyword = line.split_whitespace().next().unwrap_or_default();
+        match keyword {
+            "participant" | "actor" => {
+                let rest = line[keyword.len()..].trim();
+                let (id, label) = match rest.split_once(" as ") {
+                    Some((id, label)) => (id.trim(), Some(label.trim())),
+                    None => (rest, None),
+                };
+                participant(id, label, &mut participants);
+            }
+            "autonumber" | "activate" | "deactivate" => {}
+            _ => {
+                let (arrow_part, text) = line.split_once(':').unwrap_or((line, ""));
+                let (at, &(arrow, dashed, head)) = ARROWS
+                    .iter()
+                    .filter_map(|a| arrow_part.find(a.0).map(|at| (at, a)))
+                    .min_by_key(|(at, a)| (*at, usize::MAX - a.0.len()))?;
+                let from = arrow_part[..at].trim();
+                let to = arrow_part[at + arrow.len()..].trim().trim_start_matches(['+', '-']).trim();
+                if from.is_empty() || to.is_empty() || from.contains(' ') || to.contains(' ') {
+                    return None;
+                }
+                let from = participant(from, None, &mut participants);
+                let to = participant(to, None, &mut participants);
+                messages.push(Message { from, to, text: text.trim().to_string(), dashed, head });
+            }
+        }
+    }
+    if participants.is_empty() {
+        return None;
+    }
+
+    // Lifeline columns: far enough apart for the boxes, then widened so
+    // every message label fits between its ends.
+    let box_width: Vec<usize> = participants.iter().map(|(_, label)| label.width() + 4).collect();
+    let mut center = vec![box_width[0] / 2];
+    for i in 1..participants.len() {
+        center.push(center[i - 1] + box_width[i - 1].div_ceil(2) + box_width[i] / 2 + 3);
+    }
+    for message in &messages {
+        let (a, b) = (message.from.min(message.to), message.from.max(message.to));
+        let need = match a == b {
+            true => 0,
+            false => message.text.width() + 4,
+        };
+        let have = center[b] - center[a];
+        if need > have {
+            for c in &mut center[b..] {
+                *c += need - have;
+            }
+        }
+    }
+    let last = participants.len() - 1;
+    let self_room = messages.iter().filter(|m| m.from == m.to).map(|m| m.text.width() + 2).max().unwrap_or(0);
+    let width = (center[last] + box_width[last].div_ceil(2)).max(center[last] + 4 + self_room);
+    let height = 3 + messages.iter().map(|m| if m.from == m.to { 3 } else { 2 }).sum::<usize>() + 1;
+    let mut canvas = Canvas::new(width, height);
+
+    for (i, (_, label)) in participants.iter().enumerate() {
+        canvas.draw_box(center[i] - box_width[i] / 2, 0, box_width[i], label, Shape::Box);
+        canvas.line(center[i], 2, DOWN);
+        canvas.vline(center[i], 3, height - 1);
+    }
+    let mut row = 3;
+    for message in &messages {
+        let (from, to) = (center[message.from], center[message.to]);
+        if from == to {
+            canvas.text(from + 2, row, &message.text);
+            canvas.line(from, row + 1, RIGHT);
+            canvas.hline(from + 1, from + 2, row + 1);
+            canvas.line(from + 3, row + 1, LEFT | DOWN);
+            canvas.line(from + 3, row + 2, UP | LEFT);
+            canvas.hline(from + 1, from + 2, row + 2);
+            match message.head {
+                Some(head) => canvas.put(from + 1, row + 2, if head == '▶' { '◀' } else { head }),
+                None => canvas.line(from, row + 2, RIGHT),
+            }
+            row += 3;
+            continue;
+        }
+        let (left, right) = (from.min(to), from.max(to));
+        let text_x = left + (right - left).saturating_sub(message.text.width()) / 2;
+        canvas.text(text_x, row, &message.text);
+        canvas.line(from, row + 1, if to > from { RIGHT } else { LEFT });
+        for x in left + 1..right {
+            match canvas.cells[row + 1][x] {
+                Cell::Lines(bits) if bits != 0 => canvas.line(x, row + 1, LEFT | RIGHT),
+                _ if message.dashed => canvas.put(x, row + 1, '╌'),
+                _ => canvas.line(x, row + 1, LEFT | RIGHT),
+            }
+        }
+        match message.head {
+            Some(head) if to > from => canvas.put(to - 1, row + 1, head),
+            Some(head) => canvas.put(to + 1, row + 1, if head == '▶' { '◀' } else { head }),
+            None => canvas.line(to, row + 1, if to > from { LEFT } else { RIGHT }),
+        }
+        row += 2;
+    }
+    Some(canvas)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn draw(code: &str) -> String {
+        render_diagram(code, 200, false).map(|lines| lines.join("\n")).unwrap_or_default()
+    }
+
+    #[test]
+    fn test_top_down_flowchart() {
+        let diagram = draw("graph TD\n  A[Start] --> B{Ok?}\n  B -->|yes| C(Done)\n");
+        let expected = [
+            "┌───────┐",
+            "│ Start │",
+            "└───┬───┘",
+            "    │",
+            "    │",
+            "    ▼",
+            " ╱─────╲",
+            " │ Ok? │",
+            " ╲──┬──╱",
+            "    │",
+            "    │",
+            "    ▼ yes",
+            "╭───────╮",
+            "│ Done  │",
+            "╰───────╯",
+        ];
+        assert_eq!(diagram, expected.join("\n"));
+    }
+
+    #[test]
+    fn test_clusters_take_their_width() {
+        let diagram = draw("graph TD\n  A[👨‍👩‍👧 Café]\n");
+        assert_eq!(diagram, ["┌─────────┐", "│ 👨‍👩‍👧 Café │", "└─────────┘"].join("\n"));
+    }
+
+    #[test]
+    fn test_left_right_flowchart_branches() {
+        let diagram = draw("flowchart LR\n  a --> b\n  a --> c\n");
+        let expected = [
+            "          ┌───┐",
+            "      ┌──▶│ b │",
+            "┌───┐ │   └───┘",
+            "│ a ├─┤",
+            "└───┘ │   ┌───┐",
+            "      └──▶│ c │",
+            "          └───┘",
+        ];
+        assert_eq!(diagram, expected.join("\n"));
+    }
+
+    #[test]
+    fn test_sequence_diagram() {
+        let diagram = draw("sequenceDiagram\n  participant A as Alice\n  A->>Bob: Hi\n  Bob-->>A: Hello\n");
+        let expected = [
+            "┌───────┐   ┌─────┐",
+            "│ Alice │   │ Bob │",
+            "└───┬───┘   └──┬──┘",
+            "    │   Hi     │",
+            "    ├─────────▶│",
+            "    │  Hello   │",
+            "    │◀╌╌╌╌╌╌╌╌╌┤",
+            "    │          │",
+        ];
+        assert_eq!(diagram, expected.join("\n"));
+    }
+
+    #[test]
+    fn test_unsupported_diagrams_fall_back() {
+        assert!(render_diagram("pie\n  \"a\": 1\n", 80, false).is_none());
+        assert!(render_diagram("graph TD\n  A --> B\n  B --> A\n", 80, false).is_none());
+        assert!(render_diagram("graph TD\n  A & B --> C\n", 80, false).is_none());
+        assert!(render_diagram("sequenceDiagram\n  loop Every minute\n  A->>B: x\n  end\n", 80, false).is_none());
+        assert!(render_diagram("graph LR\n  A[A very long label] --> B\n", 10, false).is_none());
+    }
+
+    #[test]
+    fn test_parse_link_labels() {
+        assert_eq!(parse_link("-->|yes| B").unwrap().0, (Some("yes".to_string()), true));
+        assert_eq!(parse_link("-- no --> B").unwrap().0, (Some("no".to_string()), true));
+        assert_eq!(parse_link("--- B").unwrap().0, (None, false));
+    }
+}
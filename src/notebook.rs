@@ -0,0 +1,174 @@
+use std::collections::HashMap;
+
+use anyhow::{Context, Result, bail};
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::csv::fenced;
+
+#[derive(Deserialize)]
+struct Notebook {
+    cells: Option<Vec<Cell>>,
+    #[serde(default)]
+    metadata: Metadata,
+}
+
+#[derive(Deserialize, Default)]
+struct Metadata {
+    language_info: Option<LanguageInfo>,
+    kernelspec: Option<KernelSpec>,
+}
+
+#[derive(Deserialize)]
+struct LanguageInfo {
+    name: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct KernelSpec {
+    language: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Cell {
+    cell_type: String,
+    #[serde(default)]
+    source: Source,
+    #[serde(default)]
+    outputs: Vec<Output>,
+}
+
+#[derive(Deserialize)]
+struct Output {
+    output_type: String,
+    text: Option<Source>,
+    #[serde(default)]
+    data: HashMap<String, Value>,
+    ename: Option<String>,
+    evalue: Option<String>,
+}
+
+/// Notebook text, stored either whole or as a list of lines.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Source {
+    Text(String),
+    Lines(Vec<String>),
+}
+
+impl Default for Source {
+    fn default() -> Self {
+        Source::Text(String::new())
+    }
+}
+
+impl Source {
+    fn text(&self) -> String {
+        match self {
+            Source::Text(text) => text.clone(),
+            Source::Lines(lines) => lines.concat(),
+        }
+    }
+}
+
+/// Turns a Jupyter notebook (nbformat 4) into markdown: markdown cells as
+/// they are, code cells fenced in the kernel's language and their text
+/// outputs in plain fences, with a rule between cells.
+///
+/// ```
+/// let ipynb = r##"{"cells": [
+///     {"cell_type": "markdown", "source": ["# Sums"]},
+///     {"cell_type": "code", "source": "1 + 1", "outputs": [
+///         {"output_type": "execute_result", "data": {"text/plain": ["2"]}}
+///     ]}
+/// ], "metadata": {"language_info": {"name": "python"}}}"##;
+/// let markdown = mdview::notebook::to_markdown(ipynb).unwrap();
+/// assert_eq!(markdown, "# Sums\n\n---\n\n```python\n1 + 1\n```\n\n```\n2\n```\n");
+/// ```
+pub fn to_markdown(input: &str) -> Result<String> {
+    let notebook: Notebook = serde_json::from_str(input).context("Not a Jupyter notebook")?;
+    let Some(cells) = notebook.cells else {
+        bail!("Only notebook format 4 is supported");
+    };
+    let metadata = notebook.metadata;
+    let language = (metadata.language_info.and_then(|l| l.name))
+        .or_else(|| metadata.kernelspec.and_then(|k| k.language))
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+
+    let mut blocks = Vec::new();
+    for cell in cells {
+        let source = cell.source.text();
+        let mut parts = Vec::new();
+        match cell.cell_type.as_str() {
+            "markdown" => parts.push(format!("{}\n", source.trim_end())),
+            "code" => {
+                parts.push(fenced(&source, &language));
+                parts.extend(cell.outputs.iter().filter_map(output));
+            }
+            _ => parts.push(fenced(&source, "")),
+        }
+        if source.trim().is_empty() && parts.len() == 1 {
+            continue;
+        }
+        blocks.push(parts.join("\n"));
+    }
+    Ok(blocks.join("\n---\n\n"))
+}
+
+/// The markdown for one output of a code cell: streams and plain-text
+/// results fenced, markdown results as they are, errors as `name: value`.
+/// Images and other rich outputs get a placeholder.
+fn output(output: &Output) -> Option<String> {
+    let data = |mime: &str| output.data.get(mime).and_then(|v| Source::deserialize(v).ok()).map(|s| s.text());
+    match output.output_type.as_str() {
+        "stream" => output.text.as_ref().map(|t| fenced(&t.text(), "")),
+        "error" => {
+            let name = output.ename.as_deref().unwrap_or("Error");
+            Some(fenced(&format!("{name}: {}", output.evalue.as_deref().unwrap_or_default()), ""))
+        }
+        _ => {
+            if let Some(markdown) = data("text/markdown") {
+                Some(format!("{}\n", markdown.trim_end()))
+            } else if let Some(text) = data("text/plain") {
+                Some(fenced(&text, ""))
+            } else if output.data.keys().any(|mime| mime.starts_with("image/")) {
+                Some("*[image output]*\n".to_string())
+            } else {
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_outputs_and_kernel_language() {
+        let ipynb = r#"{"cells": [
+            {"cell_type": "code", "source": ["print('hi')\n", "x"], "outputs": [
+                {"output_type": "stream", "name": "stdout", "text": ["hi\n"]},
+                {"output_type": "display_data", "data": {"image/png": "iVBOR", "text/plain": ["<Figure>"]}},
+                {"output_type": "display_data", "data": {"image/png": "iVBOR"}},
+                {"output_type": "error", "ename": "NameError", "evalue": "name 'x' is not defined",
+                 "traceback": ["\u001b[31m..."]}
+            ]},
+            {"cell_type": "code", "source": [], "outputs": []},
+            {"cell_type": "raw", "source": "raw text"}
+        ], "metadata": {"kernelspec": {"name": "ir", "language": "R"}}}"#;
+        let markdown = to_markdown(ipynb).unwrap();
+        assert_eq!(
+            markdown,
+            "```r\nprint('hi')\nx\n```\n\n```\nhi\n```\n\n```\n<Figure>\n```\n\n*[image output]*\n\n\
+             ```\nNameError: name 'x' is not defined\n```\n\n---\n\n```\nraw text\n```\n"
+        );
+    }
+
+    #[test]
+    fn test_rejects_other_json() {
+        assert!(to_markdown("{\"cells\": 3}").is_err());
+        assert!(to_markdown("{\"worksheets\": []}").unwrap_err().to_string().contains("format 4"));
+    }
+}
@@ -0,0 +1,196 @@
+//! The panels of `--vault`: the quick switcher, a query fuzzy-matched over
+//! the names of every note (narrowed to a tag by `:tag name`), and the
+//! notes linking to the current one.
+
+use mdview::vault::Backlink;
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use unicode_width::UnicodeWidthStr as _;
+
+use crate::outline::fuzzy_match;
+use crate::search::truncate;
+
+/// The quick switcher: a query typed over the names of the vault's notes,
+/// narrowing them to those that fuzzy-match, and the highlighted one. Each
+/// `:tag name` in the query keeps only the notes with that tag, or one
+/// nested under it; while the name is being typed, any tag it starts.
+#[derive(Default)]
+pub struct Switcher {
+    pub input: String,
+    /// The name of each note, as [`mdview::vault::name`] gives it, in the
+    /// order of the vault's notes.
+    pub names: Vec<String>,
+    /// The tags of each note, in the same order.
+    pub tags: Vec<Vec<String>>,
+    /// Index of the highlighted entry among the matches.
+    pub selected: usize,
+}
+
+impl Switcher {
+    /// The indices of the notes matching the query, best first, with the
+    /// character positions that matched; all of them, in order, while the
+    /// query is empty.
+    pub fn matches(&self) -> Vec<(usize, Vec<usize>)> {
+        let (wanted, query) = self.query();
+        let typed = wanted.len().saturating_sub(1);
+        let has = |tag: &String, (k, wanted): (usize, &&str)| match k == typed && self.typing_tag() {
+            true => tag.to_lowercase().starts_with(&wanted.trim_start_matches('#').to_lowercase()),
+            false => mdview::tags::matches(tag, wanted),
+        };
+        let tagged = |i: usize| {
+            let tags = self.tags.get(i).map_or(&[][..], Vec::as_slice);
+            wanted.iter().enumerate().all(|wanted| tags.iter().any(|tag| has(tag, wanted)))
+        };
+        let mut scored: Vec<(i32, (usize, Vec<usize>))> = self
+            .names
+            .iter()
+            .enumerate()
+            .filter(|&(i, _)| tagged(i))
+            .filter_map(|(i, name)| {
+                let (score, positions) = fuzzy_match(&query, name)?;
+                Some((score, (i, positions)))
+            })
+            .collect();
+        // Stable, so equal scores stay in order.
+        scored.sort_by_key(|(score, _)| -score);
+        scored.into_iter().map(|(_, entry)| entry).collect()
+    }
+
+    /// The tags the query asks for with `:tag`, and the rest of it.
+    fn query(&self) -> (Vec<&str>, String) {
+        let mut tags = Vec::new();
+        let mut rest = Vec::new();
+        let mut words = self.input.split_whitespace();
+        while let Some(word) = words.next() {
+            match word {
+                ":tag" => tags.extend(words.next()),
+                word => rest.push(word),
+            }
+        }
+        (tags, rest.join(" "))
+    }
+
+    /// Whether the query ends in the name of a `:tag` still being typed.
+    fn typing_tag(&self) -> bool {
+        let mut words = self.input.rsplit(char::is_whitespace);
+        words.next().is_some_and(|word| !word.is_empty()) && words.next() == Some(":tag")
+    }
+
+    /// Moves the highlight by `delta` among `count` matches, wrapping around.
+    pub fn select(&mut self, delta: isize, count: usize) {
+        if count > 0 {
+            self.selected = (self.selected.min(count - 1) as isize + delta).rem_euclid(count as isize) as usize;
+        }
+    }
+
+    /// The switcher's list: one line per match, with the matched characters
+    /// picked out, then its tags, and the highlighted one reversed.
+    pub fn lines(&self, entries: &[(usize, Vec<usize>)]) -> Vec<Line<'static>> {
+        let matched = Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD);
+        let dim = Style::default().fg(Color::DarkGray);
+        entries
+            .iter()
+            .enumerate()
+            .map(|(i, (note, positions))| {
+                let mut spans: Vec<Span> = self.names[*note]
+                    .chars()
+                    .enumerate()
+                    .map(|(j, c)| match positions.contains(&j) {
+                        true => Span::styled(c.to_string(), matched),
+                        false => Span::raw(c.to_string()),
+                    })
+                    .collect();
+                for tag in self.tags.get(*note).into_iter().flatten() {
+                    spans.push(Span::styled(format!("  #{tag}"), dim));
+                }
+                let line = Line::from(spans);
+                match i == self.selected {
+                    true => line.style(Style::default().add_modifier(Modifier::REVERSED)),
+                    false => line,
+                }
+            })
+            .collect()
+    }
+}
+
+/// The backlinks panel: the lines of other notes that link to the current
+/// one, and the highlighted one.
+#[derive(Default)]
+pub struct Backlinks {
+    pub links: Vec<Backlink>,
+    /// Index of the highlighted entry.
+    pub selected: usize,
+}
+
+impl Backlinks {
+    /// Moves the highlight by `delta`, wrapping around.
+    pub fn select(&mut self, delta: isize) {
+        let count = self.links.len();
+        if count > 0 {
+            self.selected = (self.selected.min(count - 1) as isize + delta).rem_euclid(count as isize) as usize;
+        }
+    }
+
+    /// The panel's list, `width` columns wide: one line per backlink, the
+    /// name of the linking note, then its line, and the highlighted one
+    /// reversed.
+    pub fn lines(&self, width: usize) -> Vec<Line<'static>> {
+        let name_width = (width / 3).min(24);
+        let dim = Style::default().fg(Color::DarkGray);
+        self.links
+            .iter()
+            .enumerate()
+            .map(|(i, link)| {
+                let name = truncate(&mdview::vault::name(&link.note), name_width);
+                let pad = " ".repeat(name_width - name.width());
+                let text = truncate(&link.text, width.saturating_sub(name_width + 2));
+                let spans = vec![Span::styled(format!("{name}{pad}"), dim), Span::raw("  "), Span::raw(text)];
+                let line = Line::from(spans);
+                match i == self.selected {
+                    true => line.style(Style::default().add_modifier(Modifier::REVERSED)),
+                    false => line,
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_switcher_matches_rank_best_first() {
+        let names = ["Home", "projects/Plan", "projects/old/plan"].map(String::from).to_vec();
+        let mut switcher = Switcher { names, ..Switcher::default() };
+        assert_eq!(switcher.matches().len(), 3);
+        switcher.input = "plan".to_string();
+        let found: Vec<usize> = switcher.matches().iter().map(|(i, _)| *i).collect();
+        assert_eq!(found, [1, 2]);
+        switcher.input = "oldp".to_string();
+        assert_eq!(switcher.matches(), [(2, vec![9, 10, 11, 13])]);
+        switcher.select(-1, 2);
+        assert_eq!(switcher.selected, 1);
+    }
+
+    #[test]
+    fn test_switcher_filters_by_tag() {
+        let names = ["Home", "projects/Plan", "projects/old/plan"].map(String::from).to_vec();
+        let tags = [vec![], vec!["project/mdview".to_string(), "todo".to_string()], vec!["project".to_string()]];
+        let switcher = |input: &str| {
+            let (names, tags) = (names.clone(), tags.to_vec());
+            let switcher = Switcher { input: input.to_string(), names, tags, selected: 0 };
+            switcher.matches().into_iter().map(|(i, _)| i).collect::<Vec<_>>()
+        };
+        assert_eq!(switcher(":tag project "), [1, 2]);
+        assert_eq!(switcher(":tag Project/mdview "), [1]);
+        // The name being typed matches the tags it starts.
+        assert_eq!(switcher(":tag to"), [1]);
+        assert!(switcher(":tag to ").is_empty());
+        assert!(switcher(":tag proj old").is_empty());
+        assert_eq!(switcher(":tag project old"), [2]);
+        assert_eq!(switcher(":tag project :tag todo"), [1]);
+        assert_eq!(switcher(":tag "), [0, 1, 2]);
+        assert!(switcher(":tag rust ").is_empty());
+    }
+}
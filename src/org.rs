@@ -0,0 +1,299 @@
+use crate::csv::fenced;
+use crate::input::markdown_table;
+use crate::render::slugify;
+
+/// Turns an Org-mode document into markdown. Covers headings (tags
+/// dropped; a `#+TITLE` becomes the top heading), lists and checkboxes,
+/// `#+BEGIN_SRC`/`EXAMPLE`/`QUOTE` blocks, `:` fixed-width lines, tables,
+/// rules, links and `*bold*`, `/italic/`, `+strike+`, `=verbatim=` and
+/// `~code~`. Other keywords, comments and drawers are dropped.
+///
+/// ```
+/// let org = "#+TITLE: Notes\n* TODO Plan :work:\n- [X] /read/ the [[https://orgmode.org][manual]]\n";
+/// let markdown = mdview::org::to_markdown(org);
+/// assert_eq!(markdown, "# Notes\n\n## TODO Plan\n\n- [x] *read* the [manual](https://orgmode.org)\n");
+/// ```
+pub fn to_markdown(input: &str) -> String {
+    let lines: Vec<&str> = input.lines().collect();
+    let title = lines.iter().find_map(|line| keyword(line, "TITLE"));
+    let mut out = Vec::new();
+    if let Some(title) = title {
+        out.push(format!("# {}", inline(title)));
+        out.push(String::new());
+    }
+    let shift = usize::from(title.is_some());
+    let mut in_list = false;
+    let mut i = 0;
+    while i < lines.len() {
+        let line = lines[i];
+        let trimmed = line.trim_start();
+        i += 1;
+
+        if let Some((kind, arg)) = block_start(trimmed) {
+            let kind = kind.to_ascii_uppercase();
+            let end = format!("#+END_{kind}");
+            let start = i;
+            while i < lines.len() && !lines[i].trim_start().to_ascii_uppercase().starts_with(&end) {
+                i += 1;
+            }
+            let body = dedent(&lines[start..i]);
+            i += 1;
+            match kind.as_str() {
+                "SRC" => out.push(fenced(&body, arg)),
+                "QUOTE" => out.extend(to_markdown(&body).lines().map(|l| format!("> {l}").trim_end().to_string())),
+                "EXAMPLE" | "VERSE" => out.push(fenced(&body, "")),
+                _ => out.push(to_markdown(&body)),
+            }
+            trim_last(&mut out);
+            in_list = false;
+        } else if trimmed.starts_with("#+") || trimmed == "#" || trimmed.starts_with("# ") {
+            // Keywords and comments.
+        } else if is_drawer(trimmed) {
+            while i < lines.len() && !lines[i].trim().eq_ignore_ascii_case(":END:") {
+                i += 1;
+            }
+            i += 1;
+        } else if let Some(heading) = heading(line) {
+            let (level, title) = heading;
+            out.push(format!("{} {}", "#".repeat((level + shift).min(6)), inline(title)));
+            out.push(String::new());
+            in_list = false;
+        } else if trimmed == ":" || trimmed.starts_with(": ") {
+            let start = i - 1;
+            while i < lines.len() && (lines[i].trim() == ":" || lines[i].trim_start().starts_with(": ")) {
+                i += 1;
+            }
+            let body: Vec<&str> =
+                lines[start..i].iter().map(|l| l.trim_start()[1..].strip_prefix(' ').unwrap_or("")).collect();
+            out.push(fenced(&body.join("\n"), ""));
+            trim_last(&mut out);
+        } else if trimmed.starts_with('|') {
+            let start = i - 1;
+            while i < lines.len() && lines[i].trim_start().starts_with('|') {
+                i += 1;
+            }
+            out.push(table(&lines[start..i]));
+            trim_last(&mut out);
+        } else if trimmed.len() >= 5 && trimmed.bytes().all(|b| b == b'-') {
+            out.push("---".to_string());
+        } else if let Some(item) = list_item(line) {
+            out.push(item);
+            in_list = true;
+        } else if trimmed.is_empty() {
+            out.push(String::new());
+        } else {
+            // Indented text continues a list item; elsewhere indentation
+            // would make it a code block.
+            let indent = if in_list { line.len() - trimmed.len() } else { 0 };
+            in_list &= indent > 0;
+            out.push(format!("{}{}", " ".repeat(indent), inline(trimmed)));
+        }
+    }
+    let mut markdown = out.join("\n");
+    while markdown.ends_with('\n') {
+        markdown.pop();
+    }
+    markdown.push('\n');
+    markdown
+}
+
+/// Drops the newline that ends the last block of `out`, which is joined
+/// with newlines.
+fn trim_last(out: &mut [String]) {
+    if let Some(last) = out.last_mut() {
+        last.truncate(last.trim_end_matches('\n').len());
+    }
+}
+
+/// The value of `#+NAME: value`, case-insensitively.
+fn keyword<'a>(line: &'a str, name: &str) -> Option<&'a str> {
+    let rest = line.trim_start().strip_prefix("#+")?;
+    let head = rest.get(..name.len())?;
+    let value = rest[name.len()..].strip_prefix(':')?;
+    head.eq_ignore_ascii_case(name).then(|| value.trim())
+}
+
+/// The block kind and first argument of `#+BEGIN_KIND arg`, such as
+/// `("SRC", "rust")`.
+fn block_start(line: &str) -> Option<(&str, &str)> {
+    let rest = line.strip_prefix("#+")?;
+    let head = rest.get(..6)?;
+    if !head.eq_ignore_ascii_case("BEGIN_") {
+        return None;
+    }
+    let mut words = rest[6..].split_whitespace();
+    Some((words.next()?, words.next().unwrap_or("")))
+}
+
+/// Whether `line` opens a drawer such as `:PROPERTIES:`, which runs to
+/// `:END:`.
+fn is_drawer(line: &str) -> bool {
+    let line = line.trim_end();
+    line.len() > 2
+        && line.starts_with(':')
+        && line.ends_with(':')
+        && line[1..line.len() - 1].bytes().all(|b| b.is_ascii_alphabetic() || b == b'_' || b == b'-')
+        && !line.eq_ignore_ascii_case(":END:")
+}
+
+/// The level and title of a `** Title :tag:` heading line.
+fn heading(line: &str) -> Option<(usize, &str)> {
+    let level = line.bytes().take_while(|&b| b == b'*').count();
+    let title = line[level..].strip_prefix(' ')?.trim();
+    if level == 0 {
+        return None;
+    }
+    // Trailing tags look like `:a:b:`.
+    let title = match title.rsplit_once(char::is_whitespace) {
+        Some((rest, tags)) if tags.len() > 2 && tags.starts_with(':') && tags.ends_with(':') => rest.trim_end(),
+        _ if title.len() > 2 && title.starts_with(':') && title.ends_with(':') => "",
+        _ => title,
+    };
+    Some((level, title))
+}
+
+/// A list item as markdown: `+` and indented `*` bullets become `-`, `1)`
+/// becomes `1.`, `[X]` becomes `[x]` and `term :: text` a bold term.
+fn list_item(line: &str) -> Option<String> {
+    let trimmed = line.trim_start();
+    let indent = &line[..line.len() - trimmed.len()];
+    let (marker, rest) = if let Some(rest) = trimmed.strip_prefix("- ").or_else(|| trimmed.strip_prefix("+ ")) {
+        ("-".to_string(), rest)
+    } else if let Some(rest) = trimmed.strip_prefix("* ").filter(|_| !indent.is_empty()) {
+        ("-".to_string(), rest)
+    } else {
+        let digits = trimmed.bytes().take_while(u8::is_ascii_digit).count();
+        let rest = trimmed[digits..].strip_prefix(". ").or_else(|| trimmed[digits..].strip_prefix(") "));
+        (format!("{}.", &trimmed[..digits]), rest.filter(|_| digits > 0)?)
+    };
+    let (check, rest) = match rest.get(..4) {
+        Some("[X] " | "[x] ") => ("[x] ", &rest[4..]),
+        Some("[ ] " | "[-] ") => ("[ ] ", &rest[4..]),
+        _ => ("", rest),
+    };
+    let text = match rest.split_once(" :: ") {
+        Some((term, text)) => format!("**{}**: {}", inline(term), inline(text)),
+        None => inline(rest),
+    };
+    Some(format!("{indent}{marker} {check}{text}"))
+}
+
+/// An Org table as a markdown table. Rules (`|---+---|`) are dropped; the
+/// first row is the header.
+fn table(lines: &[&str]) -> String {
+    let rows: Vec<Vec<String>> = lines
+        .iter()
+        .map(|line| line.trim())
+        .filter(|line| !line.starts_with("|-"))
+        .map(|line| {
+            let inner = line.trim_start_matches('|');
+            let inner = inner.strip_suffix('|').unwrap_or(inner);
+            inner.split('|').map(|cell| inline(cell.trim())).collect()
+        })
+        .collect();
+    markdown_table(&rows)
+}
+
+/// Markdown for the Org inline markup in `text`.
+fn inline(text: &str) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let mut out = String::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let rest: String = chars[i..].iter().collect();
+        if rest.starts_with("[[")
+            && let Some(end) = rest.find("]]")
+        {
+            out.push_str(&link(&rest[2..end]));
+            i += rest[..end + 2].chars().count();
+            continue;
+        }
+        let c = chars[i];
+        let opens = "*/=~+".contains(c)
+            && (i == 0 || chars[i - 1].is_whitespace() || "-({'\"".contains(chars[i - 1]))
+            && chars.get(i + 1).is_some_and(|n| !n.is_whitespace());
+        let close = (i + 2..chars.len()).find(|&j| {
+            chars[j] == c
+                && !chars[j - 1].is_whitespace()
+                && chars.get(j + 1).is_none_or(|n| n.is_whitespace() || "-.,:!?;'\")}[".contains(*n))
+        });
+        match close.filter(|_| opens) {
+            Some(j) => {
+                let inner: String = chars[i + 1..j].iter().collect();
+                out.push_str(&match c {
+                    '=' | '~' if inner.contains('`') => format!("`` {inner} ``"),
+                    '=' | '~' => format!("`{inner}`"),
+                    '*' => format!("**{}**", inline(&inner)),
+                    '/' => format!("*{}*", inline(&inner)),
+                    _ => format!("~~{}~~", inline(&inner)),
+                });
+                i = j + 1;
+            }
+            None => {
+                out.push(c);
+                i += 1;
+            }
+        }
+    }
+    out
+}
+
+/// A markdown link for the inside of `[[target][description]]`. Targets
+/// `*Heading` and `#id` point at the heading's anchor, `file:` is
+/// dropped, and an image target without a description becomes an image.
+fn link(inner: &str) -> String {
+    let (target, description) = match inner.split_once("][") {
+        Some((target, description)) => (target, Some(description)),
+        None => (inner, None),
+    };
+    let target = target.strip_prefix("file:").unwrap_or(target);
+    if let Some(heading) = target.strip_prefix('*') {
+        return format!("[{}](#{})", inline(description.unwrap_or(heading)), slugify(heading));
+    }
+    let lower = target.to_ascii_lowercase();
+    let is_image = [".png", ".jpg", ".jpeg", ".gif", ".svg"].iter().any(|ext| lower.ends_with(ext));
+    match description {
+        None if is_image => format!("![]({target})"),
+        None => format!("[{target}]({target})"),
+        Some(description) => format!("[{}]({target})", inline(description)),
+    }
+}
+
+/// `lines` joined with the indentation they share removed.
+fn dedent(lines: &[&str]) -> String {
+    let indent = (lines.iter().filter(|l| !l.trim().is_empty()))
+        .map(|l| l.len() - l.trim_start().len())
+        .min()
+        .unwrap_or(0);
+    let lines: Vec<&str> = lines.iter().map(|l| l.get(indent..).unwrap_or("")).collect();
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_blocks() {
+        let org = "* Setup\n:PROPERTIES:\n:ID: 1\n:END:\n# a comment\n\
+                   #+BEGIN_SRC sh\n  cargo run\n#+END_SRC\n\n: fixed\n: width\n\n\
+                   #+begin_quote\nQuoted *text*.\n#+end_quote\n\n-----\n";
+        assert_eq!(
+            to_markdown(org),
+            "# Setup\n\n```sh\ncargo run\n```\n\n```\nfixed\nwidth\n```\n\n> Quoted **text**.\n\n---\n"
+        );
+    }
+
+    #[test]
+    fn test_lists_tables_and_links() {
+        let org = "1) first\n   continued\n   * nested =code=\n- Term :: meaning\n\n\
+                   | Name | Qty |\n|------+-----|\n| tea | 2 |\n\n\
+                   See [[*Setup][setup]], [[file:img.png]] and a/b/c or 2*3*4.\n";
+        assert_eq!(
+            to_markdown(org),
+            "1. first\n   continued\n   - nested `code`\n- **Term**: meaning\n\n\
+             | Name | Qty |\n|---|---|\n| tea | 2 |\n\n\
+             See [setup](#setup), ![](img.png) and a/b/c or 2*3*4.\n"
+        );
+    }
+}
@@ -0,0 +1,158 @@
+use mdview::Heading;
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+
+/// The heading palette: a query typed over the list of headings, narrowing
+/// it to those that fuzzy-match, and the highlighted one.
+#[derive(Default)]
+pub struct Outline {
+    pub input: String,
+    /// Index of the highlighted entry among the matches.
+    pub selected: usize,
+}
+
+/// A heading matching the query, with the character positions that matched.
+pub struct Entry {
+    /// Index into the headings.
+    pub heading: usize,
+    pub positions: Vec<usize>,
+}
+
+impl Outline {
+    /// The headings matching the query, best first; all of them, in document
+    /// order, while the query is empty.
+    pub fn matches(&self, headings: &[Heading]) -> Vec<Entry> {
+        let mut scored: Vec<(i32, Entry)> = headings
+            .iter()
+            .enumerate()
+            .filter_map(|(i, h)| {
+                let (score, positions) = fuzzy_match(&self.input, &h.title)?;
+                Some((score, Entry { heading: i, positions }))
+            })
+            .collect();
+        // Stable, so equal scores stay in document order.
+        scored.sort_by_key(|(score, _)| -score);
+        scored.into_iter().map(|(_, entry)| entry).collect()
+    }
+
+    /// Moves the highlight by `delta` among `count` matches, wrapping around.
+    pub fn select(&mut self, delta: isize, count: usize) {
+        if count > 0 {
+            self.selected = (self.selected.min(count - 1) as isize + delta).rem_euclid(count as isize) as usize;
+        }
+    }
+
+    /// The palette's list: one line per match, indented by heading level
+    /// and numbered when headings are, with the matched characters picked
+    /// out and the highlighted one reversed.
+    pub fn lines(&self, headings: &[Heading], entries: &[Entry]) -> Vec<Line<'static>> {
+        let matched = Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD);
+        entries
+            .iter()
+            .enumerate()
+            .map(|(i, entry)| {
+                let heading = &headings[entry.heading];
+                let mut spans = vec![Span::raw("  ".repeat(heading.level.saturating_sub(1) as usize))];
+                if let Some(number) = &heading.number {
+                    spans.push(Span::styled(format!("{number} "), Style::default().fg(Color::DarkGray)));
+                }
+                for (j, c) in heading.title.chars().enumerate() {
+                    let style = match entry.positions.contains(&j) {
+                        true => matched,
+                        false => Style::default(),
+                    };
+                    spans.push(Span::styled(c.to_string(), style));
+                }
+                let line = Line::from(spans);
+                match i == self.selected {
+                    true => line.style(Style::default().add_modifier(Modifier::REVERSED)),
+                    false => line,
+                }
+            })
+            .collect()
+    }
+}
+
+/// Whether the characters of `query` appear in order in `text`, and if so
+/// a score and the character positions of the best placement found. Runs
+/// of consecutive characters and matches at the start of a word score
+/// higher. Matching is smart-case, like search.
+pub fn fuzzy_match(query: &str, text: &str) -> Option<(i32, Vec<usize>)> {
+    let case_sensitive = query.chars().any(char::is_uppercase);
+    let fold = |c: char| if case_sensitive { c } else { c.to_lowercase().next().unwrap_or(c) };
+    let needle: Vec<char> = query.chars().filter(|c| !c.is_whitespace()).map(fold).collect();
+    let hay: Vec<char> = text.chars().collect();
+    let Some(&first) = needle.first() else {
+        return Some((0, Vec::new()));
+    };
+
+    let word_start = |i: usize| i == 0 || !hay[i - 1].is_alphanumeric();
+    let mut best: Option<(i32, Vec<usize>)> = None;
+    // Each place the first character occurs starts a greedy placement of the
+    // rest; the best-scoring one wins.
+    for start in (0..hay.len()).filter(|&i| fold(hay[i]) == first) {
+        let mut positions = vec![start];
+        let mut at = start + 1;
+        for &c in &needle[1..] {
+            match (at..hay.len()).find(|&i| fold(hay[i]) == c) {
+                Some(i) => {
+                    positions.push(i);
+                    at = i + 1;
+                }
+                None => break,
+            }
+        }
+        if positions.len() < needle.len() {
+            // Starting later only leaves less room.
+            break;
+        }
+        let mut score = 0;
+        for (k, &i) in positions.iter().enumerate() {
+            score += 1;
+            if k > 0 && positions[k - 1] + 1 == i {
+                score += 4;
+            }
+            if word_start(i) {
+                score += 3;
+            }
+        }
+        if best.as_ref().is_none_or(|(s, _)| score > *s) {
+            best = Some((score, positions));
+        }
+    }
+    best
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn heading(level: u8, title: &str) -> Heading {
+        Heading { line: 0, level, title: title.to_string(), source_title: title.to_string(), id: None, number: None }
+    }
+
+    #[test]
+    fn test_fuzzy_match() {
+        assert_eq!(fuzzy_match("inst", "Installation").map(|m| m.1), Some(vec![0, 1, 2, 3]));
+        assert_eq!(fuzzy_match("cfg", "Config file").map(|m| m.1), Some(vec![0, 3, 5]));
+        assert!(fuzzy_match("xyz", "Installation").is_none());
+        assert!(fuzzy_match("Inst", "installation").is_none());
+        // A placement at word starts beats the first one found.
+        assert_eq!(fuzzy_match("keys", "Key bindings, keys").map(|m| m.1), Some(vec![14, 15, 16, 17]));
+    }
+
+    #[test]
+    fn test_matches_rank_best_first() {
+        let headings = [heading(1, "Usage"), heading(2, "Key bindings"), heading(2, "Keys")];
+        let mut outline = Outline::default();
+        assert_eq!(outline.matches(&headings).len(), 3);
+        outline.input = "keys".to_string();
+        let found: Vec<usize> = outline.matches(&headings).iter().map(|e| e.heading).collect();
+        assert_eq!(found, [2, 1]);
+        outline.input = "k".to_string();
+        let found: Vec<usize> = outline.matches(&headings).iter().map(|e| e.heading).collect();
+        assert_eq!(found, [1, 2]);
+        outline.select(-1, found.len());
+        assert_eq!(outline.selected, 1);
+    }
+}
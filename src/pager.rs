@@ -0,0 +1,77 @@
+//! `dump --pager`: output taller than the terminal goes through `$PAGER`,
+//! for scrollback without the viewer's alternate screen. Every command that
+//! prints to stdout writes through here, so a reader that goes away early
+//! (`| head`) ends the output quietly instead of with an error.
+
+use std::io::{self, IsTerminal as _};
+use std::process::{Command, Stdio};
+use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
+
+use anyhow::{Context, Result};
+
+/// Writes `output` to stdout, through the pager when `page` is set and
+/// stdout is a terminal the output does not fit on. A pager that cannot be
+/// started is skipped.
+pub fn write(output: &[u8], page: bool) -> Result<()> {
+    let stdout = io::stdout();
+    let rows = crossterm::terminal::size().map_or(usize::MAX, |(_, rows)| rows as usize);
+    let lines = output.iter().filter(|&&b| b == b'\n').count();
+    let child = match page && stdout.is_terminal() && lines >= rows {
+        true => command(&std::env::var("PAGER").unwrap_or_default()).stdin(Stdio::piped()).spawn().ok(),
+        false => None,
+    };
+    let Some(mut child) = child else {
+        return write_all(&mut stdout.lock(), output);
+    };
+    // Ctrl-C is for the pager, which stops scrolling or quits on it; mdview
+    // waits for it rather than exiting under it and leaving the terminal to
+    // both.
+    let hook = signal_hook::flag::register(signal_hook::consts::SIGINT, Arc::new(AtomicBool::new(false)))?;
+    let written = child.stdin.take().map_or(Ok(()), |mut stdin| write_all(&mut stdin, output));
+    let status = child.wait();
+    signal_hook::low_level::unregister(hook);
+    written?;
+    status.context("Cannot run the pager")?;
+    Ok(())
+}
+
+/// The command for a `$PAGER` value: `less` when it is empty, with `-R` put
+/// in for less so the styling shows instead of its escapes.
+fn command(pager: &str) -> Command {
+    let mut words = pager.split_whitespace();
+    let program = words.next().unwrap_or("less");
+    let mut command = Command::new(program);
+    command.args(words);
+    if std::path::Path::new(program).file_name().is_some_and(|name| name == "less") {
+        command.arg("-R");
+    }
+    command
+}
+
+/// Writes `output` to `out`, stopping quietly when the reader has gone,
+/// as when the pager is quit before the end.
+fn write_all(out: &mut impl io::Write, output: &[u8]) -> Result<()> {
+    match out.write_all(output).and_then(|()| out.flush()) {
+        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+        result => Ok(result?),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_command_adds_raw_control_chars_for_less() {
+        let args = |pager| {
+            let command = command(pager);
+            let mut words = vec![command.get_program().to_string_lossy().into_owned()];
+            words.extend(command.get_args().map(|a| a.to_string_lossy().into_owned()));
+            words
+        };
+        assert_eq!(args(""), ["less", "-R"]);
+        assert_eq!(args("/usr/bin/less -S"), ["/usr/bin/less", "-S", "-R"]);
+        assert_eq!(args("more"), ["more"]);
+    }
+}
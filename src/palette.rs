@@ -0,0 +1,217 @@
+//! Colors for terminals without 24-bit color. Syntax highlighting, some
+//! themes and half-block images use RGB colors, which a 256- or 16-color
+//! terminal shows wrongly or not at all; they are mapped to the nearest
+//! palette entry when drawn, or for the basic 16 to the nearest in hue.
+//!
+//! ```
+//! use mdview::palette::{ColorDepth, quantize};
+//! use ratatui::style::Color;
+//! assert_eq!(quantize(Color::Rgb(255, 0, 0), ColorDepth::Ansi256), Color::Indexed(196));
+//! assert_eq!(quantize(Color::Rgb(250, 10, 20), ColorDepth::Ansi16), Color::LightRed);
+//! ```
+
+use ratatui::buffer::Buffer;
+use ratatui::style::Color;
+use serde::Deserialize;
+
+use crate::export::rgb;
+
+/// The 16 colors of the basic palette, by index.
+pub(crate) const ANSI: [Color; 16] = [
+    Color::Black, Color::Red, Color::Green, Color::Yellow,
+    Color::Blue, Color::Magenta, Color::Cyan, Color::Gray,
+    Color::DarkGray, Color::LightRed, Color::LightGreen, Color::LightYellow,
+    Color::LightBlue, Color::LightMagenta, Color::LightCyan, Color::White,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum ColorDepth {
+    #[serde(rename = "auto")]
+    Auto,
+    #[serde(rename = "truecolor", alias = "24bit")]
+    TrueColor,
+    #[serde(rename = "256")]
+    Ansi256,
+    #[serde(rename = "16")]
+    Ansi16,
+}
+
+impl ColorDepth {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "auto" => Some(Self::Auto),
+            "truecolor" | "24bit" => Some(Self::TrueColor),
+            "256" => Some(Self::Ansi256),
+            "16" => Some(Self::Ansi16),
+            _ => None,
+        }
+    }
+
+    /// The depth to draw with: `Auto` resolved by `detect`.
+    pub fn resolve(self) -> Self {
+        match self {
+            Self::Auto => detect(),
+            depth => depth,
+        }
+    }
+}
+
+/// Guesses the color depth from environment variables: `COLORTERM` set to
+/// `truecolor` or `24bit`, or a terminal known to support it, has 24-bit
+/// color; otherwise a `TERM` ending in `256color` has 256 colors and any
+/// other 16. Without `TERM` (not a Unix terminal) colors are left alone.
+pub fn detect() -> ColorDepth {
+    let var = |k: &str| std::env::var(k).unwrap_or_default();
+    let term = var("TERM");
+    if matches!(var("COLORTERM").to_ascii_lowercase().as_str(), "truecolor" | "24bit")
+        || crate::images::detect().is_some()
+        || term.is_empty()
+    {
+        ColorDepth::TrueColor
+    } else if term.ends_with("256color") {
+        ColorDepth::Ansi256
+    } else {
+        ColorDepth::Ansi16
+    }
+}
+
+/// `color` as the nearest color the terminal has at `depth`. Named colors
+/// are left alone; indexed colors go to the basic 16 only at `Ansi16`,
+/// by [`nearest_16`].
+pub fn quantize(color: Color, depth: ColorDepth) -> Color {
+    match (color, depth) {
+        (_, ColorDepth::Auto | ColorDepth::TrueColor) => color,
+        (Color::Rgb(r, g, b), ColorDepth::Ansi256) => Color::Indexed(nearest_256((r, g, b))),
+        (Color::Indexed(i @ 0..=15), ColorDepth::Ansi16) => ANSI[i as usize],
+        (Color::Rgb(..) | Color::Indexed(_), ColorDepth::Ansi16) => nearest_16(rgb(color).unwrap_or_default()),
+        _ => color,
+    }
+}
+
+/// Quantizes the colors of every cell of a drawn frame.
+pub fn quantize_buffer(buffer: &mut Buffer, depth: ColorDepth) {
+    if matches!(depth, ColorDepth::Auto | ColorDepth::TrueColor) {
+        return;
+    }
+    for cell in &mut buffer.content {
+        cell.fg = quantize(cell.fg, depth);
+        cell.bg = quantize(cell.bg, depth);
+    }
+}
+
+/// The index of the entry of the 6×6×6 color cube or the gray ramp of the
+/// 256-color palette nearest to `(r, g, b)`.
+fn nearest_256((r, g, b): (u8, u8, u8)) -> u8 {
+    // Cube levels are 0, 95, 135, 175, 215 and 255.
+    let step = |v: u8| match v {
+        0..48 => 0,
+        48..115 => 1,
+        _ => (v - 35) / 40,
+    };
+    let cube = 16 + 36 * step(r) + 6 * step(g) + step(b);
+    let average = ((r as u16 + g as u16 + b as u16) / 3) as u8;
+    let gray = 232 + (average.saturating_sub(3) / 10).min(23);
+    let target = (r, g, b);
+    let indexed = |i| rgb(Color::Indexed(i)).unwrap_or_default();
+    if distance(indexed(gray), target) < distance(indexed(cube), target) { gray } else { cube }
+}
+
+/// Colors with less chroma than this, as a fraction of the full range, are
+/// grays at 16 colors.
+const GRAY_CHROMA: f32 = 0.12;
+
+/// The basic color for `(r, g, b)`: a gray by its lightness when it has
+/// little chroma, else the red, yellow, green, cyan, blue or magenta nearest
+/// its hue, bright when its strongest channel is. The nearest color by
+/// distance would be a gray for the muted colors most syntax themes use,
+/// leaving keywords, strings and names alike.
+fn nearest_16((r, g, b): (u8, u8, u8)) -> Color {
+    let (r, g, b) = (r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0);
+    let (max, min) = (r.max(g).max(b), r.min(g).min(b));
+    let chroma = max - min;
+    if chroma < GRAY_CHROMA {
+        return match (max + min) / 2.0 {
+            l if l < 0.25 => Color::Black,
+            l if l < 0.55 => Color::DarkGray,
+            l if l < 0.9 => Color::Gray,
+            _ => Color::White,
+        };
+    }
+    let hue = match max {
+        _ if max == r => 60.0 * ((g - b) / chroma).rem_euclid(6.0),
+        _ if max == g => 60.0 * ((b - r) / chroma + 2.0),
+        _ => 60.0 * ((r - g) / chroma + 4.0),
+    };
+    // Red, yellow, green, cyan, blue and magenta, 60° apart from 0°.
+    let index = [1, 3, 2, 6, 4, 5][((hue + 30.0) / 60.0) as usize % 6];
+    ANSI[index + if max > 0.8 { 8 } else { 0 }]
+}
+
+/// Squared distance between two colors, with the channels weighted roughly
+/// by how much the eye notices them.
+fn distance((r1, g1, b1): (u8, u8, u8), (r2, g2, b2): (u8, u8, u8)) -> u32 {
+    let d = |a: u8, b: u8| (a as i32 - b as i32).pow(2) as u32;
+    2 * d(r1, r2) + 4 * d(g1, g2) + 3 * d(b1, b2)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quantize_256() {
+        let q = |r, g, b| quantize(Color::Rgb(r, g, b), ColorDepth::Ansi256);
+        assert_eq!(q(0, 0, 0), Color::Indexed(16));
+        assert_eq!(q(255, 255, 255), Color::Indexed(231));
+        assert_eq!(q(95, 135, 175), Color::Indexed(67));
+        // Grays between the cube levels go to the ramp.
+        assert_eq!(q(0x4e, 0x4e, 0x4e), Color::Indexed(239));
+        assert_eq!(q(0x2b, 0x30, 0x3b), Color::Indexed(236));
+        assert_eq!(quantize(Color::Indexed(239), ColorDepth::Ansi256), Color::Indexed(239));
+        assert_eq!(quantize(Color::Cyan, ColorDepth::Ansi256), Color::Cyan);
+    }
+
+    #[test]
+    fn test_quantize_16() {
+        let q = |color| quantize(color, ColorDepth::Ansi16);
+        assert_eq!(q(Color::Rgb(0xc0, 0xc5, 0xce)), Color::Gray);
+        assert_eq!(q(Color::Rgb(0x2b, 0x30, 0x3b)), Color::Black);
+        assert_eq!(q(Color::Rgb(0x65, 0x73, 0x7e)), Color::DarkGray);
+        assert_eq!(q(Color::Rgb(0x8f, 0xa1, 0xb3)), Color::Blue);
+        assert_eq!(q(Color::Indexed(239)), Color::DarkGray);
+        assert_eq!(q(Color::Indexed(226)), Color::LightYellow);
+        assert_eq!(q(Color::Indexed(4)), Color::Blue);
+        assert_eq!(q(Color::Reset), Color::Reset);
+        assert_eq!(quantize(Color::Rgb(1, 2, 3), ColorDepth::TrueColor), Color::Rgb(1, 2, 3));
+    }
+
+    #[test]
+    fn test_quantize_16_keeps_syntax_colors_apart() {
+        // The scope colors of base16-ocean: keywords, strings, functions,
+        // numbers, types and variables.
+        let colors = [0xb48ead, 0xa3be8c, 0x8fa1b3, 0xd08770, 0xebcb8b, 0xbf616a];
+        let mut quantized: Vec<Color> = colors
+            .iter()
+            .map(|&c| quantize(Color::Rgb((c >> 16) as u8, (c >> 8) as u8, c as u8), ColorDepth::Ansi16))
+            .collect();
+        quantized.sort_by_key(|c| c.to_string());
+        quantized.dedup();
+        assert_eq!(quantized.len(), colors.len(), "{quantized:?}");
+    }
+
+    #[test]
+    fn test_quantize_buffer() {
+        let mut buffer = Buffer::empty(ratatui::layout::Rect::new(0, 0, 2, 1));
+        buffer.content[0].set_fg(Color::Rgb(255, 0, 0)).set_bg(Color::Rgb(0, 0, 0));
+        quantize_buffer(&mut buffer, ColorDepth::Ansi256);
+        assert_eq!((buffer.content[0].fg, buffer.content[0].bg), (Color::Indexed(196), Color::Indexed(16)));
+        assert_eq!(buffer.content[1].fg, Color::Reset);
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(ColorDepth::parse("TrueColor"), Some(ColorDepth::TrueColor));
+        assert_eq!(ColorDepth::parse("256"), Some(ColorDepth::Ansi256));
+        assert_eq!(ColorDepth::parse("8"), None);
+    }
+}
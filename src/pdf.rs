@@ -0,0 +1,590 @@
+use std::collections::HashSet;
+use std::fmt::Write as _;
+
+use ratatui::style::{Modifier, Style};
+use ratatui::text::Line;
+use crate::export::rgb;
+use crate::render::{Document, char_widths};
+
+type Rgb = (u8, u8, u8);
+
+/// A4, in points.
+const PAGE_WIDTH: f32 = 595.0;
+const PAGE_HEIGHT: f32 = 842.0;
+const MARGIN: f32 = 50.0;
+/// Width of a Courier glyph as a fraction of the font size.
+const ADVANCE: f32 = 0.6;
+const MAX_FONT_SIZE: f32 = 10.0;
+/// Baseline to baseline, as a multiple of the font size.
+const LEADING: f32 = 1.3;
+/// Rows a heading needs under it at the bottom of a page, or it moves to
+/// the next one.
+const KEEP_WITH_HEADING: usize = 3;
+/// The built-in PDF fonts used for regular, bold, italic and bold italic
+/// text, as `/F0` to `/F3`.
+const FONTS: [&str; 4] = ["Courier", "Courier-Bold", "Courier-Oblique", "Courier-BoldOblique"];
+
+/// Where a rendered line lands: its page, its row on the page, and whether
+/// it is drawn (blank lines at the top of a page are not).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Place {
+    page: usize,
+    row: usize,
+    drawn: bool,
+}
+
+/// Sizes of the monospaced grid the lines are drawn on.
+struct Grid {
+    font_size: f32,
+    cell_width: f32,
+    row_height: f32,
+    rows_per_page: usize,
+}
+
+impl Grid {
+    /// Fits `columns` across the page, at most at `MAX_FONT_SIZE`.
+    fn new(columns: u16) -> Self {
+        let font_size = ((PAGE_WIDTH - 2.0 * MARGIN) / (columns.max(1) as f32 * ADVANCE)).min(MAX_FONT_SIZE);
+        let row_height = font_size * LEADING;
+        Self {
+            font_size,
+            cell_width: font_size * ADVANCE,
+            row_height,
+            rows_per_page: ((PAGE_HEIGHT - 2.0 * MARGIN) / row_height) as usize,
+        }
+    }
+
+    fn x(&self, col: usize) -> f32 {
+        MARGIN + col as f32 * self.cell_width
+    }
+
+    /// The bottom edge of `row`.
+    fn y(&self, row: usize) -> f32 {
+        PAGE_HEIGHT - MARGIN - (row + 1) as f32 * self.row_height
+    }
+}
+
+/// Writes the rendered `doc` as a PDF: the lines on a monospaced grid
+/// `columns` wide, in their colors and Courier's regular, bold and oblique
+/// faces, over a page of color `page.0` with `page.1` for uncolored text.
+/// Box-drawing characters are drawn as lines so tables and diagrams join
+/// up; other characters outside the fonts' Latin-1 range show as `?`.
+/// Headings become bookmarks, and links can be clicked.
+pub(crate) fn write_pdf(doc: &Document, columns: u16, page: (Rgb, Rgb), title: &str) -> Vec<u8> {
+    let grid = Grid::new(columns);
+    let places = paginate(doc, grid.rows_per_page);
+    let pages = places.last().map_or(1, |p| p.page + 1);
+
+    // Objects 1 and 2 are the catalog and the page tree, then the fonts, the
+    // document info and the outline root; each page is a page object and its
+    // contents, and annotations and bookmarks follow.
+    let mut objects: Vec<String> = vec![String::new(); 4 + FONTS.len() + 2 * pages];
+    let info_id = 3 + FONTS.len();
+    let outlines_id = info_id + 1;
+    let page_id = |page: usize| outlines_id + 1 + 2 * page;
+    let destination = |line: usize| {
+        let place = places.get(line).copied().unwrap_or(Place { page: pages - 1, row: 0, drawn: true });
+        format!("[{} 0 R /XYZ 0 {} null]", page_id(place.page), num(grid.y(place.row) + grid.row_height))
+    };
+
+    let mut contents = vec![String::new(); pages];
+    for content in &mut contents {
+        let _ = writeln!(content, "{} rg 0 0 {} {} re f", color(page.0), num(PAGE_WIDTH), num(PAGE_HEIGHT));
+    }
+    for (line, place) in doc.text.lines.iter().zip(&places) {
+        if place.drawn {
+            draw_line(&mut contents[place.page], &grid, line, place.row, page);
+        }
+    }
+    let muted = blend(page.1, page.0);
+    for (i, content) in contents.iter_mut().enumerate() {
+        let number = format!("{} / {pages}", i + 1);
+        let x = (PAGE_WIDTH - number.len() as f32 * grid.cell_width) / 2.0;
+        let _ = writeln!(
+            content,
+            "BT /F0 {} Tf {} rg {} {} Td {} Tj ET",
+            num(grid.font_size),
+            color(muted),
+            num(x),
+            num(MARGIN / 2.0),
+            text_string(&number)
+        );
+    }
+
+    let mut annotations = vec![Vec::new(); pages];
+    for link in &doc.links {
+        let Some(place) = places.get(link.line).filter(|p| p.drawn) else {
+            continue;
+        };
+        let action = match link.url.strip_prefix('#') {
+            Some(anchor) => match doc.anchors.get(anchor) {
+                Some(&line) => format!("/Dest {}", destination(line)),
+                None => continue,
+            },
+            None => format!("/A << /S /URI /URI {} >>", text_string(&link.url)),
+        };
+        let content: String = doc.text.lines[link.line].spans.iter().map(|s| s.content.as_ref()).collect();
+        let start = content.get(..link.start).map_or(0, width);
+        let end = content.get(..link.end).map_or(start, width);
+        let y = grid.y(place.row);
+        objects.push(format!(
+            "<< /Type /Annot /Subtype /Link /Rect [{} {} {} {}] /Border [0 0 0] {action} >>",
+            num(grid.x(start)),
+            num(y),
+            num(grid.x(end)),
+            num(y + grid.row_height)
+        ));
+        annotations[place.page].push(objects.len());
+    }
+
+    objects[0] = format!("<< /Type /Catalog /Pages 2 0 R /Outlines {outlines_id} 0 R /PageMode /UseOutlines >>");
+    let kids: Vec<String> = (0..pages).map(|p| format!("{} 0 R", page_id(p))).collect();
+    objects[1] = format!("<< /Type /Pages /Kids [{}] /Count {pages} >>", kids.join(" "));
+    for (i, font) in FONTS.iter().enumerate() {
+        objects[2 + i] = format!("<< /Type /Font /Subtype /Type1 /BaseFont /{font} /Encoding /WinAnsiEncoding >>");
+    }
+    objects[info_id - 1] = format!("<< /Title {} /Producer (mdview) >>", text_string(title));
+    let fonts: String = (0..FONTS.len()).map(|i| format!("/F{i} {} 0 R ", 3 + i)).collect();
+    for (p, content) in contents.into_iter().enumerate() {
+        let annots: Vec<String> = annotations[p].iter().map(|id| format!("{id} 0 R")).collect();
+        objects[page_id(p) - 1] = format!(
+            "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {} {}] /Resources << /Font << {fonts}>> >> \
+             /Contents {} 0 R /Annots [{}] >>",
+            num(PAGE_WIDTH),
+            num(PAGE_HEIGHT),
+            page_id(p) + 1,
+            annots.join(" ")
+        );
+        objects[page_id(p)] = format!("<< /Length {} >>\nstream\n{content}endstream", content.len());
+    }
+    objects[outlines_id - 1] = outline(doc, outlines_id, &mut objects, destination);
+
+    serialize(&objects, info_id)
+}
+
+/// Assigns each line a page and row. A page ends when it is full, or before
+/// a heading that would have fewer than `KEEP_WITH_HEADING` rows under it.
+fn paginate(doc: &Document, rows_per_page: usize) -> Vec<Place> {
+    let headings: HashSet<usize> = doc.headings.iter().map(|h| h.line).collect();
+    let rows_per_page = rows_per_page.max(KEEP_WITH_HEADING + 1);
+    let (mut page, mut row) = (0, 0);
+    let mut places = Vec::with_capacity(doc.text.lines.len());
+    for (i, line) in doc.text.lines.iter().enumerate() {
+        if row == rows_per_page || (row > 0 && headings.contains(&i) && rows_per_page - row < KEEP_WITH_HEADING) {
+            page += 1;
+            row = 0;
+        }
+        if row == 0 && page > 0 && line.width() == 0 {
+            places.push(Place { page, row, drawn: false });
+            continue;
+        }
+        places.push(Place { page, row, drawn: true });
+        row += 1;
+    }
+    places
+}
+
+/// A character cell of a line: its column, width in columns, character and
+/// resolved colors and face.
+struct Cell {
+    col: usize,
+    width: usize,
+    ch: char,
+    fg: Rgb,
+    bg: Option<Rgb>,
+    font: usize,
+    modifier: Modifier,
+}
+
+fn cells(line: &Line, page: (Rgb, Rgb)) -> Vec<Cell> {
+    let mut cells = Vec::new();
+    let mut col = 0;
+    for span in &line.spans {
+        let style: Style = line.style.patch(span.style);
+        let mut fg = style.fg.and_then(rgb).unwrap_or(page.1);
+        let mut bg = style.bg.and_then(rgb);
+        if style.add_modifier.contains(Modifier::REVERSED) {
+            (fg, bg) = (bg.unwrap_or(page.0), Some(fg));
+        }
+        if style.add_modifier.contains(Modifier::DIM) {
+            fg = blend(fg, bg.unwrap_or(page.0));
+        }
+        let bold = style.add_modifier.contains(Modifier::BOLD);
+        let italic = style.add_modifier.contains(Modifier::ITALIC);
+        let font = usize::from(bold) + 2 * usize::from(italic);
+        // A cluster is drawn as its first char, the marks and joined
+        // characters after it having no glyph of their own here.
+        for (_, ch, width) in char_widths(&span.content) {
+            if width == 0 {
+                continue;
+            }
+            cells.push(Cell { col, width, ch, fg, bg, font, modifier: style.add_modifier });
+            col += width;
+        }
+    }
+    cells
+}
+
+/// Draws one rendered line at `row`: backgrounds, then text in runs of one
+/// face and color, then underlines and strikethroughs.
+fn draw_line(out: &mut String, grid: &Grid, line: &Line, row: usize, page: (Rgb, Rgb)) {
+    let cells = cells(line, page);
+    let bottom = grid.y(row);
+    for cell in &cells {
+        if let Some(bg) = cell.bg {
+            let _ = writeln!(
+                out,
+                "{} rg {} {} {} {} re f",
+                color(bg),
+                num(grid.x(cell.col)),
+                num(bottom),
+                // A hair wider, so neighbouring cells leave no seam.
+                num(cell.width as f32 * grid.cell_width + 0.1),
+                num(grid.row_height)
+            );
+        }
+    }
+
+    let baseline = bottom + (grid.row_height - grid.font_size) / 2.0 + 0.22 * grid.font_size;
+    let mut run: Option<(usize, usize, Rgb, Vec<u8>)> = None;
+    let flush = |out: &mut String, run: &mut Option<(usize, usize, Rgb, Vec<u8>)>| {
+        if let Some((col, font, fg, bytes)) = run.take() {
+            let _ = writeln!(
+                out,
+                "BT /F{font} {} Tf {} rg {} {} Td {} Tj ET",
+                num(grid.font_size),
+                color(fg),
+                num(grid.x(col)),
+                num(baseline),
+                byte_string(&bytes)
+            );
+        }
+    };
+    for cell in &cells {
+        let (x, w) = (grid.x(cell.col), cell.width as f32 * grid.cell_width);
+        if draw_glyph(out, cell.ch, x, bottom, w, grid.row_height, cell.fg) {
+            flush(out, &mut run);
+            continue;
+        }
+        let byte = win_ansi(cell.ch).unwrap_or_else(|| fallback(cell.ch));
+        match &mut run {
+            Some((col, font, fg, bytes)) if *font == cell.font && *fg == cell.fg && *col + bytes.len() == cell.col => {
+                bytes.push(byte);
+            }
+            _ => {
+                flush(out, &mut run);
+                run = Some((cell.col, cell.font, cell.fg, vec![byte]));
+            }
+        }
+        // A wide character takes two cells; pad so the run stays on the grid.
+        if cell.width == 2
+            && let Some((_, _, _, bytes)) = &mut run
+        {
+            bytes.push(b' ');
+        }
+    }
+    flush(out, &mut run);
+
+    for cell in &cells {
+        let (x, w) = (grid.x(cell.col), cell.width as f32 * grid.cell_width);
+        for (modifier, y) in [
+            (Modifier::UNDERLINED, baseline - 0.12 * grid.font_size),
+            (Modifier::CROSSED_OUT, baseline + 0.3 * grid.font_size),
+        ] {
+            if cell.modifier.contains(modifier) {
+                let _ = writeln!(out, "{} RG 0.5 w {} {} m {} {} l S", color(cell.fg), num(x), num(y), num(x + w), num(y));
+            }
+        }
+    }
+}
+
+/// Draws the box-drawing, block and arrow characters the renderer uses as
+/// shapes filling the cell at `x`, `y` (bottom left), `w` by `h`, since the
+/// built-in fonts lack them. Returns whether `ch` was one of them.
+fn draw_glyph(out: &mut String, ch: char, x: f32, y: f32, w: f32, h: f32, fg: Rgb) -> bool {
+    let (cx, cy) = (x + w / 2.0, y + h / 2.0);
+    // Arms from the center: left, right, up, down.
+    let arms = match ch {
+        '─' | '━' | '═' | '╌' => Some((true, true, false, false)),
+        '│' | '┃' | '║' | '╎' => Some((false, false, true, true)),
+        '┌' | '╭' | '┏' | '╔' => Some((false, true, false, true)),
+        '┐' | '╮' | '┓' | '╗' => Some((true, false, false, true)),
+        '└' | '╰' | '┗' | '╚' => Some((false, true, true, false)),
+        '┘' | '╯' | '┛' | '╝' => Some((true, false, true, false)),
+        '├' | '┣' | '╠' => Some((false, true, true, true)),
+        '┤' | '┫' | '╣' => Some((true, false, true, true)),
+        '┬' | '┳' | '╦' => Some((true, true, false, true)),
+        '┴' | '┻' | '╩' => Some((true, true, true, false)),
+        '┼' | '╋' | '╬' => Some((true, true, true, true)),
+        _ => None,
+    };
+    if let Some((left, right, up, down)) = arms {
+        let heavy = matches!(ch, '━' | '┃' | '┏' | '┓' | '┗' | '┛' | '┣' | '┫' | '┳' | '┻' | '╋');
+        let dash = if matches!(ch, '╌' | '╎') { "[1.5 1.5] 0 d " } else { "" };
+        let _ = write!(out, "{} RG {} w {dash}", color(fg), if heavy { "1.2" } else { "0.6" });
+        for (on, (x1, y1)) in [(left, (x, cy)), (right, (x + w, cy)), (up, (cx, y + h)), (down, (cx, y))] {
+            if on {
+                let _ = write!(out, "{} {} m {} {} l ", num(cx), num(cy), num(x1), num(y1));
+            }
+        }
+        let _ = writeln!(out, "S{}", if dash.is_empty() { "" } else { " [] 0 d" });
+        return true;
+    }
+
+    let polygon = |points: &[(f32, f32)]| -> String {
+        let mut path = String::new();
+        for (i, (px, py)) in points.iter().enumerate() {
+            let _ = write!(path, "{} {} {} ", num(*px), num(*py), if i == 0 { "m" } else { "l" });
+        }
+        path + "h f"
+    };
+    let (s, t) = (w.min(h) * 0.35, w.min(h) * 0.2);
+    let shape = match ch {
+        '╱' => format!("0.6 w {} {} m {} {} l S", num(x), num(y), num(x + w), num(y + h)),
+        '╲' => format!("0.6 w {} {} m {} {} l S", num(x), num(y + h), num(x + w), num(y)),
+        '█' => polygon(&[(x, y), (x + w, y), (x + w, y + h), (x, y + h)]),
+        '▀' => polygon(&[(x, cy), (x + w, cy), (x + w, y + h), (x, y + h)]),
+        '▄' => polygon(&[(x, y), (x + w, y), (x + w, cy), (x, cy)]),
+        '▌' => polygon(&[(x, y), (cx, y), (cx, y + h), (x, y + h)]),
+        '▐' => polygon(&[(cx, y), (x + w, y), (x + w, y + h), (cx, y + h)]),
+        '▪' | '■' => polygon(&[(cx - t, cy - t), (cx + t, cy - t), (cx + t, cy + t), (cx - t, cy + t)]),
+        '▶' | '▸' | '►' => polygon(&[(cx - s, cy - s), (cx + s, cy), (cx - s, cy + s)]),
+        '◀' | '◂' | '◄' => polygon(&[(cx + s, cy - s), (cx - s, cy), (cx + s, cy + s)]),
+        '▲' | '▴' => polygon(&[(cx - s, cy - s), (cx + s, cy - s), (cx, cy + s)]),
+        '▼' | '▾' => polygon(&[(cx - s, cy + s), (cx + s, cy + s), (cx, cy - s)]),
+        '✓' | '✔' => format!(
+            "1 w {} {} m {} {} l {} {} l S",
+            num(cx - s),
+            num(cy),
+            num(cx - s / 3.0),
+            num(cy - s),
+            num(cx + s),
+            num(cy + s)
+        ),
+        _ => return false,
+    };
+    let _ = writeln!(out, "{} rg {} RG {shape}", color(fg), color(fg));
+    true
+}
+
+/// The WinAnsiEncoding byte of `ch`, the encoding the built-in fonts are
+/// used with: Latin-1 plus typographic punctuation.
+fn win_ansi(ch: char) -> Option<u8> {
+    const EXTRA: [(char, u8); 27] = [
+        ('€', 0x80), ('‚', 0x82), ('ƒ', 0x83), ('„', 0x84), ('…', 0x85), ('†', 0x86), ('‡', 0x87),
+        ('ˆ', 0x88), ('‰', 0x89), ('Š', 0x8a), ('‹', 0x8b), ('Œ', 0x8c), ('Ž', 0x8e), ('‘', 0x91),
+        ('’', 0x92), ('“', 0x93), ('”', 0x94), ('•', 0x95), ('–', 0x96), ('—', 0x97), ('˜', 0x98),
+        ('™', 0x99), ('š', 0x9a), ('›', 0x9b), ('œ', 0x9c), ('ž', 0x9e), ('Ÿ', 0x9f),
+    ];
+    match ch as u32 {
+        0x20..=0x7e | 0xa0..=0xff => Some(ch as u8),
+        _ => EXTRA.iter().find(|(c, _)| *c == ch).map(|&(_, b)| b),
+    }
+}
+
+/// A stand-in for a character the fonts lack.
+fn fallback(ch: char) -> u8 {
+    match ch {
+        '◦' | '○' => b'o',
+        '✖' | '✗' | '✕' => b'x',
+        '★' | '☆' => b'*',
+        '⚠' | '‼' | '❗' => b'!',
+        'ℹ' => b'i',
+        '→' => b'>',
+        '←' => b'<',
+        _ => b'?',
+    }
+}
+
+/// The bookmark tree of the headings, nested by level. Fills in the item
+/// objects and returns the outline root for object `root`.
+fn outline(doc: &Document, root: usize, objects: &mut Vec<String>, destination: impl Fn(usize) -> String) -> String {
+    let first_id = objects.len() + 1;
+    let id = |i: usize| first_id + i;
+    let mut parents: Vec<Option<usize>> = Vec::with_capacity(doc.headings.len());
+    let mut stack: Vec<usize> = Vec::new();
+    for (i, heading) in doc.headings.iter().enumerate() {
+        while stack.last().is_some_and(|&j| doc.headings[j].level >= heading.level) {
+            stack.pop();
+        }
+        parents.push(stack.last().copied());
+        stack.push(i);
+    }
+    let children = |parent: Option<usize>| -> Vec<usize> { (0..parents.len()).filter(|&i| parents[i] == parent).collect() };
+    let descendants = |i: usize| -> usize {
+        let level = doc.headings[i].level;
+        doc.headings[i + 1..].iter().take_while(|h| h.level > level).count()
+    };
+
+    for (i, heading) in doc.headings.iter().enumerate() {
+        let siblings = children(parents[i]);
+        let at = siblings.iter().position(|&s| s == i).unwrap_or(0);
+        let mut item = format!(
+            "<< /Title {} /Parent {} 0 R /Dest {}",
+            text_string(&heading.title),
+            parents[i].map_or(root, id),
+            destination(heading.line)
+        );
+        if let Some(&prev) = at.checked_sub(1).and_then(|k| siblings.get(k)) {
+            let _ = write!(item, " /Prev {} 0 R", id(prev));
+        }
+        if let Some(&next) = siblings.get(at + 1) {
+            let _ = write!(item, " /Next {} 0 R", id(next));
+        }
+        let kids = children(Some(i));
+        if let (Some(first), Some(last)) = (kids.first(), kids.last()) {
+            let _ = write!(item, " /First {} 0 R /Last {} 0 R /Count {}", id(*first), id(*last), descendants(i));
+        }
+        objects.push(item + " >>");
+    }
+
+    let top = children(None);
+    match (top.first(), top.last()) {
+        (Some(first), Some(last)) => format!(
+            "<< /Type /Outlines /First {} 0 R /Last {} 0 R /Count {} >>",
+            id(*first),
+            id(*last),
+            doc.headings.len()
+        ),
+        _ => "<< /Type /Outlines /Count 0 >>".to_string(),
+    }
+}
+
+/// The file: header, the objects numbered from 1, the cross-reference table
+/// of their offsets and the trailer.
+fn serialize(objects: &[String], info_id: usize) -> Vec<u8> {
+    let mut out = b"%PDF-1.4\n%\xe2\xe3\xcf\xd3\n".to_vec();
+    let mut offsets = Vec::with_capacity(objects.len());
+    for (i, body) in objects.iter().enumerate() {
+        offsets.push(out.len());
+        out.extend_from_slice(format!("{} 0 obj\n{body}\nendobj\n", i + 1).as_bytes());
+    }
+    let xref = out.len();
+    let mut table = format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1);
+    for offset in offsets {
+        let _ = writeln!(table, "{offset:010} 00000 n ");
+    }
+    let _ = write!(
+        table,
+        "trailer\n<< /Size {} /Root 1 0 R /Info {info_id} 0 R >>\nstartxref\n{xref}\n%%EOF\n",
+        objects.len() + 1
+    );
+    out.extend_from_slice(table.as_bytes());
+    out
+}
+
+/// A literal string of WinAnsi bytes, with the delimiters escaped and bytes
+/// outside ASCII in octal so content streams stay ASCII.
+fn byte_string(bytes: &[u8]) -> String {
+    let mut s = String::from("(");
+    for &b in bytes {
+        match b {
+            b'(' | b')' | b'\\' => {
+                s.push('\\');
+                s.push(b as char);
+            }
+            0x20..=0x7e => s.push(b as char),
+            _ => {
+                let _ = write!(s, "\\{b:03o}");
+            }
+        }
+    }
+    s + ")"
+}
+
+/// A text string for metadata, bookmarks and URIs: ASCII as is, anything
+/// else as UTF-16 with a byte order mark.
+fn text_string(text: &str) -> String {
+    if text.is_ascii() {
+        return byte_string(text.as_bytes());
+    }
+    let mut s = String::from("<FEFF");
+    for unit in text.encode_utf16() {
+        let _ = write!(s, "{unit:04X}");
+    }
+    s + ">"
+}
+
+fn color((r, g, b): Rgb) -> String {
+    format!("{} {} {}", num(r as f32 / 255.0), num(g as f32 / 255.0), num(b as f32 / 255.0))
+}
+
+/// Halfway between `a` and `b`.
+fn blend(a: Rgb, b: Rgb) -> Rgb {
+    let mid = |x: u8, y: u8| ((x as u16 + y as u16) / 2) as u8;
+    (mid(a.0, b.0), mid(a.1, b.1), mid(a.2, b.2))
+}
+
+/// A number with at most two decimals and no trailing zeros.
+fn num(v: f32) -> String {
+    let s = format!("{v:.2}");
+    s.trim_end_matches('0').trim_end_matches('.').to_string()
+}
+
+fn width(s: &str) -> usize {
+    unicode_width::UnicodeWidthStr::width(s)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render::{RenderOptions, render_document};
+
+    const PAGE: (Rgb, Rgb) = ((0xff, 0xff, 0xff), (0, 0, 0));
+
+    fn pdf(input: &str) -> Vec<u8> {
+        let doc = render_document(input, &RenderOptions::new(80));
+        write_pdf(&doc, 80, PAGE, "Doc")
+    }
+
+    fn contains(pdf: &[u8], s: &str) -> bool {
+        pdf.windows(s.len()).any(|w| w == s.as_bytes())
+    }
+
+    #[test]
+    fn test_cross_references_point_at_objects() {
+        let pdf = pdf("# Title\n\nSome **bold** text and [a link](https://example.com).\n");
+        // The header is the only part that is not ASCII.
+        let tail = std::str::from_utf8(&pdf[15..]).unwrap();
+        let xref: usize = tail.rsplit("startxref\n").next().unwrap().lines().next().unwrap().parse().unwrap();
+        let table = std::str::from_utf8(&pdf[xref..]).unwrap();
+        let offsets: Vec<usize> =
+            table.lines().skip(3).take_while(|l| l.ends_with(" n ")).map(|l| l[..10].parse().unwrap()).collect();
+        assert_eq!(table.lines().nth(1), Some(format!("0 {}", offsets.len() + 1).as_str()));
+        for (i, offset) in offsets.iter().enumerate() {
+            assert!(pdf[*offset..].starts_with(format!("{} 0 obj", i + 1).as_bytes()));
+        }
+        assert!(contains(&pdf, "/URI (https://example.com)"));
+        assert!(contains(&pdf, "/Title (Title)"));
+        assert!(contains(&pdf, "/F1 10 Tf"));
+    }
+
+    #[test]
+    fn test_long_documents_are_paginated() {
+        let input: String = (1..=150).map(|i| format!("Paragraph {i}.\n\n")).collect();
+        let doc = render_document(&format!("# Top\n\n{input}## Later\n\nEnd.\n"), &RenderOptions::new(80));
+        let places = paginate(&doc, 40);
+        assert!(places.last().unwrap().page >= 7);
+        // No page starts with a blank line, and headings are not left at the
+        // bottom of a page.
+        for (line, place) in places.iter().enumerate() {
+            if place.row == 0 && place.drawn {
+                assert!(doc.text.lines[line].width() > 0);
+            }
+        }
+        for heading in &doc.headings {
+            assert!(places[heading.line].row + KEEP_WITH_HEADING <= 40);
+        }
+    }
+
+    #[test]
+    fn test_strings() {
+        assert_eq!(win_ansi('é'), Some(0xe9));
+        assert_eq!(win_ansi('—'), Some(0x97));
+        assert_eq!(win_ansi('中'), None);
+        assert_eq!(byte_string(b"a(b)\\ \xe9"), "(a\\(b\\)\\\\ \\351)");
+        assert_eq!(text_string("Café"), "<FEFF00430061006600E9>");
+        assert_eq!(num(10.0), "10");
+        assert_eq!(num(0.126), "0.13");
+    }
+}
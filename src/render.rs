@@ -1,64 +1,777 @@
-use pulldown_cmark::{Alignment, Event, Options, Parser, Tag, TagEnd};
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use pulldown_cmark::{
+    Alignment, BlockQuoteKind, CodeBlockKind, Event, HeadingLevel, LinkType, Options, Parser, Tag, TagEnd,
+    TextMergeStream,
+};
 use ratatui::style::{Color, Modifier, Style};
 use ratatui::text::{Line, Span, Text};
+use serde::Deserialize;
+use unicode_segmentation::UnicodeSegmentation as _;
+use unicode_width::UnicodeWidthStr as _;
+
+use crate::admonitions;
+use crate::ast::{self, Block};
+use crate::bidi::{self, Direction};
+use crate::csv;
+use crate::emoji;
+use crate::highlight::{highlight_blocks, highlight_code};
+use crate::html::{self, Script, Token};
+use crate::hyphenate::{self, Language};
+use crate::images;
+use crate::invisibles;
+use crate::mermaid;
+use crate::theme::Theme;
+use crate::vault;
+use crate::wikilinks::{self, Piece};
+
+/// A link in the rendered output. `start`/`end` are byte offsets into the
+/// concatenated span content of `line`, covering the link text only.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Link {
+    pub line: usize,
+    pub start: usize,
+    pub end: usize,
+    pub url: String,
+}
+
+/// Rows reserved in the rendered text for an image drawn with a terminal
+/// graphics protocol. `col` is where the image starts on `line`, after any
+/// blockquote or list prefix.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImagePlacement {
+    pub line: usize,
+    pub col: u16,
+    pub cols: u16,
+    pub rows: u16,
+    pub path: PathBuf,
+}
+
+/// A heading in the rendered output, in document order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Heading {
+    pub line: usize,
+    /// 1 for `#` through 6 for `######`.
+    pub level: u8,
+    pub title: String,
+    /// The title as written, emoji shortcodes and all, which its slug is
+    /// taken from: its [`heading_text`].
+    pub source_title: String,
+    /// Explicit `{#id}` from the source, if any.
+    pub id: Option<String>,
+    /// `1.`, `1.2`, `1.2.3` and so on, shown before the title with
+    /// [`RenderOptions::number_headings`].
+    pub number: Option<String>,
+}
+
+/// A table wider than the render width, drawn at its natural column widths
+/// because of [`TableOverflow::Scroll`]. It takes lines `line` to `end`,
+/// exclusive, and `overflow` columns past the width.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WideTable {
+    pub line: usize,
+    pub end: usize,
+    pub overflow: usize,
+}
+
+/// What a table wider than the render width does.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TableOverflow {
+    /// Squeeze the columns, wrapping cells and cutting them off with `…`.
+    #[default]
+    Wrap,
+    /// Keep the natural column widths; the viewer scrolls the table
+    /// sideways. Recorded in [`Document::tables`].
+    Scroll,
+}
+
+/// How the destination of a link is shown next to its text.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LinkStyle {
+    /// `text (url)`.
+    #[default]
+    Inline,
+    /// Just the text, which `mdview dump` and the viewer make clickable
+    /// with OSC 8 escapes in terminals that support them.
+    Osc8,
+    /// `text [1]`, with the numbered URLs listed under a Links heading at
+    /// the end of the document.
+    Footnote,
+    /// `text¹`, with the numbered URLs listed at the end of each section,
+    /// before the next heading.
+    Section,
+    /// Just the text.
+    Hide,
+}
+
+impl LinkStyle {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "inline" => Some(Self::Inline),
+            "osc8" => Some(Self::Osc8),
+            "footnote" => Some(Self::Footnote),
+            "section" => Some(Self::Section),
+            "hide" => Some(Self::Hide),
+            _ => None,
+        }
+    }
+}
+
+/// A raw HTML `<details>` element with a `<summary>`. `line` is the
+/// summary's first line and `end` the end, exclusive, of the content below
+/// it; `open` is whether the element starts out expanded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Details {
+    pub line: usize,
+    pub end: usize,
+    pub open: bool,
+    pub title: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct Document {
+    pub text: Text<'static>,
+    pub links: Vec<Link>,
+    pub images: Vec<ImagePlacement>,
+    pub headings: Vec<Heading>,
+    /// In-document link targets (without `#`) mapped to their line. Headings
+    /// are registered by GitHub-style slug and footnote definitions as
+    /// `fn-<label>`.
+    pub anchors: HashMap<String, usize>,
+    pub details: Vec<Details>,
+    pub tables: Vec<WideTable>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ImageOptions {
+    /// Pixel size of one terminal cell.
+    pub cell_size: (u16, u16),
+    pub max_rows: u16,
+}
+
+/// Settings for [`render_document`]. Start from [`RenderOptions::new`] and
+/// chain the builder methods:
+///
+/// ```
+/// use mdview::{RenderOptions, theme};
+///
+/// let options = RenderOptions::new(80).theme(theme::LIGHT).tab_size(2).tables(false);
+/// let doc = mdview::render_document("# Hello", &options);
+/// assert!(!doc.text.lines.is_empty());
+/// ```
+#[derive(Debug, Clone)]
+pub struct RenderOptions {
+    pub(crate) width: u16,
+    pub(crate) tab_size: usize,
+    pub(crate) theme: Theme,
+    syntax_theme: Option<String>,
+    images: Option<ImageOptions>,
+    block_images: Option<u16>,
+    base_dir: Option<PathBuf>,
+    line_numbers: bool,
+    language_labels: bool,
+    ascii: bool,
+    table_min_column_width: usize,
+    table_max_cell_lines: usize,
+    table_overflow: TableOverflow,
+    pub(crate) link_style: LinkStyle,
+    pub(crate) number_headings: bool,
+    pub(crate) emoji: bool,
+    wiki_links: bool,
+    bidi: bool,
+    show_invisibles: bool,
+    hyphenate: Option<Language>,
+    pub(crate) extensions: Options,
+}
+
+/// Characters used for the renderer's own markup (as opposed to document
+/// text).
+struct Glyphs {
+    /// Blockquote bar, table column separator and code gutter separator.
+    bar: &'static str,
+    rule: char,
+    /// Table border joints for the top, header and bottom borders, each as
+    /// left, middle and right.
+    joints: [[char; 3]; 3],
+    bullets: [&'static str; 3],
+    checked: &'static str,
+    /// End of a table cell cut off at its last row.
+    ellipsis: &'static str,
+    alert_icons: bool,
+    /// Marker before a `<summary>`.
+    disclosure: &'static str,
+    /// Start of the placeholder of an image that is not drawn.
+    image: &'static str,
+    /// Marks before `<sup>` and `<sub>` text, or `None` to draw it in
+    /// superscript and subscript characters.
+    scripts: Option<[&'static str; 2]>,
+}
+
+const UNICODE: Glyphs = Glyphs {
+    bar: "│",
+    rule: '─',
+    joints: [['┌', '┬', '┐'], ['├', '┼', '┤'], ['└', '┴', '┘']],
+    bullets: ["•", "◦", "▪"],
+    checked: "✓",
+    ellipsis: "…",
+    alert_icons: true,
+    disclosure: "▾ ",
+    image: "🖼\u{fe0f} ",
+    scripts: None,
+};
+
+const ASCII: Glyphs = Glyphs {
+    bar: "|",
+    rule: '-',
+    joints: [['+'; 3]; 3],
+    bullets: ["*", "-", "+"],
+    checked: "x",
+    ellipsis: "...",
+    alert_icons: false,
+    disclosure: "> ",
+    image: "img ",
+    scripts: Some(["^", "_"]),
+};
+
+impl RenderOptions {
+    pub fn new(width: u16) -> Self {
+        Self {
+            width,
+            tab_size: 4,
+            theme: Theme::default(),
+            syntax_theme: None,
+            images: None,
+            block_images: None,
+            base_dir: None,
+            line_numbers: false,
+            language_labels: false,
+            ascii: false,
+            table_min_column_width: 5,
+            table_max_cell_lines: 5,
+            table_overflow: TableOverflow::Wrap,
+            link_style: LinkStyle::Inline,
+            number_headings: false,
+            emoji: true,
+            wiki_links: true,
+            bidi: false,
+            show_invisibles: false,
+            hyphenate: None,
+            extensions: Options::ENABLE_TABLES
+                | Options::ENABLE_STRIKETHROUGH
+                | Options::ENABLE_TASKLISTS
+                | Options::ENABLE_FOOTNOTES
+                | Options::ENABLE_GFM,
+        }
+    }
+
+    pub fn width(mut self, width: u16) -> Self {
+        self.width = width;
+        self
+    }
+
+    /// Columns per tab stop when expanding tabs in code blocks.
+    pub fn tab_size(mut self, tab_size: usize) -> Self {
+        self.tab_size = tab_size;
+        self
+    }
+
+    pub fn theme(mut self, theme: Theme) -> Self {
+        self.theme = theme;
+        self
+    }
+
+    /// Syntect theme for code blocks. Defaults to `theme.syntax_theme`.
+    pub fn syntax_theme(mut self, name: Option<String>) -> Self {
+        self.syntax_theme = name;
+        self
+    }
+
+    /// Reserve space for local images instead of rendering a placeholder.
+    pub fn images(mut self, images: Option<ImageOptions>) -> Self {
+        self.images = images;
+        self
+    }
+
+    /// Draw local images with colored half blocks (`▀`), at most `max_rows`
+    /// rows high, where no graphics protocol draws them.
+    pub fn block_images(mut self, max_rows: Option<u16>) -> Self {
+        self.block_images = max_rows;
+        self
+    }
+
+    /// Directory that relative image paths are resolved against.
+    pub fn base_dir(mut self, dir: Option<PathBuf>) -> Self {
+        self.base_dir = dir;
+        self
+    }
+
+    /// Number the lines of code blocks in a gutter.
+    pub fn line_numbers(mut self, on: bool) -> Self {
+        self.line_numbers = on;
+        self
+    }
+
+    /// Head fenced code blocks with a `─ rust ────` rule naming the language.
+    pub fn language_labels(mut self, on: bool) -> Self {
+        self.language_labels = on;
+        self
+    }
+
+    /// Narrowest a table column is squeezed to when the table does not fit.
+    /// 5 by default.
+    pub fn table_min_column_width(mut self, columns: usize) -> Self {
+        self.table_min_column_width = columns.max(1);
+        self
+    }
+
+    /// Rows a wrapped table cell may take before it is cut off with `…`. 5
+    /// by default.
+    pub fn table_max_cell_lines(mut self, lines: usize) -> Self {
+        self.table_max_cell_lines = lines.max(1);
+        self
+    }
+
+    /// Whether tables wider than the width are squeezed (the default) or
+    /// kept whole for the viewer to scroll sideways.
+    pub fn table_overflow(mut self, overflow: TableOverflow) -> Self {
+        self.table_overflow = overflow;
+        self
+    }
+
+    /// Put the number of each heading in the document's structure before
+    /// it: `1.` for the first of the top level, `1.1` for the first under
+    /// it, and so on.
+    pub fn number_headings(mut self, on: bool) -> Self {
+        self.number_headings = on;
+        self
+    }
+
+    /// How link destinations are shown; inline after the text by default.
+    pub fn link_style(mut self, style: LinkStyle) -> Self {
+        self.link_style = style;
+        self
+    }
+
+    /// Turn `:rocket:`-style shortcodes into emoji, as GitHub does. On by
+    /// default.
+    pub fn emoji(mut self, on: bool) -> Self {
+        self.emoji = on;
+        self
+    }
+
+    /// Draw `[[Page]]` and `[[Page|text]]` wiki links as links to the
+    /// Markdown file of the page beside the document (see
+    /// [`base_dir`](Self::base_dir)). On by default.
+    pub fn wiki_links(mut self, on: bool) -> Self {
+        self.wiki_links = on;
+        self
+    }
+
+    /// Put lines with right-to-left text (Hebrew, Arabic, ...) in visual
+    /// order and right-to-left paragraphs on the right, for terminals that
+    /// draw text in the order it comes. Off by default, since terminals
+    /// that reorder text themselves would undo it.
+    pub fn bidi(mut self, on: bool) -> Self {
+        self.bidi = on;
+        self
+    }
+
+    /// Hyphenate words too long for the rest of a row by the patterns of
+    /// `language`, instead of moving them whole to the next row. Off by
+    /// default; soft hyphens in the text are used either way.
+    pub fn hyphenate(mut self, language: Option<Language>) -> Self {
+        self.hyphenate = language;
+        self
+    }
+
+    /// Show control characters in the document (escape sequences, carriage
+    /// returns, form feeds) as dim symbols, and spaces at the ends of code
+    /// lines as dim dots. Off by default, when control characters are
+    /// dropped so they cannot reach the terminal.
+    pub fn show_invisibles(mut self, on: bool) -> Self {
+        self.show_invisibles = on;
+        self
+    }
+
+    /// Draw bullets, rules, table borders, quote bars, task marks and the
+    /// ellipsis of a cut-off cell with ASCII characters, and underline level
+    /// 1 and 2 headings with `=` and `-` instead of prefixing them with `#`.
+    pub fn ascii(mut self, on: bool) -> Self {
+        self.ascii = on;
+        self
+    }
+
+    /// GFM tables. When disabled, table syntax renders as plain paragraphs.
+    pub fn tables(self, on: bool) -> Self {
+        self.extension(Options::ENABLE_TABLES, on)
+    }
+
+    pub fn strikethrough(self, on: bool) -> Self {
+        self.extension(Options::ENABLE_STRIKETHROUGH, on)
+    }
+
+    /// `- [ ]` / `- [x]` task list markers.
+    pub fn task_lists(self, on: bool) -> Self {
+        self.extension(Options::ENABLE_TASKLISTS, on)
+    }
+
+    /// `[^label]` references with definitions collected at the end.
+    pub fn footnotes(self, on: bool) -> Self {
+        self.extension(Options::ENABLE_FOOTNOTES, on)
+    }
+
+    /// GitHub alerts: `> [!NOTE]`, `> [!TIP]`, `> [!IMPORTANT]`,
+    /// `> [!WARNING]` and `> [!CAUTION]` blockquotes.
+    pub fn alerts(self, on: bool) -> Self {
+        self.extension(Options::ENABLE_GFM, on)
+    }
+
+    fn extension(mut self, flag: Options, on: bool) -> Self {
+        self.extensions.set(flag, on);
+        self
+    }
+
+    pub(crate) fn syntax_theme_name(&self) -> &str {
+        self.syntax_theme.as_deref().unwrap_or(self.theme.syntax_theme)
+    }
+
+    fn glyphs(&self) -> &'static Glyphs {
+        if self.ascii { &ASCII } else { &UNICODE }
+    }
+}
+
+/// Renders markdown to ratatui `Text` at the given width.
+pub fn render_markdown(input: &str, width: u16, theme: &Theme) -> Text<'static> {
+    render_document(input, &RenderOptions::new(width).theme(*theme)).text
+}
+
+/// Renders markdown to `Text` plus the positional metadata (links, images)
+/// an interactive viewer needs. Same as [`layout`] of [`ast::parse`].
+pub fn render_document(input: &str, options: &RenderOptions) -> Document {
+    let _span = tracing::debug_span!("render_document", bytes = input.len(), width = options.width).entered();
+    layout(&ast::parse(input, options), options)
+}
+
+/// Lays out an already-parsed document at the options' width and theme, so
+/// a resize or theme change does not need the source again.
+///
+/// ```
+/// let options = mdview::RenderOptions::new(80);
+/// let blocks = mdview::ast::parse("# Title\n\nSome text.\n", &options);
+/// let doc = mdview::layout(&blocks, &options.width(40));
+/// assert_eq!(doc.text.lines[0].to_string(), "# Title");
+/// ```
+pub fn layout(blocks: &[Block], options: &RenderOptions) -> Document {
+    let _span = tracing::debug_span!("layout", blocks = blocks.len(), width = options.width).entered();
+    let events = ast::events(blocks);
+    prehighlight(&events, options);
+    render_events(events.into_iter(), options)
+}
+
+/// Renders an already-parsed event stream.
+pub(crate) fn render_events<'a>(
+    events: impl Iterator<Item = Event<'a>>,
+    options: &RenderOptions,
+) -> Document {
+    let mut renderer = Renderer::new(options);
+    // Text can arrive in pieces (around unmatched `_`, for one), which would
+    // split shortcodes.
+    renderer.process(TextMergeStream::new(events));
+    let mut anchors = renderer.anchors;
+    add_heading_anchors(&mut anchors, &renderer.headings);
+    Document {
+        text: Text::from(renderer.lines),
+        links: renderer.links,
+        images: renderer.images,
+        headings: renderer.headings,
+        anchors,
+        details: renderer.details,
+        tables: renderer.tables,
+    }
+}
+
+/// Registers a slug for every heading, numbering repeats `-1`, `-2`, ... in
+/// document order as GitHub does. Explicit ids are used verbatim. Existing
+/// anchors are kept.
+pub(crate) fn add_heading_anchors(anchors: &mut HashMap<String, usize>, headings: &[Heading]) {
+    let mut seen: HashMap<String, usize> = HashMap::new();
+    for heading in headings {
+        let slug = match &heading.id {
+            Some(id) => id.clone(),
+            None => unique_slug(&mut seen, &heading.source_title),
+        };
+        anchors.entry(slug).or_insert(heading.line);
+    }
+}
+
+/// Slug for `title`, suffixed with a counter if `seen` already has it.
+pub(crate) fn unique_slug(seen: &mut HashMap<String, usize>, title: &str) -> String {
+    let base = slugify(title);
+    let count = seen.entry(base.clone()).or_insert(0);
+    let slug = if *count == 0 { base } else { format!("{base}-{count}") };
+    *count += 1;
+    slug
+}
+
+/// The text of a heading that its title and slug are taken from: the text
+/// and code of `events`, its content, as written (emoji shortcodes not yet
+/// replaced), with line breaks as spaces. The same as [`ast::plain_text`] of
+/// the heading's content.
+pub(crate) fn heading_text<'a, 'e: 'a>(events: impl IntoIterator<Item = &'a Event<'e>>) -> String {
+    let mut text = String::new();
+    for event in events {
+        push_heading_text(&mut text, event);
+    }
+    text
+}
+
+/// Adds what `event` of a heading's content adds to its [`heading_text`].
+pub(crate) fn push_heading_text(text: &mut String, event: &Event) {
+    match event {
+        Event::Text(s) | Event::Code(s) | Event::InlineMath(s) => text.push_str(s),
+        Event::SoftBreak | Event::HardBreak => text.push(' '),
+        _ => {}
+    }
+}
 
-use crate::highlight::highlight_code;
+/// GitHub-style heading slug: lowercase, punctuation dropped, spaces turned
+/// into hyphens.
+pub fn slugify(title: &str) -> String {
+    title
+        .trim()
+        .chars()
+        .filter_map(|c| match c {
+            ' ' => Some('-'),
+            '-' | '_' => Some(c),
+            c if c.is_alphanumeric() => Some(c),
+            _ => None,
+        })
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// Splits a document into slides for presenting. A slide ends at a
+/// top-level `---` rule, which is dropped, and before each top-level H1 or
+/// H2. Slides with nothing but whitespace are skipped.
+///
+/// ```
+/// let slides = mdview::split_slides("# One\n\nIntro.\n\n## Two\n\n---\n\nThree.\n");
+/// assert_eq!(slides, ["# One\n\nIntro.\n\n", "## Two\n\n", "\nThree.\n"]);
+/// ```
+pub fn split_slides(input: &str) -> Vec<&str> {
+    let mut slides = Vec::new();
+    let mut start = 0;
+    let mut depth = 0usize;
+    for (event, range) in Parser::new_ext(input, RenderOptions::new(0).extensions).into_offset_iter() {
+        match event {
+            Event::Start(Tag::Heading { level, .. })
+                if depth == 0
+                    && matches!(level, pulldown_cmark::HeadingLevel::H1 | pulldown_cmark::HeadingLevel::H2) =>
+            {
+                slides.push(&input[start..range.start]);
+                start = range.start;
+                depth += 1;
+            }
+            Event::Rule if depth == 0 => {
+                slides.push(&input[start..range.start]);
+                start = range.end;
+            }
+            Event::Start(_) => depth += 1,
+            Event::End(_) => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+    }
+    slides.push(&input[start..]);
+    slides.retain(|slide| !slide.trim().is_empty());
+    if slides.is_empty() {
+        slides.push(input);
+    }
+    slides
+}
 
-pub fn render_markdown(input: &str, width: u16) -> Text<'static> {
-    let opts = Options::ENABLE_TABLES
-        | Options::ENABLE_STRIKETHROUGH
-        | Options::ENABLE_TASKLISTS;
-    let parser = Parser::new_ext(input, opts);
-    let mut renderer = Renderer::new(width);
-    renderer.process(parser);
-    Text::from(renderer.lines)
+/// A table cell: its logical lines, which wrap separately, and whether it
+/// is mostly code or URLs, which read badly wrapped.
+struct Cell {
+    lines: Vec<Vec<Span<'static>>>,
+    rigid: bool,
 }
 
 struct ListState {
     ordered: bool,
     counter: u64,
+    /// Digits of the list's widest number, which the others are padded to
+    /// so that the text of every item starts in the same column.
+    number_width: usize,
+}
+
+/// A block that puts a prefix before each of its lines: a quote its bar, a
+/// list item an indent as wide as its marker (`• `, `12. `), so that rows
+/// after the first line up with its text. Lines inside nested blocks get
+/// the prefixes of all of them, outermost first.
+enum Container {
+    /// A blockquote; alerts carry their kind.
+    Quote(Option<BlockQuoteKind>),
+    /// A list item and the width of its marker.
+    Item(usize),
 }
 
-struct Renderer {
+/// A footnote definition rendered into its own buffer, spliced in at the end
+/// of the document. Link and image lines are relative to `lines`.
+struct FootnoteDef {
+    label: String,
+    lines: Vec<Line<'static>>,
+    links: Vec<Link>,
+    images: Vec<ImagePlacement>,
+}
+
+/// Main-document state set aside while a footnote definition renders.
+struct FootnoteStash {
+    label: String,
+    lines: Vec<Line<'static>>,
+    spans: Vec<Span<'static>>,
+    links_mark: usize,
+    images_mark: usize,
+}
+
+/// An element of raw HTML that is open, closed by its end tag or by the end
+/// of the document.
+struct HtmlElement {
+    name: String,
+    kind: HtmlKind,
+    /// Opened by HTML inside a paragraph, and closed with it.
+    inline: bool,
+    /// The first line of an `align="center"` block, centered when it closes.
+    center: Option<usize>,
+    /// For `<details>`, the line and title of its summary.
+    summary: Option<(usize, String)>,
+}
+
+/// What closing an [`HtmlElement`] undoes.
+#[derive(Clone, Copy)]
+enum HtmlKind {
+    Style,
+    Script(Script),
+    Link,
+    Heading(HeadingLevel),
+    List(bool),
+    Item,
+    /// The summary's first line, and where its title starts in `spans`.
+    Summary(usize, usize),
+    /// Whether the `<details>` has the `open` attribute.
+    Details(bool),
+    Block,
+}
+
+struct Renderer<'o> {
+    options: &'o RenderOptions,
     lines: Vec<Line<'static>>,
     spans: Vec<Span<'static>>,
     style_stack: Vec<Style>,
     list_stack: Vec<ListState>,
-    blockquote_depth: usize,
+    /// Open quotes and list items, outermost first.
+    containers: Vec<Container>,
+    /// Digits of the widest number of each ordered list still to come, in
+    /// the order they start (`number_widths`). Lists from raw HTML are not
+    /// counted and pad nothing.
+    number_widths: std::vec::IntoIter<usize>,
+    /// The entry of `number_widths` for the list being started.
+    number_width: usize,
     in_code_block: bool,
     code_lang: Option<String>,
+    /// The rest of the code block's info string after the language.
+    code_info: String,
     code_buf: String,
     in_table: bool,
     table_alignments: Vec<Alignment>,
-    table_header: Vec<Vec<Span<'static>>>,
-    table_rows: Vec<Vec<Vec<Span<'static>>>>,
+    table_header: Vec<Cell>,
+    table_rows: Vec<Vec<Cell>>,
+    /// The finished lines of the cell being rendered, and its current line.
+    cell_lines: Vec<Vec<Span<'static>>>,
     current_cell: Vec<Span<'static>>,
+    /// Columns of the current cell taken by code spans and URLs.
+    cell_rigid_width: usize,
     in_table_header: bool,
     link_url: String,
+    link_start: Option<(usize, usize)>,
+    links: Vec<Link>,
+    /// Links from this index on belong to the line currently in `spans`.
+    line_links_mark: usize,
+    /// Number of leading spans in `spans` that are prefix (blockquote bars,
+    /// list bullets) rather than wrappable content.
+    prefix_len: usize,
+    image: Option<(String, String)>,
+    images: Vec<ImagePlacement>,
+    anchors: HashMap<String, usize>,
+    headings: Vec<Heading>,
+    heading_id: Option<String>,
+    /// The [`heading_text`] of the heading being rendered so far.
+    heading_text: Option<String>,
+    /// The level and count of the headings the next one is numbered under,
+    /// from the top, with [`RenderOptions::number_headings`].
+    heading_counts: Vec<(u8, usize)>,
+    heading_number: Option<String>,
+    footnote_numbers: HashMap<String, usize>,
+    /// Distinct link destinations in order, numbered from 1 by
+    /// [`LinkStyle::Footnote`].
+    link_urls: Vec<String>,
+    footnote_defs: Vec<FootnoteDef>,
+    footnote_stash: Option<FootnoteStash>,
     item_paragraph_count: usize,
-    width: u16,
+    /// The raw HTML block being collected, rendered when it ends.
+    html_block: Option<String>,
+    html_open: Vec<HtmlElement>,
+    details: Vec<Details>,
+    tables: Vec<WideTable>,
 }
 
-impl Renderer {
-    fn new(width: u16) -> Self {
+impl<'o> Renderer<'o> {
+    fn new(options: &'o RenderOptions) -> Self {
         Self {
+            options,
             lines: Vec::new(),
             spans: Vec::new(),
             style_stack: vec![Style::default()],
             list_stack: Vec::new(),
-            blockquote_depth: 0,
+            containers: Vec::new(),
+            number_widths: Vec::new().into_iter(),
+            number_width: 0,
             in_code_block: false,
             code_lang: None,
+            code_info: String::new(),
             code_buf: String::new(),
             in_table: false,
             table_alignments: Vec::new(),
             table_header: Vec::new(),
             table_rows: Vec::new(),
+            cell_lines: Vec::new(),
             current_cell: Vec::new(),
+            cell_rigid_width: 0,
             in_table_header: false,
             link_url: String::new(),
+            link_start: None,
+            links: Vec::new(),
+            line_links_mark: 0,
+            prefix_len: 0,
+            image: None,
+            images: Vec::new(),
+            anchors: HashMap::new(),
+            headings: Vec::new(),
+            heading_id: None,
+            heading_text: None,
+            heading_counts: Vec::new(),
+            heading_number: None,
+            footnote_numbers: HashMap::new(),
+            link_urls: Vec::new(),
+            footnote_defs: Vec::new(),
+            footnote_stash: None,
             item_paragraph_count: 0,
-            width,
+            html_block: None,
+            html_open: Vec::new(),
+            details: Vec::new(),
+            tables: Vec::new(),
         }
     }
 
@@ -80,8 +793,208 @@ impl Renderer {
     fn flush_line(&mut self) {
         if !self.spans.is_empty() {
             let spans = std::mem::take(&mut self.spans);
+            let prefix_len = std::mem::take(&mut self.prefix_len);
+            let cont = self.line_prefix();
+            let direction = match self.options.bidi {
+                true => bidi::direction(&spans_text(&spans[prefix_len.min(spans.len())..])),
+                false => None,
+            };
+            self.push_wrapped(spans, prefix_len, cont, direction);
+        }
+    }
+
+    /// Pushes a logical line, word-wrapped to the render width. The first
+    /// `prefix_len` spans stay on the first row; later rows start with
+    /// `cont`. Links recorded against this line are split and remapped onto
+    /// the rows their text lands on.
+    ///
+    /// With a `direction`, each row is put in visual order with its prefix,
+    /// and a right-to-left row is aligned right, its prefix on the right.
+    fn push_wrapped(
+        &mut self,
+        spans: Vec<Span<'static>>,
+        prefix_len: usize,
+        cont: Vec<Span<'static>>,
+        direction: Option<Direction>,
+    ) {
+        let width = self.wrap_width();
+        let base = self.lines.len();
+        let mark = self.line_links_mark.min(self.links.len());
+        self.line_links_mark = self.links.len();
+
+        let soft_hyphens = spans.iter().any(|s| s.content.contains(SOFT_HYPHEN));
+        if cell_text_width(&spans) <= width && !soft_hyphens && direction.is_none() {
+            self.lines.push(Line::from(spans));
+            return;
+        }
+
+        let (prefix, content) = spans.split_at(prefix_len.min(spans.len()));
+        let prefix_bytes: usize = prefix.iter().map(|s| s.content.len()).sum();
+        let rows = wrap_paragraph(
+            content,
+            width.saturating_sub(cell_text_width(prefix)).max(1),
+            width.saturating_sub(cell_text_width(&cont)).max(1),
+            self.options.hyphenate,
+        );
+        let levels = direction.map(|d| (d, bidi::levels(&spans_text(content), d)));
+
+        let line_links = self.links.split_off(mark);
+        for (k, mut row) in rows.into_iter().enumerate() {
+            let mut line_spans = if k == 0 { prefix.to_vec() } else { cont.clone() };
+            if let Some((direction, levels)) = &levels {
+                // The prefix goes first in logical order, so on the right of
+                // a right-to-left row.
+                let mut chars: Vec<(char, Style, usize)> = line_spans
+                    .drain(..)
+                    .flat_map(|s| s.content.chars().map(move |c| (c, s.style, usize::MAX)).collect::<Vec<_>>())
+                    .collect();
+                chars.append(&mut row);
+                bidi::reorder(&mut chars, |at| levels.get(at).copied().unwrap_or(*direction as u8));
+                let used = chars.iter().map(|c| c.0).collect::<String>().width();
+                if *direction == Direction::Rtl && used < width {
+                    line_spans.push(Span::raw(" ".repeat(width - used)));
+                }
+                row = chars;
+            }
+            let lead: usize = line_spans.iter().map(|s| s.content.len()).sum();
+
+            for link in line_links.iter().filter(|l| l.line == base) {
+                let (src_start, src_end) = (
+                    link.start.saturating_sub(prefix_bytes),
+                    link.end.saturating_sub(prefix_bytes),
+                );
+                let mut out = lead;
+                let mut range: Option<(usize, usize)> = None;
+                for &(ch, _, src) in &row {
+                    let next = out + ch.len_utf8();
+                    if src >= src_start && src < src_end {
+                        range = Some((range.map_or(out, |r| r.0), next));
+                    }
+                    out = next;
+                }
+                if let Some((start, end)) = range {
+                    self.links.push(Link { line: base + k, start, end, url: link.url.clone() });
+                }
+            }
+
+            let chars: Vec<(char, Style)> = row.iter().map(|&(c, style, _)| (c, style)).collect();
+            line_spans.extend(coalesce_chars(&chars));
+            self.lines.push(Line::from(line_spans));
+        }
+        self.links.extend(line_links.into_iter().filter(|l| l.line != base));
+        self.line_links_mark = self.links.len();
+    }
+
+    /// Columns available to wrapped lines. Footnote bodies leave room for
+    /// the `[n] ` marker they are indented by at the end of the document.
+    fn wrap_width(&self) -> usize {
+        let width = self.options.width as usize;
+        if self.footnote_stash.is_some() { width.saturating_sub(4) } else { width }
+    }
+
+    /// Starts a new line with the prefix of the blocks it is in if nothing
+    /// has been written to it yet.
+    fn start_line(&mut self) {
+        if !self.containers.is_empty() && self.spans.is_empty() {
+            self.spans = self.line_prefix();
+            self.prefix_len = self.spans.len();
+        }
+    }
+
+    /// Prefix of a line inside the open quotes and list items: each quote's
+    /// bar and each item's indent, outermost first. Also the prefix of rows
+    /// produced by wrapping or hard breaks.
+    fn line_prefix(&self) -> Vec<Span<'static>> {
+        let mut prefix: Vec<Span<'static>> = Vec::new();
+        for container in &self.containers {
+            match container {
+                Container::Quote(kind) => {
+                    let color = match kind {
+                        Some(kind) => self.options.theme.alerts[alert_index(*kind)],
+                        None => self.options.theme.blockquote_bar,
+                    };
+                    let bar = format!("{} ", self.options.glyphs().bar);
+                    prefix.push(Span::styled(bar, Style::default().fg(color)));
+                }
+                // Indents of items in items run together into one span.
+                Container::Item(width) => match prefix.last_mut() {
+                    Some(last) if last.style == Style::default() && last.content.trim().is_empty() => {
+                        last.content = format!("{}{}", last.content, " ".repeat(*width)).into();
+                    }
+                    _ => prefix.push(Span::raw(" ".repeat(*width))),
+                },
+            }
+        }
+        prefix
+    }
+
+    /// The title line of an alert or callout, in the kind's color and after
+    /// its icon.
+    fn alert_title(&mut self, kind: BlockQuoteKind, title: &str) {
+        let icon = match kind {
+            BlockQuoteKind::Note => "ℹ",
+            BlockQuoteKind::Tip => "★",
+            BlockQuoteKind::Important => "‼",
+            BlockQuoteKind::Warning => "⚠",
+            BlockQuoteKind::Caution => "✖",
+        };
+        let style = Style::default().fg(self.options.theme.alerts[alert_index(kind)]).add_modifier(Modifier::BOLD);
+        let mut spans = self.line_prefix();
+        let title = match self.options.glyphs().alert_icons {
+            true => format!("{icon} {title}"),
+            false => title.to_string(),
+        };
+        spans.push(Span::styled(title, style));
+        self.lines.push(Line::from(spans));
+    }
+
+    /// Draws a callout fence (` ```warning `) like an alert of `kind`
+    /// titled `title`, with its content rendered as markdown. An empty
+    /// title (`""`) leaves the title line out.
+    fn push_callout(&mut self, kind: BlockQuoteKind, title: &str, body: &str) {
+        self.flush_line();
+        self.containers.push(Container::Quote(Some(kind)));
+        if !title.is_empty() {
+            self.alert_title(kind, title);
+        }
+        let body = admonitions::to_fences(body);
+        let events: Vec<Event> = TextMergeStream::new(Parser::new_ext(&body, self.options.extensions)).collect();
+        // Ordered lists in the callout are padded by their own widths.
+        let number_widths = std::mem::replace(&mut self.number_widths, number_widths(&events).into_iter());
+        for event in events {
+            self.event(event);
+        }
+        self.number_widths = number_widths;
+        self.end_tag(TagEnd::BlockQuote(Some(kind)));
+        if self.lines.last().is_none_or(|l| l.width() > 0) {
+            self.push_blank();
+        }
+    }
+
+    /// Draws a mermaid code block as a diagram, returning false when it is
+    /// not one the diagram renderer supports.
+    fn push_diagram(&mut self, code: &str) -> bool {
+        self.flush_line();
+        let mut prefix = self.line_prefix();
+        prefix.push(Span::raw("  "));
+        let width = self.wrap_width().saturating_sub(cell_text_width(&prefix));
+        let Some(diagram) = mermaid::render_diagram(code, width, self.options.ascii) else {
+            return false;
+        };
+        let border = Style::default().fg(self.options.theme.table_border);
+        for row in diagram {
+            let chars: Vec<(char, Style)> = row
+                .chars()
+                .map(|c| match c {
+                    '\u{2500}'..='\u{257f}' | '▼' | '▶' | '◀' => (c, border),
+                    _ => (c, Style::default()),
+                })
+                .collect();
+            let mut spans = prefix.clone();
+            spans.extend(coalesce_chars(&chars));
             self.lines.push(Line::from(spans));
         }
+        true
     }
 
     fn push_blank(&mut self) {
@@ -89,59 +1002,88 @@ impl Renderer {
         self.lines.push(Line::default());
     }
 
-    fn blockquote_prefix(&self) -> Vec<Span<'static>> {
-        let mut prefix = Vec::new();
-        for _ in 0..self.blockquote_depth {
-            prefix.push(Span::styled(
-                "│ ",
-                Style::default().fg(Color::DarkGray),
-            ));
-        }
-        prefix
+    fn spans_len(&self) -> usize {
+        self.spans.iter().map(|s| s.content.len()).sum()
     }
 
-    fn list_indent(&self) -> String {
-        "  ".repeat(self.list_stack.len().saturating_sub(1))
+    fn process<'a>(&mut self, events: impl Iterator<Item = Event<'a>>) {
+        let events: Vec<Event> = events.collect();
+        self.number_widths = number_widths(&events).into_iter();
+        for event in events {
+            self.event(event);
+        }
+        self.close_html_to(0);
+        self.flush_line();
+        self.render_section_links();
+        self.render_footnotes();
+        self.render_link_list();
     }
 
-    fn process(&mut self, parser: Parser) {
-        for event in parser {
-            match event {
-                Event::Start(tag) => self.start_tag(tag),
-                Event::End(tag) => self.end_tag(tag),
-                Event::Text(text) => self.text(&text),
-                Event::Code(code) => self.inline_code(&code),
-                Event::SoftBreak => self.soft_break(),
-                Event::HardBreak => self.hard_break(),
-                Event::Rule => self.rule(),
-                Event::TaskListMarker(checked) => self.task_marker(checked),
-                Event::Html(html) => self.raw_html(&html),
-                Event::InlineHtml(html) => self.inline_raw_html(&html),
-                Event::FootnoteReference(label) => self.footnote_ref(&label),
-                Event::InlineMath(math) => self.math(&math),
-                Event::DisplayMath(math) => self.display_math(&math),
+    fn event(&mut self, event: Event) {
+        if let Some(text) = &mut self.heading_text {
+            push_heading_text(text, &event);
+        }
+        match event {
+            Event::Start(tag @ Tag::List(Some(_))) => {
+                self.number_width = self.number_widths.next().unwrap_or(0);
+                self.start_tag(tag);
             }
+            Event::Start(tag) => self.start_tag(tag),
+            Event::End(tag) => self.end_tag(tag),
+            Event::Text(text) if self.options.wiki_links && text.contains("[[") => self.wiki_text(&text),
+            Event::Text(text) => self.text(&text),
+            Event::Code(code) => self.inline_code(&code),
+            Event::SoftBreak => self.soft_break(),
+            Event::HardBreak => self.hard_break(),
+            Event::Rule => self.rule(),
+            Event::TaskListMarker(checked) => self.task_marker(checked),
+            Event::Html(html) => match &mut self.html_block {
+                Some(block) => block.push_str(&html),
+                None => self.raw_html(&html),
+            },
+            Event::InlineHtml(html) => self.html_tokens(&html::tokenize(&html), true),
+            Event::FootnoteReference(label) => self.footnote_ref(&label),
+            Event::InlineMath(math) => self.math(&math),
+            Event::DisplayMath(math) => self.display_math(&math),
         }
-        self.flush_line();
     }
 
     fn start_tag(&mut self, tag: Tag) {
         match tag {
-            Tag::Heading { level, .. } => {
-                self.flush_line();
+            Tag::Heading { level, id, .. } => {
+                // A heading that opens a list item goes after its marker.
+                if self.spans.len() > self.prefix_len {
+                    self.flush_line();
+                }
+                self.render_section_links();
+                self.start_line();
+                self.heading_id = id.map(|id| id.to_string());
+                self.heading_text = Some(String::new());
+                let [h1, h2, h3, h4] = self.options.theme.headings;
                 let (color, prefix) = match level {
-                    pulldown_cmark::HeadingLevel::H1 => (Color::Cyan, "# "),
-                    pulldown_cmark::HeadingLevel::H2 => (Color::Green, "## "),
-                    pulldown_cmark::HeadingLevel::H3 => (Color::Yellow, "### "),
-                    _ => (Color::White, "#### "),
+                    pulldown_cmark::HeadingLevel::H1 => (h1, "# "),
+                    pulldown_cmark::HeadingLevel::H2 => (h2, "## "),
+                    pulldown_cmark::HeadingLevel::H3 => (h3, "### "),
+                    _ => (h4, "#### "),
+                };
+                // Underlined instead; the empty span keeps the title at index 1.
+                let prefix = match underline(self.options, level) {
+                    Some(_) => "",
+                    None => prefix,
+                };
+                self.heading_number = self.number_heading(level as u8);
+                let prefix = match &self.heading_number {
+                    Some(number) => format!("{prefix}{number} "),
+                    None => prefix.to_string(),
                 };
                 let style = Style::default().fg(color).add_modifier(Modifier::BOLD);
                 self.style_stack.push(style);
-                self.spans.push(Span::styled(prefix.to_string(), style));
+                self.spans.push(Span::styled(prefix, style));
             }
 
             Tag::Paragraph => {
                 if self.in_table {
+                    self.cell_paragraph();
                     return;
                 }
                 if !self.list_stack.is_empty() {
@@ -154,9 +1096,19 @@ impl Renderer {
                 }
             }
 
-            Tag::BlockQuote(_) => {
+            Tag::BlockQuote(kind) => {
                 self.flush_line();
-                self.blockquote_depth += 1;
+                self.containers.push(Container::Quote(kind));
+                if let Some(kind) = kind {
+                    let title = match kind {
+                        BlockQuoteKind::Note => "Note",
+                        BlockQuoteKind::Tip => "Tip",
+                        BlockQuoteKind::Important => "Important",
+                        BlockQuoteKind::Warning => "Warning",
+                        BlockQuoteKind::Caution => "Caution",
+                    };
+                    self.alert_title(kind, title);
+                }
             }
 
             Tag::List(start) => {
@@ -164,33 +1116,32 @@ impl Renderer {
                 self.list_stack.push(ListState {
                     ordered: start.is_some(),
                     counter: start.unwrap_or(1),
+                    number_width: std::mem::take(&mut self.number_width),
                 });
             }
 
             Tag::Item => {
                 self.flush_line();
                 self.item_paragraph_count = 0;
-                let indent = self.list_indent();
-                let mut prefix_spans = self.blockquote_prefix();
+                let mut prefix_spans = self.line_prefix();
 
                 if let Some(list) = self.list_stack.last_mut() {
                     let bullet = if list.ordered {
-                        let s = format!("{indent}{}. ", list.counter);
+                        let s = format!("{:>width$}. ", list.counter, width = list.number_width);
                         list.counter += 1;
                         s
                     } else {
-                        let marker = match self.list_stack.len() {
-                            1 => "•",
-                            2 => "◦",
-                            _ => "▪",
-                        };
-                        format!("{indent}{marker} ")
+                        let bullets = self.options.glyphs().bullets;
+                        let marker = bullets[(self.list_stack.len() - 1).min(2)];
+                        format!("{marker} ")
                     };
+                    self.containers.push(Container::Item(bullet.width()));
                     prefix_spans.push(Span::styled(
                         bullet,
-                        Style::default().fg(Color::DarkGray),
+                        Style::default().fg(self.options.theme.muted),
                     ));
                 }
+                self.prefix_len = prefix_spans.len();
                 self.spans = prefix_spans;
             }
 
@@ -201,20 +1152,37 @@ impl Renderer {
             }
 
             Tag::Link { dest_url, .. } => {
-                self.push_style(|s| s.fg(Color::Blue).add_modifier(Modifier::UNDERLINED));
-                self.link_url = dest_url.to_string();
+                let style = self
+                    .current_style()
+                    .fg(self.options.theme.link)
+                    .add_modifier(Modifier::UNDERLINED);
+                self.style_stack.push(style);
+                self.link_url = invisibles::strip(&dest_url).into_owned();
+                if !self.in_table {
+                    self.start_line();
+                    self.link_start = Some((self.lines.len(), self.spans_len()));
+                }
+            }
+
+            Tag::FootnoteDefinition(label) => {
+                self.flush_line();
+                self.footnote_stash = Some(FootnoteStash {
+                    label: label.to_string(),
+                    lines: std::mem::take(&mut self.lines),
+                    spans: std::mem::take(&mut self.spans),
+                    links_mark: self.links.len(),
+                    images_mark: self.images.len(),
+                });
+            }
+
+            Tag::Image { dest_url, .. } => {
+                self.image = Some((invisibles::strip(&dest_url).into_owned(), String::new()));
             }
 
             Tag::CodeBlock(kind) => {
                 self.flush_line();
                 self.in_code_block = true;
-                self.code_lang = match kind {
-                    pulldown_cmark::CodeBlockKind::Fenced(lang) => {
-                        let l = lang.split_whitespace().next().unwrap_or("").to_string();
-                        if l.is_empty() { None } else { Some(l) }
-                    }
-                    _ => None,
-                };
+                (self.code_lang, self.code_info) = code_info(&kind);
                 self.code_buf.clear();
             }
 
@@ -230,25 +1198,52 @@ impl Renderer {
                 self.in_table_header = true;
             }
 
-            Tag::TableRow => {
-                if !self.in_table_header {
-                    self.table_rows.push(Vec::new());
-                }
+            Tag::TableRow if !self.in_table_header => {
+                self.table_rows.push(Vec::new());
             }
 
             Tag::TableCell => {
+                self.cell_lines.clear();
                 self.current_cell.clear();
+                self.cell_rigid_width = 0;
             }
 
+            Tag::HtmlBlock => self.html_block = Some(String::new()),
+
             _ => {}
         }
     }
 
     fn end_tag(&mut self, tag: TagEnd) {
+        if matches!(tag, TagEnd::Paragraph | TagEnd::Heading(_) | TagEnd::TableCell | TagEnd::Item) {
+            self.close_inline_html();
+        }
         match tag {
-            TagEnd::Heading(_) => {
+            TagEnd::Heading(level) => {
                 self.pop_style();
+                let title: String = self.spans.iter().skip(self.prefix_len + 1).map(|s| s.content.as_ref()).collect();
+                let source_title = self.heading_text.take().unwrap_or_default();
+                // Headings inside footnotes are not navigable.
+                if self.footnote_stash.is_none() {
+                    self.headings.push(Heading {
+                        line: self.lines.len(),
+                        level: level as u8,
+                        title,
+                        source_title,
+                        id: self.heading_id.take(),
+                        number: self.heading_number.take(),
+                    });
+                }
+                let start = self.lines.len();
                 self.flush_line();
+                if let Some(ch) = underline(self.options, level) {
+                    let style = self.lines.get(start).and_then(|l| l.spans.last());
+                    let style = style.map_or_else(Style::default, |s| s.style);
+                    let mut spans = self.line_prefix();
+                    let width = self.lines[start..].iter().map(Line::width).max().unwrap_or(0);
+                    spans.push(Span::raw(ch.to_string().repeat(width.saturating_sub(cell_text_width(&spans)))));
+                    self.lines.push(Line::from(spans).style(style));
+                }
                 self.push_blank();
             }
 
@@ -263,20 +1258,26 @@ impl Renderer {
             }
 
             TagEnd::BlockQuote(_) => {
-                self.blockquote_depth = self.blockquote_depth.saturating_sub(1);
                 self.flush_line();
+                self.containers.pop();
             }
 
             TagEnd::List(_) => {
                 self.list_stack.pop();
                 if self.list_stack.is_empty() {
                     self.flush_line();
-                    self.push_blank();
+                    // Not after a code block or table that ended the list.
+                    if self.lines.last().is_none_or(|l| l.width() > 0) {
+                        self.push_blank();
+                    }
                 }
             }
 
             TagEnd::Item => {
                 self.flush_line();
+                if matches!(self.containers.last(), Some(Container::Item(_))) {
+                    self.containers.pop();
+                }
             }
 
             TagEnd::Emphasis | TagEnd::Strong | TagEnd::Strikethrough => {
@@ -286,25 +1287,111 @@ impl Renderer {
             TagEnd::Link => {
                 self.pop_style();
                 let url = std::mem::take(&mut self.link_url);
-                self.spans.push(Span::styled(
-                    format!(" ({url})"),
-                    Style::default().fg(Color::DarkGray),
-                ));
+                if let Some((line, start)) = self.link_start.take() {
+                    let end_line = self.lines.len();
+                    self.links.push(Link {
+                        line: end_line,
+                        start: if line == end_line { start } else { 0 },
+                        end: self.spans_len(),
+                        url: url.clone(),
+                    });
+                }
+                let muted = Style::default().fg(self.options.theme.muted);
+                let span = match self.options.link_style {
+                    LinkStyle::Inline => Span::styled(format!(" ({url})"), muted),
+                    LinkStyle::Footnote => Span::styled(format!(" [{}]", self.link_number(url)), muted),
+                    LinkStyle::Section => {
+                        let n = self.link_number(url);
+                        Span::styled(self.section_mark(n), muted)
+                    }
+                    LinkStyle::Osc8 | LinkStyle::Hide => return,
+                };
+                if self.in_table {
+                    self.cell_rigid_width += span.width();
+                    self.current_cell.push(span);
+                } else {
+                    self.spans.push(span);
+                }
+            }
+
+            TagEnd::FootnoteDefinition => {
+                self.flush_line();
+                if let Some(stash) = self.footnote_stash.take() {
+                    let mut lines = std::mem::replace(&mut self.lines, stash.lines);
+                    self.spans = stash.spans;
+                    while lines.last().is_some_and(|l| l.spans.is_empty()) {
+                        lines.pop();
+                    }
+                    let links = self.links.split_off(stash.links_mark);
+                    let images = self.images.split_off(stash.images_mark);
+                    self.line_links_mark = self.links.len();
+                    self.footnote_defs.push(FootnoteDef { label: stash.label, lines, links, images });
+                }
             }
 
+            TagEnd::Image => self.end_image(),
+
             TagEnd::CodeBlock => {
                 self.in_code_block = false;
-                let code = std::mem::take(&mut self.code_buf);
+                let raw = std::mem::take(&mut self.code_buf);
                 let lang = self.code_lang.take();
+                let info = std::mem::take(&mut self.code_info);
+                let fence = lang.as_deref().and_then(fence);
+                match fence {
+                    // Before tab expansion, which would eat the tabs of tsv.
+                    Some(Fence::Table(delimiter)) if self.push_csv(&raw, delimiter) => return,
+                    Some(Fence::Callout(kind)) => {
+                        let quoted = info.strip_prefix('"').and_then(|t| t.strip_suffix('"'));
+                        let title = match info.as_str() {
+                            "" => title_case(lang.as_deref().unwrap_or_default()),
+                            info => quoted.unwrap_or(info).to_string(),
+                        };
+                        self.push_callout(kind, &title, &raw);
+                        return;
+                    }
+                    _ => {}
+                }
+                let code = code_to_highlight(&raw, self.options);
+                if matches!(fence, Some(Fence::Diagram)) && self.push_diagram(&invisibles::strip(&code)) {
+                    self.push_blank();
+                    return;
+                }
 
-                let highlighted = highlight_code(&code, lang.as_deref());
-                let prefix = self.blockquote_prefix();
-
-                for line in highlighted {
+                let highlighted = highlight_code(&code, lang.as_deref(), self.options.syntax_theme_name());
+                let muted = Style::default().fg(self.options.theme.muted);
+                let mut prefix = self.line_prefix();
+                prefix.push(Span::styled("  ", Style::default()));
+
+                if self.options.language_labels
+                    && let Some(lang) = &lang
+                {
+                    let rule = self.options.glyphs().rule.to_string();
+                    let label = format!("{rule} {lang} ");
+                    let len = self
+                        .wrap_width()
+                        .saturating_sub(cell_text_width(&prefix) + label.width());
                     let mut spans = prefix.clone();
-                    spans.push(Span::styled("  ", Style::default()));
-                    spans.extend(line.spans);
-                    self.lines.push(Line::from(spans));
+                    spans.push(Span::styled(label + &rule.repeat(len), muted));
+                    self.push_wrapped(spans, prefix.len(), prefix.clone(), None);
+                }
+
+                let gutter = highlighted.len().to_string().len();
+                for (i, line) in highlighted.into_iter().enumerate() {
+                    let mut first = prefix.clone();
+                    let mut cont = prefix.clone();
+                    if self.options.line_numbers {
+                        let bar = self.options.glyphs().bar;
+                        first.push(Span::styled(format!("{:>gutter$} {bar} ", i + 1), muted));
+                        cont.push(Span::styled(format!("{:gutter$} {bar} ", ""), muted));
+                    }
+                    let prefix_len = first.len();
+                    let mut spans: Vec<Span> =
+                        line.spans.iter().flat_map(|span| self.visible_spans(&span.content, span.style)).collect();
+                    if self.options.show_invisibles {
+                        mark_trailing_spaces(&mut spans, self.options.ascii);
+                    }
+                    first.extend(spans);
+                    self.push_wrapped(first, prefix_len, cont, None);
                 }
                 self.push_blank();
             }
@@ -321,8 +1408,21 @@ impl Renderer {
 
             TagEnd::TableRow => {}
 
+            TagEnd::HtmlBlock => {
+                if let Some(block) = self.html_block.take() {
+                    self.raw_html(&block);
+                }
+            }
+
             TagEnd::TableCell => {
-                let cell = std::mem::take(&mut self.current_cell);
+                let mut lines = std::mem::take(&mut self.cell_lines);
+                lines.push(std::mem::take(&mut self.current_cell));
+                while lines.len() > 1 && lines.last().is_some_and(Vec::is_empty) {
+                    lines.pop();
+                }
+                let width: usize = lines.iter().map(|line| cell_text_width(line)).sum();
+                let rigid = width > 0 && self.cell_rigid_width * 2 > width;
+                let cell = Cell { lines, rigid };
                 if self.in_table_header {
                     self.table_header.push(cell);
                 } else if let Some(row) = self.table_rows.last_mut() {
@@ -340,33 +1440,229 @@ impl Renderer {
             return;
         }
 
+        let text = match self.options.emoji {
+            true => emoji::replace_shortcodes(text),
+            false => text.into(),
+        };
+        let script = self.html_open.iter().rev().find_map(|e| match e.kind {
+            HtmlKind::Script(script) => Some(script),
+            _ => None,
+        });
+        let text = match (script, self.options.glyphs().scripts) {
+            (Some(script), None) => html::script(&text, script).into(),
+            (Some(script), Some([sup, sub])) => {
+                let mark = if script == Script::Super { sup } else { sub };
+                match text.chars().count() {
+                    1 => format!("{mark}{text}").into(),
+                    _ => format!("{mark}({text})").into(),
+                }
+            }
+            (None, _) => text,
+        };
+        let text = text.as_ref();
+
+        if let Some((_, alt)) = &mut self.image {
+            alt.push_str(text);
+            return;
+        }
+
         if self.in_table {
-            self.current_cell
-                .push(Span::styled(text.to_string(), self.current_style()));
+            let urls = text.split_whitespace().filter(|word| word.contains("://") || word.starts_with("www."));
+            self.cell_rigid_width += urls.map(unicode_width::UnicodeWidthStr::width).sum::<usize>();
+            self.push_text(text, self.current_style());
             return;
         }
 
-        if self.blockquote_depth > 0 && self.spans.is_empty() {
-            self.spans = self.blockquote_prefix();
+        self.start_line();
+        self.push_text(text, self.current_style());
+    }
+
+    /// Text with its wiki links drawn as links, unless it is code, an image
+    /// description or the text of a link already.
+    fn wiki_text(&mut self, text: &str) {
+        if self.in_code_block || self.image.is_some() || !self.link_url.is_empty() {
+            self.text(text);
+            return;
+        }
+        for piece in wikilinks::split(text) {
+            match piece {
+                Piece::Text(text) => self.text(text),
+                Piece::Link { target, label } => {
+                    let vault = vault::current();
+                    let dir = self.options.base_dir.as_deref();
+                    let dest_url = wikilinks::destination(target, dir, vault.as_deref()).into();
+                    let link_type = LinkType::Inline;
+                    self.start_tag(Tag::Link { link_type, dest_url, title: "".into(), id: "".into() });
+                    self.text(label);
+                    self.end_tag(TagEnd::Link);
+                }
+            }
         }
+    }
 
-        self.spans
-            .push(Span::styled(text.to_string(), self.current_style()));
+    /// Adds inline text to the table cell or line being built, with its
+    /// control characters dropped or shown (`visible_spans`) and its tabs
+    /// expanded to the tab stops of the cell or line it continues.
+    fn push_text(&mut self, text: &str, style: Style) {
+        for span in self.visible_spans(text, style) {
+            let line = if self.in_table { &mut self.current_cell } else { &mut self.spans };
+            let start = line.iter().map(Span::width).sum();
+            line.push(Span::styled(expand_tabs_from(&span.content, self.options.tab_size, start), span.style));
+        }
+    }
+
+    /// `text` in `style` with its control characters dropped, or with
+    /// `show_invisibles` shown as dim symbols.
+    fn visible_spans(&self, text: &str, style: Style) -> Vec<Span<'static>> {
+        if !self.options.show_invisibles {
+            let text = invisibles::strip(text);
+            return match text.is_empty() {
+                true => Vec::new(),
+                false => vec![Span::styled(text.into_owned(), style)],
+            };
+        }
+        invisibles::pictures(text, self.options.ascii)
+            .into_iter()
+            .map(|(run, control)| match control {
+                true => Span::styled(run.into_owned(), style.add_modifier(Modifier::DIM)),
+                false => Span::styled(run.into_owned(), style),
+            })
+            .collect()
+    }
+
+    fn end_image(&mut self) {
+        let Some((url, alt)) = self.image.take() else {
+            return;
+        };
+
+        if !self.in_table && (self.reserve_image(&url) || self.block_image(&url)) {
+            return;
+        }
+
+        let alt = invisibles::strip(&alt);
+        let alt = if alt.is_empty() { "image" } else { &alt };
+        let size = self.image_path(&url).and_then(|path| images::dimensions(&path));
+        let label = match size {
+            Some((w, h)) => format!("{}[{alt}] ({url}, {w}x{h})", self.options.glyphs().image),
+            None => format!("{}[{alt}] ({url})", self.options.glyphs().image),
+        };
+        let span = Span::styled(label, Style::default().fg(self.options.theme.muted));
+        if self.in_table {
+            self.current_cell.push(span);
+            return;
+        }
+        self.start_line();
+        // The placeholder opens the image, unless it is the text of a link
+        // (a badge), which opens the link.
+        let start = self.spans_len();
+        self.spans.push(span);
+        if self.link_start.is_none() {
+            self.links.push(Link { line: self.lines.len(), start, end: self.spans_len(), url });
+        }
+    }
+
+    /// Draws a local image with half blocks, on rows of its own. Returns
+    /// false when block images are off or the file cannot be decoded.
+    fn block_image(&mut self, url: &str) -> bool {
+        let Some(max_rows) = self.options.block_images else {
+            return false;
+        };
+        let Some(path) = self.image_path(url) else {
+            return false;
+        };
+        if self.spans.len() > self.prefix_len {
+            self.flush_line();
+        }
+        self.start_line();
+        let width = self.wrap_width().saturating_sub(cell_text_width(&self.spans));
+        let blocks = images::half_blocks(&path, width.min(u16::MAX as usize) as u16, max_rows);
+        let Some(blocks) = blocks.filter(|b| !b.is_empty()) else {
+            return false;
+        };
+        for (i, row) in blocks.iter().enumerate() {
+            let mut spans = match i {
+                0 => std::mem::take(&mut self.spans),
+                _ => self.line_prefix(),
+            };
+            let rgb = |c: Option<[u8; 3]>| c.map(|[r, g, b]| Color::Rgb(r, g, b));
+            let cells: Vec<(char, Style)> = row
+                .iter()
+                .map(|&[top, bottom]| match (rgb(top), rgb(bottom)) {
+                    (Some(top), Some(bottom)) => ('▀', Style::default().fg(top).bg(bottom)),
+                    (Some(top), None) => ('▀', Style::default().fg(top)),
+                    (None, Some(bottom)) => ('▄', Style::default().fg(bottom)),
+                    (None, None) => (' ', Style::default()),
+                })
+                .collect();
+            spans.extend(coalesce_chars(&cells));
+            self.lines.push(Line::from(spans));
+        }
+        self.prefix_len = 0;
+        true
+    }
+
+    /// The local file an image destination names, relative to the
+    /// document's directory. None for remote images.
+    fn image_path(&self, url: &str) -> Option<PathBuf> {
+        if url.contains("://") {
+            return None;
+        }
+        Some(match &self.options.base_dir {
+            Some(dir) => dir.join(url),
+            None => PathBuf::from(url),
+        })
+    }
+
+    /// Reserves blank rows for a local image the terminal can draw. Returns
+    /// false when the image cannot be shown, so the caller falls back to a
+    /// text placeholder.
+    fn reserve_image(&mut self, url: &str) -> bool {
+        let Some(image_opts) = self.options.images else {
+            return false;
+        };
+        let Some(path) = self.image_path(url) else {
+            return false;
+        };
+        let Some(dims) = images::dimensions(&path) else {
+            return false;
+        };
+
+        self.start_line();
+        let col = cell_text_width(&self.spans).min(u16::MAX as usize) as u16;
+        let (cols, rows) = images::fit(
+            dims,
+            image_opts.cell_size,
+            self.options.width.saturating_sub(col),
+            image_opts.max_rows,
+        );
+        if rows == 0 {
+            return false;
+        }
+
+        let line = self.lines.len();
+        if self.spans.is_empty() {
+            self.lines.push(Line::default());
+        } else {
+            self.flush_line();
+        }
+        for _ in 1..rows {
+            self.lines.push(Line::from(self.line_prefix()));
+        }
+        self.images.push(ImagePlacement { line, col, cols, rows, path });
+        true
     }
 
     fn inline_code(&mut self, code: &str) {
+        let style = Style::default().bg(self.options.theme.code_bg);
         if self.in_table {
-            self.current_cell.push(Span::styled(
-                format!("`{code}`"),
-                Style::default().bg(Color::Indexed(239)),
-            ));
+            let before = cell_text_width(&self.current_cell);
+            self.push_text(&format!("`{code}`"), style);
+            self.cell_rigid_width += cell_text_width(&self.current_cell) - before;
             return;
         }
 
-        self.spans.push(Span::styled(
-            format!("`{code}`"),
-            Style::default().bg(Color::Indexed(239)),
-        ));
+        self.start_line();
+        self.push_text(&format!("`{code}`"), style);
     }
 
     fn soft_break(&mut self) {
@@ -374,67 +1670,622 @@ impl Renderer {
     }
 
     fn hard_break(&mut self) {
-        self.flush_line();
-        if self.blockquote_depth > 0 {
-            self.spans = self.blockquote_prefix();
+        if self.in_table {
+            self.cell_lines.push(std::mem::take(&mut self.current_cell));
+            return;
         }
+        self.flush_line();
+        self.spans = self.line_prefix();
+        self.prefix_len = self.spans.len();
     }
 
     fn rule(&mut self) {
         self.flush_line();
-        let w = self.width.saturating_sub(2) as usize;
-        self.lines.push(Line::styled(
-            "─".repeat(w),
-            Style::default().fg(Color::DarkGray),
-        ));
+        let mut spans = self.line_prefix();
+        let w = (self.options.width as usize).saturating_sub(2 + cell_text_width(&spans));
+        spans.push(Span::raw(self.options.glyphs().rule.to_string().repeat(w)));
+        self.lines.push(Line::from(spans).style(Style::default().fg(self.options.theme.muted)));
         self.push_blank();
     }
 
     fn task_marker(&mut self, checked: bool) {
-        let marker = if checked { "[✓] " } else { "[ ] " };
-        self.spans.push(Span::styled(
-            marker.to_string(),
-            Style::default().fg(if checked { Color::Green } else { Color::DarkGray }),
-        ));
+        let marker = if checked { self.options.glyphs().checked } else { " " };
+        let span = Span::styled(
+            format!("[{marker}] "),
+            Style::default().fg(if checked {
+                self.options.theme.task_done
+            } else {
+                self.options.theme.muted
+            }),
+        );
+        // Right after the bullet, the box becomes part of it: wrapped rows
+        // line up under the task's text.
+        if self.spans.len() == self.prefix_len
+            && let Some(Container::Item(width)) = self.containers.last_mut()
+        {
+            *width += span.width();
+            self.prefix_len += 1;
+        }
+        self.spans.push(span);
     }
 
+    /// Renders a block of raw HTML: the tags the renderer knows style their
+    /// content, and the rest are dropped.
     fn raw_html(&mut self, html: &str) {
+        self.html_break();
+        let start = self.lines.len();
+        self.html_tokens(&html::tokenize(html), false);
+        self.html_break();
+        if self.lines.len() > start {
+            self.html_blank();
+        }
+    }
+
+    /// Renders HTML tokens through the same paths as markdown. `inline`
+    /// tokens come from HTML inside a paragraph, where the text between tags
+    /// arrives as markdown text.
+    fn html_tokens(&mut self, tokens: &[Token], inline: bool) {
+        let mut i = 0;
+        while i < tokens.len() {
+            match &tokens[i] {
+                Token::Text(text) => self.html_text(text),
+                Token::Close(name) => {
+                    if let Some(at) = self.html_open.iter().rposition(|e| e.name == *name) {
+                        self.close_html_to(at);
+                    } else if self.in_table && html::BLOCK.contains(&name.as_str()) {
+                        self.cell_paragraph();
+                    }
+                }
+                Token::Open { name, .. } if html::HIDDEN.contains(&name.as_str()) => {
+                    i += 1 + html::closing(&tokens[i + 1..], name);
+                }
+                Token::Open { name, .. } if name == "table" && !self.in_table => {
+                    let end = (i + 1 + html::closing(&tokens[i + 1..], name)).min(tokens.len());
+                    self.html_table(&tokens[i + 1..end]);
+                    i = end;
+                }
+                open => self.open_html(open, inline),
+            }
+            i += 1;
+        }
+    }
+
+    /// Text from an HTML block, with runs of whitespace collapsed to one
+    /// space and none at the start of a line.
+    fn html_text(&mut self, text: &str) {
+        let mut collapsed = String::with_capacity(text.len());
+        for c in text.chars() {
+            match c.is_ascii_whitespace() {
+                true if collapsed.ends_with(' ') => {}
+                true => collapsed.push(' '),
+                false => collapsed.push(c),
+            }
+        }
+        let (spans, prefix_len) = match self.in_table {
+            true => (&self.current_cell, 0),
+            false => (&self.spans, self.prefix_len),
+        };
+        let last = spans.iter().rev().find_map(|s| s.content.chars().last());
+        if spans.len() <= prefix_len || last.is_none_or(char::is_whitespace) {
+            collapsed = collapsed.trim_start().to_string();
+        }
+        if !collapsed.is_empty() {
+            self.text(&collapsed);
+        }
+    }
+
+    fn open_html(&mut self, token: &Token, inline: bool) {
+        let Token::Open { name, .. } = token else {
+            return;
+        };
+        let code_bg = self.options.theme.code_bg;
+        let block = !self.in_table;
+        let kind = match name.as_str() {
+            "b" | "strong" => {
+                self.push_style(|s| s.add_modifier(Modifier::BOLD));
+                HtmlKind::Style
+            }
+            "i" | "em" | "cite" | "var" => {
+                self.push_style(|s| s.add_modifier(Modifier::ITALIC));
+                HtmlKind::Style
+            }
+            "s" | "del" | "strike" => {
+                self.push_style(|s| s.add_modifier(Modifier::CROSSED_OUT));
+                HtmlKind::Style
+            }
+            "u" | "ins" => {
+                self.push_style(|s| s.add_modifier(Modifier::UNDERLINED));
+                HtmlKind::Style
+            }
+            "mark" => {
+                self.push_style(|s| s.add_modifier(Modifier::REVERSED));
+                HtmlKind::Style
+            }
+            "code" | "tt" | "samp" => {
+                self.style_stack.push(self.current_style().bg(code_bg));
+                HtmlKind::Style
+            }
+            "kbd" => {
+                self.style_stack.push(self.current_style().bg(code_bg).add_modifier(Modifier::BOLD));
+                HtmlKind::Style
+            }
+            "sup" => HtmlKind::Script(Script::Super),
+            "sub" => HtmlKind::Script(Script::Sub),
+            "a" => {
+                let Some(href) = token.attr("href") else {
+                    return;
+                };
+                let dest_url = href.to_string().into();
+                let link_type = LinkType::Inline;
+                self.start_tag(Tag::Link { link_type, dest_url, title: "".into(), id: "".into() });
+                HtmlKind::Link
+            }
+            "img" => {
+                let src = token.attr("src").unwrap_or_default().to_string();
+                self.image = Some((src, token.attr("alt").unwrap_or_default().to_string()));
+                self.end_image();
+                return;
+            }
+            "br" => {
+                self.trim_line_end();
+                self.hard_break();
+                return;
+            }
+            _ if !block && html::BLOCK.contains(&name.as_str()) => {
+                self.cell_paragraph();
+                return;
+            }
+            "hr" if block => {
+                self.trim_line_end();
+                self.rule();
+                return;
+            }
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" if block => {
+                self.html_break();
+                let level = name[1..].parse::<usize>().ok().and_then(|n| HeadingLevel::try_from(n).ok());
+                let level = level.unwrap_or(HeadingLevel::H6);
+                let id = token.attr("id").map(|id| id.to_string().into());
+                self.start_tag(Tag::Heading { level, id, classes: Vec::new(), attrs: Vec::new() });
+                HtmlKind::Heading(level)
+            }
+            "ul" | "ol" if block => {
+                let ordered = name == "ol";
+                let start = token.attr("start").and_then(|n| n.parse().ok()).unwrap_or(1);
+                self.html_break();
+                self.start_tag(Tag::List(ordered.then_some(start)));
+                HtmlKind::List(ordered)
+            }
+            "li" if block => {
+                self.html_break();
+                self.start_tag(Tag::Item);
+                HtmlKind::Item
+            }
+            "summary" if block => {
+                self.html_break();
+                self.push_style(|s| s.add_modifier(Modifier::BOLD));
+                self.start_line();
+                self.spans.push(Span::styled(self.options.glyphs().disclosure, self.current_style()));
+                HtmlKind::Summary(self.lines.len(), self.spans.len())
+            }
+            "details" if block => {
+                self.html_break();
+                HtmlKind::Details(token.attr("open").is_some())
+            }
+            "p" | "div" | "center" | "section" | "blockquote" | "figure" if block => {
+                self.html_break();
+                HtmlKind::Block
+            }
+            _ => return,
+        };
+        let center = (name == "center" || token.attr("align") == Some("center")) && block;
+        self.html_open.push(HtmlElement {
+            name: name.clone(),
+            kind,
+            inline,
+            center: center.then_some(self.lines.len()),
+            summary: None,
+        });
+    }
+
+    /// Closes the open HTML elements from index `len` on, innermost first.
+    fn close_html_to(&mut self, len: usize) {
+        while self.html_open.len() > len {
+            let Some(element) = self.html_open.pop() else {
+                break;
+            };
+            match element.kind {
+                HtmlKind::Style => self.pop_style(),
+                HtmlKind::Script(_) => {}
+                HtmlKind::Link => {
+                    self.trim_line_end();
+                    self.end_tag(TagEnd::Link);
+                }
+                HtmlKind::Heading(level) => {
+                    self.trim_line_end();
+                    self.end_tag(TagEnd::Heading(level));
+                }
+                HtmlKind::List(ordered) => {
+                    self.html_break();
+                    self.end_tag(TagEnd::List(ordered));
+                }
+                HtmlKind::Item => {
+                    self.trim_line_end();
+                    self.end_tag(TagEnd::Item);
+                }
+                HtmlKind::Summary(line, at) => {
+                    self.pop_style();
+                    let title: String = self.spans.iter().skip(at).map(|s| s.content.as_ref()).collect();
+                    self.html_break();
+                    let details = self.html_open.iter_mut().rev().find(|e| matches!(e.kind, HtmlKind::Details(_)));
+                    if let Some(details) = details
+                        && details.summary.is_none()
+                    {
+                        details.summary = Some((line, title.trim().to_string()));
+                    }
+                }
+                HtmlKind::Details(open) => {
+                    self.html_break();
+                    // Without a summary there is nothing to click to expand
+                    // it, so it stays as it is; so do details in footnotes.
+                    if let Some((line, title)) = element.summary.clone()
+                        && self.footnote_stash.is_none()
+                    {
+                        self.details.push(Details { line, end: self.lines.len(), open, title });
+                    }
+                }
+                HtmlKind::Block => self.html_break(),
+            }
+            if let Some(start) = element.center {
+                self.html_break();
+                self.center_lines(start);
+            }
+            if matches!(element.kind, HtmlKind::Block | HtmlKind::Details(_)) {
+                self.html_blank();
+            }
+        }
+    }
+
+    /// Closes the HTML elements opened inside the paragraph, heading, cell
+    /// or item that is ending, so an unclosed `<b>` does not run on.
+    fn close_inline_html(&mut self) {
+        let keep = self.html_open.iter().rposition(|e| !e.inline).map_or(0, |i| i + 1);
+        self.close_html_to(keep);
+    }
+
+    /// Ends the line at an HTML block boundary.
+    fn html_break(&mut self) {
+        if !self.in_table {
+            self.trim_line_end();
+            self.flush_line();
+        }
+    }
+
+    /// A blank line after an HTML block, unless there is one already.
+    fn html_blank(&mut self) {
+        if !self.in_table && self.lines.last().is_some_and(|l| l.width() > 0) {
+            self.push_blank();
+        }
+    }
+
+    /// Starts a new line in the table cell at a paragraph boundary, unless
+    /// the current one is empty.
+    fn cell_paragraph(&mut self) {
+        self.trim_line_end();
+        if !self.current_cell.is_empty() {
+            self.hard_break();
+        }
+    }
+
+    /// Drops the spaces collapsed HTML whitespace left at the end of the
+    /// current line.
+    fn trim_line_end(&mut self) {
+        let (spans, keep) = match self.in_table {
+            true => (&mut self.current_cell, 0),
+            false => (&mut self.spans, self.prefix_len),
+        };
+        while spans.len() > keep
+            && let Some(last) = spans.last_mut()
+            && last.content.ends_with(' ')
+        {
+            let trimmed = last.content.trim_end_matches(' ');
+            if !trimmed.is_empty() {
+                last.content = trimmed.to_string().into();
+                break;
+            }
+            spans.pop();
+        }
+    }
+
+    /// Centers the lines from `start` on in the render width, moving the
+    /// links and images on them along.
+    fn center_lines(&mut self, start: usize) {
+        let width = self.wrap_width();
+        let mut widths: Vec<usize> = self.lines[start..].iter().map(Line::width).collect();
+        for image in self.images.iter().filter(|i| i.line >= start) {
+            let right = (image.col + image.cols) as usize;
+            for row in image.line..image.line + image.rows as usize {
+                if let Some(w) = widths.get_mut(row - start) {
+                    *w = (*w).max(right);
+                }
+            }
+        }
+        let pads: Vec<usize> =
+            widths.iter().map(|&w| if w == 0 { 0 } else { width.saturating_sub(w) / 2 }).collect();
+        for (line, &pad) in self.lines[start..].iter_mut().zip(&pads) {
+            if pad > 0 {
+                line.spans.insert(0, Span::raw(" ".repeat(pad)));
+            }
+        }
+        for link in self.links.iter_mut().filter(|l| l.line >= start) {
+            let pad = pads.get(link.line - start).copied().unwrap_or(0);
+            link.start += pad;
+            link.end += pad;
+        }
+        for image in self.images.iter_mut().filter(|i| i.line >= start) {
+            image.col += pads.get(image.line - start).copied().unwrap_or(0) as u16;
+        }
+    }
+
+    /// Lays out an HTML `<table>` as a markdown one, its first row as the
+    /// header.
+    fn html_table(&mut self, tokens: &[Token]) {
+        let rows = html::table_rows(tokens);
+        let columns = rows.iter().map(Vec::len).max().unwrap_or(0);
+        if columns == 0 {
+            return;
+        }
+        self.html_break();
+        self.start_tag(Tag::Table(vec![Alignment::None; columns]));
+        for (k, row) in rows.iter().enumerate() {
+            self.start_tag(if k == 0 { Tag::TableHead } else { Tag::TableRow });
+            for c in 0..columns {
+                self.start_tag(Tag::TableCell);
+                let mark = self.html_open.len();
+                if let Some(cell) = row.get(c) {
+                    self.html_tokens(cell, false);
+                }
+                self.close_html_to(mark);
+                self.trim_line_end();
+                self.end_tag(TagEnd::TableCell);
+            }
+            self.end_tag(if k == 0 { TagEnd::TableHead } else { TagEnd::TableRow });
+        }
+        self.end_tag(TagEnd::Table);
+    }
+
+    /// Draws a csv or tsv code block as a table, returning false when it
+    /// has no fields. Without a header row the columns are named `A`, `B`,
+    /// ...; columns of numbers are right-aligned.
+    fn push_csv(&mut self, code: &str, delimiter: char) -> bool {
+        let mut rows = csv::parse(code, delimiter);
+        let columns = rows.iter().map(Vec::len).max().unwrap_or(0);
+        if columns == 0 {
+            return false;
+        }
+        if !csv::has_header(&rows) {
+            rows.insert(0, (0..columns).map(csv::column_name).collect());
+        }
+        let alignments = (0..columns)
+            .map(|c| {
+                let fields: Vec<&String> =
+                    rows[1..].iter().filter_map(|row| row.get(c)).filter(|f| !f.trim().is_empty()).collect();
+                match !fields.is_empty() && fields.iter().all(|f| csv::is_numeric(f)) {
+                    true => Alignment::Right,
+                    false => Alignment::None,
+                }
+            })
+            .collect();
         self.flush_line();
-        for line in html.lines() {
-            self.lines.push(Line::styled(
-                line.to_string(),
-                Style::default().add_modifier(Modifier::DIM),
-            ));
+        self.start_tag(Tag::Table(alignments));
+        for (k, row) in rows.iter().enumerate() {
+            self.start_tag(if k == 0 { Tag::TableHead } else { Tag::TableRow });
+            for c in 0..columns {
+                self.start_tag(Tag::TableCell);
+                let field = row.get(c).map_or("", |f| f.trim());
+                for (i, line) in field.lines().enumerate() {
+                    if i > 0 {
+                        self.hard_break();
+                    }
+                    self.text(line.trim());
+                }
+                self.end_tag(TagEnd::TableCell);
+            }
+            self.end_tag(if k == 0 { TagEnd::TableHead } else { TagEnd::TableRow });
         }
+        self.end_tag(TagEnd::Table);
+        true
     }
 
-    fn inline_raw_html(&mut self, html: &str) {
-        self.spans.push(Span::styled(
-            html.to_string(),
-            Style::default().add_modifier(Modifier::DIM),
-        ));
+    fn footnote_number(&mut self, label: &str) -> usize {
+        let next = self.footnote_numbers.len() + 1;
+        *self.footnote_numbers.entry(label.to_string()).or_insert(next)
     }
 
     fn footnote_ref(&mut self, label: &str) {
-        self.spans.push(Span::styled(
-            format!("[{label}]"),
-            Style::default().fg(Color::Cyan),
-        ));
+        let n = self.footnote_number(label);
+        let span = Span::styled(format!("[{n}]"), Style::default().fg(self.options.theme.footnote));
+        if self.in_table {
+            self.current_cell.push(span);
+            return;
+        }
+        self.start_line();
+        let start = self.spans_len();
+        self.spans.push(span);
+        self.links.push(Link {
+            line: self.lines.len(),
+            start,
+            end: self.spans_len(),
+            url: format!("#fn-{label}"),
+        });
+    }
+
+    /// Appends the collected footnote definitions, numbered in order of first
+    /// reference, under a "Footnotes" heading.
+    fn render_footnotes(&mut self) {
+        let mut defs = std::mem::take(&mut self.footnote_defs);
+        if defs.is_empty() {
+            return;
+        }
+        for def in &defs {
+            self.footnote_number(&def.label);
+        }
+        defs.sort_by_key(|d| self.footnote_numbers[&d.label]);
+
+        if self.lines.last().is_some_and(|l| !l.spans.is_empty()) {
+            self.lines.push(Line::default());
+        }
+        let muted = Style::default().fg(self.options.theme.muted);
+        self.lines.push(Line::styled("Footnotes", muted.add_modifier(Modifier::BOLD)));
+        self.lines.push(Line::default());
+
+        for def in defs {
+            let base = self.lines.len();
+            let marker = format!("[{}] ", self.footnote_numbers[&def.label]);
+            let indent = " ".repeat(marker.len());
+            self.anchors.insert(format!("fn-{}", def.label), base);
+
+            for link in def.links {
+                self.links.push(Link {
+                    line: base + link.line,
+                    start: link.start + marker.len(),
+                    end: link.end + marker.len(),
+                    ..link
+                });
+            }
+            for image in def.images {
+                self.images.push(ImagePlacement {
+                    line: base + image.line,
+                    col: image.col + marker.len() as u16,
+                    ..image
+                });
+            }
+            let body = if def.lines.is_empty() { vec![Line::default()] } else { def.lines };
+            for (i, line) in body.into_iter().enumerate() {
+                if i > 0 && line.spans.is_empty() {
+                    self.lines.push(line);
+                    continue;
+                }
+                let lead = if i == 0 {
+                    Span::styled(marker.clone(), Style::default().fg(self.options.theme.footnote))
+                } else {
+                    Span::raw(indent.clone())
+                };
+                let mut spans = vec![lead];
+                spans.extend(line.spans);
+                self.lines.push(Line::from(spans));
+            }
+        }
+    }
+
+    /// The number of a link destination in the list under the document,
+    /// from 1 in the order destinations first appear.
+    fn link_number(&mut self, url: String) -> usize {
+        match self.link_urls.iter().position(|u| *u == url) {
+            Some(i) => i + 1,
+            None => {
+                self.link_urls.push(url);
+                self.link_urls.len()
+            }
+        }
+    }
+
+    /// The number of the next heading, at `level`, when headings are
+    /// numbered. Levels count from the highest one above, so a document
+    /// without a `#` heading numbers its `##` ones `1.`, `2.`, and a skipped
+    /// level adds no `0`. A heading above the ones it follows at its place
+    /// in the numbering carries on from them (`## Pre` then `# A` are `1.`
+    /// and `2.`), so no number is given twice. Headings in quotes, lists and
+    /// footnotes are not part of the structure and have none, as for
+    /// [`render_section_links`](Self::render_section_links).
+    fn number_heading(&mut self, level: u8) -> Option<String> {
+        let nested = !self.containers.is_empty() || !self.list_stack.is_empty() || self.footnote_stash.is_some();
+        if !self.options.number_headings || nested {
+            return None;
+        }
+        let mut popped = 0;
+        while self.heading_counts.last().is_some_and(|&(l, _)| l > level) {
+            popped = self.heading_counts.pop().map_or(0, |(_, count)| count);
+        }
+        match self.heading_counts.last_mut() {
+            Some((l, count)) if *l == level => *count += 1,
+            _ => self.heading_counts.push((level, popped + 1)),
+        }
+        let counts: Vec<String> = self.heading_counts.iter().map(|(_, count)| count.to_string()).collect();
+        Some(match counts.len() {
+            1 => format!("{}.", counts[0]),
+            _ => counts.join("."),
+        })
+    }
+
+    /// The number of a link in [`LinkStyle::Section`]: superscript digits,
+    /// or `[n]` with ASCII glyphs.
+    fn section_mark(&self, n: usize) -> String {
+        match self.options.ascii {
+            true => format!("[{n}]"),
+            false => html::script(&n.to_string(), Script::Super),
+        }
+    }
+
+    /// The numbered destinations of the section that ends here, with
+    /// [`LinkStyle::Section`], before the next heading or the end of the
+    /// document. Numbers start over in the next section. Headings inside
+    /// quotes, lists and footnotes do not end a section.
+    fn render_section_links(&mut self) {
+        let nested = !self.containers.is_empty() || !self.list_stack.is_empty() || self.footnote_stash.is_some();
+        if self.options.link_style != LinkStyle::Section || nested || self.link_urls.is_empty() {
+            return;
+        }
+        let urls = std::mem::take(&mut self.link_urls);
+        if self.lines.last().is_some_and(|l| !l.spans.is_empty()) {
+            self.lines.push(Line::default());
+        }
+        let marks: Vec<String> = (1..=urls.len()).map(|n| self.section_mark(n)).collect();
+        let width = marks.iter().map(|m| m.width()).max().unwrap_or(0) + 1;
+        let muted = Style::default().fg(self.options.theme.muted);
+        let link = Style::default().fg(self.options.theme.link).add_modifier(Modifier::UNDERLINED);
+        for (mark, url) in marks.into_iter().zip(urls) {
+            let marker = format!("{mark}{}", " ".repeat(width - mark.width()));
+            let end = marker.len() + url.len();
+            self.links.push(Link { line: self.lines.len(), start: marker.len(), end, url: url.clone() });
+            self.lines.push(Line::from(vec![Span::styled(marker, muted), Span::styled(url, link)]));
+        }
+        self.lines.push(Line::default());
+    }
+
+    /// The numbered destinations of [`LinkStyle::Footnote`], after the
+    /// footnotes. Each URL is a link itself.
+    fn render_link_list(&mut self) {
+        let urls = std::mem::take(&mut self.link_urls);
+        if urls.is_empty() {
+            return;
+        }
+        if self.lines.last().is_some_and(|l| !l.spans.is_empty()) {
+            self.lines.push(Line::default());
+        }
+        let muted = Style::default().fg(self.options.theme.muted);
+        self.lines.push(Line::styled("Links", muted.add_modifier(Modifier::BOLD)));
+        self.lines.push(Line::default());
+        let width = format!("[{}] ", urls.len()).len();
+        for (i, url) in urls.into_iter().enumerate() {
+            let marker = format!("{:<width$}", format!("[{}]", i + 1));
+            let end = marker.len() + url.len();
+            self.links.push(Link { line: self.lines.len(), start: marker.len(), end, url: url.clone() });
+            let link = Style::default().fg(self.options.theme.link).add_modifier(Modifier::UNDERLINED);
+            self.lines.push(Line::from(vec![Span::styled(marker, muted), Span::styled(url, link)]));
+        }
     }
 
     fn math(&mut self, math: &str) {
         self.spans.push(Span::styled(
             math.to_string(),
-            Style::default().fg(Color::Yellow).add_modifier(Modifier::ITALIC),
+            Style::default().fg(self.options.theme.math).add_modifier(Modifier::ITALIC),
         ));
     }
 
     fn display_math(&mut self, math: &str) {
         self.flush_line();
-        self.lines.push(Line::styled(
-            math.to_string(),
-            Style::default().fg(Color::Yellow).add_modifier(Modifier::ITALIC),
-        ));
+        let style = Style::default().fg(self.options.theme.math).add_modifier(Modifier::ITALIC);
+        self.push_wrapped(vec![Span::styled(math.to_string(), style)], 0, Vec::new(), None);
         self.push_blank();
     }
 
@@ -446,59 +2297,359 @@ impl Renderer {
 
         let natural_widths: Vec<usize> = (0..num_cols)
             .map(|i| {
-                let header_w = cell_text_width(&self.table_header[i]);
+                let header_w = cell_width(&self.table_header[i]);
                 let max_body = self
                     .table_rows
                     .iter()
-                    .map(|row| row.get(i).map_or(0, |c| cell_text_width(c)))
+                    .map(|row| row.get(i).map_or(0, cell_width))
                     .max()
                     .unwrap_or(0);
                 header_w.max(max_body).max(3)
             })
             .collect();
 
-        let col_widths = budget_columns(&natural_widths, self.width as usize);
-        let border_style = Style::default().fg(Color::DarkGray);
+        let widest_words: Vec<usize> = (0..num_cols)
+            .map(|i| {
+                let body = self.table_rows.iter().filter_map(|row| row.get(i));
+                let cells = std::iter::once(&self.table_header[i]).chain(body);
+                let lines = cells.flat_map(|cell| &cell.lines).map(|line| spans_text(line));
+                lines.flat_map(|line| line.split_whitespace().map(str::width).max()).max().unwrap_or(0)
+            })
+            .collect();
+        // A column is rigid when most of its non-empty body cells are.
+        let rigid: Vec<bool> = (0..num_cols)
+            .map(|i| {
+                let cells = self.table_rows.iter().filter_map(|row| row.get(i)).filter(|c| cell_width(c) > 0);
+                let (rigid, all) = cells.fold((0, 0), |(rigid, all), c| (rigid + usize::from(c.rigid), all + 1));
+                rigid * 2 > all
+            })
+            .collect();
+        // Tables in footnotes move when the definitions are spliced in, so
+        // they are always squeezed.
+        let scroll = self.options.table_overflow == TableOverflow::Scroll && self.footnote_stash.is_none();
+        let prefix = self.line_prefix();
+        let available = (self.options.width as usize).saturating_sub(cell_text_width(&prefix));
+        let col_widths = match scroll {
+            true => natural_widths,
+            false => {
+                let min_col = self.options.table_min_column_width;
+                budget_columns(&natural_widths, &rigid, &widest_words, available, min_col)
+            }
+        };
+        let start = self.lines.len();
+        let border_style = Style::default().fg(self.options.theme.table_border);
+        let glyphs = self.options.glyphs();
+        let [top, middle, bottom] = glyphs.joints;
+
+        self.lines.push(build_border(&col_widths, top, glyphs.rule, border_style));
+
+        let header_lines = build_wrapped_row(
+            &self.table_header,
+            &col_widths,
+            &self.table_alignments,
+            (glyphs, border_style),
+            Style::default().add_modifier(Modifier::BOLD),
+            None,
+            self.options.table_max_cell_lines,
+        );
+        self.lines.extend(header_lines);
+
+        self.lines.push(build_border(&col_widths, middle, glyphs.rule, border_style));
+
+        let zebra_bg = self.options.theme.zebra_bg;
+        for (row_idx, row) in self.table_rows.iter().enumerate() {
+            let row_bg = if row_idx % 2 == 1 { Some(zebra_bg) } else { None };
+            let row_lines = build_wrapped_row(
+                row,
+                &col_widths,
+                &self.table_alignments,
+                (glyphs, border_style),
+                Style::default(),
+                row_bg,
+                self.options.table_max_cell_lines,
+            );
+            self.lines.extend(row_lines);
+        }
+
+        self.lines.push(build_border(&col_widths, bottom, glyphs.rule, border_style));
+        if !prefix.is_empty() {
+            for line in &mut self.lines[start..] {
+                line.spans.splice(0..0, prefix.iter().cloned());
+            }
+        }
+        let width = col_widths.iter().sum::<usize>() + col_widths.len() * 3 + 1;
+        if scroll && width > available {
+            let overflow = width - available;
+            self.tables.push(WideTable { line: start, end: self.lines.len(), overflow });
+        }
+    }
+}
+
+/// Underline character for a heading drawn setext-style in ASCII mode.
+fn underline(options: &RenderOptions, level: pulldown_cmark::HeadingLevel) -> Option<char> {
+    match level {
+        _ if !options.ascii => None,
+        pulldown_cmark::HeadingLevel::H1 => Some('='),
+        pulldown_cmark::HeadingLevel::H2 => Some('-'),
+        _ => None,
+    }
+}
+
+/// What a fenced code block is drawn as, by its language.
+#[derive(Clone, Copy)]
+enum Fence {
+    /// A table of the fields between the delimiter.
+    Table(char),
+    Diagram,
+    /// A box like an alert of the kind, holding markdown.
+    Callout(BlockQuoteKind),
+}
+
+/// Fence languages drawn as something other than highlighted code. The
+/// callouts are the admonition types of MkDocs and Sphinx, colored as the
+/// nearest GitHub alert.
+const FENCES: &[(&str, Fence)] = &[
+    ("csv", Fence::Table(',')),
+    ("tsv", Fence::Table('\t')),
+    ("mermaid", Fence::Diagram),
+    ("note", Fence::Callout(BlockQuoteKind::Note)),
+    ("info", Fence::Callout(BlockQuoteKind::Note)),
+    ("todo", Fence::Callout(BlockQuoteKind::Note)),
+    ("abstract", Fence::Callout(BlockQuoteKind::Note)),
+    ("summary", Fence::Callout(BlockQuoteKind::Note)),
+    ("tldr", Fence::Callout(BlockQuoteKind::Note)),
+    ("details", Fence::Callout(BlockQuoteKind::Note)),
+    ("question", Fence::Callout(BlockQuoteKind::Note)),
+    ("help", Fence::Callout(BlockQuoteKind::Note)),
+    ("faq", Fence::Callout(BlockQuoteKind::Note)),
+    ("example", Fence::Callout(BlockQuoteKind::Note)),
+    ("quote", Fence::Callout(BlockQuoteKind::Note)),
+    ("cite", Fence::Callout(BlockQuoteKind::Note)),
+    ("seealso", Fence::Callout(BlockQuoteKind::Note)),
+    ("tip", Fence::Callout(BlockQuoteKind::Tip)),
+    ("hint", Fence::Callout(BlockQuoteKind::Tip)),
+    ("success", Fence::Callout(BlockQuoteKind::Tip)),
+    ("check", Fence::Callout(BlockQuoteKind::Tip)),
+    ("done", Fence::Callout(BlockQuoteKind::Tip)),
+    ("important", Fence::Callout(BlockQuoteKind::Important)),
+    ("warning", Fence::Callout(BlockQuoteKind::Warning)),
+    ("attention", Fence::Callout(BlockQuoteKind::Warning)),
+    ("caution", Fence::Callout(BlockQuoteKind::Caution)),
+    ("danger", Fence::Callout(BlockQuoteKind::Caution)),
+    ("error", Fence::Callout(BlockQuoteKind::Caution)),
+    ("failure", Fence::Callout(BlockQuoteKind::Caution)),
+    ("fail", Fence::Callout(BlockQuoteKind::Caution)),
+    ("missing", Fence::Callout(BlockQuoteKind::Caution)),
+    ("bug", Fence::Callout(BlockQuoteKind::Caution)),
+];
+
+/// How fences in language `lang` are drawn, unless as highlighted code.
+fn fence(lang: &str) -> Option<Fence> {
+    FENCES.iter().find(|(name, _)| name.eq_ignore_ascii_case(lang)).map(|&(_, fence)| fence)
+}
+
+/// The language of a code block, the first word of a fence's info string,
+/// and the rest of the info string.
+fn code_info(kind: &CodeBlockKind) -> (Option<String>, String) {
+    match kind {
+        CodeBlockKind::Fenced(info) => {
+            let info = info.trim();
+            let (lang, rest) = info.split_once(char::is_whitespace).unwrap_or((info, ""));
+            (Some(lang.to_string()).filter(|l| !l.is_empty()), rest.trim().to_string())
+        }
+        CodeBlockKind::Indented => (None, String::new()),
+    }
+}
+
+/// The code of a block as it is highlighted: tabs expanded and, unless
+/// they are shown, control characters stripped. Whole escape sequences go
+/// before highlighting splits them; shown ones are marked in the
+/// highlighted spans.
+fn code_to_highlight(raw: &str, options: &RenderOptions) -> String {
+    let code = expand_tabs(raw, options.tab_size);
+    match options.show_invisibles {
+        true => code,
+        false => invisibles::strip(&code).into_owned(),
+    }
+}
+
+/// Highlights the code blocks among `events` in parallel before they are
+/// rendered, which then finds each in the highlight cache.
+pub(crate) fn prehighlight<'a: 'e, 'e>(events: impl IntoIterator<Item = &'e Event<'a>>, options: &RenderOptions) {
+    highlight_blocks(&code_blocks(events, options), options.syntax_theme_name());
+}
+
+/// The language and code to highlight of each code block among `events`,
+/// leaving out those drawn as something else.
+fn code_blocks<'a: 'e, 'e>(
+    events: impl IntoIterator<Item = &'e Event<'a>>,
+    options: &RenderOptions,
+) -> Vec<(Option<String>, String)> {
+    let mut blocks = Vec::new();
+    let mut current: Option<(Option<String>, String)> = None;
+    for event in events {
+        match event {
+            Event::Start(Tag::CodeBlock(kind)) => current = Some((code_info(kind).0, String::new())),
+            Event::Text(text) => {
+                if let Some((_, code)) = &mut current {
+                    code.push_str(text);
+                }
+            }
+            Event::End(TagEnd::CodeBlock) => {
+                if let Some((lang, raw)) = current.take()
+                    && lang.as_deref().and_then(fence).is_none()
+                {
+                    blocks.push((lang, code_to_highlight(&raw, options)));
+                }
+            }
+            _ => {}
+        }
+    }
+    blocks
+}
+
+/// Whether fences in language `lang` are drawn as callouts.
+pub(crate) fn is_callout(lang: &str) -> bool {
+    FENCES.iter().any(|(name, fence)| name.eq_ignore_ascii_case(lang) && matches!(fence, Fence::Callout(_)))
+}
+
+/// `word` with its first letter capitalized: the title of a callout fence
+/// that does not give one.
+pub(crate) fn title_case(word: &str) -> String {
+    let mut chars = word.chars();
+    chars.next().map_or_else(String::new, |first| first.to_uppercase().chain(chars).collect())
+}
+
+/// Position of an alert kind in `Theme::alerts`.
+fn alert_index(kind: BlockQuoteKind) -> usize {
+    match kind {
+        BlockQuoteKind::Note => 0,
+        BlockQuoteKind::Tip => 1,
+        BlockQuoteKind::Important => 2,
+        BlockQuoteKind::Warning => 3,
+        BlockQuoteKind::Caution => 4,
+    }
+}
 
-        self.lines.push(build_border(&col_widths, '┌', '┬', '┐', border_style));
+/// Digits of the widest number of each ordered list in `events`, in the
+/// order the lists start.
+fn number_widths(events: &[Event]) -> Vec<usize> {
+    let mut widths = Vec::new();
+    // Per open list: for an ordered one, its entry, first number and items.
+    let mut open: Vec<Option<(usize, u64, u64)>> = Vec::new();
+    for event in events {
+        match event {
+            Event::Start(Tag::List(start)) => open.push(start.map(|start| {
+                widths.push(0);
+                (widths.len() - 1, start, 0)
+            })),
+            Event::Start(Tag::Item) => {
+                if let Some(Some((_, _, items))) = open.last_mut() {
+                    *items += 1;
+                }
+            }
+            Event::End(TagEnd::List(_)) => {
+                if let Some(Some((i, start, items))) = open.pop() {
+                    widths[i] = (start + items.saturating_sub(1)).to_string().len();
+                }
+            }
+            _ => {}
+        }
+    }
+    widths
+}
 
-        let header_lines = build_wrapped_row(
-            &self.table_header,
-            &col_widths,
-            &self.table_alignments,
-            border_style,
-            Style::default().add_modifier(Modifier::BOLD),
-            None,
-            5,
-        );
-        self.lines.extend(header_lines);
+/// Replaces the spaces at the end of a code line with dim dots.
+fn mark_trailing_spaces(spans: &mut Vec<Span<'static>>, ascii: bool) {
+    let mut marked = Vec::new();
+    while let Some(span) = spans.pop() {
+        let kept = span.content.trim_end_matches(' ');
+        let trailing = span.content.len() - kept.len();
+        if trailing > 0 {
+            let dot = invisibles::trailing_space(ascii).repeat(trailing);
+            marked.push(Span::styled(dot, span.style.add_modifier(Modifier::DIM)));
+        }
+        if !kept.is_empty() {
+            spans.push(Span::styled(kept.to_string(), span.style));
+            break;
+        }
+    }
+    spans.extend(marked.into_iter().rev());
+}
 
-        self.lines.push(build_border(&col_widths, '├', '┼', '┤', border_style));
+/// Replaces tabs with spaces up to the next multiple of `tab_size` columns.
+pub(crate) fn expand_tabs(code: &str, tab_size: usize) -> String {
+    expand_tabs_from(code, tab_size, 0)
+}
 
-        let zebra_bg = Color::Indexed(235);
-        for (row_idx, row) in self.table_rows.iter().enumerate() {
-            let row_bg = if row_idx % 2 == 1 { Some(zebra_bg) } else { None };
-            let row_lines = build_wrapped_row(
-                row,
-                &col_widths,
-                &self.table_alignments,
-                border_style,
-                Style::default(),
-                row_bg,
-                5,
-            );
-            self.lines.extend(row_lines);
+/// [`expand_tabs`] for text that starts `start` columns into its line, like
+/// a word after others in a table cell.
+fn expand_tabs_from(code: &str, tab_size: usize, start: usize) -> String {
+    if !code.contains('\t') {
+        return code.to_string();
+    }
+    let tab_size = tab_size.max(1);
+    let mut out = String::with_capacity(code.len());
+    let mut col = start;
+    for (_, ch, width) in char_widths(code) {
+        match ch {
+            '\t' => {
+                let n = tab_size - col % tab_size;
+                out.extend(std::iter::repeat_n(' ', n));
+                col += n;
+            }
+            '\n' => {
+                out.push(ch);
+                col = 0;
+            }
+            _ => {
+                out.push(ch);
+                col += width;
+            }
         }
-
-        self.lines.push(build_border(&col_widths, '└', '┴', '┘', border_style));
     }
+    out
+}
+
+/// The chars of `text` with their byte offsets and display widths, each
+/// grapheme cluster's width given to its first char and none to the rest.
+/// An emoji ZWJ sequence, a flag or a letter with combining marks is
+/// several chars but one character on screen, as wide as the terminal
+/// draws it rather than its chars added up. Breaking a line before a char
+/// of no width would split a cluster, so the wrappers never do.
+pub(crate) fn char_widths(text: &str) -> impl Iterator<Item = (usize, char, usize)> + '_ {
+    text.grapheme_indices(true).flat_map(|(offset, cluster)| {
+        let width = cluster.width();
+        cluster.char_indices().map(move |(i, ch)| (offset + i, ch, if i == 0 { width } else { 0 }))
+    })
+}
+
+fn spans_text(spans: &[Span]) -> String {
+    spans.iter().map(|s| s.content.as_ref()).collect()
 }
 
 fn cell_text_width(spans: &[Span]) -> usize {
     spans.iter().map(|s| s.width()).sum()
 }
 
-fn budget_columns(natural: &[usize], terminal_width: usize) -> Vec<usize> {
+/// Width of the widest line of `cell`.
+fn cell_width(cell: &Cell) -> usize {
+    cell.lines.iter().map(|line| cell_text_width(line)).max().unwrap_or(0)
+}
+
+/// Splits the width left after borders among table columns. Columns that
+/// fit their natural width keep it and the rest share what remains, none
+/// narrower than `min_col`. Rigid columns (mostly code or URLs) keep their
+/// natural width first, narrowest first, as long as every other column can
+/// still get `min_col` and its widest word (`words`), so prose columns
+/// absorb the squeeze without splitting words; otherwise they wrap too.
+fn budget_columns(
+    natural: &[usize],
+    rigid: &[bool],
+    words: &[usize],
+    terminal_width: usize,
+    min_col: usize,
+) -> Vec<usize> {
     let num_cols = natural.len();
     let chrome = num_cols * 3 + 1;
     let available = terminal_width.saturating_sub(chrome);
@@ -508,7 +2659,6 @@ fn budget_columns(natural: &[usize], terminal_width: usize) -> Vec<usize> {
         return natural.to_vec();
     }
 
-    let min_col: usize = 5;
     let mut widths = vec![0usize; num_cols];
     let mut locked = vec![false; num_cols];
     let mut budget = available;
@@ -521,6 +2671,20 @@ fn budget_columns(natural: &[usize], terminal_width: usize) -> Vec<usize> {
         }
     }
 
+    let mut rigid: Vec<usize> = (0..num_cols).filter(|&i| !locked[i] && rigid.get(i) == Some(&true)).collect();
+    rigid.sort_by_key(|&i| natural[i]);
+    for i in rigid {
+        let others: usize = (0..num_cols)
+            .filter(|&j| !locked[j] && j != i)
+            .map(|j| words.get(j).map_or(min_col, |&word| word.clamp(min_col, natural[j].max(min_col))))
+            .sum();
+        if natural[i] + others <= budget {
+            widths[i] = natural[i];
+            budget -= natural[i];
+            locked[i] = true;
+        }
+    }
+
     loop {
         let unlocked: Vec<usize> = (0..num_cols).filter(|i| !locked[*i]).collect();
         if unlocked.is_empty() {
@@ -554,29 +2718,238 @@ fn budget_columns(natural: &[usize], terminal_width: usize) -> Vec<usize> {
     widths
 }
 
-fn build_border(widths: &[usize], left: char, mid: char, right: char, style: Style) -> Line<'static> {
+fn build_border(widths: &[usize], [left, mid, right]: [char; 3], rule: char, style: Style) -> Line<'static> {
     let mut s = String::new();
     s.push(left);
     for (i, &w) in widths.iter().enumerate() {
         for _ in 0..w + 2 {
-            s.push('─');
+            s.push(rule);
         }
         s.push(if i + 1 < widths.len() { mid } else { right });
     }
     Line::styled(s, style)
 }
 
+/// Soft hyphen: an invisible break point inside a word, shown as `-` when
+/// a line breaks there.
+const SOFT_HYPHEN: char = '\u{ad}';
+
+/// Where a line may end, and what breaking there costs on top of the room
+/// it leaves.
+#[derive(Clone, Copy, PartialEq)]
+enum Break {
+    /// At a run of spaces, which is dropped.
+    Space,
+    /// After a `/` or `-` inside a word, as in a path, URL or compound.
+    Inside,
+    /// At a soft hyphen, which shows as `-`.
+    Soft,
+    /// Between syllables, by the hyphenation patterns of the language, with
+    /// a `-` added.
+    Hyphen,
+    /// Between any two characters of a word too wide for a row.
+    Char,
+}
+
+impl Break {
+    fn penalty(self) -> u64 {
+        match self {
+            Break::Space => 0,
+            Break::Inside => 100,
+            Break::Soft => 50,
+            Break::Hyphen => 50,
+            Break::Char => 10_000,
+        }
+    }
+}
+
+/// Wraps paragraph text to rows `first_width` columns wide for the first
+/// and `rest_width` after. Each output row lists its chars with their style
+/// and byte offset in the concatenated input, so callers can map positions
+/// (links) onto rows. Spaces at a break are dropped.
+///
+/// Lines break at spaces, else after a `/` or `-` inside a word or at a
+/// soft hyphen, else between syllables when there is a `language` to
+/// hyphenate by, else, for a word wider than a row, anywhere. Rather than
+/// filling each row in turn, the breaks are chosen together to keep the
+/// room left at the ends of rows even, with in-word breaks weighed against
+/// that, so one long word or URL does not leave a ragged gap above it.
+pub(crate) fn wrap_paragraph(
+    spans: &[Span<'static>],
+    first_width: usize,
+    rest_width: usize,
+    language: Option<Language>,
+) -> Vec<Vec<(char, Style, usize)>> {
+    let mut flat: Vec<(char, usize, Style, usize)> = Vec::new();
+    let mut offset = 0;
+    for span in spans {
+        for (i, ch, w) in char_widths(&span.content) {
+            flat.push((ch, w, span.style, offset + i));
+        }
+        offset += span.content.len();
+    }
+    let n = flat.len();
+    // `widths[i]`: the columns of `flat[..i]`.
+    let mut widths = vec![0; n + 1];
+    for (i, c) in flat.iter().enumerate() {
+        widths[i + 1] = widths[i] + c.1;
+    }
+
+    // Candidate breaks as (where the row ends, where the next one starts,
+    // kind), in order.
+    let mut breaks: Vec<(usize, usize, Break)> = Vec::new();
+    let narrowest = first_width.min(rest_width);
+    let mut i = 0;
+    while i < n {
+        let mut j = i;
+        if flat[i].0 == ' ' {
+            while j < n && flat[j].0 == ' ' {
+                j += 1;
+            }
+            if i > 0 && j < n {
+                breaks.push((i, j, Break::Space));
+            }
+            i = j;
+            continue;
+        }
+        while j < n && flat[j].0 != ' ' {
+            j += 1;
+        }
+        let word = &flat[i..j];
+        let first = breaks.len();
+        for k in (1..word.len()).filter(|&k| word[k].1 > 0) {
+            let (prev, next) = (word[k - 1].0, word[k].0);
+            let kind = match prev {
+                SOFT_HYPHEN => Break::Soft,
+                '/' if next != '/' => Break::Inside,
+                '-' if k > 1 && word[k - 2].0.is_alphanumeric() && next.is_alphanumeric() => Break::Inside,
+                _ => continue,
+            };
+            let end = if kind == Break::Soft { i + k - 1 } else { i + k };
+            breaks.push((end, i + k, kind));
+        }
+        // Words with soft hyphens are broken only where their author allows.
+        if let Some(language) = language.filter(|_| !breaks[first..].iter().any(|b| b.2 == Break::Soft)) {
+            let chars: Vec<char> = word.iter().map(|c| c.0).collect();
+            for k in hyphenate::breaks(&chars, language) {
+                if !breaks[first..].iter().any(|b| b.1 == i + k) {
+                    breaks.push((i + k, i + k, Break::Hyphen));
+                }
+            }
+        }
+        // Pieces of the word still wider than a row may break anywhere.
+        let mut bounds: Vec<usize> = breaks[first..].iter().map(|b| b.1).collect();
+        bounds.insert(0, i);
+        bounds.push(j);
+        for piece in bounds.windows(2) {
+            if widths[piece[1]] - widths[piece[0]] > narrowest {
+                breaks.extend((piece[0] + 1..piece[1]).filter(|&k| flat[k].1 > 0).map(|k| (k, k, Break::Char)));
+            }
+        }
+        breaks[first..].sort_by_key(|b| b.1);
+        i = j;
+    }
+    breaks.push((n, n, Break::Space));
+
+    // `best[b]`: the least cost of the rows up to break `b` and the break
+    // before it, `None` for the start of the paragraph.
+    let mut best: Vec<(u64, Option<usize>)> = Vec::with_capacity(breaks.len());
+    for b in 0..breaks.len() {
+        let (end, _, kind) = breaks[b];
+        let last = b + 1 == breaks.len();
+        let mut choice = (u64::MAX, None);
+        for a in (0..=b).rev() {
+            let (start, limit, before) = match a {
+                0 => (0, first_width, 0),
+                a => (breaks[a - 1].1, rest_width, best[a - 1].0),
+            };
+            let width = widths[end] - widths[start] + usize::from(matches!(kind, Break::Soft | Break::Hyphen));
+            // Rows too wide are only taken when nothing else fits.
+            let cost = match width.checked_sub(limit) {
+                Some(over) if over > 0 => 1 << 40 | over as u64,
+                _ if last => 0,
+                _ => ((limit - width) as u64).pow(2) + kind.penalty(),
+            };
+            let total = before.saturating_add(cost);
+            if total < choice.0 {
+                choice = (total, a.checked_sub(1));
+            }
+            if width > first_width.max(rest_width) && choice.0 < u64::MAX {
+                break;
+            }
+        }
+        best.push(choice);
+    }
+
+    let mut chosen = Vec::new();
+    let mut b = Some(breaks.len() - 1);
+    while let Some(k) = b {
+        chosen.push(k);
+        b = best[k].1;
+    }
+    let mut start = 0;
+    let mut rows = Vec::new();
+    for &k in chosen.iter().rev() {
+        let (mut end, next, kind) = breaks[k];
+        while end > start && flat[end - 1].0 == ' ' {
+            end -= 1;
+        }
+        let mut row: Vec<(char, Style, usize)> =
+            flat[start..end].iter().filter(|c| c.0 != SOFT_HYPHEN).map(|&(c, _, st, o)| (c, st, o)).collect();
+        match kind {
+            Break::Soft => row.push(('-', flat[end].2, flat[end].3)),
+            Break::Hyphen => row.push(('-', flat[end - 1].2, flat[end - 1].3)),
+            _ => {}
+        }
+        rows.push(row);
+        start = next;
+    }
+    rows
+}
+
 struct StyledWord {
     chars: Vec<(char, usize, Style)>,
     width: usize,
     trailing_space: bool,
 }
 
+/// Wraps each line of a cell to `max_width`, in at most `max_lines` rows
+/// together. A cell with lines left over ends in `ellipsis`.
+fn wrap_cell(
+    cell: &[Vec<Span<'static>>],
+    max_width: usize,
+    max_lines: usize,
+    base_style: Style,
+    ellipsis: &'static str,
+) -> Vec<Vec<Span<'static>>> {
+    // `...` is cut down to fit the narrowest columns.
+    let ellipsis = match ellipsis.width() > max_width {
+        true => &ellipsis[..max_width.min(ellipsis.len())],
+        false => ellipsis,
+    };
+    let mut rows: Vec<Vec<Span<'static>>> = Vec::new();
+    for line in cell {
+        if rows.len() >= max_lines {
+            let last = rows.pop().unwrap_or_default();
+            let mut truncated = truncate_line_spans(&last, max_width.saturating_sub(ellipsis.width()));
+            truncated.push(Span::styled(ellipsis, Style::default().fg(Color::DarkGray)));
+            rows.push(truncated);
+            break;
+        }
+        rows.extend(wrap_cell_spans(line, max_width, max_lines - rows.len(), base_style, ellipsis));
+    }
+    if rows.is_empty() {
+        rows.push(Vec::new());
+    }
+    rows
+}
+
 fn wrap_cell_spans(
     spans: &[Span<'static>],
     max_width: usize,
     max_lines: usize,
     base_style: Style,
+    ellipsis: &'static str,
 ) -> Vec<Vec<Span<'static>>> {
     let flat = flatten_to_styled_chars(spans, base_style);
     let total_width: usize = flat.iter().map(|(_, w, _)| w).sum();
@@ -597,7 +2970,7 @@ fn wrap_cell_spans(
     for word in &words {
         if cur_width > 0 && cur_width + word.width > max_width {
             if lines.len() + 1 >= max_lines {
-                return finish_truncated(lines, &cur_chars, max_width);
+                return finish_truncated(lines, &cur_chars, max_width, ellipsis);
             }
             lines.push(coalesce_chars(&cur_chars));
             cur_chars.clear();
@@ -608,7 +2981,7 @@ fn wrap_cell_spans(
             for &(ch, cw, style) in &word.chars {
                 if cur_width + cw > max_width {
                     if lines.len() + 1 >= max_lines {
-                        return finish_truncated(lines, &cur_chars, max_width);
+                        return finish_truncated(lines, &cur_chars, max_width, ellipsis);
                     }
                     lines.push(coalesce_chars(&cur_chars));
                     cur_chars.clear();
@@ -650,10 +3023,11 @@ fn finish_truncated(
     mut lines: Vec<Vec<Span<'static>>>,
     cur_chars: &[(char, Style)],
     max_width: usize,
+    ellipsis: &'static str,
 ) -> Vec<Vec<Span<'static>>> {
     let coalesced = coalesce_chars(cur_chars);
-    let mut truncated = truncate_line_spans(&coalesced, max_width.saturating_sub(1));
-    truncated.push(Span::styled("…", Style::default().fg(Color::DarkGray)));
+    let mut truncated = truncate_line_spans(&coalesced, max_width.saturating_sub(ellipsis.width()));
+    truncated.push(Span::styled(ellipsis, Style::default().fg(Color::DarkGray)));
     lines.push(truncated);
     lines
 }
@@ -662,8 +3036,7 @@ fn flatten_to_styled_chars(spans: &[Span<'static>], base_style: Style) -> Vec<(c
     let mut out = Vec::new();
     for span in spans {
         let style = base_style.patch(span.style);
-        for ch in span.content.chars() {
-            let w = unicode_width::UnicodeWidthChar::width(ch).unwrap_or(0);
+        for (_, ch, w) in char_widths(&span.content) {
             out.push((ch, w, style));
         }
     }
@@ -702,7 +3075,7 @@ fn split_into_words(chars: &[(char, usize, Style)]) -> Vec<StyledWord> {
     words
 }
 
-fn coalesce_chars(chars: &[(char, Style)]) -> Vec<Span<'static>> {
+pub(crate) fn coalesce_chars(chars: &[(char, Style)]) -> Vec<Span<'static>> {
     let mut spans: Vec<Span<'static>> = Vec::new();
     let mut buf = String::new();
     let mut cur_style = Style::default();
@@ -737,8 +3110,7 @@ fn truncate_line_spans(spans: &[Span<'static>], budget: usize) -> Vec<Span<'stat
         } else {
             let mut truncated = String::new();
             let mut used = 0;
-            for ch in span.content.chars() {
-                let cw = unicode_width::UnicodeWidthChar::width(ch).unwrap_or(0);
+            for (_, ch, cw) in char_widths(&span.content) {
                 if used + cw > remaining {
                     break;
                 }
@@ -755,24 +3127,24 @@ fn truncate_line_spans(spans: &[Span<'static>], budget: usize) -> Vec<Span<'stat
 
 fn build_empty_row(
     widths: &[usize],
-    border_style: Style,
+    (bar, border_style): (&'static str, Style),
     bg_style: Option<Style>,
 ) -> Line<'static> {
     let pad_style = bg_style.unwrap_or_default();
     let mut spans: Vec<Span<'static>> = Vec::new();
-    spans.push(Span::styled("│", border_style));
+    spans.push(Span::styled(bar, border_style));
     for &w in widths {
         spans.push(Span::styled(" ".repeat(w + 2), pad_style));
-        spans.push(Span::styled("│", border_style));
+        spans.push(Span::styled(bar, border_style));
     }
     Line::from(spans)
 }
 
 fn build_wrapped_row(
-    cells: &[Vec<Span<'static>>],
+    cells: &[Cell],
     widths: &[usize],
     alignments: &[Alignment],
-    border_style: Style,
+    (glyphs, border_style): (&'static Glyphs, Style),
     cell_base_style: Style,
     row_bg: Option<Color>,
     max_lines: usize,
@@ -781,8 +3153,8 @@ fn build_wrapped_row(
 
     let wrapped: Vec<Vec<Vec<Span<'static>>>> = (0..widths.len())
         .map(|i| {
-            let cell = cells.get(i).map(|c| c.as_slice()).unwrap_or(&[]);
-            wrap_cell_spans(cell, widths[i], max_lines, cell_base_style)
+            let cell = cells.get(i).map(|c| c.lines.as_slice()).unwrap_or(&[]);
+            wrap_cell(cell, widths[i], max_lines, cell_base_style, glyphs.ellipsis)
         })
         .collect();
 
@@ -792,12 +3164,12 @@ fn build_wrapped_row(
     let multiline = num_visual_rows > 1;
 
     if multiline {
-        output_lines.push(build_empty_row(widths, border_style, None));
+        output_lines.push(build_empty_row(widths, (glyphs.bar, border_style), None));
     }
 
     for vrow in 0..num_visual_rows {
         let mut spans: Vec<Span<'static>> = Vec::new();
-        spans.push(Span::styled("│", border_style));
+        spans.push(Span::styled(glyphs.bar, border_style));
 
         for (i, &max_w) in widths.iter().enumerate() {
             let cell_line = wrapped[i].get(vrow);
@@ -825,10 +3197,10 @@ fn build_wrapped_row(
             if let Some(cell_spans) = cell_line {
                 for span in cell_spans {
                     let mut s = span.style;
-                    if let Some(bg) = bg_style {
-                        if let (None, Some(bg_color)) = (span.style.bg, bg.bg) {
-                            s = s.bg(bg_color);
-                        }
+                    if let Some(bg) = bg_style
+                        && let (None, Some(bg_color)) = (span.style.bg, bg.bg)
+                    {
+                        s = s.bg(bg_color);
                     }
                     spans.push(Span::styled(span.content.clone().into_owned(), s));
                 }
@@ -839,14 +3211,14 @@ fn build_wrapped_row(
             }
 
             spans.push(Span::styled(" ", pad_style));
-            spans.push(Span::styled("│", border_style));
+            spans.push(Span::styled(glyphs.bar, border_style));
         }
 
         output_lines.push(Line::from(spans));
     }
 
     if multiline {
-        output_lines.push(build_empty_row(widths, border_style, None));
+        output_lines.push(build_empty_row(widths, (glyphs.bar, border_style), None));
     }
 
     output_lines
@@ -879,7 +3251,7 @@ mod tests {
     #[test]
     fn test_heading_prefixes() {
         let md = load_fixture("headings.md");
-        let text = render_markdown(&md, 80);
+        let text = render_markdown(&md, 80, &Theme::default());
         let plain = text_to_plain(&text);
 
         assert!(plain.contains("# Heading 1"));
@@ -890,12 +3262,72 @@ mod tests {
         assert!(plain.contains("#### Heading 6"));
     }
 
+    #[test]
+    fn test_heading_uses_theme_color() {
+        let text = render_markdown("# Title\n", 80, &crate::theme::LIGHT);
+        let title_span = text.lines[0]
+            .spans
+            .iter()
+            .find(|s| s.content.contains("Title"))
+            .unwrap();
+        assert_eq!(title_span.style.fg, Some(crate::theme::LIGHT.headings[0]));
+    }
+
+    #[test]
+    fn test_slugify() {
+        assert_eq!(slugify("Getting Started"), "getting-started");
+        assert_eq!(slugify("What's `new` in v2.0?"), "whats-new-in-v20");
+        assert_eq!(slugify("snake_case & kebab-case"), "snake_case--kebab-case");
+        assert_eq!(slugify("Über Café"), "über-café");
+    }
+
+    #[test]
+    fn test_heading_anchors() {
+        let md = "# Intro\n\ntext\n\n## Setup\n\n## Setup\n";
+        let doc = render_document(md, &RenderOptions::new(80));
+
+        assert_eq!(doc.headings.len(), 3);
+        assert_eq!(doc.headings[1].level, 2);
+        assert_eq!(doc.headings[1].title, "Setup");
+        assert_eq!(doc.anchors["intro"], 0);
+        assert_eq!(doc.text.lines[doc.anchors["setup"]].to_string(), "## Setup");
+        assert!(doc.anchors["setup-1"] > doc.anchors["setup"]);
+    }
+
+    #[test]
+    fn test_numbered_headings() {
+        let md = "# Guide\n\n## Install\n\n### Source\n\n## Usage\n\n#### Deep\n\n# Appendix\n";
+        let doc = render_document(md, &RenderOptions::new(80).number_headings(true));
+        let numbers: Vec<_> = doc.headings.iter().map(|h| h.number.as_deref().unwrap_or_default()).collect();
+        assert_eq!(numbers, ["1.", "1.1", "1.1.1", "1.2", "1.2.1", "2."]);
+        assert_eq!(doc.text.lines[doc.headings[2].line].to_string(), "### 1.1.1 Source");
+        assert_eq!(doc.headings[2].title, "Source");
+        assert!(doc.anchors.contains_key("source"));
+
+        let doc = render_document("## One\n\n## Two\n", &RenderOptions::new(80).number_headings(true));
+        assert_eq!(doc.headings[1].number.as_deref(), Some("2."));
+
+        // A higher level than before carries on rather than start again.
+        let numbers = |md| {
+            let doc = render_document(md, &RenderOptions::new(80).number_headings(true));
+            doc.headings.iter().map(|h| h.number.clone().unwrap_or_default()).collect::<Vec<_>>()
+        };
+        assert_eq!(numbers("## Pre\n\n# A\n\n## B\n\n# C\n"), ["1.", "2.", "2.1", "3."]);
+        assert_eq!(numbers("# A\n\n### B\n\n### C\n\n## D\n\n### E\n"), ["1.", "1.1", "1.2", "1.3", "1.3.1"]);
+        // Quoted and listed headings are left out of the numbering.
+        let md = "# A\n\n> # Quoted\n\n- ## Listed\n\n# B\n\n[^n]\n\n[^n]: # Note\n";
+        assert_eq!(numbers(md), ["1.", "", "", "2."]);
+        let doc = render_document(md, &RenderOptions::new(80).number_headings(true));
+        assert!(text_to_plain(&doc.text).contains("│ # Quoted"));
+        assert_eq!(render_document("# One\n", &RenderOptions::new(80)).headings[0].number, None);
+    }
+
     // --- Inline ---
 
     #[test]
     fn test_inline_code_backticks() {
         let md = load_fixture("inline.md");
-        let text = render_markdown(&md, 80);
+        let text = render_markdown(&md, 80, &Theme::default());
         let plain = text_to_plain(&text);
 
         assert!(plain.contains("`inline code`"));
@@ -904,19 +3336,190 @@ mod tests {
     #[test]
     fn test_link_url_appended() {
         let md = load_fixture("inline.md");
-        let text = render_markdown(&md, 80);
+        let text = render_markdown(&md, 80, &Theme::default());
         let plain = text_to_plain(&text);
 
         assert!(plain.contains("link (https://example.com)"));
         assert!(plain.contains("another link (https://example.com/path?q=1)"));
     }
 
+    #[test]
+    fn test_link_styles() {
+        let md = "See [docs](https://a.io) and [more](https://b.io), then [docs](https://a.io).\n";
+        let plain = |style| text_to_plain(&render_document(md, &RenderOptions::new(80).link_style(style)).text);
+        assert_eq!(plain(LinkStyle::Hide), "See docs and more, then docs.\n");
+        assert_eq!(plain(LinkStyle::Osc8), plain(LinkStyle::Hide));
+        assert_eq!(
+            plain(LinkStyle::Footnote),
+            "See docs [1] and more [2], then docs [1].\n\nLinks\n\n[1] https://a.io\n[2] https://b.io"
+        );
+        let doc = render_document(md, &RenderOptions::new(80).link_style(LinkStyle::Footnote));
+        let listed = doc.links.last().unwrap();
+        assert_eq!((listed.line, listed.start, listed.end, listed.url.as_str()), (5, 4, 16, "https://b.io"));
+
+        let md = format!("{md}\n## Next\n\nAnd [b](https://b.io).\n");
+        let section = |options: RenderOptions| text_to_plain(&render_document(&md, &options).text);
+        let options = RenderOptions::new(80).link_style(LinkStyle::Section);
+        assert_eq!(
+            section(options.clone()),
+            "See docs¹ and more², then docs¹.\n\n¹ https://a.io\n² https://b.io\n\n## Next\n\nAnd b¹.\n\n\
+             ¹ https://b.io\n"
+        );
+        let ascii = section(options.ascii(true));
+        assert!(ascii.starts_with("See docs[1] and more[2], then docs[1].\n\n[1] https://a.io\n"));
+    }
+
+    #[test]
+    fn test_link_positions() {
+        let doc = render_document(
+            "See [the docs](docs.md) and [site](https://example.com).\n",
+            &RenderOptions::new(80),
+        );
+        assert_eq!(doc.links.len(), 2);
+        let line: String = doc.text.lines[doc.links[0].line]
+            .spans
+            .iter()
+            .map(|s| s.content.as_ref())
+            .collect();
+        assert_eq!(&line[doc.links[0].start..doc.links[0].end], "the docs");
+        assert_eq!(doc.links[0].url, "docs.md");
+        assert_eq!(&line[doc.links[1].start..doc.links[1].end], "site");
+    }
+
+    #[test]
+    fn test_image_placeholder_without_graphics() {
+        let plain = text_to_plain(&render_markdown("![a cat](cat.png)\n", 80, &Theme::default()));
+        assert!(plain.contains("🖼\u{fe0f} [a cat] (cat.png)"), "{plain}");
+
+        let dir = std::env::temp_dir().join(format!("mdview-placeholder-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        image::RgbaImage::new(8, 6).save(dir.join("cat.png")).unwrap();
+        let md = "See ![](cat.png) and [![badge](https://ci/badge.svg)](https://ci).\n";
+        let doc = render_document(md, &RenderOptions::new(100).ascii(true).base_dir(Some(dir.clone())));
+        std::fs::remove_dir_all(&dir).unwrap();
+        let line = doc.text.lines[0].to_string();
+        assert_eq!(line, "See img [image] (cat.png, 8x6) and img [badge] (https://ci/badge.svg) (https://ci).");
+        // The placeholder links to the image, except inside a link.
+        let links: Vec<(&str, &str)> =
+            doc.links.iter().map(|l| (&line[l.start..l.end], l.url.as_str())).collect();
+        assert_eq!(
+            links,
+            [("img [image] (cat.png, 8x6)", "cat.png"), ("img [badge] (https://ci/badge.svg)", "https://ci")]
+        );
+    }
+
+    #[test]
+    fn test_footnotes_rendered_at_end() {
+        let md = "Claim[^src] and more[^b].\n\n[^b]: Second note.\n\n[^src]: The source.\n\nTail paragraph.\n";
+        let doc = render_document(md, &RenderOptions::new(80));
+        let plain = text_to_plain(&doc.text);
+
+        assert!(plain.contains("Claim[1] and more[2]."));
+        let footnotes_at = plain.find("Footnotes").expect("Footnotes section");
+        assert!(plain.find("Tail paragraph").unwrap() < footnotes_at);
+        assert!(plain.contains("[1] The source."));
+        assert!(plain.contains("[2] Second note."));
+
+        let target = doc.anchors["fn-src"];
+        assert!(doc.text.lines[target].to_string().starts_with("[1] The source."));
+        assert!(doc.links.iter().any(|l| l.url == "#fn-src"));
+    }
+
+    #[test]
+    fn test_paragraph_wraps_to_width() {
+        let md = "The quick brown fox jumps over the lazy dog and keeps running far away.\n";
+        let text = render_markdown(md, 20, &Theme::default());
+        let plain = text_to_plain(&text);
+
+        assert!(text.lines.len() > 3, "Paragraph should wrap into several lines");
+        for line in plain.lines() {
+            assert!(line.chars().count() <= 20, "Line overflows: {line:?}");
+            assert!(!line.starts_with(' ') && !line.ends_with(' '));
+        }
+        let rows: Vec<&str> = plain.lines().collect();
+        assert_eq!(rows.join(" "), md.trim_end());
+        assert_eq!(rows[0], "The quick brown", "The rows are balanced rather than filled in turn");
+    }
+
+    #[test]
+    fn test_long_words_break_at_slashes_hyphens_and_soft_hyphens() {
+        let rows = |md: &str, width| {
+            text_to_plain(&render_markdown(md, width, &Theme::default())).lines().map(String::from).collect::<Vec<_>>()
+        };
+        let md = "See https://example.com/docs/render.rs for a well-known case.\n";
+        assert_eq!(rows(md, 16), ["See https://", "example.com/", "docs/render.rs", "for a well-known", "case."]);
+
+        let md = "Truly in\u{ad}com\u{ad}pre\u{ad}hen\u{ad}si\u{ad}ble.\n";
+        assert_eq!(rows(md, 14), ["Truly incom-", "prehensible."]);
+        assert_eq!(rows(md, 40), ["Truly incomprehensible."]);
+        assert_eq!(rows("abcdefghijklmnopqrstuvwxyz\n", 10), ["abcdefghij", "klmnopqrst", "uvwxyz"]);
+    }
+
+    #[test]
+    fn test_hyphenate_by_language() {
+        let rows = |md: &str, width, language: Option<&str>| {
+            let options = RenderOptions::new(width).hyphenate(language.and_then(Language::parse));
+            text_to_plain(&render_document(md, &options).text).lines().map(String::from).collect::<Vec<_>>()
+        };
+        let md = "Some hyphenation **rules** apply.\n";
+        assert_eq!(rows(md, 10, None), ["Some hyp", "henation", "rules", "apply."]);
+        assert_eq!(rows(md, 10, Some("en")), ["Some hy-", "phenation", "rules", "apply."]);
+        assert_eq!(rows(md, 40, Some("en")), ["Some hyphenation rules apply."]);
+
+        let options = RenderOptions::new(8).hyphenate(Language::parse("en"));
+        let doc = render_document("[Hyphenation](h.md) matters\n", &options);
+        let fragments: Vec<String> =
+            doc.links.iter().map(|l| doc.text.lines[l.line].to_string()[l.start..l.end].to_string()).collect();
+        assert_eq!(fragments.concat().replace('-', ""), "Hyphenation", "{fragments:?}");
+    }
+
+    #[test]
+    fn test_wrapped_blockquote_keeps_prefix() {
+        let md = "> one two three four five six seven eight nine ten\n";
+        let text = render_markdown(md, 16, &Theme::default());
+        let plain = text_to_plain(&text);
+        let quoted: Vec<&str> = plain.lines().filter(|l| !l.is_empty()).collect();
+
+        assert!(quoted.len() > 1);
+        assert!(quoted.iter().all(|l| l.starts_with("│ ")), "{quoted:?}");
+    }
+
+    #[test]
+    fn test_link_split_across_wrapped_lines() {
+        let md = "Read [the complete guide](guide.md) now\n";
+        let doc = render_document(md, &RenderOptions::new(18));
+        let fragments: Vec<String> = doc
+            .links
+            .iter()
+            .map(|l| doc.text.lines[l.line].to_string()[l.start..l.end].to_string())
+            .collect();
+
+        assert!(fragments.len() > 1, "Link should be split: {fragments:?}");
+        assert_eq!(fragments.join(" "), "the complete guide");
+    }
+
+    #[test]
+    fn test_bidi_puts_right_to_left_rows_in_visual_order() {
+        let md = "- שלום [עולם](a.md) 42\n\n> English with שלום עולם inside.\n\n```\nשלום\n```\n";
+        let doc = render_document(md, &RenderOptions::new(24).bidi(true));
+        let lines: Vec<String> = doc.text.lines.iter().map(|l| l.to_string()).collect();
+        assert_eq!(lines[0], "   42 (a.md) םלוע םולש •");
+        assert_eq!(lines[2], "│ English with םלוע םולש");
+        assert_eq!(lines[3], "│ inside.");
+        assert!(lines.contains(&"  שלום".to_string()), "{lines:?}");
+        let link = &doc.links[0];
+        assert_eq!((link.line, &lines[0][link.start..link.end]), (0, "םלוע"));
+
+        let plain = render_document(md, &RenderOptions::new(24));
+        assert_eq!(plain.text.lines[0].to_string(), "• שלום עולם (a.md) 42");
+    }
+
     // --- Lists ---
 
     #[test]
     fn test_tight_list_no_blank_lines() {
         let md = "- Apple\n- Banana\n- Cherry\n";
-        let text = render_markdown(md, 80);
+        let text = render_markdown(md, 80, &Theme::default());
         let plain = text_to_plain(&text);
 
         let item_indices: Vec<usize> = plain
@@ -935,7 +3538,7 @@ mod tests {
     #[test]
     fn test_loose_list_renders_all_items() {
         let md = "- First item\n\n- Second item\n\n- Third item\n";
-        let text = render_markdown(md, 80);
+        let text = render_markdown(md, 80, &Theme::default());
         let plain = text_to_plain(&text);
 
         let item_lines: Vec<&str> = plain.lines().filter(|l| l.contains('•')).collect();
@@ -948,7 +3551,7 @@ mod tests {
     #[test]
     fn test_ordered_list_numbering() {
         let md = "1. One\n2. Two\n3. Three\n";
-        let text = render_markdown(md, 80);
+        let text = render_markdown(md, 80, &Theme::default());
         let plain = text_to_plain(&text);
 
         assert!(plain.contains("1. One"));
@@ -959,7 +3562,7 @@ mod tests {
     #[test]
     fn test_nested_list_indent() {
         let md = "- Parent\n  - Child A\n  - Child B\n";
-        let text = render_markdown(md, 80);
+        let text = render_markdown(md, 80, &Theme::default());
         let plain = text_to_plain(&text);
 
         let parent_line = plain.lines().find(|l| l.contains("Parent")).unwrap();
@@ -973,12 +3576,57 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_wrapped_items_hang_under_their_text() {
+        let md = "9. Nine, long enough to wrap onto a second row.\n10. Ten\n\n\
+                  - [ ] A task long enough to wrap onto a second row.\n";
+        let plain = text_to_plain(&render_document(md, &RenderOptions::new(32)).text);
+        let lines: Vec<&str> = plain.lines().filter(|l| !l.is_empty()).collect();
+        assert_eq!(
+            lines,
+            [
+                " 9. Nine, long enough to wrap",
+                "    onto a second row.",
+                "10. Ten",
+                "• [ ] A task long enough to wrap",
+                "      onto a second row.",
+            ],
+            "{plain}"
+        );
+    }
+
+    #[test]
+    fn test_nested_blocks_compose_prefixes() {
+        let md = "> - Item in a quote, long enough to wrap onto a second row.\n>\n\
+                  >   ```\n>   code\n>   ```\n>\n>   > Quote in the item.\n>\n>   1. Nested\n\n\
+                  - Item\n\n  | a |\n  |---|\n  | 1 |\n";
+        let plain = text_to_plain(&render_document(md, &RenderOptions::new(40)).text);
+        let lines: Vec<&str> = plain.lines().filter(|l| !l.is_empty()).collect();
+        assert_eq!(
+            lines,
+            [
+                "│ • Item in a quote, long enough to wrap",
+                "│   onto a second row.",
+                "│     code",
+                "│   │ Quote in the item.",
+                "│   1. Nested",
+                "• Item",
+                "  ┌─────┐",
+                "  │ a   │",
+                "  ├─────┤",
+                "  │ 1   │",
+                "  └─────┘",
+            ],
+            "{plain}"
+        );
+    }
+
     // --- Tables ---
 
     #[test]
     fn test_table_border_chars() {
         let md = load_fixture("tables.md");
-        let text = render_markdown(&md, 80);
+        let text = render_markdown(&md, 80, &Theme::default());
         let plain = text_to_plain(&text);
 
         for ch in ['┌', '┬', '┐', '├', '┼', '┤', '└', '┴', '┘'] {
@@ -989,7 +3637,7 @@ mod tests {
     #[test]
     fn test_table_column_count() {
         let md = "| A | B | C |\n|---|---|---|\n| 1 | 2 | 3 |\n";
-        let text = render_markdown(md, 80);
+        let text = render_markdown(md, 80, &Theme::default());
 
         let content_lines: Vec<String> = text
             .lines
@@ -1013,7 +3661,7 @@ mod tests {
     fn test_table_fits_width() {
         let md = load_fixture("tables.md");
         let width: u16 = 60;
-        let text = render_markdown(&md, width);
+        let text = render_markdown(&md, width, &Theme::default());
 
         for (i, line) in text.lines.iter().enumerate() {
             let line_width: usize = line.spans.iter().map(|s| s.width()).sum();
@@ -1028,7 +3676,7 @@ mod tests {
     fn test_table_long_word_wraps() {
         let md = "| Path |\n|------|\n| /very/long/path/to/some/deeply/nested/file.txt |\n";
         let width: u16 = 30;
-        let text = render_markdown(md, width);
+        let text = render_markdown(md, width, &Theme::default());
 
         for (i, line) in text.lines.iter().enumerate() {
             let line_width: usize = line.spans.iter().map(|s| s.width()).sum();
@@ -1039,12 +3687,59 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_grapheme_clusters_keep_tables_and_rows_aligned() {
+        // A family (three emoji joined by ZWJ), a flag, a skin tone and a
+        // combining accent, each one character on screen.
+        let md = "| Who | Note |\n|-----|------|\n| 👨‍👩‍👧 | 🇯🇵 👍🏽 cafe\u{301} ok |\n| x | ❤️❤️❤️❤️❤️❤️❤️❤️❤️ |\n";
+        let text = render_markdown(md, 24, &Theme::default());
+        let widths: Vec<usize> = text.lines.iter().map(|l| l.width()).filter(|&w| w > 0).collect();
+        assert!(widths.iter().all(|&w| w == widths[0] && w <= 24), "{widths:?}");
+        let plain = text_to_plain(&text);
+        assert!(plain.contains("👨‍👩‍👧") && plain.contains("cafe\u{301}"), "{plain}");
+
+        let rows = |md: &str, width| {
+            text_to_plain(&render_markdown(md, width, &Theme::default())).lines().map(String::from).collect::<Vec<_>>()
+        };
+        assert_eq!(rows("🇯🇵🇯🇵🇯🇵🇯🇵🇯🇵🇯🇵\n", 5), ["🇯🇵🇯🇵", "🇯🇵🇯🇵", "🇯🇵🇯🇵"]);
+        assert_eq!(rows("e\u{301}e\u{301}e\u{301}e\u{301}\n", 3), ["e\u{301}e\u{301}e\u{301}", "e\u{301}"]);
+    }
+
+    #[test]
+    fn test_table_cell_line_breaks() {
+        let md = "| Key | Action |\n|-----|--------|\n| j | Down<br>one line |\n| k | Up |\n";
+        let plain = text_to_plain(&render_markdown(md, 80, &Theme::default()));
+        let lines: Vec<&str> = plain.lines().collect();
+        assert_eq!(
+            lines[3..8],
+            [
+                "│     │          │",
+                "│ j   │ Down     │",
+                "│     │ one line │",
+                "│     │          │",
+                "│ k   │ Up       │",
+            ]
+        );
+
+        let html = "<table><tr><th>A</th></tr><tr><td><p>One.</p><p>Two.</p></td></tr></table>\n";
+        let plain = text_to_plain(&render_markdown(html, 80, &Theme::default()));
+        assert!(plain.contains("│ One. │\n│ Two. │"));
+    }
+
+    #[test]
+    fn test_wrap_cell_shares_line_budget() {
+        let cell = vec![vec![Span::raw("one")], vec![Span::raw("two")], vec![Span::raw("three")]];
+        let rows = wrap_cell(&cell, 10, 2, Style::default(), UNICODE.ellipsis);
+        let rows: Vec<String> = rows.iter().map(|r| r.iter().map(|s| s.content.as_ref()).collect()).collect();
+        assert_eq!(rows, ["one", "two…"]);
+    }
+
     // --- Code Blocks ---
 
     #[test]
     fn test_code_block_content() {
         let md = load_fixture("code-blocks.md");
-        let text = render_markdown(&md, 80);
+        let text = render_markdown(&md, 80, &Theme::default());
         let plain = text_to_plain(&text);
 
         assert!(plain.contains("println!"));
@@ -1054,12 +3749,128 @@ mod tests {
         assert!(plain.contains("Indented code block"));
     }
 
+    #[test]
+    fn test_code_block_tabs_expanded() {
+        let md = "```\nfn main() {\n\tx();\n}\n```\n";
+        let text = render_document(md, &RenderOptions::new(80).tab_size(2)).text;
+        let plain = text_to_plain(&text);
+
+        assert!(!plain.contains('\t'));
+        assert!(plain.contains("    x();"), "2-space code indent + 2-column tab");
+    }
+
+    #[test]
+    fn test_control_characters_dropped_or_shown() {
+        let md = "Some \x1b[31mred\x1b[0m text\x0c and `co\x07de`.\n\n```rust\nlet x\x1b[2J = 1;  \r\n```\n\n\
+                  | a\x1b[1m | b |\n|---|---|\n| x | y |\n";
+        let plain = text_to_plain(&render_document(md, &RenderOptions::new(80)).text);
+        assert!(!plain.contains(invisibles::is_control), "{plain:?}");
+        assert!(plain.contains("Some red text and `code`."), "{plain}");
+        assert!(plain.contains("let x = 1;"), "{plain}");
+        assert!(plain.contains("│ a   │ b   │"), "{plain}");
+
+        let text = render_document(md, &RenderOptions::new(80).show_invisibles(true)).text;
+        let plain = text_to_plain(&text);
+        assert!(!plain.contains(invisibles::is_control), "{plain:?}");
+        assert!(plain.contains("Some ␛[31mred␛[0m text␌ and `co␇de`."), "{plain}");
+        assert!(plain.contains("let x␛[2J = 1;··"), "{plain}");
+        assert!(plain.contains("│ a␛[1m │ b   │"), "{plain}");
+        let symbol = text.lines.iter().flat_map(|l| &l.spans).find(|s| s.content == "␌").unwrap();
+        assert!(symbol.style.add_modifier.contains(Modifier::DIM));
+    }
+
+    #[test]
+    fn test_tabs_expanded_in_tables_and_indented_code() {
+        let md = "| a\tb | `c\td` |\n|---|---|\n| x | y |\n\n\tindented\tcode\n";
+        let text = render_document(md, &RenderOptions::new(80).tab_size(4)).text;
+        let plain = text_to_plain(&text);
+
+        assert!(!plain.contains('\t'));
+        assert!(plain.contains("a   b"), "{plain}");
+        assert!(plain.contains("`c  d`"), "{plain}");
+        assert!(plain.contains("indented    code"), "{plain}");
+        let widths: Vec<usize> = plain.lines().take(5).map(unicode_width::UnicodeWidthStr::width).collect();
+        assert!(widths.iter().all(|&w| w == widths[0]), "table borders line up: {widths:?}\n{plain}");
+    }
+
+    #[test]
+    fn test_code_line_numbers_and_label() {
+        let md = "```rust\nfn main() {}\nlet x = 1;\n```\n";
+        let options = RenderOptions::new(30).line_numbers(true).language_labels(true);
+        let text = render_document(md, &options).text;
+        let plain = text_to_plain(&text);
+        let lines: Vec<&str> = plain.lines().collect();
+
+        assert!(lines[0].starts_with("  ─ rust ─"), "{lines:?}");
+        assert_eq!(lines[0].chars().count(), 30);
+        assert_eq!(lines[1], "  1 │ fn main() {}");
+        assert_eq!(lines[2], "  2 │ let x = 1;");
+    }
+
+    #[test]
+    fn test_code_gutter_off_by_default() {
+        let text = render_markdown("```rust\nfn main() {}\n```\n", 80, &Theme::default());
+        assert_eq!(text_to_plain(&text).lines().next(), Some("  fn main() {}"));
+    }
+
     // --- Blockquotes ---
 
+    #[test]
+    fn test_alert_title_and_border() {
+        let md = "> [!WARNING]\n> Careful here.\n";
+        let text = render_markdown(md, 80, &Theme::default());
+        let plain = text_to_plain(&text);
+        let lines: Vec<&str> = plain.lines().collect();
+
+        assert_eq!(lines[0], "│ ⚠ Warning");
+        assert_eq!(lines[1], "│ Careful here.");
+        assert!(!plain.contains("[!WARNING]"));
+        let warning = Theme::default().alerts[3];
+        assert_eq!(text.lines[1].spans[0].style.fg, Some(warning));
+    }
+
+    #[test]
+    fn test_callout_fences_and_admonitions() {
+        let md = "```danger \"Do not\"\nRun **this**:\n\n9. a\n10. b\n```\n\n\
+                  !!! tip\n    Nested:\n\n    ```details\n    Inside.\n    ```\n\n```rust\nlet x;\n```\n";
+        let text = render_markdown(md, 80, &Theme::default());
+        let plain = text_to_plain(&text);
+        let lines: Vec<&str> = plain.lines().collect();
+        assert_eq!(lines[..5], ["│ ✖ Do not", "│ Run this:", "", "│  9. a", "│ 10. b"]);
+        assert_eq!(lines[5..8], ["", "│ ★ Tip", "│ Nested:"]);
+        assert_eq!(lines[9..12], ["│ │ ℹ Details", "│ │ Inside.", ""]);
+        assert!(plain.contains("  let x;"), "{plain}");
+        let caution = Theme::default().alerts[4];
+        assert_eq!(text.lines[0].spans[1].style.fg, Some(caution));
+        assert_eq!(text.lines[1].spans[0].style.fg, Some(caution));
+    }
+
+    #[test]
+    fn test_code_blocks_highlighted_in_parallel() {
+        let md = "```rust\n\tlet x;\n```\n\n```csv\na,b\n```\n\n> ```py\n> x = 1\n> ```\n\n    plain\n";
+        let events: Vec<_> = Parser::new(md).collect();
+        let options = RenderOptions::new(40).tab_size(2);
+        let blocks = code_blocks(&events, &options);
+        let expected = [(Some("rust"), "  let x;\n"), (Some("py"), "x = 1\n"), (None, "plain\n")];
+        assert_eq!(blocks.iter().map(|(l, c)| (l.as_deref(), c.as_str())).collect::<Vec<_>>(), expected);
+        // Highlighted ahead of the render or not, the result is the same.
+        let pool = rayon::ThreadPoolBuilder::new().num_threads(2).build().unwrap();
+        let parallel = pool.install(|| render_document(md, &options)).text;
+        crate::highlight::clear_cache();
+        assert_eq!(render_document(md, &options).text, parallel);
+    }
+
+    #[test]
+    fn test_alerts_disabled_render_as_blockquote() {
+        let options = RenderOptions::new(80).alerts(false);
+        let text = render_document("> [!NOTE]\n> Hi\n", &options).text;
+        assert!(text_to_plain(&text).contains("[!NOTE]"));
+    }
+
     #[test]
     fn test_blockquote_prefix() {
         let md = load_fixture("blockquotes.md");
-        let text = render_markdown(&md, 80);
+        let text = render_markdown(&md, 80, &Theme::default());
         let plain = text_to_plain(&text);
 
         assert!(
@@ -1072,21 +3883,21 @@ mod tests {
 
     #[test]
     fn test_empty_input() {
-        let text = render_markdown("", 80);
+        let text = render_markdown("", 80, &Theme::default());
         assert!(text.lines.is_empty() || text.lines.iter().all(|l| l.spans.is_empty()));
     }
 
     #[test]
     fn test_horizontal_rule() {
         let md = "---\n";
-        let text = render_markdown(md, 80);
+        let text = render_markdown(md, 80, &Theme::default());
         let plain = text_to_plain(&text);
         assert!(plain.contains('─'), "Horizontal rule should contain '─' characters");
     }
 
     #[test]
     fn test_heading_style() {
-        let text = render_markdown("# Title\n", 80);
+        let text = render_markdown("# Title\n", 80, &Theme::default());
         let heading_line = &text.lines[0];
         let title_span = heading_line
             .spans
@@ -1100,7 +3911,7 @@ mod tests {
     #[test]
     fn test_task_list_markers() {
         let md = "- [x] Done\n- [ ] Pending\n";
-        let text = render_markdown(md, 80);
+        let text = render_markdown(md, 80, &Theme::default());
         let plain = text_to_plain(&text);
 
         assert!(plain.contains("[✓]"), "Checked task should have ✓ marker");
@@ -1110,7 +3921,7 @@ mod tests {
     #[test]
     fn test_edge_cases_fixture() {
         let md = load_fixture("edge-cases.md");
-        let text = render_markdown(&md, 40);
+        let text = render_markdown(&md, 40, &Theme::default());
         let plain = text_to_plain(&text);
 
         assert!(plain.contains("Empty Section"));
@@ -1126,12 +3937,74 @@ mod tests {
         assert!(plain.contains("中文"), "CJK characters should pass through");
     }
 
+    // --- Raw HTML ---
+
+    #[test]
+    fn test_inline_html_styles() {
+        let md = "Press <kbd>q</kbd>, see x<sup>2</sup> and H<sub>2</sub>O, <b>bold <i>both</i></b> \
+                  <b>open\n\nnext\n";
+        let text = render_markdown(md, 80, &Theme::default());
+        assert_eq!(text.lines[0].to_string(), "Press q, see x² and H₂O, bold both open");
+        let md = "x<sup>2</sup>, H<sub>2</sub>O, e<sup>i pi</sup>\n";
+        let ascii = render_document(md, &RenderOptions::new(80).ascii(true));
+        assert_eq!(ascii.text.lines[0].to_string(), "x^2, H_2O, e^(i pi)");
+        let span = |content: &str| text.lines[0].spans.iter().find(|s| s.content == content).unwrap().style;
+        assert_eq!(span("q").bg, Some(Theme::default().code_bg));
+        assert!(span("both").add_modifier.contains(Modifier::BOLD | Modifier::ITALIC));
+        // An unclosed tag ends with its paragraph.
+        assert!(!text.lines[2].spans[0].style.add_modifier.contains(Modifier::BOLD));
+    }
+
+    #[test]
+    fn test_block_images() {
+        let dir = std::env::temp_dir().join(format!("mdview-blocks-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        image::RgbaImage::from_pixel(8, 8, image::Rgba([255, 0, 0, 255])).save(dir.join("red.png")).unwrap();
+        let md = "> ![red](red.png)\n\n![gone](missing.png)\n";
+        let options = RenderOptions::new(40).block_images(Some(2)).base_dir(Some(dir.clone()));
+        let text = render_document(md, &options).text;
+        std::fs::remove_dir_all(&dir).unwrap();
+        let plain = text_to_plain(&text);
+        let lines: Vec<&str> = plain.lines().filter(|l| !l.is_empty()).collect();
+        // Scaled down to two rows, aspect kept: four columns of two pixels.
+        assert_eq!(lines, ["│ ▀▀▀▀", "│ ▀▀▀▀", "🖼\u{fe0f} [gone] (missing.png)"]);
+        let cell = text.lines[0].spans.last().unwrap().style;
+        assert_eq!((cell.fg, cell.bg), (Some(Color::Rgb(255, 0, 0)), Some(Color::Rgb(255, 0, 0))));
+    }
+
+    #[test]
+    fn test_html_block_centered() {
+        let md = "<p align=\"center\">\n  <a href=\"https://ci\"><img src=\"ci.svg\" alt=\"CI\"></a>\n</p>\n\n\
+                  <h2 id=\"usage\">Usage <!-- note --></h2>\n";
+        let doc = render_document(md, &RenderOptions::new(40));
+        let line = doc.text.lines[0].to_string();
+        assert_eq!(line.trim(), "🖼\u{fe0f} [CI] (ci.svg) (https://ci)");
+        assert_eq!(line.len() - line.trim_start().len(), 5);
+        assert_eq!(&line[doc.links[0].start..doc.links[0].end], "🖼\u{fe0f} [CI] (ci.svg)");
+        assert_eq!(doc.headings[0].title, "Usage");
+        assert_eq!(doc.text.lines[doc.anchors["usage"]].to_string(), "## Usage");
+    }
+
+    #[test]
+    fn test_html_details_and_table() {
+        let md = "<details>\n<summary>More</summary>\n\nInside.\n\n</details>\n\n\
+                  <table>\n<tr><th>Key</th><th>Action</th></tr>\n\
+                  <tr><td>j</td><td>Down<br>a line</td></tr>\n</table>\n\
+                  <script>hidden()</script>\n";
+        let plain = text_to_plain(&render_markdown(md, 80, &Theme::default()));
+        let lines: Vec<&str> = plain.lines().collect();
+        assert_eq!(lines[..3], ["▾ More", "", "Inside."]);
+        assert!(lines.contains(&"│ Key │ Action │"));
+        assert!(lines.contains(&"│ j   │ Down   │"));
+        assert!(!plain.contains("hidden"));
+    }
+
     // --- budget_columns ---
 
     #[test]
     fn test_budget_natural_fits() {
         let natural = vec![10, 15, 8];
-        let result = budget_columns(&natural, 80);
+        let result = budget_columns(&natural, &[], &[], 80, 5);
         assert_eq!(result, natural);
     }
 
@@ -1139,7 +4012,7 @@ mod tests {
     fn test_budget_narrow_terminal() {
         let natural = vec![20, 30, 25];
         let width = 40;
-        let result = budget_columns(&natural, width);
+        let result = budget_columns(&natural, &[], &[], width, 5);
         let chrome = natural.len() * 3 + 1;
         let total: usize = result.iter().sum();
         assert!(
@@ -1153,7 +4026,7 @@ mod tests {
     fn test_budget_many_columns_tiny_terminal() {
         let natural = vec![10, 10, 10, 10, 10];
         let width = 30;
-        let result = budget_columns(&natural, width);
+        let result = budget_columns(&natural, &[], &[], width, 5);
         let chrome = natural.len() * 3 + 1;
         let total: usize = result.iter().sum();
         assert!(
@@ -1167,8 +4040,8 @@ mod tests {
     fn test_budget_single_column() {
         let natural = vec![50];
         let width = 30;
-        let result = budget_columns(&natural, width);
-        let chrome = 1 * 3 + 1;
+        let result = budget_columns(&natural, &[], &[], width, 5);
+        let chrome = 3 + 1;
         assert_eq!(result[0], width - chrome);
     }
 
@@ -1176,7 +4049,7 @@ mod tests {
     fn test_budget_small_and_large_mix() {
         let natural = vec![3, 50, 4];
         let width = 40;
-        let result = budget_columns(&natural, width);
+        let result = budget_columns(&natural, &[], &[], width, 5);
         let chrome = natural.len() * 3 + 1;
         let total: usize = result.iter().sum();
         assert!(total <= width - chrome);
@@ -1184,12 +4057,83 @@ mod tests {
         assert_eq!(result[2], 5, "Small column locks at min_col");
     }
 
+    #[test]
+    fn test_budget_rigid_columns_keep_width() {
+        let natural = vec![30, 18];
+        let result = budget_columns(&natural, &[false, true], &[], 40, 5);
+        assert_eq!(result, [40 - 7 - 18, 18]);
+        // Not when it would leave the prose column under the minimum.
+        let result = budget_columns(&natural, &[false, true], &[], 25, 5);
+        assert_eq!(result.iter().sum::<usize>(), 25 - 7);
+        assert!(result[1] < 18);
+        // Nor when the prose column could no longer fit its widest word.
+        let result = budget_columns(&natural, &[false, true], &[16, 18], 40, 5);
+        assert_eq!(result, [17, 16]);
+        let result = budget_columns(&natural, &[false, true], &[15, 18], 40, 5);
+        assert_eq!(result, [15, 18]);
+    }
+
+    #[test]
+    fn test_table_code_column_wraps_before_prose_words_split() {
+        let md = "| Code | Description |\n|------|-------------|\n\
+                  | `std::collections::HashMap` | A hash map |\n| `Vec<String>` | A vector of strings |\n";
+        let doc = render_document(md, &RenderOptions::new(40));
+        let plain: Vec<String> = doc.text.lines.iter().map(|l| l.to_string()).collect();
+        assert!(plain.iter().any(|l| l.contains("Description")), "{plain:#?}");
+        assert!(plain.iter().any(|l| l.contains(" strings ")), "{plain:#?}");
+        assert!(plain.iter().any(|l| l.contains("`std::collections ")), "the code wraps at `::`: {plain:#?}");
+    }
+
+    #[test]
+    fn test_table_code_column_does_not_wrap() {
+        let md = "| Notes | Command |\n|---|---|\n\
+                  | runs the whole suite quietly and stops at the first failure | `cargo test -q` |\n";
+        let options = RenderOptions::new(50).table_max_cell_lines(2);
+        let doc = render_document(md, &options);
+        let plain: Vec<String> = doc.text.lines.iter().map(|l| l.to_string()).collect();
+        assert!(plain.iter().any(|l| l.contains("`cargo test -q`")), "{plain:#?}");
+        let body: Vec<&String> = plain.iter().filter(|l| l.contains("runs") || l.contains('…')).collect();
+        assert!(body.iter().any(|l| l.contains('…')), "prose is cut at two lines: {plain:#?}");
+    }
+
+    #[test]
+    fn test_table_overflow_scroll() {
+        let md = "Intro.\n\n| Name | Description |\n|---|---|\n| mdview | A terminal markdown viewer |\n\n\
+                  | A | B |\n|---|---|\n| 1 | 2 |\n";
+        let doc = render_document(md, &RenderOptions::new(20).table_overflow(TableOverflow::Scroll));
+        let plain: Vec<String> = doc.text.lines.iter().map(|l| l.to_string()).collect();
+        assert_eq!(plain[5], "│ mdview │ A terminal markdown viewer │");
+        // Only the table that does not fit is recorded.
+        assert_eq!(doc.tables, [WideTable { line: 2, end: 7, overflow: 39 - 20 }]);
+        assert!(render_document(md, &RenderOptions::new(20)).tables.is_empty());
+    }
+
+    #[test]
+    fn test_csv_fence_as_table() {
+        let md = "```csv\nitem,price\nTea,\"1,50\"\n\"Cake\nslice\",12\n```\n\n```tsv\nOslo\t709000\n```\n";
+        let doc = render_document(md, &RenderOptions::new(40));
+        let plain: Vec<String> = doc.text.lines.iter().map(|l| l.to_string()).collect();
+        assert_eq!(plain[..9], [
+            "┌───────┬───────┐",
+            "│ item  │ price │",
+            "├───────┼───────┤",
+            "│ Tea   │  1,50 │",
+            "│       │       │",
+            "│ Cake  │    12 │",
+            "│ slice │       │",
+            "│       │       │",
+            "└───────┴───────┘",
+        ]);
+        // No header row: the columns get spreadsheet names.
+        assert_eq!(plain[11], "│ A    │      B │");
+    }
+
     // --- wrap_cell_spans ---
 
     #[test]
     fn test_wrap_fits_one_line() {
         let spans = vec![Span::raw("hello")];
-        let result = wrap_cell_spans(&spans, 10, 5, Style::default());
+        let result = wrap_cell_spans(&spans, 10, 5, Style::default(), UNICODE.ellipsis);
         assert_eq!(result.len(), 1);
         assert_eq!(result[0].iter().map(|s| s.content.as_ref()).collect::<String>(), "hello");
     }
@@ -1197,23 +4141,48 @@ mod tests {
     #[test]
     fn test_wrap_at_word_boundary() {
         let spans = vec![Span::raw("hello world foo")];
-        let result = wrap_cell_spans(&spans, 10, 5, Style::default());
+        let result = wrap_cell_spans(&spans, 10, 5, Style::default(), UNICODE.ellipsis);
         assert!(result.len() >= 2, "Should wrap into multiple lines");
     }
 
     #[test]
     fn test_wrap_truncation_ellipsis() {
         let spans = vec![Span::raw("one two three four five six seven eight nine ten")];
-        let result = wrap_cell_spans(&spans, 8, 2, Style::default());
+        let result = wrap_cell_spans(&spans, 8, 2, Style::default(), UNICODE.ellipsis);
         assert_eq!(result.len(), 2);
         let last_line: String = result.last().unwrap().iter().map(|s| s.content.as_ref()).collect();
         assert!(last_line.contains('…'), "Truncated line should end with ellipsis");
+
+        let result = wrap_cell_spans(&spans, 8, 2, Style::default(), ASCII.ellipsis);
+        let last_line: String = result.last().unwrap().iter().map(|s| s.content.as_ref()).collect();
+        assert_eq!(last_line, "three...");
+        let result = wrap_cell(&[spans], 2, 1, Style::default(), ASCII.ellipsis);
+        assert_eq!(result[0].iter().map(|s| s.content.as_ref()).collect::<String>(), "..");
+    }
+
+    #[test]
+    fn test_ascii_glyphs() {
+        let md = "# T\n\n- a\n  - b\n- [x] c\n\n> [!NOTE]\n> q\n\n---\n\n| a | b |\n|---|---|\n\
+                  | x | one two three four five six seven eight nine ten eleven twelve |\n\n\
+                  <details open><summary>More</summary>\n\nHidden\n\n</details>\n";
+        let options = RenderOptions::new(24).ascii(true).table_max_cell_lines(2);
+        let plain = text_to_plain(&render_document(md, &options).text);
+        assert!(plain.is_ascii(), "{plain}");
+        assert!(plain.contains("...") && plain.contains("* [x] c"), "{plain}");
     }
 
     #[test]
     fn test_wrap_empty_input() {
         let spans: Vec<Span<'static>> = vec![];
-        let result = wrap_cell_spans(&spans, 10, 5, Style::default());
+        let result = wrap_cell_spans(&spans, 10, 5, Style::default(), UNICODE.ellipsis);
         assert_eq!(result.len(), 1, "Empty input should produce one empty line");
     }
+
+    #[test]
+    fn test_split_slides_only_at_top_level() {
+        let md = "Title\n---\n\n> # Quoted\n\n- a\n\n  ---\n\n### Three\n";
+        assert_eq!(split_slides(md), [md]);
+        assert_eq!(split_slides("---\n\n---\n"), ["---\n\n---\n"]);
+        assert_eq!(split_slides("# A\n# B\n"), ["# A\n", "# B\n"]);
+    }
 }
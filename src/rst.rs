@@ -0,0 +1,494 @@
+use crate::csv::fenced;
+use crate::input::markdown_table;
+use crate::render::expand_tabs;
+
+/// Turns a reStructuredText document into markdown. Covers section titles
+/// (levels in the order their adornment styles first appear), transitions,
+/// bullet and enumerated lists, field lists, `::` literal blocks, the
+/// `code-block`, `image` and admonition directives, link targets,
+/// footnotes, grid and simple tables, block quotes and inline markup.
+/// Comments and other directives are dropped.
+///
+/// ```
+/// let rst = "Title\n=====\n\nRead the ``README`` or `the docs <https://docs.rs>`_::\n\n    cargo doc\n";
+/// let markdown = mdview::rst::to_markdown(rst);
+/// assert_eq!(markdown, "# Title\n\nRead the `README` or [the docs](https://docs.rs):\n\n```\ncargo doc\n```\n");
+/// ```
+pub fn to_markdown(input: &str) -> String {
+    let text = expand_tabs(input, 8);
+    let lines: Vec<&str> = text.lines().map(str::trim_end).collect();
+    let mut out = Vec::new();
+    let mut styles: Vec<(char, bool)> = Vec::new();
+    let mut literal = false;
+    let mut in_list = false;
+    let mut i = 0;
+    while i < lines.len() {
+        let line = lines[i];
+        let indented = line.starts_with(' ');
+        let next = lines.get(i + 1).copied().unwrap_or("");
+
+        if line.is_empty() {
+            out.push(String::new());
+            i += 1;
+        } else if indented && (literal || !in_list) {
+            let end = indented_end(&lines, i);
+            let body = dedent(&lines[i..end]);
+            if literal {
+                out.push(block(fenced(&body, "")));
+            } else {
+                out.extend(quote(&to_markdown(&body)));
+            }
+            literal = false;
+            i = end;
+        } else if indented {
+            out.push(format!("{}{}", &line[..line.len() - line.trim_start().len()], inline(line.trim_start())));
+            literal = line.ends_with("::");
+            i += 1;
+        } else if line == ".." || line.starts_with(".. ") {
+            let end = indented_end(&lines, i + 1);
+            out.extend(explicit(line.trim_start_matches('.').trim_start(), &lines[i + 1..end]));
+            in_list = false;
+            i = end;
+        } else if let Some(c) = adornment(line)
+            && adornment(lines.get(i + 2).copied().unwrap_or("")) == Some(c)
+            && !next.is_empty()
+        {
+            out.push(heading(&mut styles, (c, true), next.trim()));
+            in_list = false;
+            i += 3;
+        } else if let Some(c) = adornment(next)
+            && next.len() >= line.chars().count().min(4)
+            && adornment(line).is_none()
+        {
+            out.push(heading(&mut styles, (c, false), line));
+            in_list = false;
+            i += 2;
+        } else if adornment(line).is_some() && line.len() >= 4 {
+            out.push("---".to_string());
+            i += 1;
+        } else if line.starts_with("+-") || line.starts_with("+=") {
+            let end = (i..lines.len()).find(|&j| !lines[j].starts_with(['+', '|'])).unwrap_or(lines.len());
+            out.push(block(grid_table(&lines[i..end])));
+            i = end;
+        } else if is_simple_border(line) {
+            let end = (i..lines.len()).find(|&j| lines[j].is_empty()).unwrap_or(lines.len());
+            out.push(block(simple_table(&lines[i..end])));
+            i = end;
+        } else {
+            let text = match literal_marker(line) {
+                Some(text) => {
+                    literal = true;
+                    text
+                }
+                None => line,
+            };
+            match list_item(text) {
+                Some(item) => {
+                    in_list = true;
+                    out.push(item);
+                }
+                None if !text.is_empty() => {
+                    in_list = false;
+                    out.push(inline(text));
+                }
+                None => {}
+            }
+            i += 1;
+        }
+    }
+    let mut markdown = out.join("\n");
+    while markdown.ends_with('\n') {
+        markdown.pop();
+    }
+    markdown.push('\n');
+    markdown
+}
+
+/// The end of the indented block from line `start`: the first line after it
+/// that is neither blank nor indented, with trailing blank lines left out.
+fn indented_end(lines: &[&str], start: usize) -> usize {
+    let mut end = start;
+    for (j, line) in lines.iter().enumerate().skip(start) {
+        if !line.is_empty() && !line.starts_with(' ') {
+            break;
+        }
+        if !line.is_empty() {
+            end = j + 1;
+        }
+    }
+    end
+}
+
+/// `lines` joined with the indentation they share removed.
+fn dedent(lines: &[&str]) -> String {
+    let indent = (lines.iter().filter(|l| !l.trim().is_empty()))
+        .map(|l| l.len() - l.trim_start().len())
+        .min()
+        .unwrap_or(0);
+    let lines: Vec<&str> = lines.iter().map(|l| l.get(indent..).unwrap_or("")).collect();
+    lines.join("\n")
+}
+
+/// `markdown` without its final newline, as one entry of the output lines.
+fn block(markdown: String) -> String {
+    markdown.trim_end_matches('\n').to_string()
+}
+
+fn quote(markdown: &str) -> Vec<String> {
+    markdown.lines().map(|line| format!("> {line}").trim_end().to_string()).collect()
+}
+
+/// The character of a section adornment or transition line: at least two
+/// of the same punctuation character.
+fn adornment(line: &str) -> Option<char> {
+    let c = line.chars().next()?;
+    let punctuation = "=-~^\"'`:.#*+_<>";
+    (line.len() >= 2 && punctuation.contains(c) && line.chars().all(|x| x == c)).then_some(c)
+}
+
+/// A markdown heading for `title`, its level being the position of its
+/// adornment `style` among those seen so far.
+fn heading(styles: &mut Vec<(char, bool)>, style: (char, bool), title: &str) -> String {
+    let level = match styles.iter().position(|&s| s == style) {
+        Some(level) => level + 1,
+        None => {
+            styles.push(style);
+            styles.len()
+        }
+    };
+    format!("{} {}", "#".repeat(level.min(6)), inline(title))
+}
+
+/// `line` without the `::` that announces a literal block, or `None` if it
+/// does not end in one. A lone `::` leaves nothing and `text::` leaves
+/// `text:`.
+fn literal_marker(line: &str) -> Option<&str> {
+    let text = line.strip_suffix("::")?;
+    Some(match text.strip_suffix(' ') {
+        Some(text) => text.trim_end(),
+        None if text.is_empty() => "",
+        None => &line[..line.len() - 1],
+    })
+}
+
+/// A list item as markdown: `*` and `+` bullets become `-`, `#.`, `1)` and
+/// `(1)` become `1.`, and `:field: value` a bulleted bold field name.
+fn list_item(line: &str) -> Option<String> {
+    if let Some(rest) = line.strip_prefix("- ").or(line.strip_prefix("* ")).or(line.strip_prefix("+ ")) {
+        return Some(format!("- {}", inline(rest)));
+    }
+    if let Some(rest) = line.strip_prefix("#. ") {
+        return Some(format!("1. {}", inline(rest)));
+    }
+    let unwrapped = line.strip_prefix('(').unwrap_or(line);
+    let digits = unwrapped.bytes().take_while(u8::is_ascii_digit).count();
+    let rest = unwrapped[digits..].strip_prefix(". ").or(unwrapped[digits..].strip_prefix(") "));
+    if digits > 0
+        && let Some(rest) = rest
+    {
+        return Some(format!("{}. {}", &unwrapped[..digits], inline(rest)));
+    }
+    let field = line.strip_prefix(':')?;
+    let (name, value) = field.split_once(": ").or(field.strip_suffix(':').map(|name| (name, "")))?;
+    (!name.is_empty() && !name.contains('`')).then(|| format!("- **{}**: {}", inline(name), inline(value)))
+}
+
+/// Markdown for the explicit markup block `.. text`, whose indented body is
+/// `body`: a link target, footnote, directive or comment.
+fn explicit(text: &str, body: &[&str]) -> Vec<String> {
+    let body = dedent(body);
+    if let Some(target) = text.strip_prefix('_') {
+        return match target.split_once(": ") {
+            Some((name, url)) if !name.is_empty() => {
+                let name = name.trim_matches('`');
+                vec![format!("[{name}]: {}", url.trim())]
+            }
+            _ => Vec::new(),
+        };
+    }
+    if let Some((label, note)) = text.strip_prefix('[').and_then(|t| t.split_once("] ")) {
+        let note = format!("{note} {}", body.split_whitespace().collect::<Vec<_>>().join(" "));
+        return vec![format!("[^{}]: {}", label.trim_start_matches('#'), inline(note.trim()))];
+    }
+    let Some((name, args)) = text.split_once("::") else {
+        return Vec::new();
+    };
+    let args = args.trim();
+    let (options, content) = split_options(&body);
+    match name.trim().to_ascii_lowercase().as_str() {
+        "code-block" | "code" | "sourcecode" => vec![block(fenced(&content, args))],
+        "image" | "figure" => {
+            let alt = options.iter().find_map(|(k, v)| (*k == "alt").then_some(*v)).unwrap_or("");
+            let mut out = vec![format!("![{alt}]({args})")];
+            if !content.is_empty() {
+                out.extend([String::new(), block(to_markdown(&content))]);
+            }
+            out
+        }
+        "admonition" => {
+            let mut out = vec![format!("> **{}**", inline(args)), ">".to_string()];
+            out.extend(quote(&to_markdown(&content)));
+            out
+        }
+        kind => {
+            let Some(alert) = alert(kind) else {
+                return Vec::new();
+            };
+            let mut out = vec![format!("> [!{alert}]")];
+            out.extend(quote(&to_markdown(&format!("{args}\n\n{content}"))));
+            out
+        }
+    }
+}
+
+/// The `:name: value` options at the start of a directive's body, and the
+/// rest of it.
+fn split_options(body: &str) -> (Vec<(&str, &str)>, String) {
+    let mut options = Vec::new();
+    let mut lines = body.lines().peekable();
+    while let Some(line) = lines.next_if(|l| l.starts_with(':')) {
+        if let Some((name, value)) = line[1..].split_once(':') {
+            options.push((name, value.trim()));
+        }
+    }
+    let rest: Vec<&str> = lines.skip_while(|l| l.trim().is_empty()).collect();
+    (options, rest.join("\n"))
+}
+
+/// The GitHub alert kind standing in for an admonition directive.
+fn alert(directive: &str) -> Option<&'static str> {
+    Some(match directive {
+        "note" | "hint" | "seealso" => "NOTE",
+        "tip" => "TIP",
+        "important" => "IMPORTANT",
+        "warning" | "attention" => "WARNING",
+        "caution" | "danger" | "error" => "CAUTION",
+        _ => return None,
+    })
+}
+
+/// A grid table (`+---+` borders, `|` between cells, `+===+` under the
+/// header) as a markdown table. Lines of a cell are joined with spaces;
+/// spanning cells are not supported.
+fn grid_table(lines: &[&str]) -> String {
+    let border: Vec<char> = lines[0].chars().collect();
+    let edges: Vec<usize> = (0..border.len()).filter(|&k| border[k] == '+').collect();
+    let mut rows: Vec<Vec<String>> = Vec::new();
+    let mut header = None;
+    let mut cells = vec![String::new(); edges.len().saturating_sub(1)];
+    for line in &lines[1..] {
+        if line.starts_with('+') {
+            if cells.iter().any(|c| !c.is_empty()) {
+                rows.push(cells.iter().map(|c| inline(c)).collect());
+            }
+            cells.iter_mut().for_each(String::clear);
+            if line.contains('=') && header.is_none() {
+                header = Some(rows.len());
+            }
+            continue;
+        }
+        let chars: Vec<char> = line.chars().collect();
+        for (k, cell) in cells.iter_mut().enumerate() {
+            let part: String = chars.get(edges[k] + 1..edges[k + 1].min(chars.len())).unwrap_or(&[]).iter().collect();
+            if !part.trim().is_empty() {
+                if !cell.is_empty() {
+                    cell.push(' ');
+                }
+                cell.push_str(part.trim());
+            }
+        }
+    }
+    table_with_header(rows, header)
+}
+
+/// Whether `line` is a simple table border: runs of `=` separated by
+/// spaces, at least two of them.
+fn is_simple_border(line: &str) -> bool {
+    line.starts_with('=') && line.contains(' ') && line.chars().all(|c| c == '=' || c == ' ')
+}
+
+/// A simple table (columns marked out by `=====  =====` borders, the
+/// header between the first two) as a markdown table. A row whose first
+/// column is blank continues the row above.
+fn simple_table(lines: &[&str]) -> String {
+    let border: Vec<char> = lines[0].chars().collect();
+    let starts: Vec<usize> =
+        (0..border.len()).filter(|&k| border[k] == '=' && (k == 0 || border[k - 1] == ' ')).collect();
+    let mut rows: Vec<Vec<String>> = Vec::new();
+    let mut header = None;
+    for line in &lines[1..] {
+        if is_simple_border(line) || adornment(line) == Some('=') {
+            header = header.or(Some(rows.len()));
+            continue;
+        }
+        let chars: Vec<char> = line.chars().collect();
+        let cells: Vec<String> = (0..starts.len())
+            .map(|k| {
+                let end = starts.get(k + 1).copied().unwrap_or(chars.len()).min(chars.len());
+                chars.get(starts[k].min(end)..end).unwrap_or(&[]).iter().collect::<String>().trim().to_string()
+            })
+            .collect();
+        match rows.last_mut() {
+            Some(last) if cells[0].is_empty() => {
+                for (cell, more) in last.iter_mut().zip(cells).filter(|(_, more)| !more.is_empty()) {
+                    cell.push(' ');
+                    cell.push_str(&more);
+                }
+            }
+            _ => rows.push(cells),
+        }
+    }
+    let header = header.filter(|&h| h < rows.len());
+    let rows = rows.iter().map(|row| row.iter().map(|c| inline(c)).collect()).collect();
+    table_with_header(rows, header)
+}
+
+/// A markdown table of `rows` whose first `header` rows, if given, form the
+/// header; without one the first row does.
+fn table_with_header(mut rows: Vec<Vec<String>>, header: Option<usize>) -> String {
+    if let Some(header) = header.filter(|&h| h > 1) {
+        let merged = (0..rows[0].len())
+            .map(|k| rows[..header].iter().map(|row| row[k].as_str()).filter(|c| !c.is_empty()).collect::<Vec<_>>())
+            .map(|parts| parts.join(" "))
+            .collect();
+        rows.splice(..header, [merged]);
+    }
+    markdown_table(&rows)
+}
+
+/// Markdown for the reStructuredText inline markup in `text`.
+fn inline(text: &str) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let boundary = |k: usize| k >= chars.len() || chars[k].is_whitespace() || "-.,:;!?'\")]}/".contains(chars[k]);
+    let find = |from: usize, pattern: &str| {
+        let rest: String = chars[from..].iter().collect();
+        rest.find(pattern).map(|b| from + rest[..b].chars().count())
+    };
+    let mut out = String::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let starts_word = i == 0 || !chars[i - 1].is_alphanumeric();
+        if c == '`' && chars.get(i + 1) == Some(&'`') {
+            if let Some(end) = find(i + 2, "``") {
+                let code: String = chars[i + 2..end].iter().collect();
+                out.push_str(&code_span(&code));
+                i = end + 2;
+                continue;
+            }
+        } else if c == ':' && starts_word {
+            let name_end = (i + 1..chars.len()).find(|&k| !(chars[k].is_alphanumeric() || "-_.+".contains(chars[k])));
+            if let Some(name_end) = name_end
+                && name_end > i + 1
+                && chars[name_end] == ':'
+                && chars.get(name_end + 1) == Some(&'`')
+                && let Some(end) = find(name_end + 2, "`")
+            {
+                let role: String = chars[i + 1..name_end].iter().collect();
+                let content: String = chars[name_end + 2..end].iter().collect();
+                out.push_str(&interpreted(&role, &content));
+                i = end + 1;
+                continue;
+            }
+        } else if c == '`'
+            && let Some(end) = find(i + 1, "`")
+        {
+            let content: String = chars[i + 1..end].iter().collect();
+            let underscores = chars[end + 1..].iter().take_while(|&&u| u == '_').count().min(2);
+            out.push_str(&match underscores {
+                0 => format!("*{content}*"),
+                _ => reference(&content),
+            });
+            i = end + 1 + underscores;
+            continue;
+        } else if c == '['
+            && let Some(end) = find(i + 1, "]_")
+            && boundary(end + 2)
+        {
+            let label: String = chars[i + 1..end].iter().collect();
+            if !label.is_empty() && label.chars().all(|l| l.is_alphanumeric() || "#-_".contains(l)) {
+                out.push_str(&format!("[^{}]", label.trim_start_matches('#')));
+                i = end + 2;
+                continue;
+            }
+        } else if c.is_alphanumeric() && starts_word {
+            let end = (i..chars.len()).find(|&k| !chars[k].is_alphanumeric()).unwrap_or(chars.len());
+            let word: String = chars[i..end].iter().collect();
+            if chars.get(end) == Some(&'_') && chars.get(end + 1) != Some(&'_') && boundary(end + 1) {
+                out.push_str(&format!("[{word}]"));
+                i = end + 1;
+            } else {
+                out.push_str(&word);
+                i = end;
+            }
+            continue;
+        }
+        out.push(c);
+        i += 1;
+    }
+    out
+}
+
+/// `code` as a markdown code span.
+fn code_span(code: &str) -> String {
+    match code.contains('`') {
+        true => format!("`` {code} ``"),
+        false => format!("`{code}`"),
+    }
+}
+
+/// Markdown for `` `text <url>`_ ``, `` `<url>`_ `` or `` `name`_ ``: an
+/// inline link, or a reference link to a `.. _name: url` target.
+fn reference(content: &str) -> String {
+    match content.strip_suffix('>').and_then(|c| c.rsplit_once('<')) {
+        Some((text, url)) if text.trim().is_empty() => format!("[{url}]({url})"),
+        Some((text, url)) => format!("[{}]({url})", text.trim()),
+        None => format!("[{content}]"),
+    }
+}
+
+/// Markdown for `` :role:`content` ``. Cross references show their title
+/// (`` :ref:`Title <label>` ``); roles for code, and the unknown, show as
+/// code.
+fn interpreted(role: &str, content: &str) -> String {
+    let title = match content.strip_suffix('>').and_then(|c| c.rsplit_once('<')) {
+        Some((title, _)) if !title.trim().is_empty() => title.trim(),
+        _ => content,
+    };
+    match role {
+        "ref" | "doc" | "term" | "abbr" | "sub" | "sup" | "subscript" | "superscript" => title.to_string(),
+        "emphasis" => format!("*{content}*"),
+        "strong" => format!("**{content}**"),
+        _ => code_span(title),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sections_lists_and_directives() {
+        let rst = "=====\nGuide\n=====\n\nIntro\n-----\n\n\
+                   * one with ``code``\n  continued\n#. two\n:Author: Me\n\n\
+                   .. note:: Mind the *gap*.\n\n.. code-block:: rust\n   :linenos:\n\n   fn main() {}\n\n\
+                   .. This is a comment\n   spanning lines.\n\n\
+                   See Sphinx_ and :func:`os.path.join` [1]_.\n\n    A quote.\n\n\
+                   .. _Sphinx: https://www.sphinx-doc.org\n.. [1] A note.\n\n----\n\nUsage\n-----\n";
+        assert_eq!(
+            to_markdown(rst),
+            "# Guide\n\n## Intro\n\n- one with `code`\n  continued\n1. two\n- **Author**: Me\n\n\
+             > [!NOTE]\n> Mind the *gap*.\n\n```rust\nfn main() {}\n```\n\n\n\
+             See [Sphinx] and `os.path.join` [^1].\n\n> A quote.\n\n\
+             [Sphinx]: https://www.sphinx-doc.org\n[^1]: A note.\n\n---\n\n## Usage\n"
+        );
+    }
+
+    #[test]
+    fn test_tables() {
+        let grid = "+------+-----+\n| Name | Qty |\n+======+=====+\n| tea  | 2   |\n| (hot)|     |\n+------+-----+\n";
+        assert_eq!(to_markdown(grid), "| Name | Qty |\n|---|---|\n| tea (hot) | 2 |\n");
+        let simple = "=====  =====\nName   Qty\n=====  =====\ntea    2\n       more\n=====  =====\n";
+        assert_eq!(to_markdown(simple), "| Name | Qty |\n|---|---|\n| tea | 2 more |\n");
+    }
+}
@@ -0,0 +1,490 @@
+use anyhow::{Result, anyhow};
+use regex::{Regex, RegexBuilder};
+use mdview::Heading;
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span, Text};
+use unicode_segmentation::UnicodeSegmentation as _;
+use unicode_width::UnicodeWidthStr as _;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Match {
+    pub line: usize,
+    pub start: usize,
+    pub end: usize,
+}
+
+#[derive(Default)]
+pub struct Search {
+    pub input: String,
+    pub query: String,
+    /// Take every query as a regex, not just those written `/pattern/`.
+    pub regex: bool,
+    pub matches: Vec<Match>,
+    /// The capture groups of a regex inside the matches.
+    pub captures: Vec<Match>,
+    /// Why the query matches nothing when it is not a valid regex.
+    pub error: Option<String>,
+    pub current: usize,
+}
+
+impl Search {
+    pub fn is_active(&self) -> bool {
+        !self.query.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.query.clear();
+        self.matches.clear();
+        self.captures.clear();
+        self.error = None;
+        self.current = 0;
+    }
+
+    pub fn update(&mut self, text: &Text) {
+        self.error = None;
+        (self.matches, self.captures) = match Matcher::new(&self.query, self.regex) {
+            Ok(matcher) => matcher.find(text),
+            Err(e) => {
+                self.error = Some(e.to_string());
+                Default::default()
+            }
+        };
+        if self.current >= self.matches.len() {
+            self.current = 0;
+        }
+    }
+
+    /// Selects the first match at or below `line`, wrapping to the top.
+    pub fn select_from(&mut self, line: usize) -> Option<Match> {
+        self.current = self
+            .matches
+            .iter()
+            .position(|m| m.line >= line)
+            .unwrap_or(0);
+        self.matches.get(self.current).copied()
+    }
+
+    pub fn next(&mut self) -> Option<Match> {
+        if self.matches.is_empty() {
+            return None;
+        }
+        self.current = (self.current + 1) % self.matches.len();
+        self.matches.get(self.current).copied()
+    }
+
+    pub fn prev(&mut self) -> Option<Match> {
+        if self.matches.is_empty() {
+            return None;
+        }
+        self.current = self
+            .current
+            .checked_sub(1)
+            .unwrap_or(self.matches.len() - 1);
+        self.matches.get(self.current).copied()
+    }
+}
+
+/// The results panel: a query, every match of it in the whole document,
+/// and the highlighted one.
+#[derive(Default)]
+pub struct Results {
+    pub input: String,
+    /// Take the query as a regex, as `Search::regex`.
+    pub regex: bool,
+    pub matches: Vec<Match>,
+    /// Why the query matches nothing when it is not a valid regex.
+    pub error: Option<String>,
+    /// Index of the highlighted entry among the matches.
+    pub selected: usize,
+}
+
+impl Results {
+    /// Finds the matches of the query in `text` again, after either changed.
+    pub fn update(&mut self, text: &Text) {
+        self.error = None;
+        self.matches = match Matcher::new(&self.input, self.regex) {
+            Ok(matcher) => matcher.find(text).0,
+            Err(e) => {
+                self.error = Some(e.to_string());
+                Vec::new()
+            }
+        };
+        self.selected = self.selected.min(self.matches.len().saturating_sub(1));
+    }
+
+    /// Moves the highlight by `delta`, wrapping around.
+    pub fn select(&mut self, delta: isize) {
+        let count = self.matches.len();
+        if count > 0 {
+            self.selected = (self.selected.min(count - 1) as isize + delta).rem_euclid(count as isize) as usize;
+        }
+    }
+
+    /// The panel's list, `width` columns wide: one line per match, the title
+    /// of the section it is in, then its line cut down around it with the
+    /// match picked out, and the highlighted one reversed.
+    pub fn lines(&self, text: &Text, headings: &[Heading], width: usize) -> Vec<Line<'static>> {
+        let section_width = (width / 3).min(24);
+        let dim = Style::default().fg(Color::DarkGray);
+        let matched = Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD);
+        self.matches
+            .iter()
+            .enumerate()
+            .map(|(i, m)| {
+                let section = headings[..headings.partition_point(|h| h.line <= m.line)]
+                    .last()
+                    .map_or("", |h| h.title.as_str());
+                let content = text.lines.get(m.line).map(line_content).unwrap_or_default();
+                let [before, hit, after] = snippet(&content, m.start, m.end, width.saturating_sub(section_width + 2));
+                let section = truncate(section, section_width);
+                let pad = " ".repeat(section_width - section.width());
+                let line = Line::from(vec![
+                    Span::styled(format!("{section}{pad}"), dim),
+                    Span::raw("  "),
+                    Span::raw(before),
+                    Span::styled(hit, matched),
+                    Span::raw(after),
+                ]);
+                match i == self.selected {
+                    true => line.style(Style::default().add_modifier(Modifier::REVERSED)),
+                    false => line,
+                }
+            })
+            .collect()
+    }
+}
+
+/// `content` cut down to about `room` columns around the match at bytes
+/// `start..end`: the text before it, the match and the text after it, with
+/// `…` where something was cut off.
+fn snippet(content: &str, start: usize, end: usize, room: usize) -> [String; 3] {
+    let hit = &content[start..end];
+    let rest = content[end..].trim_end();
+    // Whatever the rest of the line leaves, but at least a third of what
+    // the match leaves, so most of the room shows what follows it.
+    let lead = room.saturating_sub(hit.width() + rest.width()).max(room.saturating_sub(hit.width()) / 3);
+    let mut before = content[..start].trim_start().to_string();
+    if before.width() > lead {
+        let mut kept = Vec::new();
+        let mut used = 1;
+        for cluster in before.graphemes(true).rev() {
+            used += cluster.width();
+            if used > lead {
+                break;
+            }
+            kept.push(cluster);
+        }
+        before = std::iter::once("…").chain(kept.into_iter().rev()).collect();
+    }
+    let after = truncate(rest, room.saturating_sub(before.width() + hit.width()));
+    [before, hit.to_string(), after]
+}
+
+/// `text` cut to `width` columns with a trailing `…`.
+pub fn truncate(text: &str, width: usize) -> String {
+    if text.width() <= width {
+        return text.to_string();
+    }
+    let mut out = String::new();
+    let mut used = 1;
+    for cluster in text.graphemes(true) {
+        used += cluster.width();
+        if used > width {
+            break;
+        }
+        out.push_str(cluster);
+    }
+    out.push('…');
+    out
+}
+
+/// What a query matches: its text, or a regex. Regexes run in time linear
+/// in the text whatever the pattern, so any query is safe to try.
+#[derive(Debug)]
+pub enum Matcher {
+    Literal(String),
+    Regex(Regex),
+}
+
+impl Matcher {
+    /// The matcher for `query`. A query written `/pattern/flags` is a regex,
+    /// and with `regex` set any query is. The flags are `i` to ignore case
+    /// and `w` to match whole words only; without `i` a regex is smart-case
+    /// like text.
+    pub fn new(query: &str, regex: bool) -> Result<Self> {
+        let (pattern, flags) = match split_regex(query) {
+            Some(parts) => parts,
+            None if regex => (query, ""),
+            None => return Ok(Matcher::Literal(query.to_string())),
+        };
+        let invalid = |e| anyhow!("Invalid regex: {e}");
+        let words = flags.contains('w');
+        // Compiled alone first so errors point into the pattern as typed.
+        if words {
+            Regex::new(pattern).map_err(invalid)?;
+        }
+        let full = match words {
+            true => format!(r"\b(?:{pattern})\b"),
+            false => pattern.to_string(),
+        };
+        RegexBuilder::new(&full)
+            .case_insensitive(flags.contains('i') || !has_uppercase(pattern))
+            .build()
+            .map(Matcher::Regex)
+            .map_err(invalid)
+    }
+
+    /// Every match in the rendered text, and the capture groups inside them.
+    /// Empty matches are skipped, as there is nothing to show.
+    pub fn find(&self, text: &Text) -> (Vec<Match>, Vec<Match>) {
+        let regex = match self {
+            Matcher::Literal(query) => return (find_matches(text, query), Vec::new()),
+            Matcher::Regex(regex) => regex,
+        };
+        let (mut matches, mut captures) = (Vec::new(), Vec::new());
+        for (line, content) in text.lines.iter().map(line_content).enumerate() {
+            for found in regex.captures_iter(&content) {
+                let groups = found.iter().flatten().filter(|m| !m.as_str().is_empty());
+                for (i, m) in groups.enumerate() {
+                    let found = Match { line, start: m.start(), end: m.end() };
+                    match i {
+                        0 => matches.push(found),
+                        _ => captures.push(found),
+                    }
+                }
+            }
+        }
+        (matches, captures)
+    }
+}
+
+/// The pattern and flags of a query written `/pattern/flags`.
+fn split_regex(query: &str) -> Option<(&str, &str)> {
+    let (pattern, flags) = query.strip_prefix('/')?.rsplit_once('/')?;
+    (!pattern.is_empty() && flags.chars().all(|c| matches!(c, 'i' | 'w'))).then_some((pattern, flags))
+}
+
+/// Whether `pattern` has an uppercase letter that is not part of an
+/// escape like `\S`.
+fn has_uppercase(pattern: &str) -> bool {
+    let mut escaped = false;
+    pattern.chars().any(|c| {
+        let upper = c.is_uppercase() && !escaped;
+        escaped = c == '\\' && !escaped;
+        upper
+    })
+}
+
+fn line_content(line: &Line) -> String {
+    line.spans.iter().map(|s| s.content.as_ref()).collect()
+}
+
+/// Finds all matches of `query` in the rendered text. Matching is smart-case:
+/// case-insensitive unless the query contains an uppercase character.
+pub fn find_matches(text: &Text, query: &str) -> Vec<Match> {
+    if query.is_empty() {
+        return Vec::new();
+    }
+    let case_sensitive = query.chars().any(char::is_uppercase);
+    let fold = |c: char| if case_sensitive { c } else { c.to_lowercase().next().unwrap_or(c) };
+    let needle: Vec<char> = query.chars().map(fold).collect();
+
+    let mut matches = Vec::new();
+    for (line_idx, line) in text.lines.iter().enumerate() {
+        let content = line_content(line);
+        let chars: Vec<(usize, char)> = content.char_indices().collect();
+        let mut i = 0;
+        while i + needle.len() <= chars.len() {
+            let hit = chars[i..i + needle.len()]
+                .iter()
+                .zip(&needle)
+                .all(|(&(_, c), &n)| fold(c) == n);
+            if hit {
+                let start = chars[i].0;
+                let end = chars
+                    .get(i + needle.len())
+                    .map_or(content.len(), |&(b, _)| b);
+                matches.push(Match { line: line_idx, start, end });
+                i += needle.len();
+            } else {
+                i += 1;
+            }
+        }
+    }
+    matches
+}
+
+/// Returns a copy of `text` with every match highlighted and the current
+/// match drawn in a distinct color.
+pub fn highlight(text: &Text<'static>, matches: &[Match], current: Option<usize>) -> Text<'static> {
+    let match_style = Style::default().fg(Color::Black).bg(Color::Yellow);
+    let current_style = Style::default().fg(Color::Black).bg(Color::LightRed);
+
+    let mut out = text.clone();
+    let mut idx = 0;
+    while idx < matches.len() {
+        let line_idx = matches[idx].line;
+        let mut ranges = Vec::new();
+        while idx < matches.len() && matches[idx].line == line_idx {
+            let style = if Some(idx) == current { current_style } else { match_style };
+            ranges.push((matches[idx].start, matches[idx].end, style));
+            idx += 1;
+        }
+        if let Some(line) = out.lines.get_mut(line_idx) {
+            line.spans = split_spans(&line.spans, &ranges);
+        }
+    }
+    out
+}
+
+/// Draws the capture groups of a regex search in `text` in a color of
+/// their own, over the match highlighting.
+pub fn highlight_captures(text: &mut Text<'static>, captures: &[Match]) {
+    let style = Style::default().fg(Color::Black).bg(Color::LightCyan);
+    for capture in captures {
+        highlight_range(text, capture.line, capture.start, capture.end, style);
+    }
+}
+
+/// Patches `style` onto the byte range `start..end` of `line` in place.
+pub fn highlight_range(text: &mut Text<'static>, line: usize, start: usize, end: usize, style: Style) {
+    if let Some(line) = text.lines.get_mut(line) {
+        line.spans = split_spans(&line.spans, &[(start, end, style)]);
+    }
+}
+
+fn split_spans(spans: &[Span<'static>], ranges: &[(usize, usize, Style)]) -> Vec<Span<'static>> {
+    let mut out = Vec::new();
+    let mut offset = 0;
+
+    for span in spans {
+        let content = span.content.as_ref();
+        let span_end = offset + content.len();
+        let mut pos = offset;
+
+        for &(start, end, style) in ranges {
+            if end <= pos || start >= span_end {
+                continue;
+            }
+            let hl_start = start.max(pos);
+            let hl_end = end.min(span_end);
+            if hl_start > pos {
+                out.push(Span::styled(content[pos - offset..hl_start - offset].to_string(), span.style));
+            }
+            out.push(Span::styled(
+                content[hl_start - offset..hl_end - offset].to_string(),
+                span.style.patch(style),
+            ));
+            pos = hl_end;
+        }
+
+        if pos < span_end {
+            out.push(Span::styled(content[pos - offset..].to_string(), span.style));
+        }
+        offset = span_end;
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(lines: &[&str]) -> Text<'static> {
+        Text::from(
+            lines
+                .iter()
+                .map(|l| Line::from(l.to_string()))
+                .collect::<Vec<_>>(),
+        )
+    }
+
+    #[test]
+    fn test_find_matches_smart_case() {
+        let t = text(&["Foo foo", "bar FOO"]);
+        assert_eq!(find_matches(&t, "foo").len(), 3);
+        assert_eq!(
+            find_matches(&t, "Foo"),
+            vec![Match { line: 0, start: 0, end: 3 }]
+        );
+    }
+
+    #[test]
+    fn test_find_matches_unicode_offsets() {
+        let t = text(&["• café au lait"]);
+        let m = find_matches(&t, "café");
+        assert_eq!(m.len(), 1);
+        assert_eq!(&"• café au lait"[m[0].start..m[0].end], "café");
+    }
+
+    #[test]
+    fn test_regex_queries() {
+        let t = text(&["fn main() -> Result", "let x = main_loop(1);"]);
+        let found = |query: &str, regex: bool| {
+            let (matches, captures) = Matcher::new(query, regex).unwrap().find(&t);
+            let slice = |m: &Match| t.lines[m.line].spans[0].content[m.start..m.end].to_string();
+            (matches.iter().map(slice).collect::<Vec<_>>(), captures.iter().map(slice).collect::<Vec<_>>())
+        };
+        assert_eq!(found("main.", false).0, Vec::<String>::new());
+        assert_eq!(found("main.", true).0, ["main(", "main_"]);
+        assert_eq!(found("/main/w", false).0, ["main"]);
+        assert_eq!(found("/RESULT/", false).0, Vec::<String>::new());
+        assert_eq!(found("/RESULT/i", false).0, ["Result"]);
+        assert_eq!(found(r"/\S+\((\d)\)/", false), (vec!["main_loop(1)".into()], vec!["1".into()]));
+        assert!(Matcher::new("/(/", false).unwrap_err().to_string().starts_with("Invalid regex"));
+        assert!(matches!(Matcher::new("/usr/bin", false).unwrap(), Matcher::Literal(_)));
+        // A pattern that backtracks exponentially still finds what is there.
+        let long = text(&[&format!("{}b", "a".repeat(5000)), "aaa"]);
+        assert_eq!(Matcher::new("/(a+)+$/", false).unwrap().find(&long).0, [Match { line: 1, start: 0, end: 3 }]);
+    }
+
+    #[test]
+    fn test_highlight_splits_across_spans() {
+        let t = Text::from(Line::from(vec![Span::raw("hel"), Span::raw("lo world")]));
+        let matches = find_matches(&t, "lo w");
+        let out = highlight(&t, &matches, Some(0));
+        let line = &out.lines[0];
+        let plain: String = line.spans.iter().map(|s| s.content.as_ref()).collect();
+        assert_eq!(plain, "hello world");
+        let highlighted: String = line
+            .spans
+            .iter()
+            .filter(|s| s.style.bg == Some(Color::LightRed))
+            .map(|s| s.content.as_ref())
+            .collect();
+        assert_eq!(highlighted, "lo w");
+    }
+
+    #[test]
+    fn test_results_list_sections_and_snippets() {
+        let t = text(&["Intro", "the quick brown fox", "Usage", "    jumps over the lazy dog and the fox"]);
+        let headings = [
+            Heading { line: 0, level: 1, title: "Intro".into(), source_title: "Intro".into(), id: None, number: None },
+            Heading { line: 2, level: 2, title: "Usage".into(), source_title: "Usage".into(), id: None, number: None },
+        ];
+        let mut results = Results { input: "fox".into(), ..Default::default() };
+        results.update(&t);
+        assert_eq!(results.matches.len(), 2);
+        let plain = |line: &Line| line.spans.iter().map(|s| s.content.as_ref()).collect::<String>();
+        let lines: Vec<String> = results.lines(&t, &headings, 40).iter().map(plain).collect();
+        assert_eq!(lines, ["Intro          the quick brown fox", "Usage          …the lazy dog and the fox"]);
+        assert_eq!(plain(&results.lines(&t, &headings, 30)[1]), "Usage       …y dog and the fox");
+        assert_eq!(snippet("a fox in a box and more", 2, 5, 10), ["a ", "fox", " in …"]);
+        results.select(-1);
+        assert_eq!(results.selected, 1);
+        results.input = "zebra".into();
+        results.update(&t);
+        assert_eq!(results.selected, 0);
+    }
+
+    #[test]
+    fn test_next_prev_wrap() {
+        let t = text(&["a", "a", "a"]);
+        let mut s = Search { query: "a".into(), ..Default::default() };
+        s.update(&t);
+        assert_eq!(s.select_from(1).map(|m| m.line), Some(1));
+        assert_eq!(s.next().map(|m| m.line), Some(2));
+        assert_eq!(s.next().map(|m| m.line), Some(0));
+        assert_eq!(s.prev().map(|m| m.line), Some(2));
+    }
+}
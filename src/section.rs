@@ -0,0 +1,174 @@
+//! The sections of a document, for `--section` and `--outline`.
+
+use std::collections::HashMap;
+use std::ops::Range;
+
+use pulldown_cmark::{Event, Parser, Tag, TagEnd};
+
+use crate::frontmatter;
+use crate::incremental::split_blocks;
+use crate::render::{RenderOptions, heading_text, slugify, unique_slug};
+
+/// A top-level heading of a document.
+struct Heading {
+    level: u8,
+    title: String,
+    /// The explicit id, when heading attributes are on, else the slug of
+    /// the title, numbered when an earlier heading has it.
+    id: String,
+    /// Byte offset of the heading in the document.
+    start: usize,
+}
+
+/// The byte range of `input` that the section headed by `query` spans: the
+/// heading through the end of its last subsection. The heading is the first
+/// whose anchor, as a link to it is written, is that of `query` (`Quick
+/// start` and `#quick-start` both match `## Quick Start`), else the first
+/// whose title contains `query`, else the first with its characters in
+/// order, ignoring case throughout.
+///
+/// ```
+/// let input = "# Guide\n\n## Install\n\nRun it.\n\n### From source\n\nBuild.\n\n## Usage\n";
+/// let range = mdview::section::find(input, "install").unwrap();
+/// assert_eq!(&input[range], "## Install\n\nRun it.\n\n### From source\n\nBuild.\n\n");
+/// ```
+pub fn find(input: &str, query: &str) -> Option<Range<usize>> {
+    let headings = headings(input);
+    let query = query.trim();
+    let slug = slugify(query.trim_start_matches('#'));
+    let lower = query.to_lowercase();
+    let in_order = |title: &str| {
+        let mut title = title.chars();
+        lower.chars().filter(|c| !c.is_whitespace()).all(|c| title.any(|t| t == c))
+    };
+    let i = headings
+        .iter()
+        .position(|h| slugify(&h.id) == slug)
+        .or_else(|| headings.iter().position(|h| h.title.to_lowercase().contains(&lower)))
+        .or_else(|| headings.iter().position(|h| in_order(&h.title.to_lowercase())))?;
+    Some(headings[i].start..end(&headings, i, input.len()))
+}
+
+/// A heading and the section under it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Section {
+    /// 1 for `#` through 6 for `######`.
+    pub level: u8,
+    pub title: String,
+    /// Source line of the heading, from 0.
+    pub line: usize,
+    /// Source lines the section spans with its subsections, from the heading
+    /// to the last line that is not blank.
+    pub lines: usize,
+}
+
+/// The top-level headings of `input` and their sections, in document order.
+/// Lines count from the top of `input`, frontmatter included.
+///
+/// ```
+/// let outline = mdview::section::outline("# Guide\n\nIntro.\n\n## Install\n\nRun it.\n");
+/// let titles: Vec<_> = outline.iter().map(|s| (s.level, s.title.as_str(), s.line, s.lines)).collect();
+/// assert_eq!(titles, [(1, "Guide", 0, 7), (2, "Install", 4, 3)]);
+/// ```
+pub fn outline(input: &str) -> Vec<Section> {
+    let headings = headings(input);
+    let mut line = 0;
+    let mut counted = 0;
+    (0..headings.len())
+        .map(|i| {
+            let heading = &headings[i];
+            line += input[counted..heading.start].matches('\n').count();
+            counted = heading.start;
+            let lines = input[heading.start..end(&headings, i, input.len())].trim_end().lines().count();
+            Section { level: heading.level, title: heading.title.clone(), line, lines }
+        })
+        .collect()
+}
+
+/// Where the section of heading `i` ends: at the next heading of the same
+/// or a higher level, else at `len`, the end of the document.
+fn end(headings: &[Heading], i: usize, len: usize) -> usize {
+    headings[i + 1..].iter().find(|h| h.level <= headings[i].level).map_or(len, |h| h.start)
+}
+
+/// The top-level headings of `input` after its frontmatter, titled and
+/// given ids as the renderer does: repeated slugs are numbered counting the
+/// headings in quotes and lists too, and those in footnotes not at all.
+fn headings(input: &str) -> Vec<Heading> {
+    let body = frontmatter::split(input).1;
+    let offset = input.len() - body.len();
+    let parser = Parser::new_ext(body, RenderOptions::new(0).extensions).into_offset_iter();
+    let mut seen = HashMap::new();
+    let mut headings = Vec::new();
+    for (range, events) in split_blocks(parser) {
+        if matches!(events.first(), Some(Event::Start(Tag::FootnoteDefinition(_)))) {
+            continue;
+        }
+        for (i, event) in events.iter().enumerate() {
+            let Event::Start(Tag::Heading { level, id, .. }) = event else {
+                continue;
+            };
+            let content = events[i + 1..].iter().take_while(|e| !matches!(e, Event::End(TagEnd::Heading(_))));
+            let title = heading_text(content);
+            let id = match id {
+                Some(id) => id.to_string(),
+                None => unique_slug(&mut seen, &title),
+            };
+            if i == 0 {
+                let (level, title) = (*level as u8, title.trim().to_string());
+                headings.push(Heading { level, title, id, start: offset + range.start });
+            }
+        }
+    }
+    headings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_matches_slugs_then_titles() {
+        let input = "# mdview\n\n## Quick Start\n\none\n\n## Install `cargo`\n\ntwo\n\n### Homebrew\n\n\
+                     three\n\n# Other\n\nfour\n";
+        let section = |query| find(input, query).map(|r| input[r].lines().next().unwrap_or_default().to_string());
+        assert_eq!(section("quick start").as_deref(), Some("## Quick Start"));
+        assert_eq!(section("#install-cargo").as_deref(), Some("## Install `cargo`"));
+        assert_eq!(section("brew").as_deref(), Some("### Homebrew"));
+        assert_eq!(section("qkst").as_deref(), Some("## Quick Start"));
+        assert_eq!(section("nowhere"), None);
+        let install = find(input, "Install").unwrap();
+        assert!(input[install].ends_with("three\n\n"));
+        let top = find(input, "mdview").unwrap();
+        assert_eq!(&input[top.end..], "# Other\n\nfour\n");
+        let outline = outline(input);
+        let lines: Vec<_> = outline.iter().map(|s| (s.level, s.line, s.lines)).collect();
+        assert_eq!(lines, [(1, 0, 13), (2, 2, 3), (2, 6, 7), (3, 10, 3), (1, 14, 3)]);
+        assert_eq!(outline[3].title, "Homebrew");
+    }
+
+    #[test]
+    fn test_titles_and_ids_are_the_renderer_s() {
+        let input = "# Intro\n\n> # Intro\n\nLine one\nline two\n===\n\n# Intro\n\n[^n]: # Intro\n\n# Intro\n";
+        let doc = crate::render_document(input, &RenderOptions::new(80));
+        let outline = outline(input);
+        assert_eq!(outline[1].title, "Line one line two");
+        assert_eq!(outline[1].title, doc.headings[2].title);
+        assert!(doc.anchors.contains_key("intro-3") && !doc.anchors.contains_key("intro-4"));
+        assert!(doc.anchors.contains_key("line-one-line-two"));
+        let first_line = |query| find(input, query).map(|r| input[..r.start].matches('\n').count());
+        assert_eq!(first_line("intro-2"), Some(outline[2].line));
+        assert_eq!(first_line("#intro-3"), Some(outline[3].line));
+        assert_eq!(first_line("line-one-line-two"), Some(4));
+    }
+
+    #[test]
+    fn test_frontmatter_is_not_a_section() {
+        let input = "---\ntitle: Hello\nauthor: Me\n---\n# Body\n\nText.\n\n## More\n";
+        let outline = outline(input);
+        let sections: Vec<_> = outline.iter().map(|s| (s.level, s.title.as_str(), s.line)).collect();
+        assert_eq!(sections, [(1, "Body", 4), (2, "More", 8)]);
+        assert_eq!(find(input, "title"), None);
+        assert_eq!(&input[find(input, "body").unwrap()], "# Body\n\nText.\n\n## More\n");
+    }
+}
@@ -0,0 +1,101 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+
+use crate::fetch::Source;
+
+/// The files open when the viewer last quit, reopened by `--last`. Their
+/// scroll positions and marks are in `State` like those of any file.
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct Session {
+    /// Canonical paths, or URLs, of the buffers in order.
+    pub files: Vec<Source>,
+    /// Index into `files` of the buffer that was shown.
+    pub current: usize,
+}
+
+impl Session {
+    /// Loads the last session, without the files that have gone since.
+    pub fn load() -> Result<Self> {
+        let path = default_path().context("Cannot locate the state directory (HOME is unset)")?;
+        let raw = match std::fs::read_to_string(&path) {
+            Ok(raw) => raw,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => bail!("No previous session to reopen"),
+            Err(e) => return Err(e).with_context(|| format!("Cannot read {}", path.display())),
+        };
+        let mut session = Self::parse(&raw).with_context(|| format!("Invalid session file {}", path.display()))?;
+        session.retain(|source| source.file().is_none_or(Path::exists));
+        if session.files.is_empty() {
+            bail!("None of the files of the last session exist anymore");
+        }
+        Ok(session)
+    }
+
+    pub fn parse(raw: &str) -> Result<Self> {
+        Ok(toml::from_str(raw)?)
+    }
+
+    /// Drops the files `keep` rejects, keeping `current` on the same file,
+    /// or the one after it when that file is dropped.
+    fn retain(&mut self, keep: impl Fn(&Source) -> bool) {
+        let before = self.files.iter().take(self.current).filter(|p| !keep(p)).count();
+        self.files.retain(|p| keep(p));
+        self.current = (self.current - before.min(self.current)).min(self.files.len().saturating_sub(1));
+    }
+
+    /// Writes the session file, replacing it atomically.
+    pub fn save(&self) -> Result<()> {
+        let path = default_path().context("Cannot locate the state directory (HOME is unset)")?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)
+                .with_context(|| format!("Cannot create {}", dir.display()))?;
+        }
+        let tmp = path.with_extension("toml.tmp");
+        std::fs::write(&tmp, toml::to_string(self)?)
+            .with_context(|| format!("Cannot write {}", tmp.display()))?;
+        std::fs::rename(&tmp, &path).with_context(|| format!("Cannot write {}", path.display()))
+    }
+}
+
+/// `$XDG_STATE_HOME/mdview/session.toml`, falling back to `~/.local/state`.
+pub fn default_path() -> Option<PathBuf> {
+    let base = std::env::var_os("XDG_STATE_HOME")
+        .filter(|v| !v.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|h| PathBuf::from(h).join(".local/state")))?;
+    Some(base.join("mdview").join("session.toml"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session(files: &[&str], current: usize) -> Session {
+        Session { files: files.iter().map(|f| Source::from(PathBuf::from(f))).collect(), current }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let saved = session(&["/docs/a.md", "https://host/b.md"], 1);
+        assert_eq!(Session::parse(&toml::to_string(&saved).unwrap()).unwrap(), saved);
+        assert_eq!(Session::parse("").unwrap(), Session::default());
+    }
+
+    #[test]
+    fn test_retain_keeps_the_current_file() {
+        let gone = |s: &Source| s.file() != Some(Path::new("gone"));
+        let cases = [
+            (session(&["gone", "a", "b"], 2), session(&["a", "b"], 1)),
+            (session(&["a", "gone", "b"], 1), session(&["a", "b"], 1)),
+            (session(&["a", "b", "gone"], 2), session(&["a", "b"], 1)),
+            (session(&["a", "b"], 0), session(&["a", "b"], 0)),
+            (session(&["gone"], 0), session(&[], 0)),
+        ];
+        for (mut before, after) in cases {
+            before.retain(gone);
+            assert_eq!(before, after);
+        }
+    }
+}
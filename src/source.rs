@@ -0,0 +1,44 @@
+use mdview::invisibles;
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span, Text};
+
+/// Raw markdown for the source pane of the split view: `height` lines of
+/// `content` from line `top` on, each after a line-number gutter. Long lines
+/// are left for the widget to clip. Control characters show as dim
+/// symbols, since this is the raw text.
+pub fn source_text(content: &str, top: usize, height: usize, tab_size: usize) -> Text<'static> {
+    let digits = content.lines().count().max(1).to_string().len();
+    let gutter = Style::default().fg(Color::DarkGray);
+    let tab = " ".repeat(tab_size);
+    content
+        .lines()
+        .enumerate()
+        .skip(top)
+        .take(height)
+        .map(|(i, line)| {
+            let line = line.replace('\t', &tab);
+            let mut spans = vec![Span::styled(format!("{:>digits$} ", i + 1), gutter)];
+            spans.extend(invisibles::pictures(&line, false).into_iter().map(|(run, symbol)| match symbol {
+                true => Span::styled(run.into_owned(), Style::default().add_modifier(Modifier::DIM)),
+                false => Span::raw(run.into_owned()),
+            }));
+            Line::from(spans)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_source_text_numbers_visible_lines() {
+        let content = (1..=12).map(|i| format!("line {i}\n")).collect::<String>();
+        let text = source_text(&content, 8, 3, 4);
+        let lines: Vec<String> = text.lines.iter().map(|l| l.to_string()).collect();
+        assert_eq!(lines, [" 9 line 9", "10 line 10", "11 line 11"]);
+
+        let text = source_text("a\x1b[2Jb\r\n", 0, 1, 4);
+        assert_eq!(text.lines[0].to_string(), "1 a␛[2Jb");
+    }
+}
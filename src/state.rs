@@ -0,0 +1,110 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::fetch::Source;
+
+/// Per-file view state remembered between sessions, keyed by a hash of the
+/// file's canonical path.
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct State {
+    files: BTreeMap<String, FileState>,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct FileState {
+    /// First rendered line in view.
+    pub scroll: usize,
+    /// Scroll positions of the `m{a-z}` marks, by letter.
+    pub marks: BTreeMap<String, usize>,
+}
+
+impl State {
+    /// Loads the state file. A missing or unreadable file gives an empty
+    /// state, since losing remembered positions is not worth failing over.
+    pub fn load() -> Self {
+        default_path()
+            .and_then(|path| std::fs::read_to_string(path).ok())
+            .and_then(|raw| Self::parse(&raw).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn parse(raw: &str) -> Result<Self> {
+        Ok(toml::from_str(raw)?)
+    }
+
+    pub fn get(&self, source: &Source) -> Option<&FileState> {
+        self.files.get(&key(source))
+    }
+
+    pub fn set(&mut self, source: &Source, file: FileState) {
+        self.files.insert(key(source), file);
+    }
+
+    /// Writes the state file, replacing it atomically.
+    pub fn save(&self) -> Result<()> {
+        let path = default_path().context("Cannot locate the data directory (HOME is unset)")?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)
+                .with_context(|| format!("Cannot create {}", dir.display()))?;
+        }
+        let tmp = path.with_extension("toml.tmp");
+        std::fs::write(&tmp, toml::to_string(self)?)
+            .with_context(|| format!("Cannot write {}", tmp.display()))?;
+        std::fs::rename(&tmp, &path).with_context(|| format!("Cannot write {}", path.display()))
+    }
+}
+
+/// `$XDG_DATA_HOME/mdview/state.toml`, falling back to `~/.local/share`.
+pub fn default_path() -> Option<PathBuf> {
+    let base = std::env::var_os("XDG_DATA_HOME")
+        .filter(|v| !v.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|h| PathBuf::from(h).join(".local/share")))?;
+    Some(base.join("mdview").join("state.toml"))
+}
+
+/// FNV-1a hash of the path or URL, hex-encoded. Stable across builds,
+/// unlike `DefaultHasher`, and keeps file names out of the state file.
+fn key(source: &Source) -> String {
+    let bytes = match source {
+        Source::File(path) => path.as_os_str().as_encoded_bytes(),
+        Source::Url(url) => url.as_bytes(),
+    };
+    let hash = bytes
+        .iter()
+        .fold(0xcbf29ce484222325u64, |h, &b| (h ^ b as u64).wrapping_mul(0x100000001b3));
+    format!("{hash:016x}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(path: &str) -> Source {
+        Source::File(PathBuf::from(path))
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut state = State::default();
+        let marks = BTreeMap::from([("a".to_string(), 3), ("z".to_string(), 100)]);
+        state.set(&file("/docs/a.md"), FileState { scroll: 42, marks });
+        let parsed = State::parse(&toml::to_string(&state).unwrap()).unwrap();
+        assert_eq!(parsed, state);
+        assert_eq!(parsed.get(&file("/docs/a.md")).unwrap().scroll, 42);
+        assert!(parsed.get(&file("/docs/b.md")).is_none());
+    }
+
+    #[test]
+    fn test_key_is_stable() {
+        assert_eq!(key(&file("")), "cbf29ce484222325");
+        assert_eq!(key(&file("a")), "af63dc4c8601ec8c");
+        // URLs keep the keys they had when they were stored as paths.
+        assert_eq!(key(&Source::Url("https://h/a.md".to_string())), key(&file("https://h/a.md")));
+    }
+}
@@ -0,0 +1,101 @@
+use pulldown_cmark::{Event, Parser, Tag, TagEnd};
+
+use crate::frontmatter::{self, Metadata};
+use crate::render::RenderOptions;
+
+/// Words read per minute for [`Stats::reading_minutes`].
+const WORDS_PER_MINUTE: usize = 200;
+
+/// Counts describing a markdown document.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Stats {
+    /// Words of prose, inline code included; code blocks are not counted.
+    pub words: usize,
+    pub headings: usize,
+    pub code_blocks: usize,
+    pub links: usize,
+    pub images: usize,
+    /// The title, author and date of the frontmatter, which is not counted.
+    pub metadata: Metadata,
+}
+
+impl Stats {
+    /// Estimated reading time in whole minutes, rounded up, at 200 words a
+    /// minute. Code blocks are not counted.
+    pub fn reading_minutes(&self) -> usize {
+        self.words.div_ceil(WORDS_PER_MINUTE)
+    }
+
+    /// Label and value pairs in display order.
+    pub fn rows(&self) -> Vec<(&'static str, String)> {
+        let mut rows = self.metadata.rows();
+        rows.extend([
+            ("Words", self.words.to_string()),
+            ("Headings", self.headings.to_string()),
+            ("Code blocks", self.code_blocks.to_string()),
+            ("Links", self.links.to_string()),
+            ("Images", self.images.to_string()),
+            ("Reading time", format!("{} min", self.reading_minutes())),
+        ]);
+        rows
+    }
+}
+
+/// Counts the words, headings, code blocks, links and images of `input`,
+/// and reads the metadata in its frontmatter.
+///
+/// ```
+/// let stats = mdview::stats::document_stats("# Title\n\nSee [the docs](docs.md).\n");
+/// assert_eq!((stats.words, stats.headings, stats.links), (4, 1, 1));
+/// ```
+pub fn document_stats(input: &str) -> Stats {
+    let mut stats = Stats { metadata: frontmatter::metadata(input), ..Stats::default() };
+    let mut in_code_block = false;
+    for event in Parser::new_ext(frontmatter::split(input).1, RenderOptions::new(0).extensions) {
+        match event {
+            Event::Start(Tag::Heading { .. }) => stats.headings += 1,
+            Event::Start(Tag::CodeBlock(_)) => {
+                stats.code_blocks += 1;
+                in_code_block = true;
+            }
+            Event::End(TagEnd::CodeBlock) => in_code_block = false,
+            Event::Start(Tag::Link { .. }) => stats.links += 1,
+            Event::Start(Tag::Image { .. }) => stats.images += 1,
+            Event::Text(text) if !in_code_block => stats.words += count_words(&text),
+            Event::Code(code) => stats.words += count_words(&code),
+            _ => {}
+        }
+    }
+    stats
+}
+
+/// Whitespace-separated tokens with a letter or digit in them, so stray
+/// punctuation is not a word.
+fn count_words(text: &str) -> usize {
+    text.split_whitespace().filter(|w| w.chars().any(char::is_alphanumeric)).count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_document_stats() {
+        let md = "# Intro\n\nTwo words and `inline code`.\n\n```rust\nfn not_counted() {}\n```\n\n\
+                  ## More\n\n![logo](logo.png) and <https://example.com>\n";
+        let stats = document_stats(md);
+        assert_eq!(stats, Stats { words: 10, headings: 2, code_blocks: 1, links: 1, images: 1, ..Stats::default() });
+
+        let stats = document_stats(&format!("---\ntitle: Intro\nauthor: Ada\n---\n{md}"));
+        assert_eq!((stats.words, stats.headings), (10, 2));
+        let labels: Vec<_> = stats.rows().into_iter().map(|(label, _)| label).take(3).collect();
+        assert_eq!(labels, ["Title", "Author", "Words"]);
+    }
+
+    #[test]
+    fn test_reading_time_rounds_up() {
+        assert_eq!(Stats::default().reading_minutes(), 0);
+        assert_eq!(Stats { words: 1, ..Stats::default() }.reading_minutes(), 1);
+        assert_eq!(Stats { words: 401, ..Stats::default() }.reading_minutes(), 3);
+    }
+}
@@ -0,0 +1,172 @@
+//! Tags of notes as Obsidian reads them: the frontmatter's `tags` and the
+//! `#tag` words of the text, which may nest (`#project/mdview`), and an
+//! index of them over a [vault](crate::vault) that rereads only the notes
+//! that changed.
+//!
+//! ```
+//! let input = "---\ntags: [rust]\n---\n# Notes\n\nWork on #project/mdview, not `#code`.\n";
+//! assert_eq!(mdview::tags::tags(input), ["rust", "project/mdview"]);
+//! assert!(mdview::tags::matches("project/mdview", "Project"));
+//! ```
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::encoding;
+use crate::frontmatter;
+use crate::include::fence_marker;
+use crate::vault::Vault;
+
+/// The tags of `input`: those of its frontmatter, then the `#tag` words of
+/// the text outside code, each once whatever its case.
+pub fn tags(input: &str) -> Vec<String> {
+    let mut tags = frontmatter::tags(input);
+    let mut fence: Option<(char, usize)> = None;
+    for line in frontmatter::split(input).1.lines() {
+        if let Some(marker) = fence_marker(line) {
+            match fence {
+                None => fence = Some(marker),
+                Some((c, len)) if marker.0 == c && marker.1 >= len => fence = None,
+                Some(_) => {}
+            }
+            continue;
+        }
+        if fence.is_none() && line.contains('#') {
+            tags.extend(inline_tags(line).map(str::to_string));
+        }
+    }
+    let mut seen = Vec::new();
+    tags.retain(|tag| {
+        let lower = tag.to_lowercase();
+        let new = !seen.contains(&lower);
+        seen.push(lower);
+        new
+    });
+    tags
+}
+
+/// Whether `tag` is `wanted` or nested under it, ignoring case.
+pub fn matches(tag: &str, wanted: &str) -> bool {
+    let (tag, wanted) = (tag.to_lowercase(), wanted.trim_start_matches('#').to_lowercase());
+    tag == wanted || tag.strip_prefix(&wanted).is_some_and(|rest| rest.starts_with('/'))
+}
+
+/// The `#tag` words of `line` outside code spans: a `#` at the start of a
+/// word, then letters, digits, `_`, `-` and `/`, not all digits so issue
+/// numbers (`#12`) are left out.
+fn inline_tags(line: &str) -> impl Iterator<Item = &str> {
+    let mut in_code = false;
+    let mut previous = ' ';
+    let mut found = Vec::new();
+    for (i, c) in line.char_indices() {
+        if c == '`' {
+            in_code = !in_code;
+        } else if c == '#' && !in_code && previous.is_whitespace() {
+            let rest = &line[i + 1..];
+            let end = rest.find(|c: char| !c.is_alphanumeric() && !matches!(c, '_' | '-' | '/'));
+            let end = end.unwrap_or(rest.len());
+            let tag = rest[..end].trim_end_matches('/');
+            if tag.chars().any(|c| !c.is_ascii_digit()) {
+                found.push(tag);
+            }
+        }
+        previous = c;
+    }
+    found.into_iter()
+}
+
+/// The tags of every note of a vault. [`update`](Self::update) rereads only
+/// the notes whose file changed since it last read them.
+#[derive(Debug, Default)]
+pub struct TagIndex {
+    notes: HashMap<PathBuf, Entry>,
+}
+
+#[derive(Debug)]
+struct Entry {
+    /// Modification time and size of the file when it was read.
+    stamp: Option<(SystemTime, u64)>,
+    tags: Vec<String>,
+}
+
+impl TagIndex {
+    /// Brings the index up to date with `vault`: notes that are new or
+    /// changed are read, and notes no longer there are dropped.
+    pub fn update(&mut self, vault: &Vault) {
+        let paths: HashSet<PathBuf> = vault.notes().iter().map(|note| vault.root().join(note)).collect();
+        self.notes.retain(|path, _| paths.contains(path));
+        for path in paths {
+            let stamp = stamp(&path);
+            if self.notes.get(&path).is_none_or(|entry| entry.stamp != stamp || stamp.is_none()) {
+                self.read(path, stamp);
+            }
+        }
+    }
+
+    /// Rereads the note at `path` after its file changed, if it is indexed.
+    pub fn refresh(&mut self, path: &Path) {
+        if self.notes.contains_key(path) {
+            self.read(path.to_path_buf(), stamp(path));
+        }
+    }
+
+    /// The tags of the note at `path`.
+    pub fn get(&self, path: &Path) -> &[String] {
+        self.notes.get(path).map_or(&[], |entry| &entry.tags)
+    }
+
+    fn read(&mut self, path: PathBuf, stamp: Option<(SystemTime, u64)>) {
+        let tags = encoding::read(&path).map(|decoded| tags(&decoded.text)).unwrap_or_default();
+        self.notes.insert(path, Entry { stamp, tags });
+    }
+}
+
+/// The modification time and size of the file at `path`.
+fn stamp(path: &Path) -> Option<(SystemTime, u64)> {
+    let metadata = std::fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tags() {
+        let input = "---\ntags:\n  - Rust\n  - \"#cli\"\n---\n# Heading #not-a-tag?\n\n\
+                     A #rust note on #project/mdview/, issue #12 and page#anchor.\n\n\
+                     ```\n#hidden\n```\n\nSee `#code` and #v2 #日本語.\n";
+        assert_eq!(tags(input), ["Rust", "cli", "not-a-tag", "project/mdview", "v2", "日本語"]);
+        assert!(tags("# Title\n\n## Section\n").is_empty());
+    }
+
+    #[test]
+    fn test_matches() {
+        assert!(matches("rust", "Rust"));
+        assert!(matches("project/mdview", "project"));
+        assert!(matches("project/mdview", "#project/mdview"));
+        assert!(!matches("projects", "project"));
+        assert!(!matches("project", "project/mdview"));
+    }
+
+    #[test]
+    fn test_index_rereads_changed_notes() {
+        let dir = std::env::temp_dir().join(format!("mdview-tags-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("a.md"), "#one\n").unwrap();
+        std::fs::write(dir.join("b.md"), "#two\n").unwrap();
+        let vault = Vault::open(&dir).unwrap();
+        let (a, b) = (vault.root().join("a.md"), vault.root().join("b.md"));
+        let mut index = TagIndex::default();
+        index.update(&vault);
+        assert_eq!((index.get(&a), index.get(&b)), (&["one".to_string()][..], &["two".to_string()][..]));
+        std::fs::write(&a, "#one #three\n").unwrap();
+        index.refresh(&a);
+        assert_eq!(index.get(&a), ["one", "three"]);
+        std::fs::remove_file(&b).unwrap();
+        index.update(&Vault::open(&dir).unwrap());
+        assert!(index.get(&b).is_empty() && !index.notes.contains_key(&b));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
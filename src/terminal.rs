@@ -0,0 +1,155 @@
+use std::time::Duration;
+
+/// How long to wait for the terminal to answer a query. Terminals that
+/// answer do so well within it; the rest answer the device attributes
+/// request sent after every query, so the wait only runs out on a terminal
+/// that ignores both.
+const QUERY_TIMEOUT: Duration = Duration::from_millis(200);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Background {
+    Light,
+    Dark,
+}
+
+/// Whether the terminal background is light or dark: asked of the terminal
+/// with OSC 11, else read from `COLORFGBG`, which rxvt and some other
+/// terminals set. `None` when neither tells.
+pub fn background() -> Option<Background> {
+    let reply = query("\x1b]11;?\x1b\\");
+    let detected = reply
+        .as_deref()
+        .and_then(parse_osc11)
+        .map(luminance_background)
+        .or_else(|| parse_colorfgbg(&std::env::var("COLORFGBG").ok()?));
+    tracing::debug!(?reply, ?detected, "terminal background");
+    detected
+}
+
+/// Writes `request` to the terminal followed by a primary device attributes
+/// request (`ESC [ c`) and returns what the terminal answered before the
+/// attributes. Every terminal answers that one, so one that does not know
+/// `request` is found out at once instead of after a timeout. `None` when
+/// stdout is not a terminal, the terminal cannot be opened or it does not
+/// answer within `QUERY_TIMEOUT`.
+#[cfg(unix)]
+fn query(request: &str) -> Option<String> {
+    use std::io::{IsTerminal as _, Read as _, Write as _};
+    use std::os::fd::AsRawFd as _;
+    use std::time::Instant;
+
+    if !std::io::stdout().is_terminal() {
+        return None;
+    }
+    let mut tty = std::fs::OpenOptions::new().read(true).write(true).open("/dev/tty").ok()?;
+    let raw = crossterm::terminal::is_raw_mode_enabled().unwrap_or(false);
+    if !raw {
+        crossterm::terminal::enable_raw_mode().ok()?;
+    }
+    let reply = (|| {
+        write!(tty, "{request}\x1b[c").ok()?;
+        tty.flush().ok()?;
+        // Each read waits in poll(2) for what is left of the timeout, so
+        // nothing is still reading the terminal once this returns.
+        let deadline = Instant::now() + QUERY_TIMEOUT;
+        let mut reply = Vec::new();
+        let mut buf = [0; 256];
+        loop {
+            let left = deadline.checked_duration_since(Instant::now())?;
+            let mut fd = libc::pollfd { fd: tty.as_raw_fd(), events: libc::POLLIN, revents: 0 };
+            // SAFETY: `fd` is one valid pollfd for the duration of the call.
+            match unsafe { libc::poll(&mut fd, 1, left.as_millis().max(1) as libc::c_int) } {
+                0 => return None,
+                n if n < 0 && std::io::Error::last_os_error().kind() == std::io::ErrorKind::Interrupted => continue,
+                n if n < 0 => return None,
+                _ => {}
+            }
+            let n = tty.read(&mut buf).ok().filter(|&n| n > 0)?;
+            reply.extend_from_slice(&buf[..n]);
+            if let Some(end) = attributes_start(&reply) {
+                reply.truncate(end);
+                return Some(reply);
+            }
+        }
+    })();
+    if !raw {
+        let _ = crossterm::terminal::disable_raw_mode();
+    }
+    Some(String::from_utf8_lossy(&reply?).into_owned())
+}
+
+#[cfg(not(unix))]
+fn query(_request: &str) -> Option<String> {
+    None
+}
+
+/// Where a complete device attributes reply (`ESC [ ? … c`) starts in
+/// `reply`, if it has arrived.
+fn attributes_start(reply: &[u8]) -> Option<usize> {
+    let start = reply.windows(3).position(|w| w == b"\x1b[?")?;
+    reply[start + 3..].contains(&b'c').then_some(start)
+}
+
+/// The color in an OSC 11 reply, `ESC ] 11 ; rgb:RRRR/GGGG/BBBB` ended by
+/// BEL or `ESC \`, as fractions. Components have one to four hex digits.
+fn parse_osc11(reply: &str) -> Option<(f32, f32, f32)> {
+    let (_, rest) = reply.split_once("\x1b]11;rgb:")?;
+    let end = rest.find(['\x07', '\x1b']).unwrap_or(rest.len());
+    let mut parts = rest[..end].split('/').map(|hex| {
+        let value = u32::from_str_radix(hex, 16).ok().filter(|_| (1..=4).contains(&hex.len()))?;
+        Some(value as f32 / ((1u32 << (4 * hex.len())) - 1) as f32)
+    });
+    let rgb = (parts.next()??, parts.next()??, parts.next()??);
+    parts.next().is_none().then_some(rgb)
+}
+
+/// Light when the relative luminance of the color is over one half.
+fn luminance_background((r, g, b): (f32, f32, f32)) -> Background {
+    if 0.2126 * r + 0.7152 * g + 0.0722 * b > 0.5 { Background::Light } else { Background::Dark }
+}
+
+/// The background in a `COLORFGBG` value, `fg;bg` or `fg;default;bg`: the
+/// last field is an ANSI color number, light for white (7) and the bright
+/// colors after bright black (9–15).
+fn parse_colorfgbg(value: &str) -> Option<Background> {
+    let bg: u8 = value.rsplit(';').next()?.parse().ok()?;
+    match bg {
+        7 | 9..=15 => Some(Background::Light),
+        0..=6 | 8 => Some(Background::Dark),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_osc11() {
+        assert_eq!(parse_osc11("\x1b]11;rgb:ffff/ffff/ffff\x1b\\"), Some((1.0, 1.0, 1.0)));
+        assert_eq!(parse_osc11("\x1b]11;rgb:00/80/ff\x07"), Some((0.0, 128.0 / 255.0, 1.0)));
+        assert_eq!(parse_osc11("\x1b]11;rgb:0/0\x07"), None);
+        assert_eq!(parse_osc11("\x1b]11;rgb:zz/00/00\x07"), None);
+        assert_eq!(parse_osc11(""), None);
+        let light = parse_osc11("\x1b]11;rgb:fdfd/f6f6/e3e3\x1b\\").map(luminance_background);
+        assert_eq!(light, Some(Background::Light));
+        let dark = parse_osc11("\x1b]11;rgb:2828/2c2c/3434\x1b\\").map(luminance_background);
+        assert_eq!(dark, Some(Background::Dark));
+    }
+
+    #[test]
+    fn test_attributes_start() {
+        assert_eq!(attributes_start(b"\x1b]11;rgb:0/0/0\x07\x1b[?62;22c"), Some(15));
+        assert_eq!(attributes_start(b"\x1b[?62;2"), None);
+        assert_eq!(attributes_start(b"\x1b]11;rgb:0/0/0\x07"), None);
+    }
+
+    #[test]
+    fn test_parse_colorfgbg() {
+        assert_eq!(parse_colorfgbg("15;0"), Some(Background::Dark));
+        assert_eq!(parse_colorfgbg("0;15"), Some(Background::Light));
+        assert_eq!(parse_colorfgbg("0;default;7"), Some(Background::Light));
+        assert_eq!(parse_colorfgbg("7;8"), Some(Background::Dark));
+        assert_eq!(parse_colorfgbg("default;default"), None);
+    }
+}
@@ -0,0 +1,124 @@
+use ratatui::style::Color;
+
+/// Named color palette for rendered markdown.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Theme {
+    pub name: &'static str,
+    /// H1, H2, H3, and H4–H6.
+    pub headings: [Color; 4],
+    pub link: Color,
+    /// Secondary text: link URLs, list bullets, rules, unchecked tasks.
+    pub muted: Color,
+    pub blockquote_bar: Color,
+    pub code_bg: Color,
+    pub table_border: Color,
+    pub zebra_bg: Color,
+    pub task_done: Color,
+    pub footnote: Color,
+    pub math: Color,
+    /// Alert title and border: note, tip, important, warning, caution.
+    pub alerts: [Color; 5],
+    /// Syntect theme used when the config does not name one.
+    pub syntax_theme: &'static str,
+}
+
+pub const DARK: Theme = Theme {
+    name: "dark",
+    headings: [Color::Cyan, Color::Green, Color::Yellow, Color::White],
+    link: Color::Blue,
+    muted: Color::DarkGray,
+    blockquote_bar: Color::DarkGray,
+    code_bg: Color::Indexed(239),
+    table_border: Color::DarkGray,
+    zebra_bg: Color::Indexed(235),
+    task_done: Color::Green,
+    footnote: Color::Cyan,
+    math: Color::Yellow,
+    alerts: [Color::Blue, Color::Green, Color::Magenta, Color::Yellow, Color::Red],
+    syntax_theme: "base16-ocean.dark",
+};
+
+pub const LIGHT: Theme = Theme {
+    name: "light",
+    headings: [Color::Blue, Color::Magenta, Color::Rgb(0x8a, 0x5a, 0x00), Color::Black],
+    link: Color::Blue,
+    muted: Color::Indexed(244),
+    blockquote_bar: Color::Indexed(248),
+    code_bg: Color::Indexed(254),
+    table_border: Color::Indexed(248),
+    zebra_bg: Color::Indexed(255),
+    task_done: Color::Green,
+    footnote: Color::Blue,
+    math: Color::Rgb(0x8a, 0x5a, 0x00),
+    alerts: [Color::Blue, Color::Green, Color::Magenta, Color::Rgb(0x8a, 0x5a, 0x00), Color::Red],
+    syntax_theme: "InspiredGitHub",
+};
+
+pub const SOLARIZED: Theme = Theme {
+    name: "solarized",
+    headings: [
+        Color::Rgb(0x26, 0x8b, 0xd2),
+        Color::Rgb(0x2a, 0xa1, 0x98),
+        Color::Rgb(0x85, 0x99, 0x00),
+        Color::Rgb(0xb5, 0x89, 0x00),
+    ],
+    link: Color::Rgb(0x6c, 0x71, 0xc4),
+    muted: Color::Rgb(0x58, 0x6e, 0x75),
+    blockquote_bar: Color::Rgb(0x58, 0x6e, 0x75),
+    code_bg: Color::Rgb(0x07, 0x36, 0x42),
+    table_border: Color::Rgb(0x58, 0x6e, 0x75),
+    zebra_bg: Color::Rgb(0x07, 0x36, 0x42),
+    task_done: Color::Rgb(0x85, 0x99, 0x00),
+    footnote: Color::Rgb(0x2a, 0xa1, 0x98),
+    math: Color::Rgb(0xcb, 0x4b, 0x16),
+    alerts: [
+        Color::Rgb(0x26, 0x8b, 0xd2),
+        Color::Rgb(0x85, 0x99, 0x00),
+        Color::Rgb(0x6c, 0x71, 0xc4),
+        Color::Rgb(0xb5, 0x89, 0x00),
+        Color::Rgb(0xdc, 0x32, 0x2f),
+    ],
+    syntax_theme: "Solarized (dark)",
+};
+
+pub const DRACULA: Theme = Theme {
+    name: "dracula",
+    headings: [
+        Color::Rgb(0xbd, 0x93, 0xf9),
+        Color::Rgb(0xff, 0x79, 0xc6),
+        Color::Rgb(0x50, 0xfa, 0x7b),
+        Color::Rgb(0x8b, 0xe9, 0xfd),
+    ],
+    link: Color::Rgb(0x8b, 0xe9, 0xfd),
+    muted: Color::Rgb(0x62, 0x72, 0xa4),
+    blockquote_bar: Color::Rgb(0x62, 0x72, 0xa4),
+    code_bg: Color::Rgb(0x44, 0x47, 0x5a),
+    table_border: Color::Rgb(0x62, 0x72, 0xa4),
+    zebra_bg: Color::Rgb(0x34, 0x37, 0x46),
+    task_done: Color::Rgb(0x50, 0xfa, 0x7b),
+    footnote: Color::Rgb(0xff, 0xb8, 0x6c),
+    math: Color::Rgb(0xf1, 0xfa, 0x8c),
+    alerts: [
+        Color::Rgb(0x8b, 0xe9, 0xfd),
+        Color::Rgb(0x50, 0xfa, 0x7b),
+        Color::Rgb(0xbd, 0x93, 0xf9),
+        Color::Rgb(0xf1, 0xfa, 0x8c),
+        Color::Rgb(0xff, 0x55, 0x55),
+    ],
+    syntax_theme: "base16-eighties.dark",
+};
+
+pub const THEMES: &[Theme] = &[DARK, LIGHT, SOLARIZED, DRACULA];
+
+pub fn by_name(name: &str) -> Option<Theme> {
+    THEMES
+        .iter()
+        .find(|t| t.name.eq_ignore_ascii_case(name))
+        .copied()
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        DARK
+    }
+}
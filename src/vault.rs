@@ -0,0 +1,242 @@
+//! Obsidian vaults: a directory of notes that link to each other by name,
+//! `[[Note]]`, wherever under the directory the note is. While a vault is in
+//! use ([`use_vault`]), wiki links and `![[Note]]` embeds find their notes in
+//! it before looking beside the document.
+//!
+//! ```
+//! let dir = std::env::temp_dir().join(format!("mdview-vault-doc-{}", std::process::id()));
+//! std::fs::create_dir_all(dir.join("projects")).unwrap();
+//! std::fs::write(dir.join("projects/Plan.md"), "See [[Ideas]].\n").unwrap();
+//! std::fs::write(dir.join("Ideas.md"), "# Ideas\n").unwrap();
+//! let vault = mdview::vault::Vault::open(&dir).unwrap();
+//! assert_eq!(vault.resolve("plan"), Some(vault.root().join("projects/Plan.md")));
+//! assert_eq!(vault.backlinks(&vault.root().join("Ideas.md"))[0].text, "See [[Ideas]].");
+//! # std::fs::remove_dir_all(&dir).unwrap();
+//! ```
+
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, PoisonError, RwLock};
+
+use anyhow::{Context, Result};
+
+use crate::encoding;
+use crate::include::fence_marker;
+use crate::input::Format;
+
+/// The vault in use, if any.
+static VAULT: RwLock<Option<Arc<Vault>>> = RwLock::new(None);
+
+/// The notes of a vault: the Markdown files under its directory, outside
+/// hidden directories such as `.obsidian` and `.trash`.
+#[derive(Debug, Clone)]
+pub struct Vault {
+    root: PathBuf,
+    /// Paths relative to the root, sorted.
+    notes: Vec<PathBuf>,
+    /// The path of each note as links name it: lowercase, with `/` between
+    /// folders and no extension.
+    keys: Vec<String>,
+}
+
+/// A line of a note that links to another note.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Backlink {
+    /// The linking note, relative to the vault.
+    pub note: PathBuf,
+    /// Source line of the link, from 0.
+    pub line: usize,
+    /// The line, trimmed.
+    pub text: String,
+}
+
+impl Vault {
+    /// Indexes the notes under `root`.
+    pub fn open(root: &Path) -> Result<Self> {
+        let root = root.canonicalize().with_context(|| format!("Cannot open vault {}", root.display()))?;
+        anyhow::ensure!(root.is_dir(), "Vault {} is not a directory", root.display());
+        let mut notes = Vec::new();
+        walk(&root, Path::new(""), &mut notes);
+        notes.sort();
+        let keys = notes.iter().map(|note| key(note)).collect();
+        Ok(Self { root, notes, keys })
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// The notes, relative to the root.
+    pub fn notes(&self) -> &[PathBuf] {
+        &self.notes
+    }
+
+    /// The note a wiki link to `target` (without a `#heading` or `|label`)
+    /// opens, found as Obsidian finds it: by its path in the vault when
+    /// `target` has a `/`, else by name anywhere, the shallowest first.
+    /// Case and the `.md` extension do not matter.
+    pub fn resolve(&self, target: &str) -> Option<PathBuf> {
+        let wanted = key(Path::new(target.trim().trim_start_matches('/')));
+        let by_name = !wanted.contains('/');
+        let suffix = format!("/{wanted}");
+        let i = (0..self.keys.len())
+            .filter(|&i| match by_name {
+                true => self.keys[i].rsplit('/').next() == Some(wanted.as_str()),
+                false => self.keys[i] == wanted || self.keys[i].ends_with(&suffix),
+            })
+            .min_by_key(|&i| self.keys[i].matches('/').count())?;
+        Some(self.root.join(&self.notes[i]))
+    }
+
+    /// The note and heading a `![[Note#Heading|label]]` embed of `target`
+    /// shows, if it names a note of the vault.
+    pub(crate) fn embed<'a>(&self, target: &'a str) -> Option<(PathBuf, Option<&'a str>)> {
+        let target = target.split_once('|').map_or(target, |(target, _)| target);
+        let (page, heading) = match target.split_once('#') {
+            Some((page, heading)) => (page, Some(heading.trim()).filter(|h| !h.is_empty())),
+            None => (target, None),
+        };
+        Some((self.resolve(page)?, heading))
+    }
+
+    /// Every line of another note that links to or embeds `note`, a path
+    /// under the root, by note and then line. Links in code blocks do not
+    /// count.
+    pub fn backlinks(&self, note: &Path) -> Vec<Backlink> {
+        let mut found = Vec::new();
+        for relative in &self.notes {
+            let path = self.root.join(relative);
+            if path == note {
+                continue;
+            }
+            let Ok(decoded) = encoding::read(&path) else {
+                continue;
+            };
+            let mut fence: Option<(char, usize)> = None;
+            for (i, line) in decoded.text.lines().enumerate() {
+                if let Some(marker) = fence_marker(line) {
+                    match fence {
+                        None => fence = Some(marker),
+                        Some((c, len)) if marker.0 == c && marker.1 >= len => fence = None,
+                        Some(_) => {}
+                    }
+                    continue;
+                }
+                if fence.is_none() && targets(line).any(|target| self.resolve(target).as_deref() == Some(note)) {
+                    found.push(Backlink { note: relative.clone(), line: i, text: line.trim().to_string() });
+                }
+            }
+        }
+        found
+    }
+}
+
+/// The name a note goes by in lists: its path in the vault without the
+/// extension.
+pub fn name(note: &Path) -> String {
+    note.with_extension("").to_string_lossy().into_owned()
+}
+
+/// Makes wiki links and embeds resolve in `vault`, or beside the document
+/// alone with `None`. Set again when the vault is indexed anew.
+pub fn use_vault(vault: Option<Arc<Vault>>) {
+    *VAULT.write().unwrap_or_else(PoisonError::into_inner) = vault;
+}
+
+/// The vault in use, if any.
+pub(crate) fn current() -> Option<Arc<Vault>> {
+    VAULT.read().unwrap_or_else(PoisonError::into_inner).clone()
+}
+
+/// Adds the Markdown files under `dir`, the folder `relative` of a vault, to
+/// `notes`. Hidden files and folders are left out.
+fn walk(dir: &Path, relative: &Path, notes: &mut Vec<PathBuf>) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let name = entry.file_name();
+        if name.to_string_lossy().starts_with('.') {
+            continue;
+        }
+        let path = entry.path();
+        match entry.file_type() {
+            Ok(kind) if kind.is_dir() => walk(&path, &relative.join(&name), notes),
+            Ok(_) if matches!(Format::from_path(&path), Some(Format::Markdown)) => notes.push(relative.join(name)),
+            _ => {}
+        }
+    }
+}
+
+/// `path` as links name the note: lowercase, with `/` between folders and
+/// no Markdown extension.
+fn key(path: &Path) -> String {
+    let path = match Format::from_path(path) {
+        Some(Format::Markdown) => path.with_extension(""),
+        _ => path.to_path_buf(),
+    };
+    let parts: Vec<String> = path.iter().map(|part| part.to_string_lossy().to_lowercase()).collect();
+    parts.join("/")
+}
+
+/// The notes the wiki links and embeds on `line` link to, without headings
+/// and labels. Links within the note itself (`[[#Heading]]`) have none.
+fn targets(line: &str) -> impl Iterator<Item = &str> {
+    line.split("[[").skip(1).filter_map(|rest| {
+        let inner = &rest[..rest.find("]]")?];
+        let target = inner.split(['|', '#']).next()?.trim();
+        (!target.is_empty()).then_some(target)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FILES: &[(&str, &str)] = &[
+        ("Home.md", "[[Plan]] and [[projects/old/plan|the old plan]]\n"),
+        ("projects/Plan.md", "Back [[home#Top]].\n\n```\n[[Home]]\n```\n"),
+        ("projects/old/plan.markdown", "[[#Intro]] ![[Home]]\n"),
+        (".obsidian/Hidden.md", "[[Home]]\n"),
+        ("image.png", ""),
+    ];
+
+    fn fixture(name: &str, files: &[(&str, &str)]) -> Vault {
+        let dir = std::env::temp_dir().join(format!("mdview-vault-{name}-{}", std::process::id()));
+        for (file, content) in files {
+            let path = dir.join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, content).unwrap();
+        }
+        Vault::open(&dir).unwrap()
+    }
+
+    #[test]
+    fn test_resolve() {
+        let vault = fixture("resolve", FILES);
+        let notes: Vec<String> = vault.notes().iter().map(|note| name(note)).collect();
+        assert_eq!(notes, ["Home", "projects/Plan", "projects/old/plan"]);
+        let resolve = |target| vault.resolve(target).map(|path| path.strip_prefix(vault.root()).unwrap().to_owned());
+        assert_eq!(resolve("plan"), Some(PathBuf::from("projects/Plan.md")));
+        assert_eq!(resolve("Old/Plan.markdown"), Some(PathBuf::from("projects/old/plan.markdown")));
+        assert_eq!(resolve("/home.md"), Some(PathBuf::from("Home.md")));
+        assert_eq!(resolve("image"), None);
+        assert_eq!(resolve("jects/plan"), None);
+        let embed = vault.embed("Plan#Next steps|steps").unwrap();
+        assert_eq!((embed.0.ends_with("projects/Plan.md"), embed.1), (true, Some("Next steps")));
+        std::fs::remove_dir_all(vault.root()).unwrap();
+    }
+
+    #[test]
+    fn test_backlinks() {
+        let vault = fixture("backlinks", FILES);
+        let backlinks = |note: &str| -> Vec<(String, usize)> {
+            let found = vault.backlinks(&vault.root().join(note));
+            found.into_iter().map(|b| (name(&b.note), b.line)).collect()
+        };
+        assert_eq!(backlinks("Home.md"), [("projects/Plan".to_string(), 0), ("projects/old/plan".to_string(), 0)]);
+        assert_eq!(backlinks("projects/Plan.md"), [("Home".to_string(), 0)]);
+        let found = vault.backlinks(&vault.root().join("projects/old/plan.markdown"));
+        assert_eq!(found[0].text, "[[Plan]] and [[projects/old/plan|the old plan]]");
+        std::fs::remove_dir_all(vault.root()).unwrap();
+    }
+}
@@ -1,17 +1,305 @@
-use std::path::Path;
-use std::sync::mpsc::Sender;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
 
 use anyhow::Result;
-use notify::{EventKind, RecursiveMode, Watcher, recommended_watcher};
+use notify::event::{ModifyKind, RenameMode};
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher as _, recommended_watcher};
 
-pub fn setup(path: &Path, tx: Sender<()>) -> Result<impl Watcher> {
+use crate::fetch::Source;
+
+/// Watches a file and the files it includes.
+///
+/// The directories holding them are watched rather than the files, since
+/// editors that save by writing a new file and renaming it over the old one
+/// (vim, VS Code) leave a watch on the file itself pointing at a file that is
+/// gone. Events for other files in those directories are ignored.
+///
+/// Changes are reported once they settle: after a quiet period, and once
+/// every changed file is back and non-empty, so a save in progress is not
+/// shown half written.
+pub struct FileWatcher {
+    watcher: RecommendedWatcher,
+    /// The watched file and its includes.
+    files: Arc<Mutex<Vec<PathBuf>>>,
+    /// Directories with a watch on them, one per directory.
+    dirs: Vec<PathBuf>,
+}
+
+/// Quiet time after the last change before it is reported.
+const QUIET: Duration = Duration::from_millis(100);
+
+/// Longest a change is held back for, whether writes keep coming or a file
+/// stays missing or empty.
+const SETTLE_LIMIT: Duration = Duration::from_secs(1);
+
+/// Watches `path` and sends it on `tx` once it, or one of the files later
+/// passed to `set_includes`, has been written, created or renamed into place
+/// and has settled.
+pub fn setup(path: &Path, tx: Sender<Source>) -> Result<FileWatcher> {
+    let files = Arc::new(Mutex::new(vec![path.to_path_buf()]));
+    let watched = Arc::clone(&files);
+    let (raw_tx, raw) = mpsc::channel();
     let mut watcher = recommended_watcher(move |res: notify::Result<notify::Event>| {
-        if let Ok(event) = res {
-            if matches!(event.kind, EventKind::Modify(_) | EventKind::Create(_)) {
-                let _ = tx.send(());
+        let event = match res {
+            Ok(event) => event,
+            Err(e) => {
+                tracing::warn!(error = %e, "watch error");
+                return;
+            }
+        };
+        let Ok(files) = watched.lock() else { return };
+        let affected = affects(&event, &files);
+        // Other files in the directory are left out, the log among them.
+        if event.paths.iter().any(|p| files.contains(p)) {
+            tracing::debug!(kind = ?event.kind, paths = ?event.paths, affected, "file event");
+        }
+        if affected {
+            for path in event.paths.iter().filter(|p| files.contains(p)) {
+                let _ = raw_tx.send(path.clone());
             }
         }
     })?;
-    watcher.watch(path, RecursiveMode::NonRecursive)?;
-    Ok(watcher)
+    let reported = path.to_path_buf();
+    // Ends when the watcher, and with it `raw_tx`, is dropped.
+    thread::spawn(move || debounce(&raw, &tx, &reported));
+    let dir = parent(path);
+    watcher.watch(&dir, RecursiveMode::NonRecursive)?;
+    Ok(FileWatcher { watcher, files, dirs: vec![dir] })
+}
+
+impl FileWatcher {
+    /// Replaces the set of watched included files. Directories that cannot be
+    /// watched are skipped; they are retried on the next call.
+    pub fn set_includes(&mut self, includes: &[PathBuf]) {
+        let Ok(mut files) = self.files.lock() else { return };
+        files.truncate(1);
+        files.extend(includes.iter().cloned());
+        let wanted: Vec<PathBuf> = files.iter().map(|f| parent(f)).collect();
+        drop(files);
+
+        for dir in &self.dirs {
+            if !wanted.contains(dir) {
+                let _ = self.watcher.unwatch(dir);
+            }
+        }
+        let mut dirs: Vec<PathBuf> = Vec::new();
+        for dir in wanted {
+            if dirs.contains(&dir) {
+                continue;
+            }
+            if self.dirs.contains(&dir) || self.watcher.watch(&dir, RecursiveMode::NonRecursive).is_ok() {
+                dirs.push(dir);
+            }
+        }
+        self.dirs = dirs;
+    }
+}
+
+/// Polls a document that has no file to watch, such as one fetched over
+/// HTTP. Polling stops when this is dropped.
+pub struct Poller {
+    stop: Arc<AtomicBool>,
+}
+
+impl Drop for Poller {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Calls `fetch` every `every` and sends `source` on `tx` whenever it returns
+/// something other than the last content, starting from `current`. Failed
+/// fetches are skipped, so a network blip does not blank the view.
+pub fn poll(
+    source: Source,
+    current: String,
+    every: Duration,
+    tx: Sender<Source>,
+    fetch: impl Fn() -> Result<String> + Send + 'static,
+) -> Poller {
+    let stop = Arc::new(AtomicBool::new(false));
+    let stopped = Arc::clone(&stop);
+    thread::spawn(move || {
+        let mut last = current;
+        loop {
+            thread::sleep(every);
+            if stopped.load(Ordering::Relaxed) {
+                return;
+            }
+            let text = match fetch() {
+                Ok(text) => text,
+                Err(e) => {
+                    tracing::warn!(%source, error = format!("{e:#}"), "poll failed");
+                    continue;
+                }
+            };
+            if text != last {
+                tracing::debug!(%source, "poll found a change");
+                last = text;
+                if tx.send(source.clone()).is_err() {
+                    return;
+                }
+            }
+        }
+    });
+    Poller { stop }
+}
+
+/// How a buffer learns that its document changed.
+pub enum Watcher {
+    File(FileWatcher),
+    /// Only held: dropping the poller stops it.
+    Poll { _poller: Poller },
+    /// A fetched document that is not polled; it changes only on a reload.
+    Off,
+}
+
+impl Watcher {
+    /// Passes the included files on to a file watcher; fetched documents
+    /// have no includes.
+    pub fn set_includes(&mut self, includes: &[PathBuf]) {
+        if let Watcher::File(watcher) = self {
+            watcher.set_includes(includes);
+        }
+    }
+}
+
+/// Turns the changed paths arriving on `raw` into one `reported` on `tx`
+/// per burst of changes, once they settle.
+fn debounce(raw: &Receiver<PathBuf>, tx: &Sender<Source>, reported: &Path) {
+    while let Ok(first) = raw.recv() {
+        let started = Instant::now();
+        let mut changed = vec![first];
+        loop {
+            while started.elapsed() < SETTLE_LIMIT
+                && let Ok(path) = raw.recv_timeout(QUIET)
+            {
+                if !changed.contains(&path) {
+                    changed.push(path);
+                }
+            }
+            // An editor between truncating and writing, or between removing
+            // and renaming, leaves an empty or missing file for a moment.
+            let settled = changed.iter().all(|p| std::fs::metadata(p).is_ok_and(|m| m.len() > 0));
+            if settled || started.elapsed() >= SETTLE_LIMIT {
+                break;
+            }
+        }
+        tracing::debug!(?changed, waited = ?started.elapsed(), "change settled");
+        if tx.send(Source::File(reported.to_path_buf())).is_err() {
+            return;
+        }
+    }
+}
+
+/// Whether `event` leaves new content at one of `files`, or removes one so
+/// the viewer can say it is gone. A rename counts for the file it lands on,
+/// not the one moved away.
+fn affects(event: &notify::Event, files: &[PathBuf]) -> bool {
+    let paths = match event.kind {
+        EventKind::Modify(ModifyKind::Metadata(_)) => return false,
+        EventKind::Modify(ModifyKind::Name(RenameMode::From)) => return false,
+        EventKind::Modify(ModifyKind::Name(_)) => &event.paths[event.paths.len().saturating_sub(1)..],
+        EventKind::Modify(_) | EventKind::Create(_) | EventKind::Remove(_) => &event.paths[..],
+        _ => return false,
+    };
+    paths.iter().any(|p| files.contains(p))
+}
+
+fn parent(path: &Path) -> PathBuf {
+    match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+        _ => PathBuf::from("."),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Context as _;
+
+    #[test]
+    fn test_replaced_file_keeps_reporting() {
+        let dir = std::env::temp_dir().join(format!("mdview-watch-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let dir = dir.canonicalize().unwrap();
+        let file = dir.join("doc.md");
+        std::fs::write(&file, "one").unwrap();
+        let (tx, rx) = mpsc::channel();
+        let _watcher = setup(&file, tx).unwrap();
+
+        // Saved the way vim does: a new file renamed over the old one, twice.
+        for text in ["two", "three"] {
+            let tmp = dir.join("doc.md.tmp");
+            std::fs::write(&tmp, text).unwrap();
+            std::fs::rename(&tmp, &file).unwrap();
+            assert_eq!(rx.recv_timeout(Duration::from_secs(5)), Ok(Source::File(file.clone())));
+            while rx.recv_timeout(Duration::from_millis(200)).is_ok() {}
+        }
+        // Other files in the directory are ignored.
+        std::fs::write(dir.join("other.md"), "x").unwrap();
+        assert!(rx.recv_timeout(Duration::from_millis(300)).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_changes_are_reported_once_settled() {
+        let dir = std::env::temp_dir().join(format!("mdview-settle-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let dir = dir.canonicalize().unwrap();
+        let file = dir.join("doc.md");
+        std::fs::write(&file, "one").unwrap();
+        let (tx, rx) = mpsc::channel();
+        let _watcher = setup(&file, tx).unwrap();
+
+        // A burst of writes, with the file empty partway, is one report of
+        // the finished content.
+        for text in ["", "t", "tw", "two"] {
+            std::fs::write(&file, text).unwrap();
+            thread::sleep(Duration::from_millis(20));
+        }
+        assert_eq!(rx.recv_timeout(Duration::from_secs(5)), Ok(Source::File(file.clone())));
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "two");
+        assert!(rx.recv_timeout(Duration::from_millis(300)).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_poll_reports_changes() {
+        let versions = Arc::new(Mutex::new(vec!["two", "two", "one"]));
+        let remaining = Arc::clone(&versions);
+        // Then offline for good.
+        let fetch = move || remaining.lock().unwrap().pop().map(str::to_string).context("offline");
+        let url = Source::Url("https://host/doc.md".to_string());
+        let (tx, rx) = mpsc::channel();
+        let poller = poll(url.clone(), "one".to_string(), Duration::from_millis(10), tx, fetch);
+
+        // "one" is what was shown, so only the change to "two" is reported.
+        assert_eq!(rx.recv_timeout(Duration::from_secs(5)), Ok(url));
+        assert!(rx.recv_timeout(Duration::from_millis(100)).is_err());
+        assert!(versions.lock().unwrap().is_empty());
+        drop(poller);
+    }
+
+    #[test]
+    fn test_affects() {
+        let file = PathBuf::from("/d/doc.md");
+        let event = |kind, paths: &[&str]| {
+            let mut event = notify::Event::new(kind);
+            event.paths = paths.iter().map(PathBuf::from).collect();
+            event
+        };
+        let files = [file];
+        let renamed = |mode| EventKind::Modify(ModifyKind::Name(mode));
+        assert!(affects(&event(renamed(RenameMode::Both), &["/d/.tmp", "/d/doc.md"]), &files));
+        assert!(!affects(&event(renamed(RenameMode::Both), &["/d/doc.md", "/d/doc.md~"]), &files));
+        assert!(!affects(&event(renamed(RenameMode::From), &["/d/doc.md"]), &files));
+        assert!(affects(&event(EventKind::Create(notify::event::CreateKind::File), &["/d/doc.md"]), &files));
+        assert!(!affects(&event(EventKind::Create(notify::event::CreateKind::File), &["/d/x.md"]), &files));
+        assert!(affects(&event(EventKind::Remove(notify::event::RemoveKind::File), &["/d/doc.md"]), &files));
+    }
 }
@@ -0,0 +1,153 @@
+//! Wiki links, `[[Page Name]]` and `[[Page Name|shown text]]`, as Obsidian
+//! vaults and other Zettelkasten notes write them. The renderer draws them
+//! as links to the Markdown file of that name beside the document, which
+//! the viewer opens like any local link. `[[Page#Heading]]` links to a
+//! heading of the page and `[[#Heading]]` to one of the same document.
+//! While a [vault](crate::vault) is in use, pages are its notes.
+
+use std::path::{Path, PathBuf};
+
+use crate::input::Format;
+use crate::render::slugify;
+use crate::vault::Vault;
+
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum Piece<'a> {
+    Text(&'a str),
+    Link { target: &'a str, label: &'a str },
+}
+
+/// Splits `text` into plain text and wiki links. Embeds (`![[image.png]]`),
+/// empty targets and brackets holding other brackets stay text.
+pub(crate) fn split(text: &str) -> Vec<Piece<'_>> {
+    let mut pieces = Vec::new();
+    // Where the plain text not pushed yet starts, and where to look on.
+    let (mut start, mut from) = (0, 0);
+    while let Some(open) = text[from..].find("[[").map(|i| from + i) {
+        let inner_start = open + 2;
+        let Some(len) = text[inner_start..].find("]]") else {
+            break;
+        };
+        let inner = &text[inner_start..inner_start + len];
+        let (target, label) = inner.split_once('|').unwrap_or((inner, inner));
+        let (target, label) = (target.trim(), label.trim());
+        if text[..open].ends_with('!') || inner.contains(['[', ']', '\n']) || target.is_empty() {
+            from = open + 1;
+            continue;
+        }
+        if open > start {
+            pieces.push(Piece::Text(&text[start..open]));
+        }
+        pieces.push(Piece::Link { target, label: if label.is_empty() { target } else { label } });
+        start = inner_start + len + 2;
+        from = start;
+    }
+    if start < text.len() {
+        pieces.push(Piece::Text(&text[start..]));
+    }
+    pieces
+}
+
+/// The link destination of a wiki link to `target`: the note of the page in
+/// `vault`, else the Markdown file of the page in `dir`, the directory of
+/// the document, and the anchor of the heading. A page with no file of
+/// exactly its name takes one whose name differs only in case or in spaces
+/// written as `-` or `_`, and without any is linked as `Page.md`, a broken
+/// link like any other.
+pub(crate) fn destination(target: &str, dir: Option<&Path>, vault: Option<&Vault>) -> String {
+    let (page, heading) = match target.split_once('#') {
+        Some((page, heading)) => (page.trim(), Some(heading)),
+        None => (target, None),
+    };
+    let anchor = heading.map(|heading| format!("#{}", slugify(heading))).unwrap_or_default();
+    if page.is_empty() {
+        return anchor;
+    }
+    if let Some(note) = vault.and_then(|vault| vault.resolve(page)) {
+        let note = match dir {
+            Some(dir) => relative(&note, dir),
+            None => note,
+        };
+        return note.to_string_lossy().into_owned() + &anchor;
+    }
+    let file = match matches!(Format::from_path(Path::new(page)), Some(Format::Markdown)) {
+        true => page.to_string(),
+        false => format!("{page}.md"),
+    };
+    let found = dir.filter(|dir| !dir.join(&file).exists()).and_then(|dir| find(dir, &file));
+    found.unwrap_or(file) + &anchor
+}
+
+/// The Markdown file in `dir` that `file`, a relative path, names loosely.
+fn find(dir: &Path, file: &str) -> Option<String> {
+    let (folder, name) = file.rsplit_once('/').unwrap_or(("", file));
+    let loose = |name: &str| -> Option<String> {
+        let stem = Path::new(name).file_stem()?.to_str()?;
+        Some(stem.chars().map(|c| if c == ' ' || c == '_' { '-' } else { c.to_ascii_lowercase() }).collect())
+    };
+    let wanted = loose(name)?;
+    std::fs::read_dir(dir.join(folder)).ok()?.flatten().find_map(|entry| {
+        let path = entry.path();
+        let name = path.file_name()?.to_str()?;
+        let markdown = matches!(Format::from_path(&path), Some(Format::Markdown));
+        (markdown && loose(name)? == wanted).then(|| match folder {
+            "" => name.to_string(),
+            folder => format!("{folder}/{name}"),
+        })
+    })
+}
+
+/// `path` relative to `dir`, both absolute.
+fn relative(path: &Path, dir: &Path) -> PathBuf {
+    let common = path.components().zip(dir.components()).take_while(|(a, b)| a == b).count();
+    let mut out: PathBuf = dir.components().skip(common).map(|_| "..").collect();
+    out.extend(path.components().skip(common));
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split() {
+        let link = |target, label| Piece::Link { target, label };
+        assert_eq!(
+            split("See [[Page Name]] and [[page|the page]]."),
+            [
+                Piece::Text("See "),
+                link("Page Name", "Page Name"),
+                Piece::Text(" and "),
+                link("page", "the page"),
+                Piece::Text("."),
+            ]
+        );
+        assert_eq!(split("[[#Usage|]]"), [link("#Usage", "#Usage")]);
+        for text in ["![[image.png]]", "[[]]", "[[ |x]]", "[[1, [2]]]", "[[open", "a ]] b [[c"] {
+            assert_eq!(split(text), [Piece::Text(text)], "{text}");
+        }
+    }
+
+    #[test]
+    fn test_destination() {
+        let dir = std::env::temp_dir().join(format!("mdview-wikilinks-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("notes")).unwrap();
+        for file in ["Exact Name.md", "loose-name.md", "notes/Deep_Page.markdown", "other.txt"] {
+            std::fs::write(dir.join(file), "").unwrap();
+        }
+        let dest = |target| destination(target, Some(&dir), None);
+        assert_eq!(dest("Exact Name"), "Exact Name.md");
+        assert_eq!(dest("Loose Name#Getting Started"), "loose-name.md#getting-started");
+        assert_eq!(dest("notes/deep page"), "notes/Deep_Page.markdown");
+        assert_eq!(dest("other"), "other.md");
+        assert_eq!(dest("#The End"), "#the-end");
+        assert_eq!(destination("Readme.md", None, None), "Readme.md");
+        // A vault finds notes in any folder, relative to the document.
+        let vault = Vault::open(&dir).unwrap();
+        let notes = vault.root().join("notes");
+        assert_eq!(destination("Exact Name#Top", Some(&notes), Some(&vault)), "../Exact Name.md#top");
+        assert_eq!(destination("deep_page", Some(vault.root()), Some(&vault)), "notes/Deep_Page.markdown");
+        assert_eq!(destination("Loose Name", Some(vault.root()), Some(&vault)), "loose-name.md");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
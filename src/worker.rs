@@ -0,0 +1,117 @@
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+
+use mdview::{Document, IncrementalRenderer};
+
+/// A render to run off the UI thread. The buffer's renderer travels with the
+/// job so its block cache and line layout stay with the result.
+pub struct Job {
+    pub buffer: usize,
+    pub renderer: IncrementalRenderer,
+    pub source: String,
+    /// Show the result from the top rather than keeping the view on the
+    /// same content.
+    pub reset_scroll: bool,
+}
+
+/// A finished `Job`, handing the renderer back.
+pub struct Done {
+    pub buffer: usize,
+    pub renderer: IncrementalRenderer,
+    /// The document, or the message of the panic that ended the render.
+    pub doc: Result<Document, String>,
+    pub reset_scroll: bool,
+}
+
+/// A thread rendering jobs one at a time, in the order submitted.
+pub struct Worker {
+    jobs: Sender<Job>,
+    pub done: Receiver<Done>,
+}
+
+/// Name of the render thread, whose panics are not printed since the
+/// viewer holds the terminal.
+const THREAD_NAME: &str = "render";
+
+pub fn spawn() -> Worker {
+    let (jobs, job_rx) = mpsc::channel::<Job>();
+    let (done_tx, done) = mpsc::channel();
+    let print = panic::take_hook();
+    panic::set_hook(Box::new(move |info| match thread::current().name() {
+        Some(THREAD_NAME) => tracing::error!(%info, "render panicked"),
+        _ => print(info),
+    }));
+    let thread = thread::Builder::new().name(THREAD_NAME.to_string());
+    let spawned = thread.spawn(move || {
+        for mut job in job_rx {
+            let doc = render(&mut job, |renderer, source| renderer.render(source));
+            let result = Done {
+                buffer: job.buffer,
+                renderer: job.renderer,
+                doc,
+                reset_scroll: job.reset_scroll,
+            };
+            if done_tx.send(result).is_err() {
+                break;
+            }
+        }
+    });
+    spawned.expect("cannot start the render thread");
+    Worker { jobs, done }
+}
+
+/// Runs `render` on the job's renderer and source, catching a panic as its
+/// message.
+fn render(job: &mut Job, render: impl FnOnce(&mut IncrementalRenderer, &str) -> Document) -> Result<Document, String> {
+    let span = tracing::debug_span!("render", buffer = job.buffer, bytes = job.source.len());
+    let doc = span.in_scope(|| panic::catch_unwind(AssertUnwindSafe(|| render(&mut job.renderer, &job.source))));
+    doc.map_err(|payload| {
+        // The block cache may be half updated.
+        job.renderer.clear_cache();
+        match (payload.downcast_ref::<&str>(), payload.downcast_ref::<String>()) {
+            (Some(message), _) => message.to_string(),
+            (_, Some(message)) => message.clone(),
+            _ => "the renderer panicked".to_string(),
+        }
+    })
+}
+
+impl Worker {
+    pub fn submit(&self, job: Job) {
+        // The thread only stops once `done` is dropped, along with `self`.
+        let _ = self.jobs.send(job);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mdview::RenderOptions;
+
+    #[test]
+    fn test_jobs_come_back_rendered() {
+        let worker = spawn();
+        let renderer = IncrementalRenderer::new(RenderOptions::new(40));
+        let source = "# Title\n".to_string();
+        worker.submit(Job { buffer: 3, renderer, source, reset_scroll: true });
+        let done = worker.done.recv().unwrap();
+        assert_eq!(done.buffer, 3);
+        assert!(done.reset_scroll);
+        assert_eq!(done.doc.unwrap().text.lines[0].to_string(), "# Title");
+    }
+
+    #[test]
+    fn test_a_panic_comes_back_as_its_message() {
+        let renderer = IncrementalRenderer::new(RenderOptions::new(40));
+        let mut job = Job { buffer: 0, renderer, source: "# Title\n".to_string(), reset_scroll: false };
+        assert_eq!(render(&mut job, |_, _| panic!("bad table")).err().as_deref(), Some("bad table"));
+        let doc = render(&mut job, |_, source| panic!("bad {}", source.trim()));
+        assert_eq!(doc.err().as_deref(), Some("bad # Title"));
+        let doc = render(&mut job, |_, _| panic::panic_any(3));
+        assert_eq!(doc.err().as_deref(), Some("the renderer panicked"));
+        // The renderer is still usable after one.
+        let doc = render(&mut job, |renderer, source| renderer.render(source));
+        assert_eq!(doc.unwrap().text.lines[0].to_string(), "# Title");
+    }
+}